@@ -0,0 +1,116 @@
+//! Allocator for user programs, built on the memory syscalls.
+//!
+//! Small allocations are rounded up to a power of two and carved out of pages taken from the heap
+//! with `brk`, with freed blocks kept on a list per size for reuse. Anything larger than half a
+//! page gets its own pages from `mmap`, which are returned with `munmap` when freed.
+//!
+//! The kernel has its own allocator, so this isn't registered here. User programs register it
+//! with `#[global_allocator]` to use `Vec`, `String` and friends.
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::null_mut,
+};
+
+use crate::{
+    mutex::Mutex,
+    syscall::{self, PROT_READ, PROT_WRITE},
+};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Smallest block handed out, which has to fit the pointer to the next free block
+const MIN_BLOCK: usize = 16;
+/// Largest block carved out of heap pages
+const MAX_BLOCK: usize = PAGE_SIZE / 2;
+/// Number of block sizes, one for each power of two from [MIN_BLOCK] to [MAX_BLOCK]
+const CLASS_COUNT: usize = (MAX_BLOCK.trailing_zeros() - MIN_BLOCK.trailing_zeros()) as usize + 1;
+
+/// Allocator for user programs, see the [module docs](self)
+pub struct UserAllocator {
+    /// Address of the first free block of each size, or 0 if there are none. Each free block
+    /// starts with the address of the next one.
+    free_lists: Mutex<[usize; CLASS_COUNT]>,
+}
+
+impl UserAllocator {
+    pub const fn new() -> Self {
+        Self {
+            free_lists: Mutex::new([0; CLASS_COUNT]),
+        }
+    }
+
+    /// Returns the index of the block size used for an allocation, or `None` if it's too large
+    fn class(layout: Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align()).max(MIN_BLOCK);
+
+        (size <= MAX_BLOCK).then(|| {
+            (size.next_power_of_two().trailing_zeros() - MIN_BLOCK.trailing_zeros()) as usize
+        })
+    }
+
+    /// Takes another page from the heap, returning its address
+    fn grow_heap() -> Option<usize> {
+        // anything else moving the break could leave it unaligned
+        let start = syscall::brk(0).next_multiple_of(PAGE_SIZE);
+        let end = start + PAGE_SIZE;
+
+        (syscall::brk(end) >= end).then_some(start)
+    }
+}
+
+impl Default for UserAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for UserAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(class) = Self::class(layout) else {
+            // pages are only ever page aligned
+            if layout.align() > PAGE_SIZE {
+                return null_mut();
+            }
+
+            return syscall::mmap(0, layout.size(), PROT_READ | PROT_WRITE, None)
+                .unwrap_or(null_mut());
+        };
+
+        let mut free_lists = self.free_lists.lock();
+
+        if free_lists[class] == 0 {
+            let Some(page) = Self::grow_heap() else {
+                return null_mut();
+            };
+
+            // blocks are linked in address order, each pointing to the one after it
+            let block_size = MIN_BLOCK << class;
+            for block in (page..page + PAGE_SIZE).step_by(block_size) {
+                let next = block + block_size;
+                let next = if next < page + PAGE_SIZE { next } else { 0 };
+
+                unsafe { *(block as *mut usize) = next };
+            }
+
+            free_lists[class] = page;
+        }
+
+        let block = free_lists[class];
+        free_lists[class] = unsafe { *(block as *const usize) };
+
+        block as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let Some(class) = Self::class(layout) else {
+            syscall::munmap(ptr, layout.size());
+            return;
+        };
+
+        let mut free_lists = self.free_lists.lock();
+
+        unsafe { *(ptr as *mut usize) = free_lists[class] };
+        free_lists[class] = ptr as usize;
+    }
+}
@@ -3,6 +3,12 @@ use core::{borrow::Borrow, ops::Deref};
 
 use super::syscall;
 
+/// Path of the standard input device, which returns characters typed on the keyboard or received
+/// over serial without waiting for input
+pub const STDIN: &str = "stdin//";
+/// Path of the serial console input device
+pub const SERIAL: &str = "serial//";
+
 /// Wrapper type for [str] which represents a path of the form
 /// {device}//{path}, where {device} can be omitted to mean default device
 #[derive(PartialEq, Eq, Debug)]
@@ -69,7 +75,7 @@ impl ToOwned for Path {
 }
 
 /// Owned version of [Path], uses [String] instead of [str]
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 #[repr(C)]
 pub struct PathBuf {
     path: String,
@@ -81,7 +87,7 @@ impl Borrow<Path> for PathBuf {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct File {
     /// Current offset into the file
     offset: usize,
@@ -120,6 +126,11 @@ impl File {
     pub fn offset(&self) -> usize {
         self.offset
     }
+
+    /// Sets the offset the next read starts from.
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
 }
 
 /// Trait representing an arbitrary file system, such as initrd or ext4.
@@ -9,6 +9,8 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+#[cfg(feature = "alloc")]
+pub mod allocator;
 #[cfg(feature = "alloc")]
 pub mod fs;
 #[cfg(feature = "alloc")]
@@ -16,4 +18,5 @@ pub mod syscall;
 
 pub mod cursor;
 pub mod mutex;
+pub mod ring_buffer;
 pub mod volatile;
@@ -0,0 +1,80 @@
+/// Fixed capacity FIFO queue, which can be constructed in a const context
+/// and so is suitable for static buffers shared with interrupt handlers.
+pub struct RingBuffer<T: Copy, const N: usize> {
+    buffer: [Option<T>; N],
+    /// Index of the oldest element
+    head: usize,
+    /// Number of stored elements
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    /// Constructs a new empty ring buffer
+    pub const fn new() -> Self {
+        Self {
+            buffer: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of stored elements
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no stored elements
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if no more elements can be pushed
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Pushes an element to the back of the buffer, returning it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+
+        self.buffer[(self.head + self.len) % N] = Some(value);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Removes the oldest element from the buffer
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let value = self.buffer[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        value
+    }
+
+    /// Returns the oldest element without removing it
+    pub fn peek(&self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.buffer[self.head]
+        }
+    }
+
+    /// Removes all elements from the buffer
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T: Copy, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -8,6 +8,32 @@ pub const NO_FUNCTION: usize = 0;
 pub const OPEN: usize = 1;
 /// Index of `read` syscall
 pub const READ: usize = 2;
+/// Index of `shutdown` syscall
+pub const SHUTDOWN: usize = 3;
+/// Index of `reboot` syscall
+pub const REBOOT: usize = 4;
+/// Index of `exit` syscall
+pub const EXIT: usize = 5;
+/// Index of `fork` syscall
+pub const FORK: usize = 6;
+/// Index of `mmap` syscall
+pub const MMAP: usize = 7;
+/// Index of `munmap` syscall
+pub const MUNMAP: usize = 8;
+/// Index of `mprotect` syscall
+pub const MPROTECT: usize = 9;
+/// Index of `brk` syscall
+pub const BRK: usize = 10;
+
+/// Memory can be read. Pages can't be mapped without read access, so this is always implied.
+pub const PROT_READ: usize = 1 << 0;
+/// Memory can be written
+pub const PROT_WRITE: usize = 1 << 1;
+/// Memory can be executed
+pub const PROT_EXEC: usize = 1 << 2;
+
+/// Value returned by memory syscalls on failure
+const FAILED: usize = usize::MAX;
 
 /// Helper macro to generate a syscall with the provided opcode and registers,
 /// making sure to pass arguments in the correct registers
@@ -85,3 +111,117 @@ pub fn read(file: &mut File, buffer: &mut [u8]) -> usize {
 
     bytes_read
 }
+
+/// Performs a `shutdown` syscall, powering off the machine.
+pub fn shutdown() -> ! {
+    unsafe {
+        syscall!(SHUTDOWN);
+    }
+
+    unreachable!("shutdown syscall returned")
+}
+
+/// Performs a `reboot` syscall, restarting the machine.
+pub fn reboot() -> ! {
+    unsafe {
+        syscall!(REBOOT);
+    }
+
+    unreachable!("reboot syscall returned")
+}
+
+/// Performs a `fork` syscall, creating a copy of the calling process.
+/// Returns the child's pid in the parent and 0 in the child, or `None` if the copy couldn't be made.
+pub fn fork() -> Option<usize> {
+    let pid: isize;
+
+    unsafe {
+        asm!("int 0x80", inlateout("rax") FORK => pid);
+    }
+
+    usize::try_from(pid).ok()
+}
+
+/// Performs an `mmap` syscall, mapping at least `len` bytes of memory with the given `PROT_*`
+/// flags. The memory is placed at `addr` if that's free, or wherever there's space otherwise.
+///
+/// Without a file the memory is zeroed, and otherwise it's a private copy of the file from its
+/// current offset, with anything past the end of the file zeroed.
+/// Returns the start of the memory, or `None` if it couldn't be mapped.
+pub fn mmap(addr: usize, len: usize, prot: usize, file: Option<&File>) -> Option<*mut u8> {
+    let file = file.map_or(0, |file| file as *const File as usize);
+    let result: usize;
+
+    unsafe {
+        asm!(
+            "int 0x80",
+            inlateout("rax") MMAP => result,
+            in("rdi") addr,
+            in("rsi") len,
+            in("rdx") prot,
+            in("rcx") file,
+        );
+    }
+
+    (result != FAILED).then_some(result as *mut u8)
+}
+
+/// Performs a `munmap` syscall, unmapping every page between `addr` and `addr + len`.
+/// Returns false if the range is invalid.
+pub fn munmap(addr: *mut u8, len: usize) -> bool {
+    let result: usize;
+
+    unsafe {
+        asm!(
+            "int 0x80",
+            inlateout("rax") MUNMAP => result,
+            in("rdi") addr,
+            in("rsi") len,
+        );
+    }
+
+    result != FAILED
+}
+
+/// Performs an `mprotect` syscall, changing the `PROT_*` flags of every page between `addr` and
+/// `addr + len`. Returns false if the range is invalid.
+pub fn mprotect(addr: *mut u8, len: usize, prot: usize) -> bool {
+    let result: usize;
+
+    unsafe {
+        asm!(
+            "int 0x80",
+            inlateout("rax") MPROTECT => result,
+            in("rdi") addr,
+            in("rsi") len,
+            in("rdx") prot,
+        );
+    }
+
+    result != FAILED
+}
+
+/// Performs a `brk` syscall, moving the end of the heap to `addr`, or just querying it if `addr`
+/// is 0. Returns the end of the heap afterwards, which is unchanged if it couldn't be moved.
+pub fn brk(addr: usize) -> usize {
+    let result: usize;
+
+    unsafe {
+        asm!(
+            "int 0x80",
+            inlateout("rax") BRK => result,
+            in("rdi") addr,
+        );
+    }
+
+    result
+}
+
+/// Performs an `exit` syscall, ending the calling process with the given status.
+pub fn exit(status: i64) -> ! {
+    unsafe {
+        syscall!(EXIT; status);
+    }
+
+    unreachable!("exit syscall returned")
+}
@@ -143,6 +143,22 @@ impl<S: StorageDevice> Initrd<S> {
         unsafe { Self::new_shared(location, len) }
     }
 
+    /// Returns an iterator over the path and length of every file.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, usize)> {
+        self.header.entries.iter().filter_map(|entry| {
+            self.header
+                .get_path(entry.path_index)
+                .map(|path| (path, entry.len))
+        })
+    }
+
+    /// Returns the contents of a file without copying, or `None` if it doesn't exist.
+    pub fn file_contents(&self, path: impl AsRef<Path>) -> Option<&[u8]> {
+        let entry = self.find_entry(path)?;
+
+        self.data.get(entry.offset..entry.offset + entry.len)
+    }
+
     /// Finds the table entry storing information about a specific file.
     fn find_entry(&self, path: impl AsRef<Path>) -> Option<&TableEntry> {
         let path = path.as_ref();
@@ -187,8 +203,8 @@ impl<S: StorageDevice> fs::FileSystem for Initrd<S> {
 
         log::trace!("\t* copying from {start_addr:#X} in initrd");
 
-        // then find how much to read - length, capped by the buffer size
-        let to_read = (entry.len).min(buffer.len());
+        // then find how much to read - what's left of the file, capped by the buffer size
+        let to_read = entry.len.saturating_sub(file.offset()).min(buffer.len());
 
         log::trace!(
             "\t* copying {to_read:#X} bytes to buffer at addr {:#X}",
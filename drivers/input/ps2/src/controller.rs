@@ -0,0 +1,361 @@
+use bitflags::bitflags;
+use x86_64::port::Port;
+
+use crate::keyboard::ScancodeSet;
+
+/// I/O ports of the 8042 controller
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const COMMAND_PORT: u16 = 0x64;
+
+/// Controller commands
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_SECOND_PORT: u8 = 0xA7;
+const CMD_ENABLE_SECOND_PORT: u8 = 0xA8;
+const CMD_TEST_SECOND_PORT: u8 = 0xA9;
+const CMD_SELF_TEST: u8 = 0xAA;
+const CMD_TEST_FIRST_PORT: u8 = 0xAB;
+const CMD_DISABLE_FIRST_PORT: u8 = 0xAD;
+const CMD_ENABLE_FIRST_PORT: u8 = 0xAE;
+/// Sends the next data byte to the second port instead of the first
+const CMD_WRITE_SECOND_PORT: u8 = 0xD4;
+
+/// Responses from the controller and devices
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+const DEVICE_ACK: u8 = 0xFA;
+const DEVICE_RESEND: u8 = 0xFE;
+const DEVICE_SELF_TEST_PASSED: u8 = 0xAA;
+
+/// Keyboard commands
+const KEYBOARD_SET_TYPEMATIC: u8 = 0xF3;
+const KEYBOARD_ENABLE_SCANNING: u8 = 0xF4;
+const KEYBOARD_RESET: u8 = 0xFF;
+
+/// Mouse commands
+const MOUSE_GET_ID: u8 = 0xF2;
+const MOUSE_SET_SAMPLE_RATE: u8 = 0xF3;
+const MOUSE_ENABLE_REPORTING: u8 = 0xF4;
+const MOUSE_RESET: u8 = 0xFF;
+
+/// Device ID reported by a mouse with a scroll wheel
+const MOUSE_ID_INTELLIMOUSE: u8 = 0x03;
+/// Sample rates which switch a mouse into IntelliMouse mode when sent in order
+const INTELLIMOUSE_SEQUENCE: [u8; 3] = [200, 100, 80];
+
+/// Number of times to poll the status register before giving up
+const TIMEOUT: usize = 100_000;
+/// Number of times to resend a byte to a device before giving up
+const RETRIES: usize = 3;
+
+bitflags! {
+    /// Status register of the controller
+    #[derive(Debug, Clone, Copy)]
+    pub struct Status: u8 {
+        /// Data is waiting to be read from the data port
+        const OUTPUT_FULL = 1 << 0;
+        /// Controller has not yet read the last written byte
+        const INPUT_FULL = 1 << 1;
+        const SYSTEM_FLAG = 1 << 2;
+        /// Last write was a command rather than data
+        const COMMAND = 1 << 3;
+        /// Data in the output buffer came from the second port
+        const AUX_OUTPUT_FULL = 1 << 5;
+        const TIMEOUT_ERROR = 1 << 6;
+        const PARITY_ERROR = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Controller configuration byte
+    #[derive(Debug, Clone, Copy)]
+    pub struct Config: u8 {
+        const FIRST_PORT_INTERRUPT = 1 << 0;
+        const SECOND_PORT_INTERRUPT = 1 << 1;
+        const SYSTEM_FLAG = 1 << 2;
+        const FIRST_PORT_CLOCK_DISABLED = 1 << 4;
+        const SECOND_PORT_CLOCK_DISABLED = 1 << 5;
+        /// Controller translates scancode set 2 from the keyboard into set 1
+        const FIRST_PORT_TRANSLATION = 1 << 6;
+    }
+}
+
+/// Errors which can occur while talking to the controller or its devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Controller did not become ready in time
+    Timeout,
+    /// Controller self test returned the given value
+    SelfTestFailed(u8),
+    /// Port test returned the given value
+    PortTestFailed(u8),
+    /// Controller only has a single port, so no mouse can be connected
+    NoSecondPort,
+    /// Device responded to a command with the given value instead of an ACK
+    UnexpectedResponse(u8),
+}
+
+/// One of the two devices connected to the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    /// Device on the first port, normally a keyboard
+    Keyboard,
+    /// Device on the second (auxiliary) port, normally a mouse
+    Mouse,
+}
+
+/// The 8042 PS/2 controller
+pub struct Controller {
+    data: Port<u8>,
+    status: Port<u8>,
+    command: Port<u8>,
+}
+
+impl Controller {
+    /// Constructs a new controller using the standard ports.
+    ///
+    /// # Safety
+    /// Only one controller should exist at a time, and the system must have an 8042 controller
+    pub const unsafe fn new() -> Self {
+        Self {
+            data: Port::new(DATA_PORT),
+            status: Port::new(STATUS_PORT),
+            command: Port::new(COMMAND_PORT),
+        }
+    }
+
+    /// Initialises the controller and the keyboard on the first port, returning the scancode set
+    /// the keyboard will deliver. Interrupts from the controller should not be handled until this
+    /// returns, since responses from the keyboard are read by polling.
+    ///
+    /// # Safety
+    /// Writes to controller I/O ports, which can have side effects such as resetting the system
+    pub unsafe fn init(&mut self) -> Result<ScancodeSet, Error> {
+        // disable devices so they can't send data during init
+        self.write_command(CMD_DISABLE_FIRST_PORT)?;
+        self.write_command(CMD_DISABLE_SECOND_PORT)?;
+        self.flush();
+        log::trace!("\t* disabled devices");
+
+        // disable interrupts while initialising, remembering if translation is enabled
+        let mut config = self.read_config()?;
+        config.remove(Config::FIRST_PORT_INTERRUPT | Config::SECOND_PORT_INTERRUPT);
+        self.write_config(config)?;
+
+        // self test can reset the controller, so restore config afterwards
+        self.write_command(CMD_SELF_TEST)?;
+        match self.read_data()? {
+            SELF_TEST_PASSED => {}
+            response => return Err(Error::SelfTestFailed(response)),
+        }
+        self.write_config(config)?;
+        log::trace!("\t* controller self test passed");
+
+        self.write_command(CMD_TEST_FIRST_PORT)?;
+        match self.read_data()? {
+            PORT_TEST_PASSED => {}
+            response => return Err(Error::PortTestFailed(response)),
+        }
+
+        self.write_command(CMD_ENABLE_FIRST_PORT)?;
+
+        // reset the keyboard, which also restores its default scancode set and typematic rate
+        self.send(Device::Keyboard, KEYBOARD_RESET)?;
+        match self.read_data()? {
+            DEVICE_SELF_TEST_PASSED => {}
+            response => return Err(Error::UnexpectedResponse(response)),
+        }
+        log::trace!("\t* keyboard reset");
+
+        self.send(Device::Keyboard, KEYBOARD_ENABLE_SCANNING)?;
+
+        config.insert(Config::FIRST_PORT_INTERRUPT);
+        config.remove(Config::FIRST_PORT_CLOCK_DISABLED);
+        self.write_config(config)?;
+
+        // keyboards always start in set 2, so set 1 is only seen if the controller translates it
+        Ok(if config.contains(Config::FIRST_PORT_TRANSLATION) {
+            ScancodeSet::Set1
+        } else {
+            ScancodeSet::Set2
+        })
+    }
+
+    /// Sets the keyboard's key repeat rate and delay, using the format of the `0xF3` command.
+    ///
+    /// # Safety
+    /// Should only be called with interrupts for the first port disabled,
+    /// since the ACK is read by polling
+    pub unsafe fn set_typematic(&mut self, typematic: u8) -> Result<(), Error> {
+        self.send(Device::Keyboard, KEYBOARD_SET_TYPEMATIC)?;
+        self.send(Device::Keyboard, typematic)
+    }
+
+    /// Enables the mouse on the second port, returning true if it has a scroll wheel and so sends
+    /// 4-byte packets. Must be called after [Controller::init], and before interrupts from the
+    /// controller are handled.
+    ///
+    /// # Safety
+    /// Writes to controller I/O ports, which can have side effects such as resetting the system
+    pub unsafe fn init_mouse(&mut self) -> Result<bool, Error> {
+        // a single port controller ignores the enable command, leaving the clock disabled
+        self.write_command(CMD_ENABLE_SECOND_PORT)?;
+        let mut config = self.read_config()?;
+        if config.contains(Config::SECOND_PORT_CLOCK_DISABLED) {
+            return Err(Error::NoSecondPort);
+        }
+
+        // keep the keyboard from interrupting while the mouse is polled
+        config.remove(Config::FIRST_PORT_INTERRUPT | Config::SECOND_PORT_INTERRUPT);
+        self.write_config(config)?;
+
+        let result = self.reset_mouse();
+
+        // always give keyboard interrupts back, even if the mouse failed
+        config.insert(Config::FIRST_PORT_INTERRUPT);
+        config.set(Config::SECOND_PORT_INTERRUPT, result.is_ok());
+        self.write_config(config)?;
+
+        result
+    }
+
+    /// Tests and resets the mouse, then enables IntelliMouse mode and data reporting
+    unsafe fn reset_mouse(&mut self) -> Result<bool, Error> {
+        self.write_command(CMD_TEST_SECOND_PORT)?;
+        match self.read_data()? {
+            PORT_TEST_PASSED => {}
+            response => return Err(Error::PortTestFailed(response)),
+        }
+
+        // reset responds with the self test result followed by the device ID
+        self.send(Device::Mouse, MOUSE_RESET)?;
+        match self.read_aux_data()? {
+            DEVICE_SELF_TEST_PASSED => {}
+            response => return Err(Error::UnexpectedResponse(response)),
+        }
+        self.read_aux_data()?;
+        log::trace!("\t* mouse reset");
+
+        for rate in INTELLIMOUSE_SEQUENCE {
+            self.send(Device::Mouse, MOUSE_SET_SAMPLE_RATE)?;
+            self.send(Device::Mouse, rate)?;
+        }
+
+        self.send(Device::Mouse, MOUSE_GET_ID)?;
+        let has_wheel = self.read_aux_data()? == MOUSE_ID_INTELLIMOUSE;
+
+        self.send(Device::Mouse, MOUSE_ENABLE_REPORTING)?;
+
+        Ok(has_wheel)
+    }
+
+    /// Reads the current status register
+    pub fn status(&mut self) -> Status {
+        Status::from_bits_retain(unsafe { self.status.read() })
+    }
+
+    /// Reads a byte from the data port without waiting, such as from an interrupt handler.
+    ///
+    /// # Safety
+    /// Reading the data port consumes the byte, so it must not be needed elsewhere
+    pub unsafe fn read_data_unchecked(&mut self) -> u8 {
+        self.data.read()
+    }
+
+    /// Waits for a byte from the data port
+    unsafe fn read_data(&mut self) -> Result<u8, Error> {
+        self.wait_for(|status| status.contains(Status::OUTPUT_FULL))?;
+
+        Ok(self.data.read())
+    }
+
+    /// Waits for a byte from the second port, discarding any keyboard data that arrives first
+    unsafe fn read_aux_data(&mut self) -> Result<u8, Error> {
+        for _ in 0..TIMEOUT {
+            let status = self.status();
+
+            if status.contains(Status::OUTPUT_FULL | Status::AUX_OUTPUT_FULL) {
+                return Ok(self.data.read());
+            } else if status.contains(Status::OUTPUT_FULL) {
+                self.data.read();
+            }
+
+            core::hint::spin_loop();
+        }
+
+        Err(Error::Timeout)
+    }
+
+    /// Writes a byte to the data port once the controller is ready
+    unsafe fn write_data(&mut self, data: u8) -> Result<(), Error> {
+        self.wait_for(|status| !status.contains(Status::INPUT_FULL))?;
+        self.data.write(data);
+
+        Ok(())
+    }
+
+    /// Writes a command to the controller once it's ready
+    unsafe fn write_command(&mut self, command: u8) -> Result<(), Error> {
+        self.wait_for(|status| !status.contains(Status::INPUT_FULL))?;
+        self.command.write(command);
+
+        Ok(())
+    }
+
+    /// Reads the controller configuration byte
+    unsafe fn read_config(&mut self) -> Result<Config, Error> {
+        self.write_command(CMD_READ_CONFIG)?;
+
+        Ok(Config::from_bits_retain(self.read_data()?))
+    }
+
+    /// Writes the controller configuration byte
+    unsafe fn write_config(&mut self, config: Config) -> Result<(), Error> {
+        self.write_command(CMD_WRITE_CONFIG)?;
+        self.write_data(config.bits())
+    }
+
+    /// Sends a byte to a device, resending it if requested, and waits for an ACK
+    unsafe fn send(&mut self, device: Device, byte: u8) -> Result<(), Error> {
+        for _ in 0..RETRIES {
+            if device == Device::Mouse {
+                self.write_command(CMD_WRITE_SECOND_PORT)?;
+            }
+            self.write_data(byte)?;
+
+            let response = match device {
+                Device::Keyboard => self.read_data()?,
+                Device::Mouse => self.read_aux_data()?,
+            };
+
+            match response {
+                DEVICE_ACK => return Ok(()),
+                DEVICE_RESEND => continue,
+                response => return Err(Error::UnexpectedResponse(response)),
+            }
+        }
+
+        Err(Error::UnexpectedResponse(DEVICE_RESEND))
+    }
+
+    /// Discards any data waiting in the output buffer
+    unsafe fn flush(&mut self) {
+        while self.status().contains(Status::OUTPUT_FULL) {
+            self.data.read();
+        }
+    }
+
+    /// Polls the status register until `condition` is met
+    fn wait_for(&mut self, condition: impl Fn(Status) -> bool) -> Result<(), Error> {
+        for _ in 0..TIMEOUT {
+            if condition(self.status()) {
+                return Ok(());
+            }
+
+            core::hint::spin_loop();
+        }
+
+        Err(Error::Timeout)
+    }
+}
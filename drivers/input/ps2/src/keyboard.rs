@@ -0,0 +1,747 @@
+use bitflags::bitflags;
+
+use crate::layout::Keymap;
+
+/// Prefix byte for extended keys
+const EXTENDED: u8 = 0xE0;
+/// Prefix byte for the pause key, which sends a sequence with no release
+const PAUSE: u8 = 0xE1;
+/// Prefix byte for key releases in scancode set 2
+const SET2_RELEASE: u8 = 0xF0;
+/// Bit set on key releases in scancode set 1
+const SET1_RELEASE: u8 = 0x80;
+
+/// Number of bytes following the pause prefix in each scancode set
+const SET1_PAUSE_LEN: u8 = 5;
+const SET2_PAUSE_LEN: u8 = 7;
+
+/// Scancode set being sent by the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSet {
+    Set1,
+    Set2,
+}
+
+/// Physical key, named after its position on a US keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyCode {
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+
+    Grave,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Key0,
+    Minus,
+    Equals,
+    Backspace,
+
+    Tab,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    LeftBracket,
+    RightBracket,
+    /// Also the `#` key on ISO keyboards
+    Backslash,
+
+    CapsLock,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Apostrophe,
+    Enter,
+
+    LeftShift,
+    /// Extra key next to left shift on ISO keyboards
+    NonUsBackslash,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    RightShift,
+
+    LeftCtrl,
+    LeftSuper,
+    LeftAlt,
+    Space,
+    RightAlt,
+    RightSuper,
+    Menu,
+    RightCtrl,
+
+    Insert,
+    Home,
+    PageUp,
+    Delete,
+    End,
+    PageDown,
+    Up,
+    Left,
+    Down,
+    Right,
+
+    NumLock,
+    KeypadDivide,
+    KeypadMultiply,
+    KeypadMinus,
+    KeypadPlus,
+    KeypadEnter,
+    KeypadPeriod,
+    Keypad0,
+    Keypad1,
+    Keypad2,
+    Keypad3,
+    Keypad4,
+    Keypad5,
+    Keypad6,
+    Keypad7,
+    Keypad8,
+    Keypad9,
+}
+
+bitflags! {
+    /// Modifier keys and lock states
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Modifiers: u16 {
+        const LEFT_SHIFT = 1 << 0;
+        const RIGHT_SHIFT = 1 << 1;
+        const LEFT_CTRL = 1 << 2;
+        const RIGHT_CTRL = 1 << 3;
+        const LEFT_ALT = 1 << 4;
+        /// Also known as AltGr
+        const RIGHT_ALT = 1 << 5;
+        const LEFT_SUPER = 1 << 6;
+        const RIGHT_SUPER = 1 << 7;
+        const CAPS_LOCK = 1 << 8;
+        const NUM_LOCK = 1 << 9;
+        const SCROLL_LOCK = 1 << 10;
+    }
+}
+
+impl Modifiers {
+    /// Returns true if either shift key is held
+    pub fn shift(&self) -> bool {
+        self.intersects(Self::LEFT_SHIFT | Self::RIGHT_SHIFT)
+    }
+
+    /// Returns true if either ctrl key is held
+    pub fn ctrl(&self) -> bool {
+        self.intersects(Self::LEFT_CTRL | Self::RIGHT_CTRL)
+    }
+
+    /// Returns true if the left alt key is held
+    pub fn alt(&self) -> bool {
+        self.contains(Self::LEFT_ALT)
+    }
+
+    /// Returns true if the right alt key is held
+    pub fn alt_gr(&self) -> bool {
+        self.contains(Self::RIGHT_ALT)
+    }
+}
+
+/// Whether a key was pressed, released, or is being held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    /// Key was already pressed, and the keyboard is repeating it
+    Repeat,
+    Released,
+}
+
+/// A single decoded key event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub state: KeyState,
+    /// Modifiers after this event was processed
+    pub modifiers: Modifiers,
+    /// Character produced by the current keymap, if any
+    pub character: Option<char>,
+}
+
+/// State of the multi-byte scancode decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Start,
+    Extended,
+    Release,
+    ExtendedRelease,
+    /// Skipping the given number of bytes of a pause sequence
+    Pause(u8),
+}
+
+/// Decodes scancodes into key events, tracking modifiers and held keys
+pub struct Keyboard {
+    set: ScancodeSet,
+    state: DecodeState,
+    modifiers: Modifiers,
+    /// Bitmap of currently held keys, indexed by [KeyCode]
+    pressed: [u64; 2],
+    keymap: &'static dyn Keymap,
+}
+
+impl Keyboard {
+    /// Constructs a new keyboard decoder for the given scancode set and keymap
+    pub const fn new(set: ScancodeSet, keymap: &'static dyn Keymap) -> Self {
+        Self {
+            set,
+            state: DecodeState::Start,
+            modifiers: Modifiers::NUM_LOCK,
+            pressed: [0; 2],
+            keymap,
+        }
+    }
+
+    /// Changes the keymap used to produce characters
+    pub fn set_keymap(&mut self, keymap: &'static dyn Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Returns the currently active modifiers
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Processes a single byte from the keyboard, returning an event once a full scancode is read
+    pub fn process_byte(&mut self, byte: u8) -> Option<KeyEvent> {
+        let (code, pressed) = match self.set {
+            ScancodeSet::Set1 => self.decode_set1(byte)?,
+            ScancodeSet::Set2 => self.decode_set2(byte)?,
+        };
+
+        Some(self.handle_key(code, pressed))
+    }
+
+    /// Decodes a byte of scancode set 1, returning the key and whether it was pressed
+    fn decode_set1(&mut self, byte: u8) -> Option<(KeyCode, bool)> {
+        let state = core::mem::replace(&mut self.state, DecodeState::Start);
+        let pressed = byte & SET1_RELEASE == 0;
+
+        match (state, byte) {
+            (DecodeState::Pause(remaining), _) => {
+                self.skip_pause(remaining);
+                None
+            }
+            (_, PAUSE) => {
+                self.state = DecodeState::Pause(SET1_PAUSE_LEN);
+                Some((KeyCode::Pause, true))
+            }
+            (_, EXTENDED) => {
+                self.state = DecodeState::Extended;
+                None
+            }
+            (DecodeState::Extended, _) => Some((set1_extended(byte & !SET1_RELEASE)?, pressed)),
+            _ => Some((set1(byte & !SET1_RELEASE)?, pressed)),
+        }
+    }
+
+    /// Decodes a byte of scancode set 2, returning the key and whether it was pressed
+    fn decode_set2(&mut self, byte: u8) -> Option<(KeyCode, bool)> {
+        let state = core::mem::replace(&mut self.state, DecodeState::Start);
+
+        match (state, byte) {
+            (DecodeState::Pause(remaining), _) => {
+                self.skip_pause(remaining);
+                None
+            }
+            (_, PAUSE) => {
+                self.state = DecodeState::Pause(SET2_PAUSE_LEN);
+                Some((KeyCode::Pause, true))
+            }
+            (DecodeState::Start, EXTENDED) => {
+                self.state = DecodeState::Extended;
+                None
+            }
+            (DecodeState::Start, SET2_RELEASE) => {
+                self.state = DecodeState::Release;
+                None
+            }
+            (DecodeState::Extended, SET2_RELEASE) => {
+                self.state = DecodeState::ExtendedRelease;
+                None
+            }
+            (DecodeState::Start, _) => Some((set2(byte)?, true)),
+            (DecodeState::Release, _) => Some((set2(byte)?, false)),
+            (DecodeState::Extended, _) => Some((set2_extended(byte)?, true)),
+            (DecodeState::ExtendedRelease, _) => Some((set2_extended(byte)?, false)),
+        }
+    }
+
+    /// Consumes one byte of a pause sequence
+    fn skip_pause(&mut self, remaining: u8) {
+        if remaining > 1 {
+            self.state = DecodeState::Pause(remaining - 1);
+        }
+    }
+
+    /// Updates held keys and modifiers, then builds the event for the key
+    fn handle_key(&mut self, code: KeyCode, pressed: bool) -> KeyEvent {
+        let (index, bit) = (code as usize / 64, 1 << (code as usize % 64));
+        let was_pressed = self.pressed[index] & bit != 0;
+
+        let state = match (pressed, was_pressed) {
+            (true, true) => KeyState::Repeat,
+            (true, false) => KeyState::Pressed,
+            (false, _) => KeyState::Released,
+        };
+
+        // pause never sends a release, so don't track it
+        if pressed && code != KeyCode::Pause {
+            self.pressed[index] |= bit;
+        } else {
+            self.pressed[index] &= !bit;
+        }
+
+        let held = match code {
+            KeyCode::LeftShift => Some(Modifiers::LEFT_SHIFT),
+            KeyCode::RightShift => Some(Modifiers::RIGHT_SHIFT),
+            KeyCode::LeftCtrl => Some(Modifiers::LEFT_CTRL),
+            KeyCode::RightCtrl => Some(Modifiers::RIGHT_CTRL),
+            KeyCode::LeftAlt => Some(Modifiers::LEFT_ALT),
+            KeyCode::RightAlt => Some(Modifiers::RIGHT_ALT),
+            KeyCode::LeftSuper => Some(Modifiers::LEFT_SUPER),
+            KeyCode::RightSuper => Some(Modifiers::RIGHT_SUPER),
+            _ => None,
+        };
+
+        let lock = match code {
+            KeyCode::CapsLock => Some(Modifiers::CAPS_LOCK),
+            KeyCode::NumLock => Some(Modifiers::NUM_LOCK),
+            KeyCode::ScrollLock => Some(Modifiers::SCROLL_LOCK),
+            _ => None,
+        };
+
+        if let Some(modifier) = held {
+            self.modifiers.set(modifier, pressed);
+        } else if let (Some(lock), KeyState::Pressed) = (lock, state) {
+            self.modifiers.toggle(lock);
+        }
+
+        let character = match state {
+            KeyState::Released => None,
+            _ => self.keymap.map(code, self.modifiers),
+        };
+
+        KeyEvent {
+            code,
+            state,
+            modifiers: self.modifiers,
+            character,
+        }
+    }
+}
+
+/// Converts a scancode set 1 make code into a key
+fn set1(code: u8) -> Option<KeyCode> {
+    Some(match code {
+        0x01 => KeyCode::Escape,
+        0x02 => KeyCode::Key1,
+        0x03 => KeyCode::Key2,
+        0x04 => KeyCode::Key3,
+        0x05 => KeyCode::Key4,
+        0x06 => KeyCode::Key5,
+        0x07 => KeyCode::Key6,
+        0x08 => KeyCode::Key7,
+        0x09 => KeyCode::Key8,
+        0x0A => KeyCode::Key9,
+        0x0B => KeyCode::Key0,
+        0x0C => KeyCode::Minus,
+        0x0D => KeyCode::Equals,
+        0x0E => KeyCode::Backspace,
+        0x0F => KeyCode::Tab,
+        0x10 => KeyCode::Q,
+        0x11 => KeyCode::W,
+        0x12 => KeyCode::E,
+        0x13 => KeyCode::R,
+        0x14 => KeyCode::T,
+        0x15 => KeyCode::Y,
+        0x16 => KeyCode::U,
+        0x17 => KeyCode::I,
+        0x18 => KeyCode::O,
+        0x19 => KeyCode::P,
+        0x1A => KeyCode::LeftBracket,
+        0x1B => KeyCode::RightBracket,
+        0x1C => KeyCode::Enter,
+        0x1D => KeyCode::LeftCtrl,
+        0x1E => KeyCode::A,
+        0x1F => KeyCode::S,
+        0x20 => KeyCode::D,
+        0x21 => KeyCode::F,
+        0x22 => KeyCode::G,
+        0x23 => KeyCode::H,
+        0x24 => KeyCode::J,
+        0x25 => KeyCode::K,
+        0x26 => KeyCode::L,
+        0x27 => KeyCode::Semicolon,
+        0x28 => KeyCode::Apostrophe,
+        0x29 => KeyCode::Grave,
+        0x2A => KeyCode::LeftShift,
+        0x2B => KeyCode::Backslash,
+        0x2C => KeyCode::Z,
+        0x2D => KeyCode::X,
+        0x2E => KeyCode::C,
+        0x2F => KeyCode::V,
+        0x30 => KeyCode::B,
+        0x31 => KeyCode::N,
+        0x32 => KeyCode::M,
+        0x33 => KeyCode::Comma,
+        0x34 => KeyCode::Period,
+        0x35 => KeyCode::Slash,
+        0x36 => KeyCode::RightShift,
+        0x37 => KeyCode::KeypadMultiply,
+        0x38 => KeyCode::LeftAlt,
+        0x39 => KeyCode::Space,
+        0x3A => KeyCode::CapsLock,
+        0x3B => KeyCode::F1,
+        0x3C => KeyCode::F2,
+        0x3D => KeyCode::F3,
+        0x3E => KeyCode::F4,
+        0x3F => KeyCode::F5,
+        0x40 => KeyCode::F6,
+        0x41 => KeyCode::F7,
+        0x42 => KeyCode::F8,
+        0x43 => KeyCode::F9,
+        0x44 => KeyCode::F10,
+        0x45 => KeyCode::NumLock,
+        0x46 => KeyCode::ScrollLock,
+        0x47 => KeyCode::Keypad7,
+        0x48 => KeyCode::Keypad8,
+        0x49 => KeyCode::Keypad9,
+        0x4A => KeyCode::KeypadMinus,
+        0x4B => KeyCode::Keypad4,
+        0x4C => KeyCode::Keypad5,
+        0x4D => KeyCode::Keypad6,
+        0x4E => KeyCode::KeypadPlus,
+        0x4F => KeyCode::Keypad1,
+        0x50 => KeyCode::Keypad2,
+        0x51 => KeyCode::Keypad3,
+        0x52 => KeyCode::Keypad0,
+        0x53 => KeyCode::KeypadPeriod,
+        0x56 => KeyCode::NonUsBackslash,
+        0x57 => KeyCode::F11,
+        0x58 => KeyCode::F12,
+        _ => return None,
+    })
+}
+
+/// Converts a scancode set 1 make code following the extended prefix into a key
+fn set1_extended(code: u8) -> Option<KeyCode> {
+    Some(match code {
+        0x1C => KeyCode::KeypadEnter,
+        0x1D => KeyCode::RightCtrl,
+        0x35 => KeyCode::KeypadDivide,
+        0x37 => KeyCode::PrintScreen,
+        0x38 => KeyCode::RightAlt,
+        0x47 => KeyCode::Home,
+        0x48 => KeyCode::Up,
+        0x49 => KeyCode::PageUp,
+        0x4B => KeyCode::Left,
+        0x4D => KeyCode::Right,
+        0x4F => KeyCode::End,
+        0x50 => KeyCode::Down,
+        0x51 => KeyCode::PageDown,
+        0x52 => KeyCode::Insert,
+        0x53 => KeyCode::Delete,
+        0x5B => KeyCode::LeftSuper,
+        0x5C => KeyCode::RightSuper,
+        0x5D => KeyCode::Menu,
+        _ => return None,
+    })
+}
+
+/// Converts a scancode set 2 make code into a key
+fn set2(code: u8) -> Option<KeyCode> {
+    Some(match code {
+        0x01 => KeyCode::F9,
+        0x03 => KeyCode::F5,
+        0x04 => KeyCode::F3,
+        0x05 => KeyCode::F1,
+        0x06 => KeyCode::F2,
+        0x07 => KeyCode::F12,
+        0x09 => KeyCode::F10,
+        0x0A => KeyCode::F8,
+        0x0B => KeyCode::F6,
+        0x0C => KeyCode::F4,
+        0x0D => KeyCode::Tab,
+        0x0E => KeyCode::Grave,
+        0x11 => KeyCode::LeftAlt,
+        0x12 => KeyCode::LeftShift,
+        0x14 => KeyCode::LeftCtrl,
+        0x15 => KeyCode::Q,
+        0x16 => KeyCode::Key1,
+        0x1A => KeyCode::Z,
+        0x1B => KeyCode::S,
+        0x1C => KeyCode::A,
+        0x1D => KeyCode::W,
+        0x1E => KeyCode::Key2,
+        0x21 => KeyCode::C,
+        0x22 => KeyCode::X,
+        0x23 => KeyCode::D,
+        0x24 => KeyCode::E,
+        0x25 => KeyCode::Key4,
+        0x26 => KeyCode::Key3,
+        0x29 => KeyCode::Space,
+        0x2A => KeyCode::V,
+        0x2B => KeyCode::F,
+        0x2C => KeyCode::T,
+        0x2D => KeyCode::R,
+        0x2E => KeyCode::Key5,
+        0x31 => KeyCode::N,
+        0x32 => KeyCode::B,
+        0x33 => KeyCode::H,
+        0x34 => KeyCode::G,
+        0x35 => KeyCode::Y,
+        0x36 => KeyCode::Key6,
+        0x3A => KeyCode::M,
+        0x3B => KeyCode::J,
+        0x3C => KeyCode::U,
+        0x3D => KeyCode::Key7,
+        0x3E => KeyCode::Key8,
+        0x41 => KeyCode::Comma,
+        0x42 => KeyCode::K,
+        0x43 => KeyCode::I,
+        0x44 => KeyCode::O,
+        0x45 => KeyCode::Key0,
+        0x46 => KeyCode::Key9,
+        0x49 => KeyCode::Period,
+        0x4A => KeyCode::Slash,
+        0x4B => KeyCode::L,
+        0x4C => KeyCode::Semicolon,
+        0x4D => KeyCode::P,
+        0x4E => KeyCode::Minus,
+        0x52 => KeyCode::Apostrophe,
+        0x54 => KeyCode::LeftBracket,
+        0x55 => KeyCode::Equals,
+        0x58 => KeyCode::CapsLock,
+        0x59 => KeyCode::RightShift,
+        0x5A => KeyCode::Enter,
+        0x5B => KeyCode::RightBracket,
+        0x5D => KeyCode::Backslash,
+        0x61 => KeyCode::NonUsBackslash,
+        0x66 => KeyCode::Backspace,
+        0x69 => KeyCode::Keypad1,
+        0x6B => KeyCode::Keypad4,
+        0x6C => KeyCode::Keypad7,
+        0x70 => KeyCode::Keypad0,
+        0x71 => KeyCode::KeypadPeriod,
+        0x72 => KeyCode::Keypad2,
+        0x73 => KeyCode::Keypad5,
+        0x74 => KeyCode::Keypad6,
+        0x75 => KeyCode::Keypad8,
+        0x76 => KeyCode::Escape,
+        0x77 => KeyCode::NumLock,
+        0x78 => KeyCode::F11,
+        0x79 => KeyCode::KeypadPlus,
+        0x7A => KeyCode::Keypad3,
+        0x7B => KeyCode::KeypadMinus,
+        0x7C => KeyCode::KeypadMultiply,
+        0x7D => KeyCode::Keypad9,
+        0x7E => KeyCode::ScrollLock,
+        0x83 => KeyCode::F7,
+        _ => return None,
+    })
+}
+
+/// Converts a scancode set 2 make code following the extended prefix into a key
+fn set2_extended(code: u8) -> Option<KeyCode> {
+    Some(match code {
+        0x11 => KeyCode::RightAlt,
+        0x14 => KeyCode::RightCtrl,
+        0x1F => KeyCode::LeftSuper,
+        0x27 => KeyCode::RightSuper,
+        0x2F => KeyCode::Menu,
+        0x4A => KeyCode::KeypadDivide,
+        0x5A => KeyCode::KeypadEnter,
+        0x69 => KeyCode::End,
+        0x6B => KeyCode::Left,
+        0x6C => KeyCode::Home,
+        0x70 => KeyCode::Insert,
+        0x71 => KeyCode::Delete,
+        0x72 => KeyCode::Down,
+        0x74 => KeyCode::Right,
+        0x75 => KeyCode::Up,
+        0x7A => KeyCode::PageDown,
+        0x7C => KeyCode::PrintScreen,
+        0x7D => KeyCode::PageUp,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::layout::{Uk, Us};
+
+    /// Feeds every byte through the keyboard, returning the events produced
+    fn feed(keyboard: &mut Keyboard, bytes: &[u8]) -> Vec<KeyEvent> {
+        bytes
+            .iter()
+            .filter_map(|&byte| keyboard.process_byte(byte))
+            .collect()
+    }
+
+    /// Returns the code, state and character of each event
+    fn keys(events: Vec<KeyEvent>) -> Vec<(KeyCode, KeyState, Option<char>)> {
+        events
+            .into_iter()
+            .map(|event| (event.code, event.state, event.character))
+            .collect()
+    }
+
+    #[test]
+    fn set1_make_and_break() {
+        let mut keyboard = Keyboard::new(ScancodeSet::Set1, &Us);
+
+        assert_eq!(keys(feed(&mut keyboard, &[0x1E, 0x1E, 0x9E])), [
+            (KeyCode::A, KeyState::Pressed, Some('a')),
+            (KeyCode::A, KeyState::Repeat, Some('a')),
+            (KeyCode::A, KeyState::Released, None),
+        ]);
+    }
+
+    #[test]
+    fn set2_make_and_break() {
+        let mut keyboard = Keyboard::new(ScancodeSet::Set2, &Us);
+
+        assert_eq!(keys(feed(&mut keyboard, &[0x1C, 0xF0, 0x1C])), [
+            (KeyCode::A, KeyState::Pressed, Some('a')),
+            (KeyCode::A, KeyState::Released, None),
+        ]);
+    }
+
+    #[test]
+    fn extended_keys() {
+        // without the prefix, the same byte is the keypad key sharing the arrow
+        let mut keyboard = Keyboard::new(ScancodeSet::Set1, &Us);
+        assert_eq!(
+            keys(feed(&mut keyboard, &[0x48, 0xC8, 0xE0, 0x48, 0xE0, 0xC8])),
+            [
+                (KeyCode::Keypad8, KeyState::Pressed, Some('8')),
+                (KeyCode::Keypad8, KeyState::Released, None),
+                (KeyCode::Up, KeyState::Pressed, None),
+                (KeyCode::Up, KeyState::Released, None),
+            ]
+        );
+
+        let mut keyboard = Keyboard::new(ScancodeSet::Set2, &Us);
+        assert_eq!(
+            keys(feed(&mut keyboard, &[
+                0x75, 0xF0, 0x75, 0xE0, 0x75, 0xE0, 0xF0, 0x75
+            ])),
+            [
+                (KeyCode::Keypad8, KeyState::Pressed, Some('8')),
+                (KeyCode::Keypad8, KeyState::Released, None),
+                (KeyCode::Up, KeyState::Pressed, None),
+                (KeyCode::Up, KeyState::Released, None),
+            ]
+        );
+
+        let events = feed(&mut keyboard, &[0xE0, 0x14]);
+        assert_eq!(events[0].code, KeyCode::RightCtrl);
+        assert!(events[0].modifiers.ctrl());
+
+        let events = feed(&mut keyboard, &[0xE0, 0xF0, 0x14]);
+        assert!(!events[0].modifiers.ctrl());
+    }
+
+    #[test]
+    fn shift_and_caps_lock() {
+        /// Presses and releases `a` in scancode set 2, returning the character produced
+        fn type_a(keyboard: &mut Keyboard) -> Option<char> {
+            feed(keyboard, &[0x1C, 0xF0, 0x1C])[0].character
+        }
+
+        let mut keyboard = Keyboard::new(ScancodeSet::Set2, &Us);
+        assert_eq!(type_a(&mut keyboard), Some('a'));
+
+        feed(&mut keyboard, &[0x12]);
+        assert!(keyboard.modifiers().shift());
+        assert_eq!(type_a(&mut keyboard), Some('A'));
+        feed(&mut keyboard, &[0xF0, 0x12]);
+        assert!(!keyboard.modifiers().shift());
+
+        // caps lock toggles on press, so holding or releasing it changes nothing
+        feed(&mut keyboard, &[0x58, 0x58, 0xF0, 0x58]);
+        assert!(keyboard.modifiers().contains(Modifiers::CAPS_LOCK));
+        assert_eq!(type_a(&mut keyboard), Some('A'));
+
+        // shift undoes caps lock for letters
+        feed(&mut keyboard, &[0x12]);
+        assert_eq!(type_a(&mut keyboard), Some('a'));
+        feed(&mut keyboard, &[0xF0, 0x12]);
+
+        feed(&mut keyboard, &[0x58, 0xF0, 0x58]);
+        assert!(!keyboard.modifiers().contains(Modifiers::CAPS_LOCK));
+        assert_eq!(type_a(&mut keyboard), Some('a'));
+    }
+
+    #[test]
+    fn layouts() {
+        /// Types `3`, shift+`2` and altgr+`4` with the given keymap
+        fn type_keys(keymap: &'static dyn Keymap) -> [Option<char>; 3] {
+            let mut keyboard = Keyboard::new(ScancodeSet::Set1, keymap);
+
+            [
+                feed(&mut keyboard, &[0x04, 0x84])[0].character,
+                feed(&mut keyboard, &[0x2A, 0x03, 0x83, 0xAA])[1].character,
+                feed(&mut keyboard, &[0xE0, 0x38, 0x05, 0x85, 0xE0, 0xB8])[1].character,
+            ]
+        }
+
+        assert_eq!(type_keys(&Us), [Some('3'), Some('@'), Some('4')]);
+        assert_eq!(type_keys(&Uk), [Some('3'), Some('"'), Some('€')]);
+
+        // switching keymaps keeps the held modifiers
+        let mut keyboard = Keyboard::new(ScancodeSet::Set2, &Us);
+        feed(&mut keyboard, &[0x12]);
+        assert_eq!(feed(&mut keyboard, &[0x26])[0].character, Some('#'));
+        keyboard.set_keymap(&Uk);
+        assert_eq!(feed(&mut keyboard, &[0x26])[0].character, Some('£'));
+    }
+}
@@ -0,0 +1,99 @@
+mod uk;
+mod us;
+
+pub use uk::Uk;
+pub use us::Us;
+
+use crate::keyboard::{KeyCode, Modifiers};
+
+/// Maps physical keys to the characters they produce
+pub trait Keymap: Sync {
+    /// Returns the character produced by the key with the given modifiers, if any
+    fn map(&self, code: KeyCode, modifiers: Modifiers) -> Option<char>;
+}
+
+/// Finds a keymap by name, such as `us` or `uk`
+pub fn by_name(name: &str) -> Option<&'static dyn Keymap> {
+    match name {
+        "us" => Some(&Us),
+        "uk" => Some(&Uk),
+        _ => None,
+    }
+}
+
+/// Maps keys which produce the same character on every layout, such as control keys and the keypad
+fn map_common(code: KeyCode, modifiers: Modifiers) -> Option<char> {
+    let num_lock = modifiers.contains(Modifiers::NUM_LOCK);
+
+    Some(match code {
+        KeyCode::Escape => '\x1B',
+        KeyCode::Backspace => '\x08',
+        KeyCode::Tab => '\t',
+        KeyCode::Enter | KeyCode::KeypadEnter => '\n',
+        KeyCode::Space => ' ',
+        KeyCode::Delete => '\x7F',
+        KeyCode::KeypadDivide => '/',
+        KeyCode::KeypadMultiply => '*',
+        KeyCode::KeypadMinus => '-',
+        KeyCode::KeypadPlus => '+',
+        KeyCode::KeypadPeriod if num_lock => '.',
+        KeyCode::Keypad0 if num_lock => '0',
+        KeyCode::Keypad1 if num_lock => '1',
+        KeyCode::Keypad2 if num_lock => '2',
+        KeyCode::Keypad3 if num_lock => '3',
+        KeyCode::Keypad4 if num_lock => '4',
+        KeyCode::Keypad5 if num_lock => '5',
+        KeyCode::Keypad6 if num_lock => '6',
+        KeyCode::Keypad7 if num_lock => '7',
+        KeyCode::Keypad8 if num_lock => '8',
+        KeyCode::Keypad9 if num_lock => '9',
+        _ => return None,
+    })
+}
+
+/// Applies shift, caps lock and ctrl to a letter key
+fn map_letter(letter: char, modifiers: Modifiers) -> char {
+    if modifiers.ctrl() {
+        // ctrl+letter gives the matching control character, such as ctrl+c giving ETX
+        return ((letter as u8) & 0x1F) as char;
+    }
+
+    if modifiers.shift() != modifiers.contains(Modifiers::CAPS_LOCK) {
+        letter.to_ascii_uppercase()
+    } else {
+        letter
+    }
+}
+
+/// Returns the lowercase letter for a letter key
+fn letter(code: KeyCode) -> Option<char> {
+    Some(match code {
+        KeyCode::A => 'a',
+        KeyCode::B => 'b',
+        KeyCode::C => 'c',
+        KeyCode::D => 'd',
+        KeyCode::E => 'e',
+        KeyCode::F => 'f',
+        KeyCode::G => 'g',
+        KeyCode::H => 'h',
+        KeyCode::I => 'i',
+        KeyCode::J => 'j',
+        KeyCode::K => 'k',
+        KeyCode::L => 'l',
+        KeyCode::M => 'm',
+        KeyCode::N => 'n',
+        KeyCode::O => 'o',
+        KeyCode::P => 'p',
+        KeyCode::Q => 'q',
+        KeyCode::R => 'r',
+        KeyCode::S => 's',
+        KeyCode::T => 't',
+        KeyCode::U => 'u',
+        KeyCode::V => 'v',
+        KeyCode::W => 'w',
+        KeyCode::X => 'x',
+        KeyCode::Y => 'y',
+        KeyCode::Z => 'z',
+        _ => return None,
+    })
+}
@@ -0,0 +1,22 @@
+use super::{Keymap, Us};
+use crate::keyboard::{KeyCode, Modifiers};
+
+/// UK QWERTY layout, which only differs from [Us] for a handful of keys
+pub struct Uk;
+
+impl Keymap for Uk {
+    fn map(&self, code: KeyCode, modifiers: Modifiers) -> Option<char> {
+        let (normal, shifted) = match code {
+            KeyCode::Key4 if modifiers.alt_gr() => return Some('€'),
+            KeyCode::Grave => ('`', '¬'),
+            KeyCode::Key2 => ('2', '"'),
+            KeyCode::Key3 => ('3', '£'),
+            KeyCode::Apostrophe => ('\'', '@'),
+            KeyCode::Backslash => ('#', '~'),
+            KeyCode::NonUsBackslash => ('\\', '|'),
+            _ => return Us.map(code, modifiers),
+        };
+
+        Some(if modifiers.shift() { shifted } else { normal })
+    }
+}
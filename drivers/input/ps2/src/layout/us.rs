@@ -0,0 +1,40 @@
+use super::{letter, map_common, map_letter, Keymap};
+use crate::keyboard::{KeyCode, Modifiers};
+
+/// US QWERTY layout
+pub struct Us;
+
+impl Keymap for Us {
+    fn map(&self, code: KeyCode, modifiers: Modifiers) -> Option<char> {
+        if let Some(letter) = letter(code) {
+            return Some(map_letter(letter, modifiers));
+        }
+
+        let (normal, shifted) = match code {
+            KeyCode::Grave => ('`', '~'),
+            KeyCode::Key1 => ('1', '!'),
+            KeyCode::Key2 => ('2', '@'),
+            KeyCode::Key3 => ('3', '#'),
+            KeyCode::Key4 => ('4', '$'),
+            KeyCode::Key5 => ('5', '%'),
+            KeyCode::Key6 => ('6', '^'),
+            KeyCode::Key7 => ('7', '&'),
+            KeyCode::Key8 => ('8', '*'),
+            KeyCode::Key9 => ('9', '('),
+            KeyCode::Key0 => ('0', ')'),
+            KeyCode::Minus => ('-', '_'),
+            KeyCode::Equals => ('=', '+'),
+            KeyCode::LeftBracket => ('[', '{'),
+            KeyCode::RightBracket => (']', '}'),
+            KeyCode::Backslash | KeyCode::NonUsBackslash => ('\\', '|'),
+            KeyCode::Semicolon => (';', ':'),
+            KeyCode::Apostrophe => ('\'', '"'),
+            KeyCode::Comma => (',', '<'),
+            KeyCode::Period => ('.', '>'),
+            KeyCode::Slash => ('/', '?'),
+            _ => return map_common(code, modifiers),
+        };
+
+        Some(if modifiers.shift() { shifted } else { normal })
+    }
+}
@@ -0,0 +1,14 @@
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod controller;
+pub mod keyboard;
+pub mod layout;
+pub mod mouse;
+
+pub use controller::{Controller, Device, Error};
+pub use keyboard::{KeyCode, KeyEvent, KeyState, Keyboard, Modifiers, ScancodeSet};
+pub use layout::Keymap;
+pub use mouse::{Mouse, MouseButtons, MouseEvent};
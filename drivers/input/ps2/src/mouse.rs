@@ -0,0 +1,131 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Mouse buttons, in the same bit positions as the first byte of a packet
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MouseButtons: u8 {
+        const LEFT = 1 << 0;
+        const RIGHT = 1 << 1;
+        const MIDDLE = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// Flags in the first byte of a packet
+    #[derive(Debug, Clone, Copy)]
+    struct PacketFlags: u8 {
+        /// Always set, used to find the start of a packet
+        const ALWAYS_ONE = 1 << 3;
+        const X_SIGN = 1 << 4;
+        const Y_SIGN = 1 << 5;
+        const X_OVERFLOW = 1 << 6;
+        const Y_OVERFLOW = 1 << 7;
+    }
+}
+
+/// A single decoded mouse packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    /// Relative horizontal motion, positive to the right
+    pub dx: i16,
+    /// Relative vertical motion, positive downwards to match screen coordinates
+    pub dy: i16,
+    /// Relative wheel motion, positive when scrolling down, always zero without a wheel
+    pub wheel: i8,
+    /// Buttons currently held
+    pub buttons: MouseButtons,
+    /// Buttons which were pressed or released since the last event
+    pub changed: MouseButtons,
+}
+
+/// Assembles bytes from the mouse into packets
+pub struct Mouse {
+    has_wheel: bool,
+    packet: [u8; 4],
+    index: usize,
+    buttons: MouseButtons,
+}
+
+impl Mouse {
+    /// Constructs a new mouse decoder, with `has_wheel` selecting 4-byte IntelliMouse packets
+    pub const fn new(has_wheel: bool) -> Self {
+        Self {
+            has_wheel,
+            packet: [0; 4],
+            index: 0,
+            buttons: MouseButtons::empty(),
+        }
+    }
+
+    /// Returns the length of packets sent by the mouse
+    pub fn packet_len(&self) -> usize {
+        if self.has_wheel {
+            4
+        } else {
+            3
+        }
+    }
+
+    /// Processes a single byte from the mouse, returning an event once a full packet is read
+    pub fn process_byte(&mut self, byte: u8) -> Option<MouseEvent> {
+        // drop bytes until we're back in sync with the start of a packet
+        if self.index == 0 && !PacketFlags::from_bits_retain(byte).contains(PacketFlags::ALWAYS_ONE)
+        {
+            return None;
+        }
+
+        self.packet[self.index] = byte;
+        self.index += 1;
+
+        if self.index < self.packet_len() {
+            return None;
+        }
+        self.index = 0;
+
+        Some(self.decode_packet())
+    }
+
+    /// Decodes a complete packet
+    fn decode_packet(&mut self) -> MouseEvent {
+        /*      +-------------------+
+        u8      | flags and buttons |
+        u8      | x movement        |
+        u8      | y movement        |
+        u8      | wheel (optional)  |
+                +-------------------+ */
+        let flags = PacketFlags::from_bits_retain(self.packet[0]);
+
+        // motion is a 9-bit two's complement value, with the sign bit in the flags
+        let motion = |value: u8, sign: PacketFlags, overflow: PacketFlags| -> i16 {
+            if flags.contains(overflow) {
+                0
+            } else if flags.contains(sign) {
+                value as i16 - 0x100
+            } else {
+                value as i16
+            }
+        };
+
+        let dx = motion(self.packet[1], PacketFlags::X_SIGN, PacketFlags::X_OVERFLOW);
+        let dy = motion(self.packet[2], PacketFlags::Y_SIGN, PacketFlags::Y_OVERFLOW);
+
+        // wheel motion is a 4-bit two's complement value, so sign extend it
+        let wheel = if self.has_wheel {
+            ((self.packet[3] as i8) << 4) >> 4
+        } else {
+            0
+        };
+
+        let buttons = MouseButtons::from_bits_truncate(self.packet[0]);
+        let changed = buttons.symmetric_difference(self.buttons);
+        self.buttons = buttons;
+
+        MouseEvent {
+            dx,
+            dy: -dy,
+            wheel,
+            buttons,
+            changed,
+        }
+    }
+}
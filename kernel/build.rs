@@ -0,0 +1,8 @@
+fn main() {
+    // test kernels are linked by rustc rather than the makefile, so give them the same layout
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    println!("cargo:rustc-link-arg=-T{manifest_dir}/layout.ld");
+    println!("cargo:rustc-link-arg=-n");
+    println!("cargo:rerun-if-changed=layout.ld");
+}
@@ -0,0 +1,163 @@
+use bitflags::bitflags;
+
+use super::{
+    sdt::{Sdt, SdtHeader},
+    GenericAddress,
+};
+
+bitflags! {
+    /// Fixed feature flags from the FADT
+    #[derive(Debug, Clone, Copy)]
+    pub struct FadtFlags: u32 {
+        const WBINVD = 1 << 0;
+        const WBINVD_FLUSH = 1 << 1;
+        const PROC_C1 = 1 << 2;
+        const P_LVL2_UP = 1 << 3;
+        const POWER_BUTTON = 1 << 4;
+        const SLEEP_BUTTON = 1 << 5;
+        const FIX_RTC = 1 << 6;
+        const RTC_S4 = 1 << 7;
+        const TMR_VAL_EXT = 1 << 8;
+        const DCK_CAP = 1 << 9;
+        /// The reset register is supported
+        const RESET_REG_SUP = 1 << 10;
+        const SEALED_CASE = 1 << 11;
+        const HEADLESS = 1 << 12;
+        const CPU_SW_SLP = 1 << 13;
+        const PCI_EXP_WAK = 1 << 14;
+        const USE_PLATFORM_CLOCK = 1 << 15;
+        const S4_RTC_STS_VALID = 1 << 16;
+        const REMOTE_POWER_ON_CAPABLE = 1 << 17;
+        const FORCE_APIC_CLUSTER_MODEL = 1 << 18;
+        const FORCE_APIC_PHYSICAL_DESTINATION_MODE = 1 << 19;
+        /// Fixed hardware is not implemented, so the PM1 blocks must not be used
+        const HW_REDUCED_ACPI = 1 << 20;
+        const LOW_POWER_S0_IDLE_CAPABLE = 1 << 21;
+    }
+}
+
+/// Raw layout of the fixed ACPI description table, including its header.
+/// Older revisions are shorter, with missing fields left as zero.
+///
+/// https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#fixed-acpi-description-table-fadt
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Fadt {
+    pub header: SdtHeader,
+    pub firmware_ctrl: u32,
+    pub dsdt: u32,
+    _reserved1: u8,
+    pub preferred_pm_profile: u8,
+    pub sci_int: u16,
+    pub smi_cmd: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub s4bios_req: u8,
+    pub pstate_cnt: u8,
+    pub pm1a_evt_blk: u32,
+    pub pm1b_evt_blk: u32,
+    pub pm1a_cnt_blk: u32,
+    pub pm1b_cnt_blk: u32,
+    pub pm2_cnt_blk: u32,
+    pub pm_tmr_blk: u32,
+    pub gpe0_blk: u32,
+    pub gpe1_blk: u32,
+    pub pm1_evt_len: u8,
+    pub pm1_cnt_len: u8,
+    pub pm2_cnt_len: u8,
+    pub pm_tmr_len: u8,
+    pub gpe0_blk_len: u8,
+    pub gpe1_blk_len: u8,
+    pub gpe1_base: u8,
+    pub cst_cnt: u8,
+    pub p_lvl2_lat: u16,
+    pub p_lvl3_lat: u16,
+    pub flush_size: u16,
+    pub flush_stride: u16,
+    pub duty_offset: u8,
+    pub duty_width: u8,
+    pub day_alrm: u8,
+    pub mon_alrm: u8,
+    pub century: u8,
+    pub iapc_boot_arch: u16,
+    _reserved2: u8,
+    flags: u32,
+    pub reset_reg: GenericAddress,
+    pub reset_value: u8,
+    pub arm_boot_arch: u16,
+    pub fadt_minor_version: u8,
+    pub x_firmware_ctrl: u64,
+    pub x_dsdt: u64,
+    pub x_pm1a_evt_blk: GenericAddress,
+    pub x_pm1b_evt_blk: GenericAddress,
+    pub x_pm1a_cnt_blk: GenericAddress,
+    pub x_pm1b_cnt_blk: GenericAddress,
+    pub x_pm2_cnt_blk: GenericAddress,
+    pub x_pm_tmr_blk: GenericAddress,
+    pub x_gpe0_blk: GenericAddress,
+    pub x_gpe1_blk: GenericAddress,
+    pub sleep_control_reg: GenericAddress,
+    pub sleep_status_reg: GenericAddress,
+    pub hypervisor_vendor_id: u64,
+}
+
+impl Fadt {
+    /// Copies the FADT out of the given table, zero filling any fields past the end of the table
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        let len = bytes.len().min(core::mem::size_of::<Self>());
+
+        // every field is an integer, so all zeroes is a valid value
+        let mut fadt: Self = unsafe { core::mem::zeroed() };
+        unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), &mut fadt as *mut _ as *mut u8, len);
+        }
+
+        Some(fadt)
+    }
+
+    /// Returns the fixed feature flags
+    pub fn flags(&self) -> FadtFlags {
+        FadtFlags::from_bits_truncate(self.flags)
+    }
+
+    /// Returns the physical address of the DSDT, preferring the 64-bit field
+    pub fn dsdt_address(&self) -> usize {
+        match self.x_dsdt {
+            0 => self.dsdt as usize,
+            x_dsdt => x_dsdt as usize,
+        }
+    }
+
+    /// Returns the PM1a control block, preferring the extended field
+    pub fn pm1a_control_block(&self) -> Option<GenericAddress> {
+        GenericAddress::prefer_extended(self.x_pm1a_cnt_blk, self.pm1a_cnt_blk, self.pm1_cnt_len)
+    }
+
+    /// Returns the PM1b control block, preferring the extended field
+    pub fn pm1b_control_block(&self) -> Option<GenericAddress> {
+        GenericAddress::prefer_extended(self.x_pm1b_cnt_blk, self.pm1b_cnt_blk, self.pm1_cnt_len)
+    }
+
+    /// Returns the sleep control register used on hardware-reduced systems, if it's set
+    pub fn sleep_control_register(&self) -> Option<GenericAddress> {
+        let sleep_control_reg = self.sleep_control_reg;
+
+        if sleep_control_reg.address != 0 {
+            Some(sleep_control_reg)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reset register and the value to write to it, if supported
+    pub fn reset_register(&self) -> Option<(GenericAddress, u8)> {
+        let reset_reg = self.reset_reg;
+
+        if self.flags().contains(FadtFlags::RESET_REG_SUP) && reset_reg.address != 0 {
+            Some((reset_reg, self.reset_value))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use super::{
+    sdt::{self, Sdt},
+    GenericAddress,
+};
+
+/// Stores information decoded from the high precision event timer table
+///
+/// https://www.intel.com/content/dam/www/public/us/en/documents/technical-specifications/software-developers-hpet-spec-1-0a.pdf
+// the kernel doesn't drive the HPET yet
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct Hpet {
+    pub hardware_revision: u8,
+    /// Number of comparators in the first timer block
+    pub comparator_count: u8,
+    /// Whether the main counter is 64 bits wide
+    pub counter_64bit: bool,
+    /// Whether the timer can replace the legacy PIT and RTC interrupts
+    pub legacy_replacement: bool,
+    pub pci_vendor_id: u16,
+    /// Location of the timer block registers
+    pub base_address: GenericAddress,
+    pub hpet_number: u8,
+    /// Minimum clock ticks that can be set without losing interrupts in periodic mode
+    pub minimum_tick: u16,
+    pub page_protection: u8,
+}
+
+impl Hpet {
+    /// Decodes the HPET description table
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        /*      +-------------------+
+        u32     | event timer block |
+        gas     | base address      |
+        u8      | hpet number       |
+        u16     | minimum tick      |
+        u8      | page protection   |
+                +-------------------+ */
+        let data = table.data();
+        let block_id = sdt::read::<u32>(data, 0)?;
+
+        Some(Self {
+            hardware_revision: block_id as u8,
+            comparator_count: ((block_id >> 8) & 0x1F) as u8 + 1,
+            counter_64bit: block_id & (1 << 13) != 0,
+            legacy_replacement: block_id & (1 << 15) != 0,
+            pci_vendor_id: (block_id >> 16) as u16,
+            base_address: sdt::read(data, 4)?,
+            hpet_number: sdt::read(data, 16)?,
+            minimum_tick: sdt::read(data, 17)?,
+            page_protection: sdt::read(data, 19)?,
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use alloc::vec::Vec;
+
+use super::sdt::{self, Sdt};
+
+/// Processor local APIC entry
+const PROCESSOR_LOCAL_APIC: u8 = 0;
+/// I/O APIC entry
+const IO_APIC: u8 = 1;
+/// Interrupt source override entry
+const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+/// Local APIC address override entry
+const LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+/// Processor local x2APIC entry
+const PROCESSOR_LOCAL_X2APIC: u8 = 9;
+
+/// Stores information decoded from the multiple APIC description table
+///
+/// https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#multiple-apic-description-table-madt
+#[derive(Debug)]
+pub struct Madt {
+    /// Physical address of the local APIC of each processor
+    pub local_apic_address: u64,
+    /// Whether the system also has dual legacy 8259 PICs installed
+    // the 8259 PICs are always used until there's an APIC driver
+    #[allow(dead_code)]
+    pub pcat_compatible: bool,
+    pub processors: Vec<Processor>,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<InterruptSourceOverride>,
+}
+
+/// A single processor and its local APIC
+// only counted until processors are started
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct Processor {
+    pub processor_uid: u32,
+    pub apic_id: u32,
+    /// Processor is ready to use
+    pub enabled: bool,
+    /// Processor is disabled, but can be enabled at runtime
+    pub online_capable: bool,
+}
+
+/// A single I/O APIC
+// no APIC driver programs these yet
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct IoApic {
+    pub id: u8,
+    pub address: u32,
+    /// First global system interrupt handled by this I/O APIC
+    pub gsi_base: u32,
+}
+
+/// Describes how an ISA interrupt is mapped to a global system interrupt
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptSourceOverride {
+    pub bus: u8,
+    /// ISA IRQ number
+    pub source: u8,
+    pub gsi: u32,
+    // no APIC driver programs these yet
+    #[allow(dead_code)]
+    pub polarity: Polarity,
+    #[allow(dead_code)]
+    pub trigger_mode: TriggerMode,
+}
+
+/// Polarity of an interrupt line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    ConformsToBus,
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Trigger mode of an interrupt line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    ConformsToBus,
+    Edge,
+    Level,
+}
+
+impl Madt {
+    /// Decodes the MADT and each of its interrupt controller structures
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        /*      +---------------------------+
+        u32     | local interrupt ctrl addr |
+        u32     | flags                     |
+        varies  | interrupt ctrl structures |
+                +---------------------------+ */
+        let data = table.data();
+
+        let mut madt = Self {
+            local_apic_address: sdt::read::<u32>(data, 0)? as u64,
+            pcat_compatible: sdt::read::<u32>(data, 4)? & 1 == 1,
+            processors: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+        };
+
+        // each structure starts with a type and length byte
+        let mut offset = 8;
+        while let (Some(entry_type), Some(length)) = (
+            sdt::read::<u8>(data, offset),
+            sdt::read::<u8>(data, offset + 1),
+        ) {
+            let length = length as usize;
+            if length < 2 || offset + length > data.len() {
+                log::warn!("malformed MADT entry at offset {offset:#X}");
+                break;
+            }
+
+            let entry = &data[offset..offset + length];
+            madt.parse_entry(entry_type, entry);
+
+            offset += length;
+        }
+
+        Some(madt)
+    }
+
+    /// Decodes a single interrupt controller structure, skipping unsupported types
+    fn parse_entry(&mut self, entry_type: u8, entry: &[u8]) {
+        match entry_type {
+            PROCESSOR_LOCAL_APIC => {
+                /*      +-------------------+
+                u8      | processor uid     |
+                u8      | apic id           |
+                u32     | flags             |
+                        +-------------------+ */
+                if let (Some(uid), Some(apic_id), Some(flags)) = (
+                    sdt::read::<u8>(entry, 2),
+                    sdt::read::<u8>(entry, 3),
+                    sdt::read::<u32>(entry, 4),
+                ) {
+                    self.processors
+                        .push(Processor::new(uid as u32, apic_id as u32, flags));
+                }
+            }
+            IO_APIC => {
+                /*      +-------------------+
+                u8      | io apic id        |
+                u8      | reserved          |
+                u32     | io apic address   |
+                u32     | gsi base          |
+                        +-------------------+ */
+                if let (Some(id), Some(address), Some(gsi_base)) = (
+                    sdt::read::<u8>(entry, 2),
+                    sdt::read::<u32>(entry, 4),
+                    sdt::read::<u32>(entry, 8),
+                ) {
+                    self.io_apics.push(IoApic {
+                        id,
+                        address,
+                        gsi_base,
+                    });
+                }
+            }
+            INTERRUPT_SOURCE_OVERRIDE => {
+                /*      +-------------------+
+                u8      | bus               |
+                u8      | source            |
+                u32     | gsi               |
+                u16     | flags             |
+                        +-------------------+ */
+                if let (Some(bus), Some(source), Some(gsi), Some(flags)) = (
+                    sdt::read::<u8>(entry, 2),
+                    sdt::read::<u8>(entry, 3),
+                    sdt::read::<u32>(entry, 4),
+                    sdt::read::<u16>(entry, 8),
+                ) {
+                    self.overrides.push(InterruptSourceOverride {
+                        bus,
+                        source,
+                        gsi,
+                        polarity: match flags & 0b11 {
+                            0b01 => Polarity::ActiveHigh,
+                            0b11 => Polarity::ActiveLow,
+                            _ => Polarity::ConformsToBus,
+                        },
+                        trigger_mode: match (flags >> 2) & 0b11 {
+                            0b01 => TriggerMode::Edge,
+                            0b11 => TriggerMode::Level,
+                            _ => TriggerMode::ConformsToBus,
+                        },
+                    });
+                }
+            }
+            LOCAL_APIC_ADDRESS_OVERRIDE => {
+                /*      +-------------------+
+                u16     | reserved          |
+                u64     | local apic addr   |
+                        +-------------------+ */
+                if let Some(address) = sdt::read::<u64>(entry, 4) {
+                    self.local_apic_address = address;
+                }
+            }
+            PROCESSOR_LOCAL_X2APIC => {
+                /*      +-------------------+
+                u16     | reserved          |
+                u32     | x2apic id         |
+                u32     | flags             |
+                u32     | processor uid     |
+                        +-------------------+ */
+                if let (Some(apic_id), Some(flags), Some(uid)) = (
+                    sdt::read::<u32>(entry, 4),
+                    sdt::read::<u32>(entry, 8),
+                    sdt::read::<u32>(entry, 12),
+                ) {
+                    self.processors.push(Processor::new(uid, apic_id, flags));
+                }
+            }
+            _ => log::trace!("\t\t* skipping MADT entry of type {entry_type}"),
+        }
+    }
+
+    /// Finds the global system interrupt that the given ISA IRQ is connected to
+    pub fn isa_irq_to_gsi(&self, irq: u8) -> u32 {
+        self.overrides
+            .iter()
+            .find(|entry| entry.bus == 0 && entry.source == irq)
+            .map(|entry| entry.gsi)
+            .unwrap_or(irq as u32)
+    }
+}
+
+impl Processor {
+    /// Constructs a processor from the flags shared by local APIC and x2APIC entries
+    fn new(processor_uid: u32, apic_id: u32, flags: u32) -> Self {
+        Self {
+            processor_uid,
+            apic_id,
+            enabled: flags & 1 == 1,
+            online_capable: flags & 2 == 2,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+
+use super::sdt::{self, Sdt};
+
+/// Stores the PCI express memory mapped configuration regions decoded from the MCFG table
+///
+/// https://wiki.osdev.org/PCI_Express
+#[derive(Debug)]
+pub struct Mcfg {
+    pub entries: Vec<McfgEntry>,
+}
+
+/// Configuration space for a range of buses in a single PCI segment group
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct McfgEntry {
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    _reserved: u32,
+}
+
+impl Mcfg {
+    /// Decodes the MCFG table and each of its configuration space entries
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        /*      +-------------------+
+        u64     | reserved          |
+        varies  | entries           |
+                +-------------------+ */
+        let data = table.data();
+
+        let entries = (8..data.len())
+            .step_by(core::mem::size_of::<McfgEntry>())
+            .map_while(|offset| sdt::read(data, offset))
+            .collect();
+
+        Some(Self { entries })
+    }
+
+    /// Finds the physical address of the configuration space for the given PCI function
+    pub fn function_address(&self, segment: u16, bus: u8, device: u8, function: u8) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|entry| {
+                entry.segment_group == segment && (entry.start_bus..=entry.end_bus).contains(&bus)
+            })
+            .map(|entry| {
+                let bus_offset = (bus - entry.start_bus) as u64;
+
+                entry.base_address
+                    + (bus_offset << 20 | (device as u64) << 15 | (function as u64) << 12)
+            })
+    }
+}
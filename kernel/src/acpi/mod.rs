@@ -0,0 +1,263 @@
+mod fadt;
+mod hpet;
+mod madt;
+mod mcfg;
+mod power;
+mod sdt;
+
+use crabstd::mutex::Mutex;
+pub use fadt::{Fadt, FadtFlags};
+pub use hpet::Hpet;
+use kernel_shared::{handoff::Rsdp, memory::paging::PHYS_MEM_OFFSET};
+pub use madt::Madt;
+pub use mcfg::Mcfg;
+pub use power::{reboot, shutdown};
+use sdt::{signature_str, Sdt};
+use x86_64::port::Port;
+
+/// Tables decoded during [init], or `None` if no valid RSDP was found
+pub static ACPI: Mutex<Option<Acpi>> = Mutex::new(None);
+
+/// Stores the ACPI tables used by other subsystems
+#[derive(Debug)]
+pub struct Acpi {
+    /// ACPI revision from the RSDP, 0 for ACPI 1.0 and 2 for ACPI 2.0+
+    // nothing depends on the revision yet
+    #[allow(dead_code)]
+    pub revision: u8,
+    pub madt: Option<Madt>,
+    pub fadt: Option<Fadt>,
+    pub hpet: Option<Hpet>,
+    pub mcfg: Option<Mcfg>,
+    /// `SLP_TYPa` and `SLP_TYPb` values for the soft off state, from the `\_S5` object in the DSDT
+    pub s5_sleep_type: Option<(u8, u8)>,
+}
+
+/// Address space of a [GenericAddress]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AddressSpace {
+    SYSTEM_MEMORY = 0,
+    SYSTEM_IO = 1,
+    PCI_CONFIGURATION = 2,
+}
+
+/// Describes the location of a register, which may be in memory or I/O space
+///
+/// https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#generic-address-structure-gas
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// Returns the address space, if it's one of the spaces supported by the kernel
+    pub fn address_space(&self) -> Option<AddressSpace> {
+        match self.address_space {
+            0 => Some(AddressSpace::SYSTEM_MEMORY),
+            1 => Some(AddressSpace::SYSTEM_IO),
+            2 => Some(AddressSpace::PCI_CONFIGURATION),
+            _ => None,
+        }
+    }
+
+    /// Returns the width of a single access in bytes
+    fn access_width(&self) -> u8 {
+        match self.access_size {
+            1..=4 => 1 << (self.access_size - 1),
+            _ => (self.bit_width / 8).max(1),
+        }
+    }
+
+    /// Reads the register, returning `None` if the address space or width is unsupported
+    ///
+    /// # Safety
+    /// The register must be safe to read, and any memory must lie within the physical memory mapping
+    pub unsafe fn read(&self) -> Option<u64> {
+        let address = self.address;
+
+        let value = match (self.address_space()?, self.access_width()) {
+            (AddressSpace::SYSTEM_IO, 1) => Port::<u8>::new(address as u16).read() as u64,
+            (AddressSpace::SYSTEM_IO, 2) => Port::<u16>::new(address as u16).read() as u64,
+            (AddressSpace::SYSTEM_IO, 4) => Port::<u32>::new(address as u16).read() as u64,
+            (AddressSpace::SYSTEM_MEMORY, width) => {
+                let ptr = address as usize + PHYS_MEM_OFFSET;
+
+                match width {
+                    1 => (ptr as *const u8).read_volatile() as u64,
+                    2 => (ptr as *const u16).read_volatile() as u64,
+                    4 => (ptr as *const u32).read_volatile() as u64,
+                    _ => (ptr as *const u64).read_volatile(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Writes a value to the register, returning false if the address space or width is unsupported
+    ///
+    /// # Safety
+    /// Writing to the register can have arbitrary side effects, and any memory must lie within the
+    /// physical memory mapping
+    pub unsafe fn write(&self, value: u64) -> bool {
+        let address = self.address;
+
+        let Some(address_space) = self.address_space() else {
+            return false;
+        };
+
+        match (address_space, self.access_width()) {
+            (AddressSpace::SYSTEM_IO, 1) => Port::<u8>::new(address as u16).write(value as u8),
+            (AddressSpace::SYSTEM_IO, 2) => Port::<u16>::new(address as u16).write(value as u16),
+            (AddressSpace::SYSTEM_IO, 4) => Port::<u32>::new(address as u16).write(value as u32),
+            (AddressSpace::SYSTEM_MEMORY, width) => {
+                let ptr = address as usize + PHYS_MEM_OFFSET;
+
+                match width {
+                    1 => (ptr as *mut u8).write_volatile(value as u8),
+                    2 => (ptr as *mut u16).write_volatile(value as u16),
+                    4 => (ptr as *mut u32).write_volatile(value as u32),
+                    _ => (ptr as *mut u64).write_volatile(value),
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Picks an extended FADT address if it's set, otherwise converts the legacy I/O port block
+    fn prefer_extended(extended: GenericAddress, legacy: u32, legacy_len: u8) -> Option<Self> {
+        if extended.address != 0 {
+            Some(extended)
+        } else if legacy != 0 {
+            Some(Self {
+                address_space: AddressSpace::SYSTEM_IO as u8,
+                bit_width: legacy_len * 8,
+                bit_offset: 0,
+                access_size: 0,
+                address: legacy as u64,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Walks the RSDT/XSDT found by the loader and decodes each known table
+pub fn init(rsdp: Rsdp) {
+    log::trace!("initialising acpi");
+
+    if rsdp.root_address == 0 {
+        log::warn!("\t* no valid RSDP passed by bootloader");
+        return;
+    }
+    let (revision, root_address, entry_size) = (
+        rsdp.revision,
+        rsdp.root_address as usize,
+        rsdp.entry_size as usize,
+    );
+
+    let Some(root) = (unsafe { Sdt::from_phys(root_address) }) else {
+        log::warn!("\t* invalid root table at {root_address:#X}");
+        return;
+    };
+    log::trace!(
+        "\t* found `{}` at {root_address:#X} with revision {revision}",
+        signature_str(&root.signature())
+    );
+
+    let mut acpi = Acpi {
+        revision,
+        madt: None,
+        fadt: None,
+        hpet: None,
+        mcfg: None,
+        s5_sleep_type: None,
+    };
+
+    // root table is followed by a list of 32-bit (RSDT) or 64-bit (XSDT) physical addresses
+    for entry in root.data().chunks_exact(entry_size) {
+        let address = match entry_size {
+            8 => u64::from_ne_bytes(entry.try_into().unwrap()) as usize,
+            _ => u32::from_ne_bytes(entry.try_into().unwrap()) as usize,
+        };
+
+        let Some(table) = (unsafe { Sdt::from_phys(address) }) else {
+            continue;
+        };
+
+        let signature = table.signature();
+        log::trace!(
+            "\t* found table `{}` at {address:#X}",
+            signature_str(&signature)
+        );
+
+        match &signature {
+            b"APIC" => acpi.madt = Madt::parse(&table),
+            b"FACP" => acpi.fadt = Fadt::parse(&table),
+            b"HPET" => acpi.hpet = Hpet::parse(&table),
+            b"MCFG" => acpi.mcfg = Mcfg::parse(&table),
+            _ => {}
+        }
+    }
+
+    // sleep states are defined in AML, so find the DSDT through the FADT
+    if let Some(fadt) = &acpi.fadt {
+        let dsdt_address = fadt.dsdt_address();
+
+        if let Some(dsdt) = unsafe { Sdt::from_phys(dsdt_address) } {
+            log::trace!("\t* found table `DSDT` at {dsdt_address:#X}");
+            acpi.s5_sleep_type = power::find_s5_sleep_type(&dsdt);
+        }
+
+        if acpi.s5_sleep_type.is_none() {
+            log::warn!("\t* no `\\_S5` object found, shutdown will not be supported");
+        }
+    }
+
+    if let Some(madt) = &acpi.madt {
+        log::trace!(
+            "\t* {} processors, {} I/O APICs, {} interrupt overrides",
+            madt.processors.len(),
+            madt.io_apics.len(),
+            madt.overrides.len()
+        );
+        log::trace!("\t* ISA IRQ 0 is routed to GSI {}", madt.isa_irq_to_gsi(0));
+    }
+
+    if let Some(address) = (acpi.mcfg.as_ref()).and_then(|mcfg| mcfg.function_address(0, 0, 0, 0)) {
+        log::trace!("\t* PCI express configuration space for bus 0 at {address:#X}");
+    }
+
+    *ACPI.lock() = Some(acpi);
+
+    log::trace!("acpi initialised");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ACPI;
+
+    #[test_case]
+    fn firmware_tables_are_decoded() {
+        let acpi = ACPI.lock();
+        let acpi = acpi.as_ref().expect("no ACPI tables found");
+
+        let madt = acpi.madt.as_ref().unwrap();
+        assert!(!madt.processors.is_empty());
+        assert!(!madt.io_apics.is_empty());
+
+        // the DSDT is only reached through the FADT, and QEMU's defines `\_S5`
+        assert!(acpi.fadt.is_some());
+        assert!(acpi.s5_sleep_type.is_some());
+    }
+}
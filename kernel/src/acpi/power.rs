@@ -0,0 +1,183 @@
+use core::arch::asm;
+
+use x86_64::port::Port;
+
+use super::{sdt::Sdt, FadtFlags, ACPI};
+
+/// AML opcode which defines a named object
+const NAME_OP: u8 = 0x08;
+/// AML opcode which defines a package
+const PACKAGE_OP: u8 = 0x12;
+/// AML opcodes for integer constants
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const BYTE_PREFIX: u8 = 0x0A;
+
+/// Bits of the PM1 control register
+const SCI_EN: u64 = 1 << 0;
+const SLP_TYP_SHIFT: u64 = 10;
+const SLP_TYP_MASK: u64 = 0b111 << SLP_TYP_SHIFT;
+const SLP_EN: u64 = 1 << 13;
+
+/// Bits of the sleep control register, used on hardware-reduced systems
+const SLEEP_CONTROL_TYP_SHIFT: u64 = 2;
+const SLEEP_CONTROL_EN: u64 = 1 << 5;
+
+/// Ports and commands for the 8042 PS/2 controller
+const PS2_STATUS_PORT: u16 = 0x64;
+const PS2_COMMAND_PORT: u16 = 0x64;
+const PS2_INPUT_FULL: u8 = 1 << 1;
+const PS2_PULSE_RESET: u8 = 0xFE;
+
+/// Finds the `\_S5` package in the DSDT and returns the `SLP_TYPa` and `SLP_TYPb` values.
+///
+/// This doesn't interpret the AML, and instead just looks for the encoding of
+/// `Name (_S5, Package () { a, b, ... })`, which is how every known firmware defines it.
+pub(super) fn find_s5_sleep_type(dsdt: &Sdt) -> Option<(u8, u8)> {
+    let aml = dsdt.data();
+
+    aml.windows(4)
+        .enumerate()
+        .filter(|(_, name)| *name == b"_S5_")
+        .find_map(|(start, _)| {
+            /*      +-------------------+
+            u8      | NameOp            |
+            u8      | `\` (optional)    |
+            [u8; 4] | `_S5_`            |
+            u8      | PackageOp         |
+            varies  | PkgLength         |
+            u8      | NumElements       |
+            varies  | SLP_TYPa          |
+            varies  | SLP_TYPb          |
+                    +-------------------+ */
+            let is_name = match start {
+                0 => false,
+                1 => aml[0] == NAME_OP,
+                _ => {
+                    aml[start - 1] == NAME_OP
+                        || (aml[start - 1] == b'\\' && aml[start - 2] == NAME_OP)
+                }
+            };
+
+            if !is_name || *aml.get(start + 4)? != PACKAGE_OP {
+                return None;
+            }
+
+            // top two bits of the first PkgLength byte give the number of bytes that follow
+            let pkg_length_bytes = (*aml.get(start + 5)? >> 6) as usize;
+            let offset = start + 5 + 1 + pkg_length_bytes + 1;
+
+            let (slp_typ_a, len) = parse_byte_const(aml, offset)?;
+            let (slp_typ_b, _) = parse_byte_const(aml, offset + len)?;
+
+            Some((slp_typ_a, slp_typ_b))
+        })
+}
+
+/// Parses an AML integer constant which fits in a byte, returning the value and its encoded length
+fn parse_byte_const(aml: &[u8], offset: usize) -> Option<(u8, usize)> {
+    match *aml.get(offset)? {
+        ZERO_OP => Some((0, 1)),
+        ONE_OP => Some((1, 1)),
+        BYTE_PREFIX => Some((*aml.get(offset + 1)?, 2)),
+        _ => None,
+    }
+}
+
+/// Powers off the machine by entering the ACPI soft off (S5) state.
+/// If that fails, the CPU is halted instead.
+pub fn shutdown() -> ! {
+    x86_64::interrupts::disable_interrupts();
+    log::info!("shutting down");
+
+    if let Some(acpi) = ACPI.lock().as_ref() {
+        if let (Some(fadt), Some((slp_typ_a, slp_typ_b))) = (&acpi.fadt, acpi.s5_sleep_type) {
+            unsafe {
+                if fadt.flags().contains(FadtFlags::HW_REDUCED_ACPI) {
+                    // an empty register would otherwise be written at physical address 0
+                    if let Some(sleep_control_reg) = fadt.sleep_control_register() {
+                        sleep_control_reg.write(
+                            ((slp_typ_a as u64) << SLEEP_CONTROL_TYP_SHIFT) | SLEEP_CONTROL_EN,
+                        );
+                    }
+                } else if let Some(pm1a) = fadt.pm1a_control_block() {
+                    // firmware may still own the fixed hardware, so hand it over to the OS first
+                    let smi_cmd = fadt.smi_cmd;
+                    if pm1a.read().unwrap_or(0) & SCI_EN == 0 && smi_cmd != 0 {
+                        log::trace!("\t* enabling ACPI mode");
+                        Port::<u8>::new(smi_cmd as u16).write(fadt.acpi_enable);
+
+                        for _ in 0..1_000_000 {
+                            if pm1a.read().unwrap_or(SCI_EN) & SCI_EN != 0 {
+                                break;
+                            }
+                            core::hint::spin_loop();
+                        }
+                    }
+
+                    let pm1b = fadt.pm1b_control_block();
+
+                    // both blocks must be written, since some hardware is split between them
+                    for (block, slp_typ) in [(Some(pm1a), slp_typ_a), (pm1b, slp_typ_b)] {
+                        let Some(block) = block else {
+                            continue;
+                        };
+
+                        let value = block.read().unwrap_or(0) & !SLP_TYP_MASK;
+                        block.write(value | ((slp_typ as u64) << SLP_TYP_SHIFT) | SLP_EN);
+                    }
+                }
+            }
+        }
+    }
+
+    log::error!("failed to shut down, halting instead");
+    x86_64::hlt_loop()
+}
+
+/// Restarts the machine, trying the ACPI reset register, then the 8042 controller,
+/// and finally forcing a triple fault.
+pub fn reboot() -> ! {
+    x86_64::interrupts::disable_interrupts();
+    log::info!("rebooting");
+
+    if let Some((reset_reg, reset_value)) = ACPI
+        .lock()
+        .as_ref()
+        .and_then(|acpi| acpi.fadt.as_ref())
+        .and_then(|fadt| fadt.reset_register())
+    {
+        log::trace!("\t* writing to ACPI reset register");
+        unsafe {
+            reset_reg.write(reset_value as u64);
+        }
+    }
+
+    log::trace!("\t* pulsing 8042 reset line");
+    unsafe {
+        let mut status: Port<u8> = Port::new(PS2_STATUS_PORT);
+        let mut command: Port<u8> = Port::new(PS2_COMMAND_PORT);
+
+        // wait for the controller to be ready for a command
+        for _ in 0..1_000_000 {
+            if status.read() & PS2_INPUT_FULL == 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        command.write(PS2_PULSE_RESET);
+    }
+
+    log::trace!("\t* forcing triple fault");
+    unsafe {
+        // with an empty IDT, any interrupt escalates to a triple fault
+        let null_idt = [0u8; 10];
+        asm!(
+            "lidt [{}]",
+            "int3",
+            in(reg) &null_idt,
+            options(noreturn)
+        );
+    }
+}
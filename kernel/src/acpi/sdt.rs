@@ -0,0 +1,101 @@
+use kernel_shared::memory::paging::PHYS_MEM_OFFSET;
+
+/// Header shared by every system description table
+///
+/// https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#system-description-table-header
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+/// A system description table in physical memory which has passed checksum validation
+#[derive(Debug, Clone, Copy)]
+pub struct Sdt {
+    bytes: &'static [u8],
+}
+
+impl Sdt {
+    /// Size of [SdtHeader] in bytes
+    pub const HEADER_SIZE: usize = core::mem::size_of::<SdtHeader>();
+
+    /// Reads the table at the given physical address, returning `None` if the checksum is invalid
+    ///
+    /// # Safety
+    /// `phys_addr` must point to a system description table within the physical memory mapping
+    pub unsafe fn from_phys(phys_addr: usize) -> Option<Self> {
+        let header = &*((phys_addr + PHYS_MEM_OFFSET) as *const SdtHeader);
+        let length = header.length as usize;
+
+        if length < Self::HEADER_SIZE {
+            log::warn!("table at {phys_addr:#X} has invalid length {length:#X}");
+            return None;
+        }
+
+        let bytes = core::slice::from_raw_parts(header as *const _ as *const u8, length);
+
+        if !checksum(bytes) {
+            log::warn!(
+                "table `{}` at {phys_addr:#X} has invalid checksum",
+                signature_str(&header.signature)
+            );
+            return None;
+        }
+
+        Some(Self { bytes })
+    }
+
+    /// Returns the table header
+    pub fn header(&self) -> SdtHeader {
+        self.read(0).unwrap()
+    }
+
+    /// Returns the four byte signature of the table, such as `APIC` for the MADT
+    pub fn signature(&self) -> [u8; 4] {
+        self.header().signature
+    }
+
+    /// Returns the entire table, including the header
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    /// Returns the table contents following the header
+    pub fn data(&self) -> &'static [u8] {
+        &self.bytes[Self::HEADER_SIZE..]
+    }
+
+    /// Reads a value at the given offset from the start of the table, returning `None` if it lies
+    /// outside the table
+    pub fn read<T: Copy>(&self, offset: usize) -> Option<T> {
+        read(self.bytes, offset)
+    }
+}
+
+/// Reads a possibly unaligned value at the given offset into `bytes`, returning `None` if it
+/// would read past the end of the slice
+pub fn read<T: Copy>(bytes: &[u8], offset: usize) -> Option<T> {
+    if offset + core::mem::size_of::<T>() > bytes.len() {
+        return None;
+    }
+
+    Some(unsafe { (bytes.as_ptr().add(offset) as *const T).read_unaligned() })
+}
+
+/// Returns true if the bytes sum to zero, ignoring overflow
+pub fn checksum(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// Converts a signature into a printable string
+pub fn signature_str(signature: &[u8]) -> &str {
+    core::str::from_utf8(signature).unwrap_or("????")
+}
@@ -0,0 +1,169 @@
+use core::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crabstd::mutex::Mutex;
+use kernel_shared::{
+    elf::{ElfFile, SymbolTable},
+    handoff::KernelImage,
+    memory::paging::{active_table::ActivePageTable, PHYS_MEM_OFFSET},
+    serial_println,
+};
+use x86_64::{registers::RBP, structures::ExceptionStackFrame};
+
+use crate::println;
+
+/// Maximum number of frames to walk, in case the chain of frame pointers loops
+const MAX_FRAMES: usize = 64;
+
+/// Symbols of the kernel, read from the kernel module
+static SYMBOLS: Mutex<Option<SymbolTable>> = Mutex::new(None);
+
+/// How far the loader moved the kernel from its link address, which symbols are relative to
+static KERNEL_SLIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// Loads the kernel symbol table from the kernel module, so backtraces can show function names
+pub fn init(kernel: KernelImage) {
+    log::trace!("loading kernel symbols");
+
+    log::trace!("\t* kernel slide is {:#X}", kernel.slide);
+    KERNEL_SLIDE.store(kernel.slide as usize, Ordering::Relaxed);
+
+    // the loader never frees the kernel module, so it can be read through the physical memory mapping
+    let data = unsafe {
+        core::slice::from_raw_parts(
+            (kernel.elf.start as usize + PHYS_MEM_OFFSET) as *const u8,
+            kernel.elf.len(),
+        )
+    };
+
+    let symbols = ElfFile::new(data).and_then(|elf| elf.symbol_table());
+    if symbols.is_none() {
+        log::warn!("\t* kernel has no symbol table, backtraces will not be symbolised");
+    }
+    *SYMBOLS.lock() = symbols;
+
+    log::trace!("kernel symbols loaded");
+}
+
+/// Prints a backtrace of the calling function to serial and the screen
+#[inline(never)]
+pub fn print() {
+    print_from(RBP::read());
+}
+
+/// Prints where an exception happened, followed by a backtrace of the interrupted code.
+/// Must be called directly from an exception handler.
+#[inline(never)]
+pub fn print_exception(stack_frame: &ExceptionStackFrame) {
+    emit(format_args!(
+        "exception at {}",
+        Location::instruction(stack_frame.instruction_pointer as usize)
+    ));
+
+    // skip the frames of this function and the exception handler
+    let interrupted = next_frame(RBP::read())
+        .and_then(|(rbp, _)| next_frame(rbp))
+        .map(|(rbp, _)| rbp);
+
+    if let Some(rbp) = interrupted {
+        print_from(rbp);
+    }
+}
+
+/// Walks the chain of frame pointers starting at `rbp`, printing each return address
+fn print_from(mut rbp: usize) {
+    emit(format_args!("backtrace:"));
+
+    for index in 0..MAX_FRAMES {
+        let Some((next_rbp, return_address)) = next_frame(rbp) else {
+            break;
+        };
+
+        emit(format_args!(
+            "{index:>4}: {}",
+            Location::return_address(return_address)
+        ));
+        rbp = next_rbp;
+    }
+}
+
+/// Reads the saved frame pointer and return address from the frame at `rbp`,
+/// or `None` if it doesn't point to a valid frame
+fn next_frame(rbp: usize) -> Option<(usize, usize)> {
+    /*      +-------------------+
+    rbp     | saved rbp         |
+    rbp + 8 | return address    |
+            +-------------------+ */
+    // frames only ever live on kernel stacks, which are in the higher half
+    if rbp < 0xFFFF_8000_0000_0000 || rbp % 8 != 0 {
+        return None;
+    }
+
+    // only used to read the tables, so it's fine to have multiple of these
+    let active_table = unsafe { ActivePageTable::new() };
+    active_table.translate(rbp)?;
+    active_table.translate(rbp + 15)?;
+
+    let frame = rbp as *const usize;
+    let (next_rbp, return_address) = unsafe { (*frame, *frame.add(1)) };
+
+    (return_address != 0).then_some((next_rbp, return_address))
+}
+
+/// Prints a line to both serial and the screen
+fn emit(args: fmt::Arguments) {
+    serial_println!("{}", args);
+    println!("{}", args);
+}
+
+/// An address in kernel code, formatted alongside the function it lies in
+struct Location {
+    addr: usize,
+    /// Return addresses point after the call, which may be past the end of the calling function
+    is_return_address: bool,
+}
+
+impl Location {
+    fn instruction(addr: usize) -> Self {
+        Self {
+            addr,
+            is_return_address: false,
+        }
+    }
+
+    fn return_address(addr: usize) -> Self {
+        Self {
+            addr,
+            is_return_address: true,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018X}", self.addr)?;
+
+        // avoid deadlocking if we panicked while loading symbols
+        if SYMBOLS.is_locked() {
+            return Ok(());
+        }
+
+        let adjust = self.is_return_address as usize;
+        let addr = self.addr.wrapping_sub(KERNEL_SLIDE.load(Ordering::Relaxed));
+
+        let symbols = *SYMBOLS.lock();
+        let symbol = symbols.and_then(|symbols| symbols.lookup(addr - adjust));
+
+        match symbol {
+            Some((name, offset)) => write!(
+                f,
+                " - {:#}+{:#X}",
+                rustc_demangle::demangle(name),
+                offset + adjust
+            ),
+            None => write!(f, " - <unknown>"),
+        }
+    }
+}
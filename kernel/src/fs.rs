@@ -0,0 +1,33 @@
+use core::ops::Deref;
+
+use crabstd::fs::{File, FileSystem};
+
+/// Reads a file from whichever device its path names into the buffer, returning the number of
+/// bytes read
+pub fn read(file: &File, buffer: &mut [u8]) -> usize {
+    let device = match file.path().device().unwrap() {
+        "" => *crate::ROOT_DEVICE.lock(),
+        device => device,
+    };
+
+    match device {
+        "ramfs" => crate::RAMFS
+            .lock()
+            .as_ref()
+            .unwrap()
+            .read_file(file, buffer),
+        "serial" => crate::io::serial::read(buffer),
+        "stdin" => {
+            let read = crate::input::read_keyboard(buffer);
+            read + crate::io::serial::read(&mut buffer[read..])
+        }
+        _ => {
+            log::warn!(
+                "attempted to read from invalid device `{}`, path `{}`",
+                device,
+                file.path().deref()
+            );
+            0
+        }
+    }
+}
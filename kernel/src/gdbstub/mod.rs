@@ -0,0 +1,371 @@
+use crabstd::mutex::Mutex;
+use kernel_shared::{
+    memory::paging::{active_table::ActivePageTable, PHYS_MEM_OFFSET},
+    serial_port::{ports, InterruptEnableFlags, SerialPort},
+};
+use x86_64::registers::CpuFlags;
+
+use self::packet::{decode_hex, parse_hex, Connection, Response, PACKET_SIZE};
+use crate::interrupts::TrapFrame;
+
+mod packet;
+
+/// Signal reported to gdb for breakpoints and single-steps
+const SIGTRAP: u8 = 5;
+/// Maximum number of software breakpoints that can be inserted at once
+const MAX_BREAKPOINTS: usize = 32;
+/// Opcode of the `int3` instruction
+const INT3: u8 = 0xCC;
+
+/// Number of registers sent to gdb, in the order of its x86_64 register file:
+/// rax, rbx, rcx, rdx, rsi, rdi, rbp, rsp, r8-r15, rip, eflags, cs, ss, ds, es, fs, gs
+const REGISTER_COUNT: usize = 24;
+
+/// Exception which stopped the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    Breakpoint,
+    SingleStep,
+}
+
+/// A software breakpoint inserted by gdb
+#[derive(Debug, Clone, Copy)]
+struct Breakpoint {
+    addr: usize,
+    /// Byte overwritten by `int3`
+    original: u8,
+}
+
+/// What to do after handling a packet
+enum Action {
+    Reply,
+    Resume { step: bool },
+}
+
+struct GdbStub {
+    connection: Connection,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+}
+
+static STUB: Mutex<Option<GdbStub>> = Mutex::new(None);
+
+/// Starts listening for gdb on COM2, if it exists
+pub fn init() {
+    log::trace!("initialising gdb stub");
+
+    let mut port = unsafe { SerialPort::new(ports::COM2) };
+    if !port.is_present() {
+        log::trace!("\t* no serial port at COM2, gdb stub disabled");
+        return;
+    }
+
+    port.init();
+    // the stub only ever polls while the rest of the kernel is stopped
+    port.set_interrupts(InterruptEnableFlags::empty());
+    log::trace!("\t* listening on COM2");
+
+    *STUB.lock() = Some(GdbStub {
+        connection: Connection::new(port),
+        breakpoints: [None; MAX_BREAKPOINTS],
+    });
+
+    log::trace!("gdb stub initialised");
+}
+
+/// Hands control to gdb after a breakpoint or single-step, returning once gdb resumes execution.
+/// Returns false without doing anything if the stub isn't running.
+pub fn handle_trap(frame: &mut TrapFrame, trap: Trap) -> bool {
+    let mut stub = STUB.lock();
+    let Some(stub) = stub.as_mut() else {
+        return false;
+    };
+
+    stub.handle_trap(frame, trap);
+    true
+}
+
+impl GdbStub {
+    fn handle_trap(&mut self, frame: &mut TrapFrame, trap: Trap) {
+        frame.stack_frame.cpu_flags.remove(CpuFlags::TRAP_FLAG);
+
+        // int3 leaves rip after itself, so point back at the breakpoint gdb inserted
+        let rip = frame.stack_frame.instruction_pointer as usize;
+        let swbreak =
+            trap == Trap::Breakpoint && self.breakpoint_index(rip.wrapping_sub(1)).is_some();
+        if swbreak {
+            frame.stack_frame.instruction_pointer -= 1;
+        }
+
+        let mut response = Response::new();
+        response.push(b"T");
+        response.push_hex(&[SIGTRAP]);
+        if swbreak {
+            response.push(b"swbreak:;");
+        }
+        self.connection.send(response.as_bytes());
+
+        loop {
+            let mut buffer = [0; PACKET_SIZE];
+            let packet = self.connection.receive(&mut buffer);
+
+            let mut response = Response::new();
+            match self.handle_packet(frame, packet, &mut response) {
+                Action::Reply => self.connection.send(response.as_bytes()),
+                Action::Resume { step } => {
+                    if step {
+                        frame.stack_frame.cpu_flags.insert(CpuFlags::TRAP_FLAG);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Handles a single packet from gdb, writing any reply to `response`
+    fn handle_packet(
+        &mut self,
+        frame: &mut TrapFrame,
+        packet: &[u8],
+        response: &mut Response,
+    ) -> Action {
+        let Some((&command, args)) = packet.split_first() else {
+            return Action::Reply;
+        };
+
+        let result = match command {
+            b'?' => {
+                response.push(b"S");
+                response.push_hex(&[SIGTRAP]);
+                Some(())
+            }
+            b'g' => {
+                for (value, size) in registers(frame) {
+                    response.push_hex(&value.to_le_bytes()[..size]);
+                }
+                Some(())
+            }
+            b'G' => write_registers(frame, args).map(|_| response.push(b"OK")),
+            b'm' => read_memory(args, response),
+            b'M' => write_memory(args).map(|_| response.push(b"OK")),
+            b'Z' | b'z' => {
+                let insert = command == b'Z';
+
+                match args {
+                    // only software breakpoints are supported
+                    [b'0', b',', args @ ..] => self
+                        .set_breakpoint(args, insert)
+                        .map(|_| response.push(b"OK")),
+                    _ => return Action::Reply,
+                }
+            }
+            b'c' | b's' => {
+                if let Some(addr) = parse_hex(args) {
+                    frame.stack_frame.instruction_pointer = addr;
+                }
+
+                return Action::Resume {
+                    step: command == b's',
+                };
+            }
+            b'D' | b'k' => {
+                self.remove_all_breakpoints();
+                if command == b'D' {
+                    self.connection.send(b"OK");
+                }
+
+                return Action::Resume { step: false };
+            }
+            b'H' => {
+                response.push(b"OK");
+                Some(())
+            }
+            b'q' if args.starts_with(b"Supported") => {
+                response.push(b"PacketSize=400;swbreak+");
+                Some(())
+            }
+            b'q' if args.starts_with(b"Attached") => {
+                response.push(b"1");
+                Some(())
+            }
+            // an empty reply tells gdb the packet isn't supported
+            _ => Some(()),
+        };
+
+        if result.is_none() {
+            response.push(b"E01");
+        }
+
+        Action::Reply
+    }
+
+    /// Finds the index of the breakpoint at an address
+    fn breakpoint_index(&self, addr: usize) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.is_some_and(|breakpoint| breakpoint.addr == addr))
+    }
+
+    /// Inserts or removes a breakpoint, given the arguments `addr,kind` of a `Z0` or `z0` packet
+    fn set_breakpoint(&mut self, args: &[u8], insert: bool) -> Option<()> {
+        let (addr, _kind) = split_once(args, b',')?;
+        let addr = parse_hex(addr)? as usize;
+        let ptr = translate(addr)?;
+
+        match (insert, self.breakpoint_index(addr)) {
+            // gdb may insert the same breakpoint twice
+            (true, Some(_)) | (false, None) => {}
+            (true, None) => {
+                let slot = self.breakpoints.iter_mut().find(|slot| slot.is_none())?;
+
+                unsafe {
+                    *slot = Some(Breakpoint {
+                        addr,
+                        original: ptr.read_volatile(),
+                    });
+                    ptr.write_volatile(INT3);
+                }
+            }
+            (false, Some(index)) => {
+                let breakpoint = self.breakpoints[index].take()?;
+                unsafe { ptr.write_volatile(breakpoint.original) };
+            }
+        }
+
+        Some(())
+    }
+
+    /// Restores every byte overwritten by a breakpoint
+    fn remove_all_breakpoints(&mut self) {
+        for breakpoint in self.breakpoints.iter_mut().filter_map(Option::take) {
+            if let Some(ptr) = translate(breakpoint.addr) {
+                unsafe { ptr.write_volatile(breakpoint.original) };
+            }
+        }
+    }
+}
+
+/// Returns the value and size in bytes of each register sent to gdb
+fn registers(frame: &TrapFrame) -> [(u64, usize); REGISTER_COUNT] {
+    let stack_frame = &frame.stack_frame;
+
+    [
+        (frame.rax, 8),
+        (frame.rbx, 8),
+        (frame.rcx, 8),
+        (frame.rdx, 8),
+        (frame.rsi, 8),
+        (frame.rdi, 8),
+        (frame.rbp, 8),
+        (stack_frame.stack_pointer, 8),
+        (frame.r8, 8),
+        (frame.r9, 8),
+        (frame.r10, 8),
+        (frame.r11, 8),
+        (frame.r12, 8),
+        (frame.r13, 8),
+        (frame.r14, 8),
+        (frame.r15, 8),
+        (stack_frame.instruction_pointer, 8),
+        (stack_frame.cpu_flags.bits(), 4),
+        (stack_frame.code_segment.0 as u64, 4),
+        (stack_frame.stack_segment.0 as u64, 4),
+        // data segments are unused in long mode
+        (0, 4),
+        (0, 4),
+        (0, 4),
+        (0, 4),
+    ]
+}
+
+/// Writes registers from the hex encoded contents of a `G` packet.
+/// Segment registers are ignored, since changing them is unlikely to end well.
+fn write_registers(frame: &mut TrapFrame, args: &[u8]) -> Option<()> {
+    let mut values = [0u64; REGISTER_COUNT];
+    let mut digits = args;
+
+    for ((_, size), value) in registers(frame).into_iter().zip(values.iter_mut()) {
+        let mut bytes = [0; 8];
+        decode_hex(digits.get(..size * 2)?, &mut bytes[..size])?;
+
+        *value = u64::from_le_bytes(bytes);
+        digits = &digits[size * 2..];
+    }
+
+    let [rax, rbx, rcx, rdx, rsi, rdi, rbp, rsp, r8, r9, r10, r11, r12, r13, r14, r15, rip, eflags, ..] =
+        values;
+
+    frame.rax = rax;
+    frame.rbx = rbx;
+    frame.rcx = rcx;
+    frame.rdx = rdx;
+    frame.rsi = rsi;
+    frame.rdi = rdi;
+    frame.rbp = rbp;
+    frame.r8 = r8;
+    frame.r9 = r9;
+    frame.r10 = r10;
+    frame.r11 = r11;
+    frame.r12 = r12;
+    frame.r13 = r13;
+    frame.r14 = r14;
+    frame.r15 = r15;
+    frame.stack_frame.stack_pointer = rsp;
+    frame.stack_frame.instruction_pointer = rip;
+    frame.stack_frame.cpu_flags = CpuFlags::from_bits_retain(eflags);
+
+    Some(())
+}
+
+/// Reads memory for an `m addr,length` packet
+fn read_memory(args: &[u8], response: &mut Response) -> Option<()> {
+    let (addr, len) = split_once(args, b',')?;
+    let addr = parse_hex(addr)? as usize;
+    // each byte takes two characters in the response
+    let len = (parse_hex(len)? as usize).min(PACKET_SIZE / 2);
+
+    for offset in 0..len {
+        let byte = unsafe { translate(addr.checked_add(offset)?)?.read_volatile() };
+        response.push_hex(&[byte]);
+    }
+
+    Some(())
+}
+
+/// Writes memory for an `M addr,length:data` packet
+fn write_memory(args: &[u8]) -> Option<()> {
+    let (location, data) = split_once(args, b':')?;
+    let (addr, len) = split_once(location, b',')?;
+    let addr = parse_hex(addr)? as usize;
+    let len = parse_hex(len)? as usize;
+
+    let mut bytes = [0; PACKET_SIZE / 2];
+    let bytes = bytes.get_mut(..len)?;
+    decode_hex(data, bytes)?;
+
+    for (offset, byte) in bytes.iter().enumerate() {
+        unsafe { translate(addr.checked_add(offset)?)?.write_volatile(*byte) };
+    }
+
+    Some(())
+}
+
+/// Finds where a virtual address can be accessed through the physical memory mapping, which also
+/// allows writing to read-only pages such as kernel code
+fn translate(addr: usize) -> Option<*mut u8> {
+    if (0x0000_8000_0000_0000..0xFFFF_8000_0000_0000).contains(&addr) {
+        return None;
+    }
+
+    // only used to read the tables, so it's fine to have multiple of these
+    let active_table = unsafe { ActivePageTable::new() };
+    active_table
+        .translate(addr)
+        .map(|phys_addr| (phys_addr + PHYS_MEM_OFFSET) as *mut u8)
+}
+
+/// Splits a slice at the first occurrence of `separator`
+fn split_once(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let index = bytes.iter().position(|&byte| byte == separator)?;
+
+    Some((&bytes[..index], &bytes[index + 1..]))
+}
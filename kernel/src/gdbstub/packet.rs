@@ -0,0 +1,168 @@
+use kernel_shared::serial_port::SerialPort;
+
+/// Maximum size of a packet, advertised to gdb in `qSupported`
+pub const PACKET_SIZE: usize = 1024;
+
+/// Connection to gdb over a serial port, using the remote serial protocol framing
+/// `$<data>#<checksum>`
+pub struct Connection {
+    port: SerialPort,
+}
+
+impl Connection {
+    pub fn new(port: SerialPort) -> Self {
+        Self { port }
+    }
+
+    /// Waits for a valid packet, acknowledging it, and returns its contents
+    pub fn receive<'a>(&mut self, buffer: &'a mut [u8; PACKET_SIZE]) -> &'a [u8] {
+        loop {
+            // skip anything outside a packet, such as stray acks
+            while self.read_byte() != b'$' {}
+
+            let mut len = 0;
+            let mut checksum = 0u8;
+            let mut overflowed = false;
+
+            loop {
+                let byte = self.read_byte();
+                if byte == b'#' {
+                    break;
+                }
+
+                match buffer.get_mut(len) {
+                    Some(slot) => *slot = byte,
+                    None => overflowed = true,
+                }
+                len += 1;
+                checksum = checksum.wrapping_add(byte);
+            }
+
+            let expected = [self.read_byte(), self.read_byte()];
+
+            if !overflowed && parse_hex(&expected) == Some(checksum as u64) {
+                self.write_byte(b'+');
+                return &buffer[..len];
+            }
+
+            self.write_byte(b'-');
+        }
+    }
+
+    /// Sends a packet, retransmitting until gdb acknowledges it
+    pub fn send(&mut self, data: &[u8]) {
+        let checksum = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        loop {
+            self.write_byte(b'$');
+            for &byte in data {
+                self.write_byte(byte);
+            }
+            self.write_byte(b'#');
+            self.write_byte(HEX_DIGITS[(checksum >> 4) as usize]);
+            self.write_byte(HEX_DIGITS[(checksum & 0xF) as usize]);
+
+            match self.read_byte() {
+                b'+' => return,
+                _ => continue,
+            }
+        }
+    }
+
+    /// Waits for a byte to be received
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.port.receive() {
+                return byte;
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Sends a byte without any translation
+    fn write_byte(&mut self, byte: u8) {
+        while !self.port.is_output_empty() {
+            core::hint::spin_loop();
+        }
+
+        self.port.send_raw(byte);
+    }
+}
+
+/// Fixed size buffer for building a response packet
+pub struct Response {
+    buffer: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl Response {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Appends raw bytes, silently truncating the response if it's full
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let Some(slot) = self.buffer.get_mut(self.len) else {
+                return;
+            };
+
+            *slot = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Appends bytes encoded as pairs of hex digits
+    pub fn push_hex(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.push(&[
+                HEX_DIGITS[(byte >> 4) as usize],
+                HEX_DIGITS[(byte & 0xF) as usize],
+            ]);
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Converts a single hex digit to its value
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parses a big-endian hex number, as used for addresses and lengths
+pub fn parse_hex(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 16 {
+        return None;
+    }
+
+    digits.iter().try_fold(0, |value, &digit| {
+        Some(value << 4 | hex_value(digit)? as u64)
+    })
+}
+
+/// Decodes pairs of hex digits into bytes, returning `None` if the lengths don't match
+pub fn decode_hex(digits: &[u8], bytes: &mut [u8]) -> Option<()> {
+    if digits.len() != bytes.len() * 2 {
+        return None;
+    }
+
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = hex_value(pair[0])? << 4 | hex_value(pair[1])?;
+    }
+
+    Some(())
+}
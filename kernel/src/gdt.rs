@@ -1,38 +1,42 @@
-use core::ptr::addr_of;
+use core::ptr::{addr_of, addr_of_mut};
 
+use kernel_shared::memory::paging::active_table::ActivePageTable;
 use lazy_static::lazy_static;
 use x86_64::{
     segment_selector::SegmentSelector,
     structures::{Descriptor, GlobalDescriptorTable, TaskStateSegment},
 };
 
-pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+use crate::memory::{stack, KernelFrameAllocator};
 
-lazy_static! {
-    static ref TSS: TaskStateSegment = {
-        let mut tss = TaskStateSegment::default();
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const PAGE_FAULT_IST_INDEX: u16 = 1;
 
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-            const STACK_SIZE: usize = 4096 * 5;
-            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+/// Size of each interrupt stack
+const IST_STACK_SIZE: usize = 4096 * 5;
 
-            let stack_start = unsafe { addr_of!(STACK) as usize };
+/// Size of the stack switched to when an interrupt or syscall arrives from user mode
+const PRIVILEGE_STACK_SIZE: usize = 4096 * 16;
 
-            stack_start + STACK_SIZE
-        };
+/// Interrupt stacks are only known once memory is initialised, so they're filled in by [init]
+/// before the GDT pointing at this is first used
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
 
-        tss
-    };
+lazy_static! {
     static ref GDT: (GlobalDescriptorTable, Selectors) = {
         let mut gdt = GlobalDescriptorTable::default();
 
         let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
         let data_selector = gdt.add_entry(Descriptor::kernel_data_segment());
-        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
+        let user_data_selector = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code_selector = gdt.add_entry(Descriptor::user_code_segment());
+        let tss_selector = gdt.add_entry(Descriptor::tss_segment(unsafe { &*addr_of!(TSS) }));
 
         (gdt, Selectors {
             code_selector,
             data_selector,
+            user_code_selector,
+            user_data_selector,
             tss_selector,
         })
     };
@@ -42,12 +46,37 @@ lazy_static! {
 struct Selectors {
     code_selector: SegmentSelector,
     data_selector: SegmentSelector,
+    user_code_selector: SegmentSelector,
+    user_data_selector: SegmentSelector,
     tss_selector: SegmentSelector,
 }
 
-pub fn init() {
+/// Returns the code and data selectors for user mode
+pub fn user_selectors() -> (SegmentSelector, SegmentSelector) {
+    (GDT.1.user_code_selector, GDT.1.user_data_selector)
+}
+
+pub fn init(active_table: &mut ActivePageTable, frame_alloc: &mut KernelFrameAllocator) {
     log::trace!("initialising gdt");
 
+    // interrupt stacks get guard pages too, so an overflow in a handler double faults cleanly
+    let mut interrupt_stack_table = unsafe { (*addr_of!(TSS)).interrupt_stack_table };
+    for index in [DOUBLE_FAULT_IST_INDEX, PAGE_FAULT_IST_INDEX] {
+        let stack = stack::allocate(active_table, frame_alloc, IST_STACK_SIZE)
+            .expect("failed to allocate interrupt stack");
+        interrupt_stack_table[index as usize] = stack.top();
+
+        log::trace!("\t* interrupt stack {index} at {:#X}", stack.top());
+    }
+    unsafe { (*addr_of_mut!(TSS)).interrupt_stack_table = interrupt_stack_table };
+
+    let stack = stack::allocate(active_table, frame_alloc, PRIVILEGE_STACK_SIZE)
+        .expect("failed to allocate privilege stack");
+    let mut privilege_stack_table = unsafe { (*addr_of!(TSS)).privilege_stack_table };
+    privilege_stack_table[0] = stack.top();
+    unsafe { (*addr_of_mut!(TSS)).privilege_stack_table = privilege_stack_table };
+    log::trace!("\t* privilege stack at {:#X}", stack.top());
+
     GDT.0.load();
     log::trace!("\t* loaded GDT");
 
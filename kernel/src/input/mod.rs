@@ -0,0 +1,128 @@
+use crabstd::{mutex::Mutex, ring_buffer::RingBuffer};
+use ps2::{layout, Controller, KeyEvent, KeyState, Keyboard, Mouse, MouseEvent};
+
+/// Key repeat delay of 500ms and rate of 10.9 characters per second
+const TYPEMATIC: u8 = (0b01 << 5) | 0x0B;
+
+/// The 8042 PS/2 controller
+pub static CONTROLLER: Mutex<Controller> = Mutex::new(unsafe { Controller::new() });
+
+/// Keyboard decoder, or `None` if no keyboard was found
+pub static KEYBOARD: Mutex<Option<Keyboard>> = Mutex::new(None);
+
+/// Decoded key events, waiting to be consumed by a console or `stdin`
+pub static KEY_EVENTS: Mutex<RingBuffer<KeyEvent, 256>> = Mutex::new(RingBuffer::new());
+
+/// Mouse packet decoder, or `None` if no mouse was found
+pub static MOUSE: Mutex<Option<Mouse>> = Mutex::new(None);
+
+/// Decoded mouse events, waiting to be consumed by UI code
+pub static MOUSE_EVENTS: Mutex<RingBuffer<MouseEvent, 256>> = Mutex::new(RingBuffer::new());
+
+/// Initialises the PS/2 controller, keyboard and mouse, translating keys with the named keymap
+/// (US by default).
+/// Must be called before interrupts are enabled, since the controller is polled during init.
+pub fn init(keymap: Option<&str>) {
+    log::trace!("initialising input");
+
+    let mut controller = CONTROLLER.lock();
+
+    let set = match unsafe { controller.init() } {
+        Ok(set) => set,
+        Err(err) => {
+            log::warn!("\t* failed to initialise PS/2 controller: {err:?}");
+            return;
+        }
+    };
+    log::trace!("\t* keyboard using scancode {set:?}");
+
+    if let Err(err) = unsafe { controller.set_typematic(TYPEMATIC) } {
+        log::warn!("\t* failed to set key repeat rate: {err:?}");
+    }
+
+    let keymap = match keymap.map(|name| (name, layout::by_name(name))) {
+        Some((_, Some(keymap))) => keymap,
+        Some((name, None)) => {
+            log::warn!("\t* unknown keymap `{name}`, using `us`");
+            &layout::Us
+        }
+        None => &layout::Us,
+    };
+    *KEYBOARD.lock() = Some(Keyboard::new(set, keymap));
+
+    match unsafe { controller.init_mouse() } {
+        Ok(has_wheel) => {
+            log::trace!("\t* mouse enabled, has wheel: {has_wheel}");
+            *MOUSE.lock() = Some(Mouse::new(has_wheel));
+        }
+        Err(err) => log::warn!("\t* failed to initialise mouse: {err:?}"),
+    }
+
+    log::trace!("input initialised");
+}
+
+/// Reads a byte from the keyboard and queues any resulting key event.
+/// Should only be called from the keyboard interrupt handler.
+pub fn handle_keyboard_interrupt() {
+    let byte = unsafe { CONTROLLER.lock().read_data_unchecked() };
+
+    let event = match KEYBOARD.lock().as_mut() {
+        Some(keyboard) => keyboard.process_byte(byte),
+        None => None,
+    };
+
+    if let Some(event) = event {
+        if KEY_EVENTS.lock().push(event).is_err() {
+            log::warn!("key event queue full, dropping {:?}", event.code);
+        }
+    }
+}
+
+/// Reads a byte from the mouse and queues any resulting mouse event.
+/// Should only be called from the mouse interrupt handler.
+pub fn handle_mouse_interrupt() {
+    let byte = unsafe { CONTROLLER.lock().read_data_unchecked() };
+
+    let event = match MOUSE.lock().as_mut() {
+        Some(mouse) => mouse.process_byte(byte),
+        None => None,
+    };
+
+    if let Some(event) = event {
+        // UI code only cares about recent motion, so drop the oldest event when full
+        let mut events = MOUSE_EVENTS.lock();
+        if events.push(event).is_err() {
+            events.pop();
+            let _ = events.push(event);
+        }
+    }
+}
+
+/// Reads characters typed on the keyboard into the buffer as UTF-8, without waiting for input,
+/// returning the number of bytes read.
+pub fn read_keyboard(buffer: &mut [u8]) -> usize {
+    x86_64::interrupts::without_interrupts(|| {
+        let mut events = KEY_EVENTS.lock();
+        let mut written = 0;
+
+        while let Some(event) = events.peek() {
+            let character = match (event.state, event.character) {
+                (KeyState::Pressed | KeyState::Repeat, Some(character)) => character,
+                _ => {
+                    events.pop();
+                    continue;
+                }
+            };
+
+            // leave characters which don't fit for the next read
+            if written + character.len_utf8() > buffer.len() {
+                break;
+            }
+
+            written += character.encode_utf8(&mut buffer[written..]).len();
+            events.pop();
+        }
+
+        written
+    })
+}
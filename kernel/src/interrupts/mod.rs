@@ -1,22 +1,43 @@
-use core::{arch::asm, fmt::Debug};
+use core::{
+    arch::asm,
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use bitflags::bitflags;
 use crabstd::mutex::Mutex;
+use kernel_shared::memory::stack::{slot_containing, KERNEL_STACK_SLOT};
 use lazy_static::lazy_static;
 use x86_64::{
-    registers::CR2,
+    registers::{CpuFlags, CR2},
     structures::{ExceptionStackFrame, InterruptDescriptorTable},
 };
 
-use self::pic::ChainedPics;
-use crate::{gdt, print, println};
+pub use self::trap::TrapFrame;
+use self::{pic::ChainedPics, trap::trap_handler};
+use crate::{
+    backtrace,
+    gdbstub::{self, Trap},
+    gdt, input,
+    io::{self, WRITER},
+    memory::address_space::USER_SPACE_END,
+    println,
+    process::{self, ExitStatus},
+};
 
 mod pic;
 mod syscall;
+mod trap;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
+/// Number of timer ticks between each toggle of the cursor
+const CURSOR_BLINK_TICKS: usize = 9;
+
+/// Number of timer interrupts since boot
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
 pub static PICS: Mutex<ChainedPics> =
     Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
@@ -24,6 +45,16 @@ pub static PICS: Mutex<ChainedPics> =
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
+    Keyboard,
+    Com1 = PIC_1_OFFSET + 4,
+    Mouse = PIC_2_OFFSET + 4,
+}
+
+impl InterruptIndex {
+    /// Returns the IRQ line of the interrupt
+    pub fn irq(self) -> u8 {
+        self as u8 - PIC_1_OFFSET
+    }
 }
 
 lazy_static! {
@@ -31,9 +62,15 @@ lazy_static! {
         let mut idt = InterruptDescriptorTable::default();
 
         idt.divide_error.set(divide_by_zero_handler);
-        idt.breakpoint.set(breakpoint_handler);
+        idt.debug.set(debug_entry);
+        idt.breakpoint.set(breakpoint_entry);
         idt.invalid_opcode.set(invalid_opcode_handler);
-        idt.page_fault.set(page_fault_handler);
+        // page faults get their own stack, so overflowing the kernel stack can still be reported
+        unsafe {
+            idt.page_fault
+                .set(page_fault_handler)
+                .set_ist_index(gdt::PAGE_FAULT_IST_INDEX);
+        }
         idt.general_protection_fault
             .set(general_protection_fault_handler);
         unsafe {
@@ -43,7 +80,11 @@ lazy_static! {
         }
 
         idt[InterruptIndex::Timer as u8].set(timer_interrupt_handler);
-        idt[0x80].set(syscall_handler);
+        idt[InterruptIndex::Keyboard as u8].set(keyboard_interrupt_handler);
+        idt[InterruptIndex::Com1 as u8].set(com1_interrupt_handler);
+        idt[InterruptIndex::Mouse as u8].set(mouse_interrupt_handler);
+        // syscalls are made from user mode
+        idt[0x80].set(syscall_handler).set_privilege_level(3);
 
         idt
     };
@@ -52,6 +93,7 @@ lazy_static! {
 extern "x86-interrupt" fn divide_by_zero_handler(stack_frame: ExceptionStackFrame) {
     log::error!("EXCEPTION: DIVIDE BY ZERO\n{}", stack_frame);
     println!("\nEXCEPTION: DIVIDE BY ZERO\n{}", stack_frame);
+    backtrace::print_exception(&stack_frame);
 
     x86_64::hlt_loop();
 }
@@ -66,11 +108,19 @@ extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: ExceptionStackFram
         "\nEXCEPTION: INVALID OPCODE at {:#X}\n{}",
         stack_frame.instruction_pointer, stack_frame
     );
+    backtrace::print_exception(&stack_frame);
 
     x86_64::hlt_loop();
 }
 
-extern "x86-interrupt" fn breakpoint_handler(stack_frame: ExceptionStackFrame) {
+trap_handler!(breakpoint_entry => breakpoint_handler);
+
+extern "C" fn breakpoint_handler(frame: &mut TrapFrame) {
+    if gdbstub::handle_trap(frame, Trap::Breakpoint) {
+        return;
+    }
+
+    let stack_frame = &frame.stack_frame;
     log::info!(
         "EXCEPTION: BREAKPOINT at {:#X}\n{}",
         stack_frame.instruction_pointer,
@@ -82,14 +132,29 @@ extern "x86-interrupt" fn breakpoint_handler(stack_frame: ExceptionStackFrame) {
     );
 }
 
+trap_handler!(debug_entry => debug_handler);
+
+extern "C" fn debug_handler(frame: &mut TrapFrame) {
+    if gdbstub::handle_trap(frame, Trap::SingleStep) {
+        return;
+    }
+
+    // nothing else sets the trap flag, so just stop single-stepping
+    frame.stack_frame.cpu_flags.remove(CpuFlags::TRAP_FLAG);
+    log::warn!(
+        "unexpected debug exception at {:#X}",
+        frame.stack_frame.instruction_pointer
+    );
+}
+
 extern "x86-interrupt" fn double_fault(stack_frame: ExceptionStackFrame, err: u64) -> ! {
     log::error!("DOUBLE FAULT with err {}\n{}", err, stack_frame);
     panic!("\nDOUBLE FAULT with err {}\n{}", err, stack_frame);
 }
 
 bitflags! {
-    #[derive(Debug)]
-    struct PageFaultErrorCode: u64 {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PageFaultErrorCode: u64 {
         const PROTECTION_VIOLATION = 1 << 0;
         const CAUSED_BY_WRITE = 1 << 1;
         const USER_MODE = 1 << 2;
@@ -99,20 +164,69 @@ bitflags! {
 }
 
 extern "x86-interrupt" fn page_fault_handler(stack_frame: ExceptionStackFrame, error_code: u64) {
+    let addr = CR2::read();
+    let error = PageFaultErrorCode::from_bits(error_code).unwrap();
+
+    // user memory is mapped on first touch, by the kernel as well as the process itself
+    if addr < USER_SPACE_END {
+        match process::handle_page_fault(addr, error) {
+            Some(Ok(())) => return,
+            Some(Err(err)) if error.contains(PageFaultErrorCode::USER_MODE) => {
+                let pid = process::current_pid().unwrap();
+
+                log::error!(
+                    "killing process {pid}: {err} while accessing {addr:#X}\nerror code: {:?}\n{}",
+                    error,
+                    stack_frame
+                );
+                println!(
+                    "\nkilling process {pid}: {err} while accessing {addr:#X} at {:#X}",
+                    stack_frame.instruction_pointer
+                );
+
+                process::exit_current(ExitStatus::Killed);
+            }
+            // the kernel faulting on user memory is a kernel bug, so is reported below
+            _ => {}
+        }
+    }
+
+    // everything in the stack region that isn't mapped is a guard page
+    let overflowed_slot =
+        slot_containing(addr).filter(|_| !error.contains(PageFaultErrorCode::PROTECTION_VIOLATION));
+    if let Some(slot) = overflowed_slot {
+        let stack = if slot == KERNEL_STACK_SLOT {
+            "kernel stack"
+        } else {
+            "stack"
+        };
+
+        log::error!(
+            "EXCEPTION: STACK OVERFLOW in {stack} (slot {slot}) while accessing {addr:#X}\n{}",
+            stack_frame
+        );
+        println!(
+            "\nEXCEPTION: STACK OVERFLOW in {stack} (slot {slot}) while accessing {addr:#X}\n{}",
+            stack_frame
+        );
+        backtrace::print_exception(&stack_frame);
+
+        x86_64::hlt_loop();
+    }
+
     log::error!(
         "EXCEPTION: PAGE FAULT while accessing {:#X}\
         \nerror code: {:?}\n{}",
-        CR2::read(),
-        PageFaultErrorCode::from_bits(error_code).unwrap(),
+        addr,
+        error,
         stack_frame
     );
     println!(
         "\nEXCEPTION: PAGE FAULT while accessing {:#X}\
         \nerror code: {:?}\n{}",
-        CR2::read(),
-        PageFaultErrorCode::from_bits(error_code).unwrap(),
-        stack_frame
+        addr, error, stack_frame
     );
+    backtrace::print_exception(&stack_frame);
 
     x86_64::hlt_loop();
 }
@@ -135,6 +249,7 @@ extern "x86-interrupt" fn general_protection_fault_handler(
         error_code,
         stack_frame
     );
+    backtrace::print_exception(&stack_frame);
 
     x86_64::hlt_loop();
 }
@@ -142,11 +257,18 @@ extern "x86-interrupt" fn general_protection_fault_handler(
 /// Stub interrupt handler that simply jumps to the correct syscall based on the value in `rax`
 #[naked]
 extern "x86-interrupt" fn syscall_handler(_stack_frame: ExceptionStackFrame) {
-    // make sure to mask rax to prevent jumping outside the syscall table
+    // make sure to mask rax to prevent jumping outside the syscall table.
+    // the kernel is position independent, so the table has to be found relative to rip, and the
+    // syscall number stays in rax, so r11 is borrowed and swapped for the target before returning
+    // into it
     unsafe {
         asm!(
             "and rax, 0xFF",
-            "jmp [{} + rax*8]",
+            "push r11",
+            "lea r11, [rip + {}]",
+            "mov r11, [r11 + rax*8]",
+            "xchg r11, [rsp]",
+            "ret",
             sym syscall::SYSCALL_TABLE,
             options(noreturn)
         );
@@ -154,7 +276,12 @@ extern "x86-interrupt" fn syscall_handler(_stack_frame: ExceptionStackFrame) {
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: ExceptionStackFrame) {
-    print!(".");
+    // blink the cursor roughly twice a second
+    if TICKS.fetch_add(1, Ordering::Relaxed) % CURSOR_BLINK_TICKS == 0 {
+        if let Some(writer) = WRITER.lock().get_mut() {
+            writer.toggle_cursor();
+        }
+    }
 
     unsafe {
         PICS.lock()
@@ -162,6 +289,33 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: ExceptionStackFr
     }
 }
 
+extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: ExceptionStackFrame) {
+    input::handle_keyboard_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Keyboard as u8);
+    }
+}
+
+extern "x86-interrupt" fn com1_interrupt_handler(_stack_frame: ExceptionStackFrame) {
+    io::serial::handle_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Com1 as u8);
+    }
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: ExceptionStackFrame) {
+    input::handle_mouse_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Mouse as u8);
+    }
+}
+
 pub fn init() {
     log::trace!("initialising interrupts");
 
@@ -169,7 +323,11 @@ pub fn init() {
     log::trace!("\t* loaded IDT");
 
     unsafe {
-        PICS.lock().init();
+        let mut pics = PICS.lock();
+        pics.init();
+        pics.unmask(InterruptIndex::Keyboard.irq());
+        pics.unmask(InterruptIndex::Com1.irq());
+        pics.unmask(InterruptIndex::Mouse.irq());
     }
     log::trace!("\t* initialised PIC");
 
@@ -178,3 +336,37 @@ pub fn init() {
 
     log::trace!("interrupts initialised");
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{arch::asm, sync::atomic::Ordering};
+
+    use super::TICKS;
+
+    #[test_case]
+    fn breakpoint_returns() {
+        unsafe { asm!("int3") };
+    }
+
+    #[test_case]
+    fn timer_interrupt_fires() {
+        let ticks = TICKS.load(Ordering::Relaxed);
+
+        // the timer fires every ~55ms, so this should only take a couple of interrupts
+        for _ in 0..100 {
+            if TICKS.load(Ordering::Relaxed) > ticks {
+                return;
+            }
+
+            unsafe { asm!("hlt") };
+        }
+
+        panic!("no timer interrupt received");
+    }
+
+    #[test_case]
+    fn interrupts_are_enabled() {
+        assert!(x86_64::registers::CpuFlags::read()
+            .contains(x86_64::registers::CpuFlags::INTERRUPT_FLAG));
+    }
+}
@@ -22,6 +22,9 @@ const CMD_END_OF_INTERRUPT: u8 = 0x20;
 
 const MODE_8086: u8 = 0x01;
 
+/// IRQ line of the first PIC which the second PIC is connected to
+const CASCADE_IRQ: u8 = 2;
+
 struct Pic {
     offset: u8,
     command: Port<u8>,
@@ -108,6 +111,20 @@ impl ChainedPics {
         self.pics[1].write_mask(mask2);
     }
 
+    /// Unmasks the given IRQ line, also unmasking the cascade line if it's on the second PIC
+    pub unsafe fn unmask(&mut self, irq: u8) {
+        let [mut mask1, mut mask2] = self.read_masks();
+
+        if irq < 8 {
+            mask1 &= !(1 << irq);
+        } else {
+            mask1 &= !(1 << CASCADE_IRQ);
+            mask2 &= !(1 << (irq - 8));
+        }
+
+        self.write_masks(mask1, mask2);
+    }
+
     pub fn handles_interrupt(&self, interrupt_id: u8) -> bool {
         self.pics.iter().any(|p| p.handles_interrupt(interrupt_id))
     }
@@ -1,9 +1,16 @@
-use core::{arch::asm, ops::Deref};
+use core::arch::asm;
 
 use crabstd::{
     fs::{File, FileSystem, Path},
     syscall as syscalls,
 };
+use kernel_shared::memory::paging::entry::EntryFlags;
+
+use super::{trap::trap_handler, TrapFrame};
+use crate::{
+    memory::address_space::{Backing, VmaError},
+    process::ExitStatus,
+};
 
 macro_rules! syscall {
     ($arg1:expr) => {
@@ -56,6 +63,14 @@ pub(super) static SYSCALL_TABLE: [&(); 256] = syscall_table!(
     syscalls::NO_FUNCTION => no_function,
     syscalls::OPEN => open,
     syscalls::READ => read,
+    syscalls::SHUTDOWN => shutdown,
+    syscalls::REBOOT => reboot,
+    syscalls::EXIT => exit,
+    syscalls::FORK => fork_entry,
+    syscalls::MMAP => mmap_entry,
+    syscalls::MUNMAP => munmap_entry,
+    syscalls::MPROTECT => mprotect_entry,
+    syscalls::BRK => brk_entry,
 );
 
 #[no_mangle]
@@ -90,23 +105,17 @@ extern "x86-interrupt" fn read() {
         buffer.len()
     );
 
-    let device = file.path().device().unwrap();
-
-    let written = match device {
-        "ramfs" => crate::RAMFS
-            .lock()
-            .as_ref()
-            .unwrap()
-            .read_file(file, buffer),
-        _ => {
-            log::warn!(
-                "attempted to read from invalid device `{}`, path `{}`",
-                device,
-                file.path().deref()
-            );
-            0
+    // pages of the buffer that haven't been touched yet are faulted in now, as the file system
+    // is locked while it copies into the buffer
+    if !crate::process::fault_in(buffer.as_ptr() as usize, buffer.len()) {
+        log::warn!("\t* buffer is not writable by the current process");
+        unsafe {
+            *bytes_read = 0;
         }
-    };
+        return;
+    }
+
+    let written = crate::fs::read(file, buffer);
 
     unsafe {
         *bytes_read = written;
@@ -128,6 +137,10 @@ extern "x86-interrupt" fn open() {
     log::trace!("\t* path: {path:?}");
 
     let (device, path) = path.device_path().unwrap();
+    let device = match device {
+        "" => *crate::ROOT_DEVICE.lock(),
+        device => device,
+    };
 
     let driver_response = match device {
         "ramfs" => crate::RAMFS
@@ -135,6 +148,7 @@ extern "x86-interrupt" fn open() {
             .as_ref()
             .unwrap()
             .open_file(Path::new(path)),
+        "serial" | "stdin" => true,
         _ => {
             log::warn!(
                 "attempted to open path `{}` on invalid device `{}`",
@@ -149,3 +163,200 @@ extern "x86-interrupt" fn open() {
         *is_valid = driver_response;
     }
 }
+
+#[no_mangle]
+extern "x86-interrupt" fn shutdown() {
+    log::info!("shutdown syscall called");
+
+    crate::acpi::shutdown();
+}
+
+#[no_mangle]
+extern "x86-interrupt" fn reboot() {
+    log::info!("reboot syscall called");
+
+    crate::acpi::reboot();
+}
+
+#[no_mangle]
+extern "x86-interrupt" fn exit() {
+    let status: i64;
+
+    unsafe {
+        syscall!(status);
+    }
+
+    log::info!("exit syscall called");
+    log::trace!("\t* status: {status}");
+
+    if !crate::process::is_running() {
+        log::warn!("\t* no process is running");
+        return;
+    }
+
+    crate::process::exit_current(ExitStatus::Exited(status));
+}
+
+trap_handler!(fork_entry => fork);
+
+/// Needs the caller's full register state, which the child process resumes with
+extern "C" fn fork(frame: &mut TrapFrame) {
+    log::info!("fork syscall called");
+
+    frame.rax = match crate::process::fork_current(frame) {
+        Some(pid) => pid as u64,
+        None => {
+            log::warn!("\t* fork failed");
+            u64::MAX
+        }
+    };
+}
+
+/// Converts `PROT_*` flags to the flags a region's pages are mapped with
+fn prot_flags(prot: usize) -> EntryFlags {
+    let mut flags = EntryFlags::empty();
+
+    if prot & syscalls::PROT_WRITE != 0 {
+        flags |= EntryFlags::WRITABLE;
+    }
+    if prot & syscalls::PROT_EXEC == 0 {
+        flags |= EntryFlags::NO_EXECUTE;
+    }
+
+    flags
+}
+
+/// Converts the result of a memory syscall to the value returned in `rax`, which is -1 on failure
+fn memory_result(result: Option<Result<usize, VmaError>>) -> u64 {
+    match result {
+        Some(Ok(value)) => value as u64,
+        Some(Err(err)) => {
+            log::warn!("\t* {err}");
+            u64::MAX
+        }
+        None => {
+            log::warn!("\t* no process is running");
+            u64::MAX
+        }
+    }
+}
+
+trap_handler!(mmap_entry => mmap);
+
+extern "C" fn mmap(frame: &mut TrapFrame) {
+    let addr = frame.rdi as usize;
+    let len = frame.rsi as usize;
+    let prot = frame.rdx as usize;
+    let file = frame.rcx as *const File;
+
+    log::info!("mmap syscall called");
+    log::trace!("\t* addr: {addr:#X}, len: {len:#X}, prot: {prot:#b}");
+
+    // the file is copied, as the process can drop its own copy while the mapping still exists
+    let backing = match unsafe { file.as_ref() } {
+        Some(file) => {
+            log::trace!("\t* file: {file:?}");
+            Backing::File {
+                file: file.clone(),
+                offset: file.offset(),
+                len,
+            }
+        }
+        None => Backing::Anonymous,
+    };
+
+    frame.rax = memory_result(crate::process::with_address_space(|address_space, _| {
+        address_space.map(addr, len, prot_flags(prot), backing)
+    }));
+}
+
+trap_handler!(munmap_entry => munmap);
+
+extern "C" fn munmap(frame: &mut TrapFrame) {
+    let addr = frame.rdi as usize;
+    let len = frame.rsi as usize;
+
+    log::info!("munmap syscall called");
+    log::trace!("\t* addr: {addr:#X}, len: {len:#X}");
+
+    frame.rax = memory_result(crate::process::with_address_space(
+        |address_space, frame_alloc| address_space.unmap(addr, len, frame_alloc).map(|_| 0),
+    ));
+}
+
+trap_handler!(mprotect_entry => mprotect);
+
+extern "C" fn mprotect(frame: &mut TrapFrame) {
+    let addr = frame.rdi as usize;
+    let len = frame.rsi as usize;
+    let prot = frame.rdx as usize;
+
+    log::info!("mprotect syscall called");
+    log::trace!("\t* addr: {addr:#X}, len: {len:#X}, prot: {prot:#b}");
+
+    frame.rax = memory_result(crate::process::with_address_space(|address_space, _| {
+        address_space
+            .protect(addr, len, prot_flags(prot))
+            .map(|_| 0)
+    }));
+}
+
+trap_handler!(brk_entry => brk);
+
+/// Returns the program break in `rax`, which is left where it was if it can't be moved
+extern "C" fn brk(frame: &mut TrapFrame) {
+    let addr = frame.rdi as usize;
+
+    log::info!("brk syscall called");
+    log::trace!("\t* addr: {addr:#X}");
+
+    let brk = crate::process::with_address_space(|address_space, frame_alloc| {
+        if addr != 0 && !address_space.set_brk(addr, frame_alloc) {
+            log::warn!("\t* can't move program break");
+        }
+
+        address_space.brk()
+    });
+
+    frame.rax = memory_result(brk.map(Ok));
+}
+
+#[cfg(test)]
+mod tests {
+    use crabstd::fs::File;
+
+    #[test_case]
+    fn open_existing_file() {
+        assert!(File::new("ramfs//test").is_some());
+    }
+
+    #[test_case]
+    fn open_missing_file() {
+        assert!(File::new("ramfs//missing").is_none());
+        assert!(File::new("nodevice//test").is_none());
+    }
+
+    #[test_case]
+    fn open_on_root_device() {
+        assert!(File::new("//test").is_some());
+        assert!(File::new("//missing").is_none());
+    }
+
+    #[test_case]
+    fn read_file() {
+        let mut file = File::new("ramfs//silly").unwrap();
+        let mut buffer = [0; 32];
+
+        let read = file.read(&mut buffer);
+        assert_eq!(&buffer[..read], b"blehhh :p");
+    }
+
+    #[test_case]
+    fn read_into_small_buffer() {
+        let mut file = File::new("ramfs//test").unwrap();
+        let mut buffer = [0; 4];
+
+        assert_eq!(file.read(&mut buffer), 4);
+        assert_eq!(&buffer, b"this");
+    }
+}
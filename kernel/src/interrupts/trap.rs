@@ -0,0 +1,78 @@
+use x86_64::structures::ExceptionStackFrame;
+
+/// Full register state of the interrupted code, as pushed by a [trap_handler] entry stub
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    /// Pushed by the CPU, so changes to it take effect on return
+    pub stack_frame: ExceptionStackFrame,
+}
+
+/// Defines an interrupt entry stub which saves every general purpose register to a [TrapFrame],
+/// calls `$handler` with it, and restores the (possibly modified) registers before returning.
+/// Must only be used for interrupts without an error code.
+macro_rules! trap_handler {
+    ($name:ident => $handler:path) => {
+        #[naked]
+        extern "x86-interrupt" fn $name(_stack_frame: x86_64::structures::ExceptionStackFrame) {
+            // the cpu aligns the stack before pushing 5 values, so after pushing 15 more the stack
+            // is 16-byte aligned again for the call
+            unsafe {
+                core::arch::asm!(
+                    "push rax",
+                    "push rbx",
+                    "push rcx",
+                    "push rdx",
+                    "push rsi",
+                    "push rdi",
+                    "push rbp",
+                    "push r8",
+                    "push r9",
+                    "push r10",
+                    "push r11",
+                    "push r12",
+                    "push r13",
+                    "push r14",
+                    "push r15",
+                    "mov rdi, rsp",
+                    "call {}",
+                    "pop r15",
+                    "pop r14",
+                    "pop r13",
+                    "pop r12",
+                    "pop r11",
+                    "pop r10",
+                    "pop r9",
+                    "pop r8",
+                    "pop rbp",
+                    "pop rdi",
+                    "pop rsi",
+                    "pop rdx",
+                    "pop rcx",
+                    "pop rbx",
+                    "pop rax",
+                    "iretq",
+                    sym $handler,
+                    options(noreturn)
+                );
+            }
+        }
+    };
+}
+
+pub(super) use trap_handler;
@@ -2,7 +2,7 @@ use core::{fmt, ops::Range, ptr};
 
 use crabstd::volatile::Volatile;
 use font_constants::{BACKUP_CHAR, CHAR_RASTER_HEIGHT, CHAR_RASTER_WIDTH};
-use multiboot::FramebufferInfo;
+use kernel_shared::handoff::Framebuffer;
 use noto_sans_mono_bitmap::{
     get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar,
 };
@@ -56,25 +56,22 @@ fn get_char_raster(c: char) -> RasterizedChar {
 /// Struct representing a pixel-based framebuffer
 pub struct FrameBufferWriter {
     framebuffer: &'static mut [Volatile<u8>],
-    info: FramebufferInfo,
+    info: Framebuffer,
     x_pos: usize,
     y_pos: usize,
     cursor_drawn: bool,
 }
 
 impl FrameBufferWriter {
-    pub fn from_framebuffer(info: FramebufferInfo) -> Self {
+    pub fn from_framebuffer(info: Framebuffer) -> Self {
         let buffer = unsafe {
-            core::slice::from_raw_parts_mut(
-                0xFFFFFFFF40000000 as *mut _,
-                (info.pitch * info.height) as usize,
-            )
+            core::slice::from_raw_parts_mut(info.virt_addr as *mut _, info.size())
         };
 
         FrameBufferWriter::new(buffer, info)
     }
 
-    pub fn new(framebuffer: &'static mut [Volatile<u8>], info: FramebufferInfo) -> Self {
+    pub fn new(framebuffer: &'static mut [Volatile<u8>], info: Framebuffer) -> Self {
         let mut buffer = Self {
             framebuffer,
             info,
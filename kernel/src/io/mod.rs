@@ -3,15 +3,17 @@
 use core::{
     cell::Cell,
     fmt::{self, Write},
+    sync::atomic::{AtomicU8, Ordering},
 };
 
 use crabstd::mutex::Mutex;
+use kernel_shared::{cmdline::Consoles, handoff::Framebuffer};
 use x86_64::interrupts;
 
 use self::{framebuffer::FrameBufferWriter, textbuffer::TextBufferWriter};
-use crate::BootInfo;
 
 pub mod framebuffer;
+pub mod serial;
 pub mod textbuffer;
 
 /// Stores some form of text writer
@@ -21,9 +23,8 @@ pub enum Writer {
 }
 
 impl Writer {
-    /// Selects the correct writer type based in information returned by multiboot
-    pub fn from_bootinfo(bootinfo: &BootInfo) -> Option<Self> {
-        let framebuffer_info = bootinfo.framebuffer_info.unwrap();
+    /// Selects the correct writer type based on the framebuffer passed by the loader
+    pub fn from_framebuffer(framebuffer_info: Framebuffer) -> Option<Self> {
         log::trace!("\t* framebuffer type: {}", framebuffer_info.buffer_type);
         log::trace!("\t* framebuffer bits per pixel: {}", framebuffer_info.bpp);
         log::trace!(
@@ -46,6 +47,13 @@ impl Writer {
             _ => None,
         }
     }
+
+    /// Toggles the text cursor, if the writer draws one
+    pub fn toggle_cursor(&mut self) {
+        if let Writer::Framebuffer(framebuffer) = self {
+            framebuffer.toggle_cursor();
+        }
+    }
 }
 
 impl Write for Writer {
@@ -59,8 +67,34 @@ impl Write for Writer {
 
 pub static WRITER: Mutex<Cell<Option<Writer>>> = Mutex::new(Cell::new(None));
 
+/// Consoles that [print!] and [println!] write to, selected by the `console` command line option
+static CONSOLES: AtomicU8 = AtomicU8::new(Consoles::all().bits());
+
+/// Selects which consoles output is written to
+pub fn set_consoles(consoles: Consoles) {
+    CONSOLES.store(consoles.bits(), Ordering::Relaxed);
+}
+
+/// Returns the consoles output is written to
+pub fn consoles() -> Consoles {
+    Consoles::from_bits_truncate(CONSOLES.load(Ordering::Relaxed))
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
+    let consoles = consoles();
+
+    if consoles.contains(Consoles::SERIAL) {
+        serial::SerialConsole.write_fmt(args).unwrap();
+    }
+
+    if consoles.contains(Consoles::FRAMEBUFFER) {
+        print_framebuffer(args);
+    }
+}
+
+/// Writes to the framebuffer, regardless of which consoles are selected
+pub fn print_framebuffer(args: fmt::Arguments) {
     interrupts::without_interrupts(|| {
         let mut writer = WRITER.lock();
         // safe because writer _must_ be initialised as part of booting process
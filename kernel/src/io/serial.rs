@@ -0,0 +1,145 @@
+use core::fmt;
+
+use crabstd::{mutex::Mutex, ring_buffer::RingBuffer};
+use kernel_shared::{
+    serial::SERIAL1,
+    serial_port::{InterruptEnableFlags, SerialPort},
+};
+use x86_64::interrupts;
+
+/// Bytes received from COM1, waiting to be read
+static RX: Mutex<RingBuffer<u8, 1024>> = Mutex::new(RingBuffer::new());
+
+/// Bytes waiting to be sent to COM1
+static TX: Mutex<RingBuffer<u8, 4096>> = Mutex::new(RingBuffer::new());
+
+/// Console on COM1, which echoes received input and buffers output so the transmit interrupt can
+/// send it in the background
+pub struct SerialConsole;
+
+impl fmt::Write for SerialConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+/// Enables the receive interrupt on COM1
+pub fn init() {
+    log::trace!("initialising serial console");
+
+    interrupts::without_interrupts(|| {
+        SERIAL1
+            .lock()
+            .set_interrupts(InterruptEnableFlags::RECEIVED_DATA)
+    });
+
+    log::trace!("serial console initialised");
+}
+
+/// Queues bytes to be sent, translating `\n` into `\r\n` for terminals
+pub fn write(bytes: &[u8]) {
+    interrupts::without_interrupts(|| {
+        let mut port = SERIAL1.lock();
+        let mut tx = TX.lock();
+
+        for &byte in bytes {
+            if byte == b'\n' {
+                queue(&mut port, &mut tx, b'\r');
+            }
+            queue(&mut port, &mut tx, byte);
+        }
+
+        transmit(&mut port, &mut tx);
+    })
+}
+
+/// Reads received bytes into the buffer without waiting for input,
+/// returning the number of bytes read.
+pub fn read(buffer: &mut [u8]) -> usize {
+    interrupts::without_interrupts(|| {
+        let mut rx = RX.lock();
+        let mut read = 0;
+
+        for slot in buffer.iter_mut() {
+            let Some(byte) = rx.pop() else {
+                break;
+            };
+
+            *slot = byte;
+            read += 1;
+        }
+
+        read
+    })
+}
+
+/// Moves received bytes into the receive buffer, and refills the transmit FIFO.
+/// Should only be called from the COM1 interrupt handler.
+pub fn handle_interrupt() {
+    let mut port = SERIAL1.lock();
+    let mut rx = RX.lock();
+    let mut tx = TX.lock();
+
+    while let Some(byte) = port.receive() {
+        // terminals send `\r` for enter and DEL for backspace, so match the keyboard instead
+        let byte = match byte {
+            b'\r' => b'\n',
+            0x7F => 0x08,
+            byte => byte,
+        };
+
+        if rx.push(byte).is_err() {
+            // nobody is reading, so don't echo input that will be lost
+            continue;
+        }
+
+        match byte {
+            b'\n' => {
+                queue(&mut port, &mut tx, b'\r');
+                queue(&mut port, &mut tx, b'\n');
+            }
+            0x08 => {
+                queue(&mut port, &mut tx, 0x08);
+                queue(&mut port, &mut tx, b' ');
+                queue(&mut port, &mut tx, 0x08);
+            }
+            byte => queue(&mut port, &mut tx, byte),
+        }
+    }
+
+    transmit(&mut port, &mut tx);
+}
+
+/// Pushes a byte to the transmit buffer, sending bytes synchronously if it's full
+fn queue(port: &mut SerialPort, tx: &mut RingBuffer<u8, 4096>, byte: u8) {
+    while tx.push(byte).is_err() {
+        while !port.is_output_empty() {
+            core::hint::spin_loop();
+        }
+
+        transmit(port, tx);
+    }
+}
+
+/// Fills the transmit FIFO from the buffer, and only enables the transmit interrupt while there is
+/// still data waiting
+fn transmit(port: &mut SerialPort, tx: &mut RingBuffer<u8, 4096>) {
+    if port.is_output_empty() {
+        for _ in 0..SerialPort::FIFO_SIZE {
+            let Some(byte) = tx.pop() else {
+                break;
+            };
+
+            port.send_raw(byte);
+        }
+    }
+
+    let interrupts = if tx.is_empty() {
+        InterruptEnableFlags::RECEIVED_DATA
+    } else {
+        InterruptEnableFlags::RECEIVED_DATA | InterruptEnableFlags::TRANSMIT_EMPTY
+    };
+    port.set_interrupts(interrupts);
+}
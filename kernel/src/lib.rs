@@ -1,5 +1,7 @@
 #![no_std]
+#![cfg_attr(test, no_main)]
 #![feature(
+    custom_test_frameworks,
     inline_const_pat,
     const_mut_refs,
     const_trait_impl,
@@ -11,78 +13,105 @@
     naked_functions,
     array_windows
 )]
+#![test_runner(crate::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
 extern crate alloc;
 
-use alloc::string::{String, ToString};
+use alloc::{string::String, vec::Vec};
 use core::{
     panic::PanicInfo,
     sync::atomic::{AtomicBool, Ordering},
 };
 
-use crabstd::{fs::File, mutex::Mutex};
+use crabstd::mutex::Mutex;
 use initrd::Initrd;
 use kernel_shared::{
+    cmdline::CommandLine,
+    handoff::{Handoff, PhysRange},
     logger::Logger,
-    memory::{frame_alloc::bitmap::BitmapFrameAllocator, paging::active_table::ActivePageTable},
-    serial_println,
+    memory::paging::{active_table::ActivePageTable, PHYS_MEM_OFFSET},
 };
 use ram::Ram;
 
-use crate::io::{Writer, WRITER};
+use crate::{
+    io::{Writer, WRITER},
+    memory::KernelFrameAllocator,
+};
 
+mod acpi;
+mod backtrace;
+mod fs;
+mod gdbstub;
 mod gdt;
+mod input;
 mod interrupts;
 mod io;
 mod memory;
+#[cfg(not(test))]
+mod monitor;
+mod process;
+mod smbios;
+#[cfg(test)]
+mod testing;
 
 static LOGGER: Logger = Logger::new(log::LevelFilter::Trace);
 
-pub const MODULE_COUNT: usize = 4;
-pub type BootInfo = multiboot::BootInfo<MODULE_COUNT>;
-
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    serial_println!("{}", info);
+    kernel_shared::serial_println!("{}", info);
     log::error!("{}", info);
     println!("err: {}", info);
+    backtrace::print();
 
     x86_64::hlt_loop()
 }
 
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    testing::panic_handler(info)
+}
+
 static RAMFS: Mutex<Option<Initrd<Ram>>> = Mutex::new(None);
 
-// needed for false positive on `BootInfo::new`
-#[allow(clippy::not_unsafe_ptr_arg_deref)]
+/// Device used for paths which don't name one, set by the `root` command line option
+static ROOT_DEVICE: Mutex<&'static str> = Mutex::new("ramfs");
+
 #[no_mangle]
-pub extern "C" fn kernel_main(addr: *const u32, loader_start: usize, loader_end: usize) {
-    // bootinfo is only valid for this scope
-    let (
-        InitInfo {
-            mut frame_alloc,
-            mut active_table,
-            initrd_range: (initrd_start, initrd_end),
-        },
-        bootinfo_start,
-        bootinfo_end,
-    ) = {
-        let bootinfo = unsafe { BootInfo::new(addr) };
-
-        (
-            init(&bootinfo, loader_start, loader_end),
-            addr as usize,
-            addr as usize + bootinfo.total_size,
-        )
-    };
+pub extern "C" fn kernel_main(handoff_addr: usize) {
+    // handoff lives in loader memory, which is freed during init, so it's copied out first
+    let handoff = unsafe { *((handoff_addr + PHYS_MEM_OFFSET) as *const Handoff) };
+    assert!(
+        handoff.is_valid(),
+        "kernel started with an invalid handoff (version {}, expected {})",
+        handoff.version,
+        kernel_shared::handoff::HANDOFF_VERSION
+    );
 
-    unsafe {
-        memory::free_region(
-            &mut active_table,
-            &mut frame_alloc,
-            bootinfo_start,
-            bootinfo_end,
-        )
-    }
+    let init_info = init(&handoff);
+
+    #[cfg(test)]
+    testing::run(init_info, test_main);
+
+    #[cfg(not(test))]
+    run(init_info);
+}
+
+/// Runs the kernel once everything is initialised
+#[cfg(not(test))]
+fn run(
+    InitInfo {
+        mut frame_alloc,
+        mut active_table,
+        initrd_range: (initrd_start, initrd_end),
+        init,
+    }: InitInfo,
+) -> ! {
+    use alloc::string::ToString;
+
+    use crabstd::fs::File;
 
     fn read_file(path: &str) -> Option<String> {
         let mut buf = [0; 16384];
@@ -112,6 +141,21 @@ pub extern "C" fn kernel_main(addr: *const u32, loader_start: usize, loader_end:
         read_file("ramfs//big")
     );
 
+    // the monitor only takes over once the init program has exited
+    if let Some(path) = init.as_deref() {
+        match process::spawn(path, &active_table, &mut frame_alloc) {
+            Ok(process) => {
+                let pid = process.pid();
+                let status = process::run(process, &mut frame_alloc);
+
+                log::info!("init process {pid} {status}");
+            }
+            Err(err) => log::error!("failed to start init program `{path}`: {err}"),
+        }
+    }
+
+    monitor::run(&mut active_table, &mut frame_alloc);
+
     // finally free initrd info to remove all mappings in user-space
     unsafe {
         memory::free_region(
@@ -127,13 +171,15 @@ pub extern "C" fn kernel_main(addr: *const u32, loader_start: usize, loader_end:
 
 /// Struct representing information returned by [init]
 struct InitInfo {
-    frame_alloc: BitmapFrameAllocator,
+    frame_alloc: KernelFrameAllocator,
     active_table: ActivePageTable,
     initrd_range: (usize, usize),
+    /// Path of the program to run as the first process, from the `init` command line option
+    init: Option<String>,
 }
 
 /// Initialises everything required for kernel
-fn init(bootinfo: &BootInfo, loader_start: usize, loader_end: usize) -> InitInfo {
+fn init(handoff: &Handoff) -> InitInfo {
     static INIT_CALLED: AtomicBool = AtomicBool::new(false);
 
     if INIT_CALLED.swap(true, Ordering::Relaxed) {
@@ -141,36 +187,80 @@ fn init(bootinfo: &BootInfo, loader_start: usize, loader_end: usize) -> InitInfo
     }
 
     LOGGER.init().expect("failed to init logger");
+
+    // anything in loader memory must be read before memory is initialised, since that frees it
+    let cmdline = core::str::from_utf8(unsafe { handoff.command_line.as_slice() })
+        .map(String::from)
+        .unwrap_or_default();
+    let cmdline = cmdline.as_str();
+    let options = CommandLine::parse(cmdline);
+    LOGGER.set_level(options.log_level);
     log::trace!("logger initialised");
 
-    let initrd = bootinfo
-        .modules
+    log::trace!("boot command line: `{cmdline}`");
+    for error in CommandLine::errors(cmdline) {
+        log::warn!("ignoring boot option: {error}");
+    }
+
+    for module in unsafe { handoff.modules.as_slice() } {
+        log::trace!(
+            "module {:?} found in range {:#X}-{:#X}",
+            module.name(),
+            module.range.start,
+            module.range.end
+        );
+    }
+
+    let initrd = unsafe { handoff.module("initrd") }
+        .expect("no initrd module")
+        .range;
+
+    // memory the multiboot information may share a page with, which mustn't be freed with it
+    let mut in_use: Vec<PhysRange> = unsafe { handoff.modules.as_slice() }
         .iter()
-        .flatten()
-        .find(|module| module.string == c"initrd")
-        .expect("no initrd module");
-    log::trace!(
-        "initrd module found in range {:#X}-{:#X}",
-        initrd.start,
-        initrd.end
+        .map(|module| module.range)
+        .collect();
+    in_use.push(handoff.loader);
+
+    let (mut frame_alloc, mut active_table) = memory::init(
+        unsafe { handoff.memory_map.as_slice() },
+        handoff.frame_allocator,
+        handoff.loader,
     );
+    backtrace::init(handoff.kernel);
 
-    let (frame_alloc, active_table) = memory::init(bootinfo, loader_start, loader_end);
+    // multiboot information was never mapped for the kernel, so its frames can just be returned
+    unsafe { memory::free_frames(&mut frame_alloc, handoff.multiboot, &in_use) }
 
     log::trace!("initialising stdio");
     *WRITER.lock().get_mut() =
-        Some(Writer::from_bootinfo(bootinfo).expect("invalid framebuffer type"));
+        Some(Writer::from_framebuffer(handoff.framebuffer).expect("invalid framebuffer type"));
+    io::set_consoles(options.consoles);
+    log::trace!("\t* consoles: {:?}", options.consoles);
     log::trace!("stdio initialised");
 
+    // read through the physical memory mapping, which is shared with every process
     *RAMFS.lock() =
-        unsafe { Initrd::new_ram(initrd.start as usize, (initrd.end - initrd.start) as usize) };
+        unsafe { Initrd::new_ram(initrd.start as usize + PHYS_MEM_OFFSET, initrd.len()) };
 
     if RAMFS.lock().is_none() {
         panic!("no ramfs driver loaded");
     }
     log::trace!("ramfs initialised");
 
-    gdt::init();
+    // the command line copy is dropped once init returns
+    if let Some(root) = options.root {
+        *ROOT_DEVICE.lock() = String::from(root).leak();
+    }
+    log::trace!("root device: {}", ROOT_DEVICE.lock());
+
+    acpi::init(handoff.rsdp);
+    smbios::init(handoff.smbios);
+
+    gdt::init(&mut active_table, &mut frame_alloc);
+    io::serial::init();
+    gdbstub::init();
+    input::init(options.keymap);
     interrupts::init();
 
     log::trace!("kernel initialised");
@@ -179,5 +269,32 @@ fn init(bootinfo: &BootInfo, loader_start: usize, loader_end: usize) -> InitInfo
         frame_alloc,
         active_table,
         initrd_range: (initrd.start as usize, initrd.end as usize),
+        init: options.init.map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RAMFS;
+
+    #[test_case]
+    fn initrd_lists_files() {
+        let ramfs = RAMFS.lock();
+        let ramfs = ramfs.as_ref().unwrap();
+
+        let mut files = ramfs.files();
+        assert!(files.any(|(path, len)| &**path == "silly" && len == 9));
+    }
+
+    #[test_case]
+    fn initrd_file_contents() {
+        let ramfs = RAMFS.lock();
+        let ramfs = ramfs.as_ref().unwrap();
+
+        assert_eq!(
+            ramfs.file_contents("test"),
+            Some(&b"this is a text file saved in my initrd file :3"[..])
+        );
+        assert_eq!(ramfs.file_contents("missing"), None);
     }
 }
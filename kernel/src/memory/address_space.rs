@@ -0,0 +1,1049 @@
+//! Address spaces of user processes, described by the regions (VMAs) mapped in them.
+//!
+//! Nothing is mapped when a region is added. Instead the page fault handler calls
+//! [AddressSpace::handle_fault] on first touch, which allocates a frame, fills it from the
+//! region's backing and maps it.
+//!
+//! Regions can also be added, removed and have their permissions changed while a process runs,
+//! through [AddressSpace::map], [AddressSpace::unmap], [AddressSpace::protect] and the program
+//! break moved by [AddressSpace::set_brk].
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::Display;
+
+use crabstd::fs::File;
+use kernel_shared::memory::{
+    frame_alloc::FrameAllocator,
+    paging::{
+        active_table::ActivePageTable,
+        entry::EntryFlags,
+        mapper::Mapper,
+        physical::{OffsetPhysicalMemory, PhysicalMemory},
+        table::{Level4, Table},
+        ENTRY_COUNT,
+    },
+};
+use x86_64::{
+    align_down_to_page, align_up_to_page,
+    registers::CR3,
+    structures::{Frame, Page, PAGE_SIZE},
+    PhysicalAddress, VirtualAddress,
+};
+
+use super::{shared_frames, KernelFrameAllocator};
+use crate::interrupts::PageFaultErrorCode;
+
+/// Lowest address user regions can start at, leaving the first page unmapped to catch null
+/// pointers
+pub const USER_SPACE_START: usize = PAGE_SIZE;
+
+/// End of the lower half, which is all user space
+pub const USER_SPACE_END: usize = 0x0000_8000_0000_0000;
+
+/// Number of level 4 entries covering user space, with the rest shared with the kernel
+const USER_P4_ENTRIES: usize = ENTRY_COUNT / 2;
+
+/// Lowest address [AddressSpace::map] places regions at when it isn't given a free address, well
+/// clear of the program and its heap
+const MMAP_BASE: usize = 0x0000_1000_0000_0000;
+
+/// Why a region couldn't be added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmaError {
+    /// Start or end isn't page aligned, or the region is empty
+    Unaligned,
+    /// Region lies partly outside user space
+    OutsideUserSpace,
+    /// Region overlaps one already in the address space
+    Overlaps,
+    /// No free range is large enough for the region
+    NoSpace,
+    /// Only part of a stack was changed, which would split it
+    SplitsStack,
+}
+
+impl Display for VmaError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VmaError::Unaligned => write!(f, "region is not page aligned"),
+            VmaError::OutsideUserSpace => write!(f, "region lies outside user space"),
+            VmaError::Overlaps => write!(f, "region overlaps an existing region"),
+            VmaError::NoSpace => write!(f, "no free range is large enough for region"),
+            VmaError::SplitsStack => write!(f, "stacks can't be split"),
+        }
+    }
+}
+
+/// Why a page fault couldn't be handled, meaning the access was a real violation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultError {
+    /// No region covers the address
+    Unmapped,
+    /// The region doesn't allow the access
+    ProtectionViolation,
+    /// No frame was free to back the page
+    OutOfMemory,
+}
+
+impl Display for FaultError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FaultError::Unmapped => write!(f, "address is not mapped"),
+            FaultError::ProtectionViolation => write!(f, "access not allowed by region"),
+            FaultError::OutOfMemory => write!(f, "out of memory"),
+        }
+    }
+}
+
+/// Where the contents of a region's pages come from
+#[derive(Debug, Clone)]
+pub enum Backing {
+    /// Zeroed memory
+    Anonymous,
+    /// The first `len` bytes of the region are read from `file` starting at `offset`, with the
+    /// rest zeroed
+    File {
+        file: File,
+        offset: usize,
+        len: usize,
+    },
+    /// Zeroed memory which grows down on faults below it, as far as `limit`
+    Stack { limit: usize },
+}
+
+/// A region of an address space, from `start` up to but not including `end`
+#[derive(Clone)]
+pub struct Vma {
+    pub start: VirtualAddress,
+    pub end: VirtualAddress,
+    /// Flags every page in the region is mapped with
+    pub flags: EntryFlags,
+    pub backing: Backing,
+}
+
+impl Vma {
+    pub fn new(
+        start: VirtualAddress,
+        end: VirtualAddress,
+        flags: EntryFlags,
+        backing: Backing,
+    ) -> Self {
+        Self {
+            start,
+            end,
+            flags: flags | EntryFlags::PRESENT,
+            backing,
+        }
+    }
+
+    /// Lowest address the region can cover, which for stacks includes the space they can grow into
+    fn lowest_start(&self) -> VirtualAddress {
+        match self.backing {
+            Backing::Stack { limit } => limit,
+            _ => self.start,
+        }
+    }
+
+    /// Checks if the region allows the access described by a page fault error code
+    fn allows(&self, error: PageFaultErrorCode) -> bool {
+        let write_denied = error.contains(PageFaultErrorCode::CAUSED_BY_WRITE)
+            && !self.flags.contains(EntryFlags::WRITABLE);
+        let execute_denied = error.contains(PageFaultErrorCode::INSTRUCTION_FETCH)
+            && self.flags.contains(EntryFlags::NO_EXECUTE);
+
+        !write_denied && !execute_denied
+    }
+}
+
+/// The page tables and regions of a single user address space
+pub struct AddressSpace {
+    mapper: Mapper,
+    p4_frame: Frame,
+    /// Regions keyed by start address
+    vmas: BTreeMap<VirtualAddress, Vma>,
+    /// Start of the heap, which the program break can't be moved below
+    heap_start: VirtualAddress,
+    /// Program break, the end of the heap, which isn't necessarily page aligned
+    brk: VirtualAddress,
+}
+
+// page tables are only ever reached through the physical memory mapping, not the pointer itself
+unsafe impl Send for AddressSpace {}
+
+impl AddressSpace {
+    /// Creates an empty address space, sharing the kernel half of `active_table`
+    pub fn new(
+        active_table: &ActivePageTable,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Option<Self> {
+        Self::with_kernel_half(active_table.p4(), frame_alloc)
+    }
+
+    /// Creates an empty address space, sharing the kernel half of another level 4 table
+    fn with_kernel_half(
+        kernel_p4: &Table<Level4>,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Option<Self> {
+        let p4_frame = frame_alloc.allocate_frame()?;
+
+        let memory = OffsetPhysicalMemory;
+        let table = memory.ptr(p4_frame.start_address()) as *mut Table<Level4>;
+        let mut mapper = unsafe { Mapper::new(table, memory) };
+
+        // kernel tables are shared rather than copied, so kernel mappings made later in existing
+        // level 4 entries show up in every address space
+        let p4 = mapper.p4_mut();
+        p4.zero();
+        for index in USER_P4_ENTRIES..ENTRY_COUNT {
+            let entry = &kernel_p4[index];
+            if let Some(frame) = entry.pointed_frame() {
+                p4[index].set(frame, entry.flags());
+            }
+        }
+
+        Some(Self {
+            mapper,
+            p4_frame,
+            vmas: BTreeMap::new(),
+            heap_start: USER_SPACE_START,
+            brk: USER_SPACE_START,
+        })
+    }
+
+    /// Adds a region, which is only mapped once it's touched
+    pub fn add(&mut self, vma: Vma) -> Result<(), VmaError> {
+        if vma.start % PAGE_SIZE != 0 || vma.end % PAGE_SIZE != 0 || vma.start >= vma.end {
+            return Err(VmaError::Unaligned);
+        }
+
+        if vma.lowest_start() < USER_SPACE_START || vma.end > USER_SPACE_END {
+            return Err(VmaError::OutsideUserSpace);
+        }
+
+        if !self.is_free(vma.lowest_start(), vma.end) {
+            return Err(VmaError::Overlaps);
+        }
+
+        self.vmas.insert(vma.start, vma);
+        Ok(())
+    }
+
+    /// Checks if no region, or space a stack can grow into, overlaps a range
+    fn is_free(&self, start: VirtualAddress, end: VirtualAddress) -> bool {
+        !self
+            .vmas
+            .values()
+            .any(|other| start < other.end && other.lowest_start() < end)
+    }
+
+    /// Adds a region of at least `len` bytes at `hint` if that range is free, or at the lowest
+    /// free range above [MMAP_BASE] otherwise, returning where it was placed
+    pub fn map(
+        &mut self,
+        hint: VirtualAddress,
+        len: usize,
+        flags: EntryFlags,
+        backing: Backing,
+    ) -> Result<VirtualAddress, VmaError> {
+        if len == 0 {
+            return Err(VmaError::Unaligned);
+        }
+        if len > USER_SPACE_END {
+            return Err(VmaError::NoSpace);
+        }
+        let len = align_up_to_page(len);
+
+        let fits = |start: VirtualAddress| {
+            start >= USER_SPACE_START
+                && start % PAGE_SIZE == 0
+                && start
+                    .checked_add(len)
+                    .is_some_and(|end| end <= USER_SPACE_END && self.is_free(start, end))
+        };
+
+        let start = if fits(hint) {
+            hint
+        } else {
+            // regions are sorted, so the first gap large enough is found by walking up them
+            let mut start = MMAP_BASE;
+            for vma in self.vmas.values() {
+                if vma.end <= start {
+                    continue;
+                }
+                if start + len <= vma.lowest_start() {
+                    break;
+                }
+
+                start = vma.end;
+            }
+
+            if start + len > USER_SPACE_END {
+                return Err(VmaError::NoSpace);
+            }
+            start
+        };
+
+        self.add(Vma::new(start, start + len, flags, backing))?;
+        Ok(start)
+    }
+
+    /// Removes every region between `start` and `start + len`, splitting any that lie partly
+    /// outside it, and frees the pages mapped in them
+    pub fn unmap(
+        &mut self,
+        start: VirtualAddress,
+        len: usize,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Result<(), VmaError> {
+        let end = Self::range_end(start, len)?;
+        self.split(start)?;
+        self.split(end)?;
+
+        let starts: Vec<VirtualAddress> = self.vmas.range(start..end).map(|(&s, _)| s).collect();
+        for vma_start in starts {
+            let vma = self.vmas.remove(&vma_start).unwrap();
+
+            let start_page = Page::containing_address(vma.start);
+            let end_page = Page::containing_address(vma.end - 1);
+            for page in Page::range_inclusive(start_page, end_page) {
+                if self.mapper.page_flags(page).is_some() {
+                    let frame = self.mapper.unmap_frame(page);
+                    shared_frames::release(frame, frame_alloc);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Changes the flags of every region between `start` and `start + len`, splitting any that
+    /// lie partly outside it, along with the pages already mapped in them
+    pub fn protect(
+        &mut self,
+        start: VirtualAddress,
+        len: usize,
+        flags: EntryFlags,
+    ) -> Result<(), VmaError> {
+        let end = Self::range_end(start, len)?;
+        self.split(start)?;
+        self.split(end)?;
+
+        let flags = flags | EntryFlags::PRESENT;
+        for vma in self.vmas.range_mut(start..end).map(|(_, vma)| vma) {
+            vma.flags = flags;
+
+            let start_page = Page::containing_address(vma.start);
+            let end_page = Page::containing_address(vma.end - 1);
+            for page in Page::range_inclusive(start_page, end_page) {
+                let Some(old_flags) = self.mapper.page_flags(page) else {
+                    continue;
+                };
+
+                // shared pages stay read only until they're copied, even once their region
+                // becomes writable
+                let mut new_flags = flags | EntryFlags::USER_ACCESSIBLE;
+                if old_flags.contains(EntryFlags::COPY_ON_WRITE) {
+                    new_flags.remove(EntryFlags::WRITABLE);
+                    new_flags.insert(EntryFlags::COPY_ON_WRITE);
+                }
+
+                self.mapper.update_flags(page, |_| new_flags);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the end of a range given to [AddressSpace::unmap] or [AddressSpace::protect],
+    /// rounded up to a page
+    fn range_end(start: VirtualAddress, len: usize) -> Result<VirtualAddress, VmaError> {
+        if start % PAGE_SIZE != 0 || len == 0 {
+            return Err(VmaError::Unaligned);
+        }
+
+        match start.checked_add(len) {
+            Some(end) if start >= USER_SPACE_START && end <= USER_SPACE_END => {
+                Ok(align_up_to_page(end))
+            }
+            _ => Err(VmaError::OutsideUserSpace),
+        }
+    }
+
+    /// Splits the region containing `addr` in two, so one of them starts at `addr`
+    fn split(&mut self, addr: VirtualAddress) -> Result<(), VmaError> {
+        let Some(mut upper) = self.find(addr).filter(|vma| vma.start != addr).cloned() else {
+            return Ok(());
+        };
+        let lower_start = upper.start;
+        let skipped = addr - upper.start;
+
+        match &mut upper.backing {
+            Backing::Anonymous => {}
+            Backing::File { offset, len, .. } => {
+                *offset += skipped;
+                *len = len.saturating_sub(skipped);
+            }
+            Backing::Stack { .. } => return Err(VmaError::SplitsStack),
+        }
+
+        upper.start = addr;
+        self.vmas.get_mut(&lower_start).unwrap().end = addr;
+        self.vmas.insert(addr, upper);
+
+        Ok(())
+    }
+
+    /// Sets where the heap starts, rounded up to a page, with the program break moved to it.
+    /// Must be called before the heap is grown.
+    pub fn set_heap_start(&mut self, addr: VirtualAddress) {
+        self.heap_start = align_up_to_page(addr);
+        self.brk = self.heap_start;
+    }
+
+    /// Returns the program break
+    pub fn brk(&self) -> VirtualAddress {
+        self.brk
+    }
+
+    /// Moves the program break, growing the heap region or unmapping whole pages past the new
+    /// break. Returns false if the heap can't be moved there.
+    pub fn set_brk(&mut self, brk: VirtualAddress, frame_alloc: &mut KernelFrameAllocator) -> bool {
+        if brk < self.heap_start || brk > USER_SPACE_END {
+            return false;
+        }
+
+        let old_end = align_up_to_page(self.brk);
+        let new_end = align_up_to_page(brk);
+
+        if new_end > old_end {
+            if !self.is_free(old_end, new_end) {
+                return false;
+            }
+
+            let heap_start = self.heap_start;
+            let heap = self
+                .vmas
+                .range_mut(heap_start..old_end)
+                .next_back()
+                .map(|(_, vma)| vma)
+                .filter(|vma| vma.end == old_end);
+
+            match heap {
+                Some(heap) => heap.end = new_end,
+                None => {
+                    let vma = Vma::new(
+                        old_end,
+                        new_end,
+                        EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+                        Backing::Anonymous,
+                    );
+                    if self.add(vma).is_err() {
+                        return false;
+                    }
+                }
+            }
+        } else if new_end < old_end && self.unmap(new_end, old_end - new_end, frame_alloc).is_err()
+        {
+            return false;
+        }
+
+        self.brk = brk;
+        true
+    }
+
+    /// Finds the region containing an address
+    pub fn find(&self, addr: VirtualAddress) -> Option<&Vma> {
+        self.vmas
+            .range(..=addr)
+            .next_back()
+            .map(|(_, vma)| vma)
+            .filter(|vma| addr < vma.end)
+    }
+
+    /// Grows the stack above an address down to cover it, returning false if there's no stack
+    /// that can grow that far
+    fn grow_stack(&mut self, addr: VirtualAddress) -> bool {
+        let Some((&start, vma)) = self.vmas.range(addr..).next() else {
+            return false;
+        };
+        match vma.backing {
+            Backing::Stack { limit } if addr >= limit => {}
+            _ => return false,
+        }
+
+        let mut vma = self.vmas.remove(&start).unwrap();
+        vma.start = align_down_to_page(addr);
+        log::trace!("growing stack down to {:#X}", vma.start);
+
+        self.vmas.insert(vma.start, vma);
+        true
+    }
+
+    /// Translates an address in this address space to its physical address
+    #[allow(dead_code)]
+    pub fn translate(&self, addr: VirtualAddress) -> Option<PhysicalAddress> {
+        self.mapper.translate(addr)
+    }
+
+    /// Handles a page fault at `addr`, mapping in the page if a region allows the access
+    pub fn handle_fault(
+        &mut self,
+        addr: VirtualAddress,
+        error: PageFaultErrorCode,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Result<(), FaultError> {
+        if self.find(addr).is_none() && !self.grow_stack(addr) {
+            return Err(FaultError::Unmapped);
+        }
+        let vma = self.find(addr).unwrap();
+
+        if !vma.allows(error) {
+            return Err(FaultError::ProtectionViolation);
+        }
+
+        let page = Page::containing_address(addr);
+        let flags = vma.flags | EntryFlags::USER_ACCESSIBLE;
+
+        // pages are only ever mapped with fewer permissions than their region allows when they're
+        // shared copy on write
+        if let Some(page_flags) = self.mapper.page_flags(page) {
+            if error.contains(PageFaultErrorCode::CAUSED_BY_WRITE)
+                && page_flags.contains(EntryFlags::COPY_ON_WRITE)
+            {
+                return self.copy_on_write(page, flags, frame_alloc);
+            }
+
+            // already mapped, so the fault came from a stale translation
+            return Ok(());
+        }
+
+        let frame = frame_alloc
+            .allocate_frame()
+            .ok_or(FaultError::OutOfMemory)?;
+        let contents = unsafe {
+            core::slice::from_raw_parts_mut(
+                OffsetPhysicalMemory.ptr(frame.start_address()),
+                PAGE_SIZE,
+            )
+        };
+        contents.fill(0);
+
+        if let Backing::File { file, offset, len } = &vma.backing {
+            let page_offset = page.start_address() - vma.start;
+
+            if page_offset < *len {
+                let to_read = (*len - page_offset).min(PAGE_SIZE);
+
+                let mut file = file.clone();
+                file.seek(offset + page_offset);
+                crate::fs::read(&file, &mut contents[..to_read]);
+            }
+        }
+
+        self.mapper.map_to(page, frame, flags, frame_alloc);
+
+        Ok(())
+    }
+
+    /// Gives a copy on write page its own frame, mapping it with `flags`
+    fn copy_on_write(
+        &mut self,
+        page: Page,
+        flags: EntryFlags,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Result<(), FaultError> {
+        let shared = self.mapper.translate_page(page).unwrap();
+
+        // every other address space has already copied the page or gone away
+        if shared_frames::references(&shared) == 1 {
+            self.mapper.update_flags(page, |_| flags);
+            return Ok(());
+        }
+
+        let frame = frame_alloc
+            .allocate_frame()
+            .ok_or(FaultError::OutOfMemory)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                OffsetPhysicalMemory.ptr(shared.start_address()),
+                OffsetPhysicalMemory.ptr(frame.start_address()),
+                PAGE_SIZE,
+            );
+        }
+
+        let shared = self.mapper.remap(page, frame, flags);
+        shared_frames::release(shared, frame_alloc);
+
+        Ok(())
+    }
+
+    /// Creates a copy of this address space which shares every mapped page with it. Pages either
+    /// side could write to are marked copy on write in both, so they're copied on the first write.
+    pub fn fork(&mut self, frame_alloc: &mut KernelFrameAllocator) -> Option<Self> {
+        let mut child = Self::with_kernel_half(self.mapper.p4(), frame_alloc)?;
+        child.heap_start = self.heap_start;
+        child.brk = self.brk;
+
+        for vma in self.vmas.values() {
+            let mut flags = vma.flags | EntryFlags::USER_ACCESSIBLE;
+            if flags.contains(EntryFlags::WRITABLE) {
+                flags.remove(EntryFlags::WRITABLE);
+                flags.insert(EntryFlags::COPY_ON_WRITE);
+            }
+
+            let start_page = Page::containing_address(vma.start);
+            let end_page = Page::containing_address(vma.end - 1);
+            for page in Page::range_inclusive(start_page, end_page) {
+                let Some(frame) = self.mapper.translate_page(page) else {
+                    continue;
+                };
+
+                self.mapper.update_flags(page, |_| flags);
+                shared_frames::share(&frame);
+                child.mapper.map_to(page, frame, flags, frame_alloc);
+            }
+
+            child.vmas.insert(vma.start, vma.clone());
+        }
+
+        Some(child)
+    }
+
+    /// Switches to this address space
+    ///
+    /// # Safety
+    /// The address space must stay alive for as long as it's active
+    pub unsafe fn activate(&self) {
+        CR3::write(Frame::containing_address(self.p4_frame.start_address()), 0);
+    }
+
+    /// Frees every page and table in the user half, along with the level 4 table itself
+    ///
+    /// # Safety
+    /// The address space must not be active
+    pub unsafe fn free(self, frame_alloc: &mut KernelFrameAllocator) {
+        let memory = OffsetPhysicalMemory;
+        let p4 = self.mapper.p4();
+
+        for p4_index in 0..USER_P4_ENTRIES {
+            let Some(p3) = p4.next_table(p4_index, &memory) else {
+                continue;
+            };
+
+            for p3_index in 0..ENTRY_COUNT {
+                let Some(p2) = p3.next_table(p3_index, &memory) else {
+                    continue;
+                };
+
+                for p2_index in 0..ENTRY_COUNT {
+                    let Some(p1) = p2.next_table(p2_index, &memory) else {
+                        continue;
+                    };
+
+                    for p1_index in 0..ENTRY_COUNT {
+                        if let Some(frame) = p1[p1_index].pointed_frame() {
+                            shared_frames::release(frame, frame_alloc);
+                        }
+                    }
+
+                    frame_alloc.deallocate_frame(p2[p2_index].pointed_frame().unwrap());
+                }
+
+                frame_alloc.deallocate_frame(p3[p3_index].pointed_frame().unwrap());
+            }
+
+            frame_alloc.deallocate_frame(p4[p4_index].pointed_frame().unwrap());
+        }
+
+        frame_alloc.deallocate_frame(self.p4_frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crabstd::fs::File;
+    use kernel_shared::memory::paging::{entry::EntryFlags, PHYS_MEM_OFFSET};
+    use x86_64::structures::{Page, PAGE_SIZE};
+
+    use super::{AddressSpace, Backing, FaultError, Vma, VmaError, MMAP_BASE, USER_SPACE_END};
+    use crate::{interrupts::PageFaultErrorCode, testing::with_memory};
+
+    const BASE: usize = 0x40_0000;
+
+    const WRITE: PageFaultErrorCode = PageFaultErrorCode::CAUSED_BY_WRITE;
+
+    /// Reads a byte of an address space through the physical memory mapping
+    fn read_byte(space: &AddressSpace, addr: usize) -> u8 {
+        let phys = space.translate(addr).unwrap();
+        unsafe { *((phys + PHYS_MEM_OFFSET) as *const u8) }
+    }
+
+    /// Writes a byte of an address space through the physical memory mapping, ignoring permissions
+    fn write_byte(space: &AddressSpace, addr: usize, value: u8) {
+        let phys = space.translate(addr).unwrap();
+        unsafe { *((phys + PHYS_MEM_OFFSET) as *mut u8) = value }
+    }
+
+    #[test_case]
+    fn overlapping_regions_are_rejected() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            let vma = |start, end| Vma::new(start, end, EntryFlags::empty(), Backing::Anonymous);
+
+            assert_eq!(space.add(vma(BASE, BASE + PAGE_SIZE)), Ok(()));
+            assert_eq!(
+                space.add(vma(BASE, BASE + 2 * PAGE_SIZE)),
+                Err(VmaError::Overlaps)
+            );
+            assert_eq!(
+                space.add(vma(BASE + 1, BASE + PAGE_SIZE)),
+                Err(VmaError::Unaligned)
+            );
+            assert_eq!(
+                space.add(vma(0, PAGE_SIZE)),
+                Err(VmaError::OutsideUserSpace)
+            );
+            assert_eq!(
+                space.add(Vma::new(
+                    BASE + 2 * PAGE_SIZE,
+                    BASE + 3 * PAGE_SIZE,
+                    EntryFlags::empty(),
+                    Backing::Stack { limit: BASE }
+                )),
+                Err(VmaError::Overlaps)
+            );
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn anonymous_pages_are_mapped_on_first_touch() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            space
+                .add(Vma::new(
+                    BASE,
+                    BASE + 4 * PAGE_SIZE,
+                    EntryFlags::WRITABLE,
+                    Backing::Anonymous,
+                ))
+                .unwrap();
+
+            assert_eq!(space.translate(BASE + PAGE_SIZE), None);
+            assert_eq!(
+                space.handle_fault(BASE + PAGE_SIZE + 8, WRITE, frame_alloc),
+                Ok(())
+            );
+            assert!(space.translate(BASE + PAGE_SIZE).is_some());
+            assert_eq!(read_byte(&space, BASE + PAGE_SIZE + 8), 0);
+
+            // only the touched page is mapped
+            assert_eq!(space.translate(BASE), None);
+
+            assert_eq!(
+                space.handle_fault(BASE + 4 * PAGE_SIZE, WRITE, frame_alloc),
+                Err(FaultError::Unmapped)
+            );
+
+            unsafe { space.free(frame_alloc) };
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn read_only_regions_reject_writes() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            space
+                .add(Vma::new(
+                    BASE,
+                    BASE + PAGE_SIZE,
+                    EntryFlags::NO_EXECUTE,
+                    Backing::Anonymous,
+                ))
+                .unwrap();
+
+            assert_eq!(
+                space.handle_fault(BASE, WRITE, frame_alloc),
+                Err(FaultError::ProtectionViolation)
+            );
+            assert_eq!(
+                space.handle_fault(BASE, PageFaultErrorCode::INSTRUCTION_FETCH, frame_alloc),
+                Err(FaultError::ProtectionViolation)
+            );
+            assert_eq!(
+                space.handle_fault(BASE, PageFaultErrorCode::empty(), frame_alloc),
+                Ok(())
+            );
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn file_pages_are_read_from_file() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            let file = File::new("ramfs//test").unwrap();
+            space
+                .add(Vma::new(
+                    BASE,
+                    BASE + 2 * PAGE_SIZE,
+                    EntryFlags::empty(),
+                    Backing::File {
+                        file,
+                        offset: 5,
+                        len: 7,
+                    },
+                ))
+                .unwrap();
+
+            space
+                .handle_fault(BASE, PageFaultErrorCode::empty(), frame_alloc)
+                .unwrap();
+            let contents: [u8; 8] = core::array::from_fn(|i| read_byte(&space, BASE + i));
+            assert_eq!(&contents, b"is a te\0");
+
+            // pages past the end of the file data are zeroed
+            space
+                .handle_fault(BASE + PAGE_SIZE, PageFaultErrorCode::empty(), frame_alloc)
+                .unwrap();
+            assert_eq!(read_byte(&space, BASE + PAGE_SIZE), 0);
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn stacks_grow_down_to_their_limit() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            let top = USER_SPACE_END - PAGE_SIZE;
+            let limit = top - 4 * PAGE_SIZE;
+            space
+                .add(Vma::new(
+                    top - PAGE_SIZE,
+                    top,
+                    EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+                    Backing::Stack { limit },
+                ))
+                .unwrap();
+
+            assert_eq!(space.handle_fault(limit + 8, WRITE, frame_alloc), Ok(()));
+            assert!(space.translate(limit).is_some());
+            assert_eq!(space.find(top - 1).unwrap().start, limit);
+
+            assert_eq!(
+                space.handle_fault(limit - 8, WRITE, frame_alloc),
+                Err(FaultError::Unmapped)
+            );
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn forked_pages_are_copied_on_write() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let mut parent = AddressSpace::new(active_table, frame_alloc).unwrap();
+            parent
+                .add(Vma::new(
+                    BASE,
+                    BASE + 2 * PAGE_SIZE,
+                    EntryFlags::WRITABLE,
+                    Backing::Anonymous,
+                ))
+                .unwrap();
+            parent.handle_fault(BASE, WRITE, frame_alloc).unwrap();
+            write_byte(&parent, BASE, 1);
+
+            let mut child = parent.fork(frame_alloc).unwrap();
+            assert_eq!(child.translate(BASE), parent.translate(BASE));
+            // untouched pages stay unmapped in both
+            assert_eq!(child.translate(BASE + PAGE_SIZE), None);
+
+            let page = Page::containing_address(BASE);
+            for space in [&parent, &child] {
+                let flags = space.mapper.page_flags(page).unwrap();
+                assert!(flags.contains(EntryFlags::COPY_ON_WRITE));
+                assert!(!flags.contains(EntryFlags::WRITABLE));
+            }
+
+            // the first write copies the page, leaving the other address space with the original
+            assert_eq!(parent.handle_fault(BASE, WRITE, frame_alloc), Ok(()));
+            assert_ne!(child.translate(BASE), parent.translate(BASE));
+            write_byte(&parent, BASE, 2);
+            assert_eq!(read_byte(&parent, BASE), 2);
+            assert_eq!(read_byte(&child, BASE), 1);
+
+            // the last reference just becomes writable again
+            let frame = child.translate(BASE);
+            assert_eq!(child.handle_fault(BASE, WRITE, frame_alloc), Ok(()));
+            assert_eq!(child.translate(BASE), frame);
+            assert!(child
+                .mapper
+                .page_flags(page)
+                .unwrap()
+                .contains(EntryFlags::WRITABLE));
+
+            unsafe {
+                parent.free(frame_alloc);
+                child.free(frame_alloc);
+            }
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn mapped_regions_are_placed_in_free_space() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            let map = |space: &mut AddressSpace, hint, len| {
+                space.map(hint, len, EntryFlags::WRITABLE, Backing::Anonymous)
+            };
+
+            // free hints are used as they are
+            assert_eq!(map(&mut space, BASE, 1), Ok(BASE));
+
+            // anything else goes above the mmap base, after what's already there
+            assert_eq!(map(&mut space, BASE, PAGE_SIZE), Ok(MMAP_BASE));
+            assert_eq!(map(&mut space, 0, 2 * PAGE_SIZE), Ok(MMAP_BASE + PAGE_SIZE));
+            assert_eq!(
+                map(&mut space, BASE + 1, PAGE_SIZE),
+                Ok(MMAP_BASE + 3 * PAGE_SIZE)
+            );
+
+            assert_eq!(map(&mut space, 0, 0), Err(VmaError::Unaligned));
+            assert_eq!(map(&mut space, 0, USER_SPACE_END), Err(VmaError::NoSpace));
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn unmapping_splits_regions() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            let file = File::new("ramfs//test").unwrap();
+            space
+                .add(Vma::new(
+                    BASE,
+                    BASE + 3 * PAGE_SIZE,
+                    EntryFlags::WRITABLE,
+                    Backing::File {
+                        file,
+                        offset: 0,
+                        len: PAGE_SIZE + 5,
+                    },
+                ))
+                .unwrap();
+            for page in 0..3 {
+                space
+                    .handle_fault(BASE + page * PAGE_SIZE, WRITE, frame_alloc)
+                    .unwrap();
+            }
+
+            assert_eq!(space.unmap(BASE + PAGE_SIZE, 1, frame_alloc), Ok(()));
+            assert_eq!(space.translate(BASE + PAGE_SIZE), None);
+            assert!(space.translate(BASE).is_some());
+            assert!(space.translate(BASE + 2 * PAGE_SIZE).is_some());
+
+            assert_eq!(space.find(BASE).unwrap().end, BASE + PAGE_SIZE);
+            assert!(space.find(BASE + PAGE_SIZE).is_none());
+            let upper = space.find(BASE + 2 * PAGE_SIZE).unwrap();
+            assert_eq!(upper.start, BASE + 2 * PAGE_SIZE);
+            assert!(matches!(upper.backing, Backing::File {
+                offset: 0x2000,
+                len: 0,
+                ..
+            }));
+
+            assert_eq!(
+                space.handle_fault(BASE + PAGE_SIZE, WRITE, frame_alloc),
+                Err(FaultError::Unmapped)
+            );
+            assert_eq!(
+                space.unmap(BASE + 1, PAGE_SIZE, frame_alloc),
+                Err(VmaError::Unaligned)
+            );
+
+            unsafe { space.free(frame_alloc) };
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn protecting_changes_region_and_pages() {
+        with_memory(|active_table, frame_alloc| {
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            space
+                .add(Vma::new(
+                    BASE,
+                    BASE + 2 * PAGE_SIZE,
+                    EntryFlags::WRITABLE,
+                    Backing::Anonymous,
+                ))
+                .unwrap();
+            space.handle_fault(BASE, WRITE, frame_alloc).unwrap();
+
+            assert_eq!(space.protect(BASE, PAGE_SIZE, EntryFlags::empty()), Ok(()));
+            let page = Page::containing_address(BASE);
+            assert!(!space
+                .mapper
+                .page_flags(page)
+                .unwrap()
+                .contains(EntryFlags::WRITABLE));
+            assert_eq!(
+                space.handle_fault(BASE, WRITE, frame_alloc),
+                Err(FaultError::ProtectionViolation)
+            );
+
+            // the rest of the region keeps its flags
+            assert_eq!(
+                space.handle_fault(BASE + PAGE_SIZE, WRITE, frame_alloc),
+                Ok(())
+            );
+
+            unsafe { space.free(frame_alloc) };
+        });
+    }
+
+    #[test_case]
+    fn program_break_grows_and_shrinks_heap() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+            space.set_heap_start(BASE + 1);
+            assert_eq!(space.brk(), BASE + PAGE_SIZE);
+
+            let heap = BASE + PAGE_SIZE;
+            assert!(space.set_brk(heap + 8, frame_alloc));
+            assert!(space.set_brk(heap + 2 * PAGE_SIZE + 8, frame_alloc));
+            assert_eq!(space.brk(), heap + 2 * PAGE_SIZE + 8);
+
+            // grown in place, rather than with a region per call
+            let vma = space.find(heap).unwrap();
+            assert_eq!((vma.start, vma.end), (heap, heap + 3 * PAGE_SIZE));
+
+            space
+                .handle_fault(heap + 2 * PAGE_SIZE, WRITE, frame_alloc)
+                .unwrap();
+            assert!(space.set_brk(heap + PAGE_SIZE, frame_alloc));
+            assert_eq!(space.translate(heap + 2 * PAGE_SIZE), None);
+            assert_eq!(
+                space.handle_fault(heap + 2 * PAGE_SIZE, WRITE, frame_alloc),
+                Err(FaultError::Unmapped)
+            );
+
+            assert!(!space.set_brk(BASE, frame_alloc));
+            assert_eq!(space.brk(), heap + PAGE_SIZE);
+
+            unsafe { space.free(frame_alloc) };
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+}
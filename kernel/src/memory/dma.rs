@@ -0,0 +1,67 @@
+use kernel_shared::memory::paging::physical::{OffsetPhysicalMemory, PhysicalMemory};
+use x86_64::{
+    structures::{Frame, PAGE_SIZE},
+    PhysicalAddress,
+};
+
+use super::KernelFrameAllocator;
+
+/// Physically contiguous memory for a device to access directly, which the kernel reaches through
+/// the physical memory mapping
+#[derive(Debug)]
+pub struct DmaBuffer {
+    frame: Frame,
+    count: usize,
+}
+
+impl DmaBuffer {
+    /// Physical address of the start of the buffer, to hand to the device
+    pub fn phys_addr(&self) -> PhysicalAddress {
+        self.frame.start_address()
+    }
+
+    /// Size of the buffer in bytes, which is a whole number of frames
+    pub fn size(&self) -> usize {
+        self.count * PAGE_SIZE
+    }
+
+    /// Returns the buffer's memory, as mapped in the physical memory mapping
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(OffsetPhysicalMemory.ptr(self.phys_addr()), self.size())
+        }
+    }
+}
+
+/// Allocates a zeroed buffer of at least `size` bytes, starting at a physical address aligned to
+/// `align` and ending at or below `max_addr`, such as the end of [Zone::Dma32] for devices with
+/// 32-bit addresses
+///
+/// [Zone::Dma32]: kernel_shared::memory::frame_alloc::Zone::Dma32
+pub fn allocate(
+    frame_alloc: &mut KernelFrameAllocator,
+    size: usize,
+    align: usize,
+    max_addr: PhysicalAddress,
+) -> Option<DmaBuffer> {
+    let count = size.div_ceil(PAGE_SIZE).max(1);
+    let frame = frame_alloc.allocate_contiguous(count, align, max_addr)?;
+
+    let mut buffer = DmaBuffer { frame, count };
+    buffer.as_mut_slice().fill(0);
+    log::trace!(
+        "allocated dma buffer at {:#X}-{:#X}",
+        buffer.phys_addr(),
+        buffer.phys_addr() + buffer.size()
+    );
+
+    Some(buffer)
+}
+
+/// Frees a buffer's frames
+///
+/// # Safety
+/// The device must be done with the buffer
+pub unsafe fn free(buffer: DmaBuffer, frame_alloc: &mut KernelFrameAllocator) {
+    frame_alloc.deallocate_contiguous(buffer.frame, buffer.count);
+}
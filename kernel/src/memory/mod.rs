@@ -1,21 +1,44 @@
+pub mod address_space;
+// no drivers use dma yet
+#[allow(dead_code)]
+pub mod dma;
 mod heap_allocator;
+pub mod shared_frames;
+pub mod stack;
 
 use alloc::boxed::Box;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+#[cfg(feature = "buddy_allocator")]
 use kernel_shared::memory::{
-    frame_alloc::bitmap::BitmapFrameAllocator, paging::active_table::ActivePageTable,
+    frame_alloc::buddy::BuddyFrameAllocator,
+    paging::{physical::OffsetPhysicalMemory, PHYS_MEM_OFFSET},
 };
+use kernel_shared::{
+    handoff::{FrameAllocatorState, PhysRange},
+    memory::{
+        frame_alloc::{bitmap::BitmapFrameAllocator, FrameAllocator},
+        paging::active_table::ActivePageTable,
+    },
+};
+use multiboot::MemoryMapEntry;
 use x86_64::structures::Page;
+#[cfg(feature = "buddy_allocator")]
+use x86_64::structures::PAGE_SIZE;
 
-use crate::BootInfo;
+/// Frame allocator the kernel uses once memory is initialised. The loader's bitmap allocator is
+/// kept unless the kernel is built with the `buddy_allocator` feature.
+#[cfg(not(feature = "buddy_allocator"))]
+pub type KernelFrameAllocator = BitmapFrameAllocator;
+#[cfg(feature = "buddy_allocator")]
+pub type KernelFrameAllocator = BuddyFrameAllocator;
 
-/// Initialises memory
+/// Initialises memory, carrying on with the frame allocator set up by the loader
 pub fn init(
-    bootinfo: &BootInfo,
-    loader_start: usize,
-    loader_end: usize,
-) -> (BitmapFrameAllocator, ActivePageTable) {
+    memory_map: &[MemoryMapEntry],
+    frame_allocator: FrameAllocatorState,
+    loader: PhysRange,
+) -> (KernelFrameAllocator, ActivePageTable) {
     static INIT_CALLED: AtomicBool = AtomicBool::new(false);
 
     if INIT_CALLED.swap(true, Ordering::Relaxed) {
@@ -25,9 +48,13 @@ pub fn init(
     log::info!("initialising memory");
 
     // clone and leak memory map to make sure we have a reference that doesnt live in old loader memory space
-    let memory_map = Box::new(bootinfo.memory_map.unwrap().entries.to_vec());
-    let mut frame_alloc =
-        unsafe { BitmapFrameAllocator::from_address(Box::leak(memory_map), 0xFFFFFFFF00000000) };
+    let memory_map = Box::new(memory_map.to_vec());
+    let mut frame_alloc = unsafe {
+        BitmapFrameAllocator::from_address(
+            Box::leak(memory_map),
+            frame_allocator.virt_addr as usize,
+        )
+    };
 
     let mut active_table = unsafe { ActivePageTable::new() };
 
@@ -35,20 +62,23 @@ pub fn init(
         free_region(
             &mut active_table,
             &mut frame_alloc,
-            loader_start,
-            loader_end,
+            loader.start as usize,
+            loader.end as usize,
         );
     }
     log::trace!("\t* loader memory freed");
 
+    #[cfg(feature = "buddy_allocator")]
+    let frame_alloc = into_buddy(frame_alloc);
+
     log::info!("memory initialised");
 
     (frame_alloc, active_table)
 }
 
-pub unsafe fn free_region(
+pub unsafe fn free_region<A: FrameAllocator>(
     active_table: &mut ActivePageTable,
-    frame_alloc: &mut BitmapFrameAllocator,
+    frame_alloc: &mut A,
     addr_start: usize,
     addr_end: usize,
 ) {
@@ -59,3 +89,165 @@ pub unsafe fn free_region(
         active_table.unmap(page, frame_alloc, true);
     }
 }
+
+/// Hands the frames the loader's bitmap allocator has free to a buddy allocator, whose metadata
+/// takes frames from the bitmap first. The bitmaps themselves stay allocated.
+#[cfg(feature = "buddy_allocator")]
+fn into_buddy(mut bitmap: BitmapFrameAllocator) -> BuddyFrameAllocator {
+    let len = <BuddyFrameAllocator>::metadata_len(bitmap.memory_regions());
+    let frame = bitmap
+        .allocate_contiguous(len.div_ceil(PAGE_SIZE), 0, usize::MAX)
+        .expect("no memory for buddy allocator metadata");
+
+    let metadata = unsafe {
+        core::slice::from_raw_parts_mut((frame.start_address() + PHYS_MEM_OFFSET) as *mut u8, len)
+    };
+    let buddy =
+        unsafe { BuddyFrameAllocator::from_bitmap(&bitmap, OffsetPhysicalMemory, metadata) };
+    log::trace!("\t* frames moved to buddy allocator");
+
+    buddy
+}
+
+/// Returns the frames of a physical range to the frame allocator, for memory that was never mapped.
+/// Frames only partly covered by the range, or shared with any of the `in_use` ranges, are kept.
+pub unsafe fn free_frames(
+    frame_alloc: &mut KernelFrameAllocator,
+    range: PhysRange,
+    in_use: &[PhysRange],
+) {
+    for frame in range.unshared_frames(in_use) {
+        frame_alloc.deallocate_frame(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kernel_shared::memory::{
+        frame_alloc::{FrameAllocator, Zone},
+        paging::{entry::EntryFlags, PHYS_MEM_OFFSET},
+    };
+    use x86_64::structures::{Page, PAGE_SIZE};
+
+    use super::{dma, stack};
+    use crate::testing::with_memory;
+
+    /// Address just past the physical memory mapping, which nothing else uses
+    const UNUSED_ADDR: usize = 0xFFFF_C000_0000_0000;
+
+    #[test_case]
+    fn frame_allocator_returns_distinct_frames() {
+        with_memory(|_, frame_alloc| {
+            let used = frame_alloc.used_frames();
+
+            let first = frame_alloc.allocate_frame().unwrap();
+            let second = frame_alloc.allocate_frame().unwrap();
+            assert_ne!(first, second);
+            assert_eq!(frame_alloc.used_frames(), used + 2);
+
+            frame_alloc.deallocate_frame(first);
+            frame_alloc.deallocate_frame(second);
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn frame_allocator_reuses_freed_frames() {
+        with_memory(|_, frame_alloc| {
+            let frame = frame_alloc.allocate_frame().unwrap();
+            let number = frame.number;
+            frame_alloc.deallocate_frame(frame);
+
+            let again = frame_alloc.allocate_frame().unwrap();
+            assert_eq!(again.number, number);
+            frame_alloc.deallocate_frame(again);
+        });
+    }
+
+    #[test_case]
+    fn physical_memory_is_mapped_at_offset() {
+        with_memory(|active_table, frame_alloc| {
+            let frame = frame_alloc.allocate_frame().unwrap();
+
+            assert_eq!(
+                active_table.translate(frame.start_address() + PHYS_MEM_OFFSET + 0x123),
+                Some(frame.start_address() + 0x123)
+            );
+
+            frame_alloc.deallocate_frame(frame);
+        });
+    }
+
+    #[test_case]
+    fn map_translate_unmap() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let page = Page::containing_address(UNUSED_ADDR);
+            assert_eq!(active_table.translate(UNUSED_ADDR), None);
+
+            let frame = frame_alloc.allocate_frame().unwrap();
+            let frame_addr = frame.start_address();
+            active_table.map_to(page, frame, EntryFlags::WRITABLE, frame_alloc);
+            assert_eq!(
+                active_table.translate(UNUSED_ADDR + 0x456),
+                Some(frame_addr + 0x456)
+            );
+
+            // writes through the new mapping must land in the frame
+            unsafe {
+                (UNUSED_ADDR as *mut u64).write_volatile(0xC0FFEE);
+                assert_eq!(
+                    ((frame_addr + PHYS_MEM_OFFSET) as *const u64).read_volatile(),
+                    0xC0FFEE
+                );
+            }
+
+            // unmapping frees the frame and any tables created for it
+            active_table.unmap(page, frame_alloc, true);
+            assert_eq!(active_table.translate(UNUSED_ADDR), None);
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn dma_buffers_are_contiguous_and_mapped() {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+            let max_addr = Zone::Dma.range().end;
+
+            let mut buffer =
+                dma::allocate(frame_alloc, 3 * PAGE_SIZE - 1, 0x4000, max_addr).unwrap();
+            let phys = buffer.phys_addr();
+            assert_eq!(buffer.size(), 3 * PAGE_SIZE);
+            assert_eq!(phys % 0x4000, 0);
+            assert!(phys + buffer.size() <= max_addr);
+            assert_eq!(frame_alloc.used_frames(), used + 3);
+
+            let slice = buffer.as_mut_slice();
+            assert!(slice.iter().all(|&byte| byte == 0));
+            for page in 0..3 {
+                let addr = slice.as_ptr() as usize + page * PAGE_SIZE;
+                assert_eq!(active_table.translate(addr), Some(phys + page * PAGE_SIZE));
+            }
+
+            unsafe { dma::free(buffer, frame_alloc) };
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn stacks_have_guard_page() {
+        with_memory(|active_table, frame_alloc| {
+            let size = 4 * PAGE_SIZE;
+            let stack = stack::allocate(active_table, frame_alloc, size).unwrap();
+            let top = stack.top();
+
+            assert!(active_table.translate(top - 1).is_some());
+            assert!(active_table.translate(top - size).is_some());
+            assert_eq!(active_table.translate(top - size - 1), None);
+
+            unsafe { stack::free(stack, active_table, frame_alloc) };
+            assert_eq!(active_table.translate(top - 1), None);
+        });
+    }
+}
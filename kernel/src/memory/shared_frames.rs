@@ -0,0 +1,41 @@
+//! Reference counts of frames mapped in more than one place, such as pages shared by a fork.
+//! Frames missing from the table have a single owner, so only shared frames cost anything.
+
+use alloc::collections::BTreeMap;
+
+use crabstd::mutex::Mutex;
+use kernel_shared::memory::frame_alloc::FrameAllocator;
+use x86_64::structures::Frame;
+
+use super::KernelFrameAllocator;
+
+/// Number of references to each shared frame, keyed by frame number
+static SHARED_FRAMES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Adds a reference to a frame
+pub fn share(frame: &Frame) {
+    *SHARED_FRAMES.lock().entry(frame.number).or_insert(1) += 1;
+}
+
+/// Returns the number of references to a frame
+pub fn references(frame: &Frame) -> usize {
+    SHARED_FRAMES
+        .lock()
+        .get(&frame.number)
+        .copied()
+        .unwrap_or(1)
+}
+
+/// Drops a reference to a frame, freeing it once nothing else refers to it
+pub fn release(frame: Frame, frame_alloc: &mut KernelFrameAllocator) {
+    let mut shared = SHARED_FRAMES.lock();
+
+    match shared.get_mut(&frame.number) {
+        Some(count) if *count > 2 => *count -= 1,
+        // back to a single owner
+        Some(_) => {
+            shared.remove(&frame.number);
+        }
+        None => frame_alloc.deallocate_frame(frame),
+    }
+}
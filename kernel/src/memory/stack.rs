@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crabstd::mutex::Mutex;
+use kernel_shared::memory::{
+    paging::active_table::ActivePageTable,
+    stack::{map_stack, slot_top, unmap_stack, KERNEL_STACK_SLOT, STACK_SLOTS},
+};
+
+use super::KernelFrameAllocator;
+
+/// Next slot that has never been used
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(KERNEL_STACK_SLOT + 1);
+
+/// Slots of stacks that have been freed, which are reused before new ones
+static FREE_SLOTS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// A stack in the stack region, with an unmapped guard page below it
+#[derive(Debug)]
+pub struct Stack {
+    slot: usize,
+    size: usize,
+}
+
+impl Stack {
+    /// Address just past the top of the stack, which is the initial stack pointer
+    pub fn top(&self) -> usize {
+        slot_top(self.slot)
+    }
+}
+
+/// Allocates and maps a stack of `size` bytes, returning `None` if the stack region is full
+pub fn allocate(
+    active_table: &mut ActivePageTable,
+    frame_alloc: &mut KernelFrameAllocator,
+    size: usize,
+) -> Option<Stack> {
+    let slot = match FREE_SLOTS.lock().pop() {
+        Some(slot) => slot,
+        None => {
+            let slot = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+            if slot >= STACK_SLOTS {
+                return None;
+            }
+
+            slot
+        }
+    };
+
+    map_stack(active_table, frame_alloc, slot, size);
+
+    Some(Stack { slot, size })
+}
+
+/// Unmaps a stack and frees its frames, so its slot can be reused
+///
+/// # Safety
+/// Nothing must still be using the stack
+#[allow(dead_code)]
+pub unsafe fn free(
+    stack: Stack,
+    active_table: &mut ActivePageTable,
+    frame_alloc: &mut KernelFrameAllocator,
+) {
+    unmap_stack(active_table, frame_alloc, stack.slot, stack.size);
+    FREE_SLOTS.lock().push(stack.slot);
+}
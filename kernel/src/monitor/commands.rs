@@ -0,0 +1,437 @@
+use core::str::SplitWhitespace;
+
+use kernel_shared::memory::{frame_alloc::Zone, paging::entry::Entry};
+use x86_64::{
+    port::Port,
+    structures::{GlobalDescriptorTable, InterruptDescriptorTable, Page, PAGE_SIZE},
+    DescriptorTablePointer,
+};
+
+use super::Monitor;
+use crate::{acpi, process, RAMFS};
+
+/// Function which runs a command, given the remaining arguments on the line
+type Handler = fn(&mut Monitor, &mut SplitWhitespace) -> Result<(), &'static str>;
+
+/// A single monitor command
+struct Command {
+    name: &'static str,
+    args: &'static str,
+    help: &'static str,
+    handler: Handler,
+}
+
+/// Every command understood by the monitor
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        args: "",
+        help: "lists all commands",
+        handler: help,
+    },
+    Command {
+        name: "ls",
+        args: "",
+        help: "lists files in the initrd",
+        handler: ls,
+    },
+    Command {
+        name: "cat",
+        args: "<path>",
+        help: "prints a file from the initrd",
+        handler: cat,
+    },
+    Command {
+        name: "exec",
+        args: "<path>",
+        help: "runs a program from the initrd until it exits",
+        handler: exec,
+    },
+    Command {
+        name: "memmap",
+        args: "",
+        help: "prints the usable memory regions",
+        handler: memmap,
+    },
+    Command {
+        name: "frames",
+        args: "",
+        help: "prints frame allocator usage",
+        handler: frames,
+    },
+    Command {
+        name: "translate",
+        args: "<addr>",
+        help: "walks the page tables for a virtual address",
+        handler: translate,
+    },
+    Command {
+        name: "inb",
+        args: "<port>",
+        help: "reads a byte from an io port",
+        handler: |_, args| port_read::<u8>(args),
+    },
+    Command {
+        name: "inw",
+        args: "<port>",
+        help: "reads a word from an io port",
+        handler: |_, args| port_read::<u16>(args),
+    },
+    Command {
+        name: "inl",
+        args: "<port>",
+        help: "reads a dword from an io port",
+        handler: |_, args| port_read::<u32>(args),
+    },
+    Command {
+        name: "outb",
+        args: "<port> <value>",
+        help: "writes a byte to an io port",
+        handler: |_, args| port_write::<u8>(args),
+    },
+    Command {
+        name: "outw",
+        args: "<port> <value>",
+        help: "writes a word to an io port",
+        handler: |_, args| port_write::<u16>(args),
+    },
+    Command {
+        name: "outl",
+        args: "<port> <value>",
+        help: "writes a dword to an io port",
+        handler: |_, args| port_write::<u32>(args),
+    },
+    Command {
+        name: "idt",
+        args: "",
+        help: "prints present entries in the loaded IDT",
+        handler: idt,
+    },
+    Command {
+        name: "gdt",
+        args: "",
+        help: "prints entries in the loaded GDT",
+        handler: gdt,
+    },
+    Command {
+        name: "gdb",
+        args: "",
+        help: "breaks into the gdb stub on COM2",
+        handler: |_, _| {
+            unsafe { core::arch::asm!("int3") };
+            Ok(())
+        },
+    },
+    Command {
+        name: "reboot",
+        args: "",
+        help: "reboots the machine",
+        handler: |_, _| acpi::reboot(),
+    },
+    Command {
+        name: "shutdown",
+        args: "",
+        help: "powers off the machine",
+        handler: |_, _| acpi::shutdown(),
+    },
+    Command {
+        name: "exit",
+        args: "",
+        help: "leaves the monitor and continues booting",
+        handler: |_, _| Ok(()),
+    },
+];
+
+/// Runs a single line of input, returning false if the monitor should exit
+pub fn execute(monitor: &mut Monitor, line: &str) -> bool {
+    let mut args = line.split_whitespace();
+
+    let Some(name) = args.next() else {
+        return true;
+    };
+
+    let Some(command) = COMMANDS.iter().find(|command| command.name == name) else {
+        mprintln!("unknown command `{name}`, type `help` for a list of commands");
+        return true;
+    };
+
+    if let Err(err) = (command.handler)(monitor, &mut args) {
+        mprintln!("{err}");
+        mprintln!("usage: {} {}", command.name, command.args);
+    }
+
+    command.name != "exit"
+}
+
+/// Parses a number, either in hex with a `0x` prefix or in decimal
+fn parse_number(string: &str) -> Option<u64> {
+    match string.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => string.parse().ok(),
+    }
+}
+
+/// Parses the next argument as a number
+fn next_number<T: TryFrom<u64>>(args: &mut SplitWhitespace) -> Result<T, &'static str> {
+    let arg = args.next().ok_or("missing argument")?;
+    let number = parse_number(arg).ok_or("invalid number")?;
+
+    T::try_from(number).map_err(|_| "number out of range")
+}
+
+fn help(_: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    for command in COMMANDS {
+        mprintln!("{:<10} {:<16} {}", command.name, command.args, command.help);
+    }
+
+    Ok(())
+}
+
+fn ls(_: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let ramfs = RAMFS.lock();
+    let ramfs = ramfs.as_ref().ok_or("no initrd loaded")?;
+
+    for (path, len) in ramfs.files() {
+        mprintln!("{len:>10}  {}", &**path);
+    }
+
+    Ok(())
+}
+
+fn cat(_: &mut Monitor, args: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let path = args.next().ok_or("missing path")?;
+    // accept both `ramfs//file` and plain `file`
+    let path = path.strip_prefix("ramfs//").unwrap_or(path);
+
+    let ramfs = RAMFS.lock();
+    let ramfs = ramfs.as_ref().ok_or("no initrd loaded")?;
+    let contents = ramfs.file_contents(path).ok_or("file not found")?;
+
+    match core::str::from_utf8(contents) {
+        Ok(text) => mprintln!("{text}"),
+        Err(_) => mprintln!("{} bytes of binary data", contents.len()),
+    }
+
+    Ok(())
+}
+
+fn exec(monitor: &mut Monitor, args: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let path = args.next().ok_or("missing path")?;
+
+    match process::spawn(path, monitor.active_table, monitor.frame_alloc) {
+        Ok(process) => {
+            let pid = process.pid();
+            let status = process::run(process, monitor.frame_alloc);
+
+            mprintln!("process {pid} {status}");
+        }
+        Err(err) => mprintln!("failed to start `{path}`: {err}"),
+    }
+
+    Ok(())
+}
+
+fn memmap(monitor: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    for region in monitor.frame_alloc.memory_regions() {
+        mprintln!(
+            "{:#018X}-{:#018X} {:>8} KiB {:?}",
+            region.base_addr,
+            region.base_addr + region.length,
+            region.length / 1024,
+            region.mem_type
+        );
+    }
+
+    Ok(())
+}
+
+fn frames(monitor: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let used = monitor.frame_alloc.used_frames();
+    let total = monitor.frame_alloc.total_frames();
+
+    mprintln!(
+        "{used}/{total} frames used ({}/{} KiB, {}%)",
+        used * PAGE_SIZE / 1024,
+        total * PAGE_SIZE / 1024,
+        used * 100 / total.max(1)
+    );
+
+    for zone in Zone::ALL.into_iter().rev() {
+        let (used, total) = monitor.frame_alloc.zone_frames(zone);
+        let range = zone.range();
+
+        mprintln!(
+            "\t{zone:?} ({:#X}-{:#X}): {used}/{total} frames used",
+            range.start,
+            range.end
+        );
+    }
+
+    Ok(())
+}
+
+fn translate(monitor: &mut Monitor, args: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let addr: usize = next_number(args)?;
+
+    if (0x0000_8000_0000_0000..0xFFFF_8000_0000_0000).contains(&addr) {
+        return Err("address is not canonical");
+    }
+
+    let page = Page::containing_address(addr);
+
+    /// Prints a single entry, returning false if the walk should stop
+    fn print_entry(level: usize, index: usize, entry: &Entry) -> bool {
+        match entry.pointed_frame() {
+            Some(frame) => {
+                mprintln!(
+                    "P{level}[{index:>3}]: {:#014X} {}",
+                    frame.start_address(),
+                    entry.flags()
+                );
+                true
+            }
+            None => {
+                mprintln!("P{level}[{index:>3}]: not present");
+                false
+            }
+        }
+    }
+
+    // walk each level by hand so every entry along the way can be shown, stopping on huge pages
+    let memory = monitor.active_table.memory();
+    let p4 = monitor.active_table.p4();
+    if print_entry(4, page.p4_index(), &p4[page.p4_index()]) {
+        if let Some(p3) = p4.next_table(page.p4_index(), memory) {
+            if print_entry(3, page.p3_index(), &p3[page.p3_index()]) {
+                if let Some(p2) = p3.next_table(page.p3_index(), memory) {
+                    if print_entry(2, page.p2_index(), &p2[page.p2_index()]) {
+                        if let Some(p1) = p2.next_table(page.p2_index(), memory) {
+                            print_entry(1, page.p1_index(), &p1[page.p1_index()]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    match monitor.active_table.translate(addr) {
+        Some(phys_addr) => mprintln!("{addr:#X} -> {phys_addr:#X}"),
+        None => mprintln!("{addr:#X} is not mapped"),
+    }
+
+    Ok(())
+}
+
+fn port_read<T>(args: &mut SplitWhitespace) -> Result<(), &'static str>
+where
+    T: x86_64::port::PortRead + core::fmt::LowerHex,
+{
+    let port = next_number(args)?;
+    let value = unsafe { Port::<T>::new(port).read() };
+
+    mprintln!("{port:#06x}: {value:#x}");
+
+    Ok(())
+}
+
+fn port_write<T>(args: &mut SplitWhitespace) -> Result<(), &'static str>
+where
+    T: x86_64::port::PortWrite + TryFrom<u64>,
+{
+    let port = next_number(args)?;
+    let value = next_number(args)?;
+
+    unsafe { Port::<T>::new(port).write(value) };
+
+    Ok(())
+}
+
+fn idt(_: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let pointer = DescriptorTablePointer::<InterruptDescriptorTable>::read_idt();
+    mprintln!("IDT at {:#X}, limit {:#X}", pointer.base(), pointer.limit());
+
+    /*      +---------------------+
+    u16     | offset bits 0..16   |
+    u16     | selector            |
+    u16     | options             |
+    u16     | offset bits 16..32  |
+    u32     | offset bits 32..64  |
+    u32     | reserved            |
+            +---------------------+ */
+    let entries = (pointer.limit() as usize + 1) / 16;
+    for vector in 0..entries {
+        let entry = unsafe { *(pointer.base() as *const [u16; 8]).add(vector) };
+        let options = entry[2];
+
+        // only show present entries, as most vectors are unused
+        if options & (1 << 15) == 0 {
+            continue;
+        }
+
+        let offset = entry[0] as u64
+            | (entry[3] as u64) << 16
+            | (entry[4] as u64) << 32
+            | (entry[5] as u64) << 48;
+        let kind = match (options >> 8) & 0xF {
+            0xE => "interrupt",
+            0xF => "trap",
+            _ => "unknown",
+        };
+
+        mprintln!(
+            "{vector:>3}: {offset:#018X} sel {:#06X} {kind:<9} dpl {} ist {}",
+            entry[1],
+            (options >> 13) & 0b11,
+            options & 0b111
+        );
+    }
+
+    Ok(())
+}
+
+fn gdt(_: &mut Monitor, _: &mut SplitWhitespace) -> Result<(), &'static str> {
+    let pointer = DescriptorTablePointer::<GlobalDescriptorTable>::read_gdt();
+    mprintln!("GDT at {:#X}, limit {:#X}", pointer.base(), pointer.limit());
+
+    let entries = (pointer.limit() as usize + 1) / 8;
+    let entry = |index: usize| unsafe { *(pointer.base() as *const u64).add(index) };
+
+    let mut index = 0;
+    while index < entries {
+        let value = entry(index);
+        let selector = index * 8;
+        index += 1;
+
+        let present = value & (1 << 47) != 0;
+        let dpl = (value >> 45) & 0b11;
+
+        if !present {
+            mprintln!("{selector:#06X}: {value:#018X} not present");
+        } else if value & (1 << 44) == 0 {
+            // system segments such as the TSS take up two entries, with the upper base in the second
+            let upper = if index < entries { entry(index) } else { 0 };
+            index += 1;
+
+            let base = (value >> 16) & 0xFF_FFFF | (value >> 32) & 0xFF00_0000 | upper << 32;
+            let limit = value & 0xFFFF | (value >> 32) & 0xF_0000;
+            let kind = match (value >> 40) & 0xF {
+                0x9 => "tss",
+                0xB => "tss (busy)",
+                _ => "system",
+            };
+
+            mprintln!("{selector:#06X}: {kind} base {base:#X} limit {limit:#X} dpl {dpl}");
+        } else {
+            let kind = if value & (1 << 43) != 0 {
+                "code"
+            } else {
+                "data"
+            };
+            let long = if value & (1 << 53) != 0 { " long" } else { "" };
+
+            mprintln!("{selector:#06X}: {value:#018X} {kind}{long} dpl {dpl}");
+        }
+    }
+
+    Ok(())
+}
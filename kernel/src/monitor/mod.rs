@@ -0,0 +1,136 @@
+use kernel_shared::{cmdline::Consoles, memory::paging::active_table::ActivePageTable};
+
+use crate::{input, io, memory::KernelFrameAllocator};
+
+/// Maximum length of a single command line
+const LINE_LENGTH: usize = 256;
+
+/// Prints to every console the monitor is reachable on
+macro_rules! mprint {
+    ($($arg:tt)*) => ($crate::print!($($arg)*));
+}
+
+/// Prints to every console the monitor is reachable on, appending a newline
+macro_rules! mprintln {
+    () => (mprint!("\n"));
+    ($($arg:tt)*) => (mprint!("{}\n", format_args!($($arg)*)));
+}
+
+mod commands;
+
+/// Where a character of input came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Keyboard,
+    /// Serial input is already echoed back by the serial console
+    Serial,
+}
+
+/// State shared between monitor commands
+pub struct Monitor<'a> {
+    active_table: &'a mut ActivePageTable,
+    frame_alloc: &'a mut KernelFrameAllocator,
+    line: [u8; LINE_LENGTH],
+    len: usize,
+}
+
+/// Runs the debug monitor until the `exit` command is entered, reading commands from the keyboard
+/// and COM1 and printing to the selected consoles
+pub fn run(active_table: &mut ActivePageTable, frame_alloc: &mut KernelFrameAllocator) {
+    log::info!("starting monitor");
+    mprintln!("crabos monitor, type `help` for a list of commands");
+
+    let mut monitor = Monitor {
+        active_table,
+        frame_alloc,
+        line: [0; LINE_LENGTH],
+        len: 0,
+    };
+
+    loop {
+        mprint!("> ");
+        monitor.read_line();
+
+        // copy the line out so commands can borrow the monitor mutably
+        let (line, len) = (monitor.line, monitor.len);
+        let line = core::str::from_utf8(&line[..len]).unwrap_or("");
+
+        if !commands::execute(&mut monitor, line) {
+            break;
+        }
+    }
+
+    log::info!("exiting monitor");
+}
+
+impl Monitor<'_> {
+    /// Waits until a full line has been entered
+    fn read_line(&mut self) {
+        self.len = 0;
+
+        let consoles = io::consoles();
+
+        loop {
+            let mut buffer = [0; 16];
+
+            if consoles.contains(Consoles::FRAMEBUFFER) {
+                let read = input::read_keyboard(&mut buffer);
+                for character in core::str::from_utf8(&buffer[..read]).unwrap_or("").chars() {
+                    if self.handle_char(character, Source::Keyboard) {
+                        return;
+                    }
+                }
+            }
+
+            if consoles.contains(Consoles::SERIAL) {
+                let read = io::serial::read(&mut buffer);
+                for &byte in &buffer[..read] {
+                    if self.handle_char(byte as char, Source::Serial) {
+                        return;
+                    }
+                }
+            }
+
+            // sleep until the next interrupt, which may bring more input
+            unsafe {
+                core::arch::asm!("hlt");
+            }
+        }
+    }
+
+    /// Adds a character to the line and echoes it, returning true once the line is complete
+    fn handle_char(&mut self, character: char, source: Source) -> bool {
+        // echo to the framebuffer, and to serial if the serial console didn't already echo it
+        let consoles = io::consoles();
+        let echo = |framebuffer: &str, serial: &str| {
+            if consoles.contains(Consoles::FRAMEBUFFER) {
+                io::print_framebuffer(format_args!("{framebuffer}"));
+            }
+            if consoles.contains(Consoles::SERIAL) && source == Source::Keyboard {
+                io::serial::write(serial.as_bytes());
+            }
+        };
+
+        match character {
+            '\n' => {
+                echo("\n", "\n");
+                return true;
+            }
+            '\x08' if self.len > 0 => {
+                self.len -= 1;
+                echo("\x08", "\x08 \x08");
+            }
+            ' '..='~' if self.len < LINE_LENGTH => {
+                self.line[self.len] = character as u8;
+                self.len += 1;
+
+                let mut buffer = [0; 4];
+                let character = character.encode_utf8(&mut buffer);
+                echo(character, character);
+            }
+            _ => {}
+        }
+
+        false
+    }
+}
@@ -0,0 +1,630 @@
+//! User processes, each running in its own [AddressSpace] until it exits or is killed.
+//!
+//! Only one process runs at a time, entered from [run] and left through [exit_current], which
+//! switches straight back to the kernel stack [run] was called on. There's no preemption, so
+//! processes created by [fork_current] wait in a queue until the ones before them have exited.
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{
+    arch::asm,
+    fmt::Display,
+    ptr::addr_of_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crabstd::{fs::File, mutex::Mutex};
+use kernel_shared::{
+    elf::{ElfError, ElfFile, ProgramHeader},
+    memory::{
+        frame_alloc::FrameAllocator,
+        paging::{active_table::ActivePageTable, entry::EntryFlags, PHYS_MEM_OFFSET},
+    },
+};
+use x86_64::{
+    align_down_to_page, align_up_to_page,
+    registers::{CpuFlags, CR3},
+    structures::{ExceptionStackFrame, Page, PAGE_SIZE},
+    VirtualAddress,
+};
+
+use crate::{
+    gdt,
+    interrupts::{PageFaultErrorCode, TrapFrame},
+    memory::{
+        address_space::{
+            AddressSpace, Backing, FaultError, Vma, VmaError, USER_SPACE_END, USER_SPACE_START,
+        },
+        KernelFrameAllocator,
+    },
+};
+
+/// Initial stack pointer of every process, leaving the last page of user space unmapped
+pub const USER_STACK_TOP: usize = USER_SPACE_END - PAGE_SIZE;
+
+/// Largest size the user stack can grow to
+const MAX_USER_STACK: usize = 8 * 1024 * 1024; // 8 MiB
+
+static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
+
+/// Process that's currently running, if any
+static CURRENT: Mutex<Option<Current>> = Mutex::new(None);
+
+/// Processes waiting to run once the current one exits
+static READY: Mutex<VecDeque<Process>> = Mutex::new(VecDeque::new());
+
+/// Stack pointer of [run] while a process runs, which [exit_current] returns to
+static mut KERNEL_CONTEXT: usize = 0;
+
+/// Why a program couldn't be started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    NotFound,
+    OutOfMemory,
+    Elf(ElfError),
+    /// A segment's file offset and address don't agree on their offset into a page
+    MisalignedSegment,
+    Vma(VmaError),
+}
+
+impl Display for SpawnError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SpawnError::NotFound => write!(f, "file not found"),
+            SpawnError::OutOfMemory => write!(f, "out of memory"),
+            SpawnError::Elf(err) => write!(f, "invalid executable: {err}"),
+            SpawnError::MisalignedSegment => write!(f, "segment is not page aligned in file"),
+            SpawnError::Vma(err) => write!(f, "invalid segment: {err}"),
+        }
+    }
+}
+
+/// How a process ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Process called `exit` with the given status
+    Exited(i64),
+    /// Process was killed after a fault it couldn't recover from
+    Killed,
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ExitStatus::Exited(status) => write!(f, "exited with status {status}"),
+            ExitStatus::Killed => write!(f, "was killed"),
+        }
+    }
+}
+
+/// A user program ready to run
+pub struct Process {
+    pid: usize,
+    address_space: AddressSpace,
+    /// Registers the process starts with
+    context: TrapFrame,
+}
+
+impl Process {
+    /// Creates a process which starts at `entry`, adding a stack to its address space
+    pub fn new(mut address_space: AddressSpace, entry: VirtualAddress) -> Result<Self, SpawnError> {
+        address_space
+            .add(Vma::new(
+                USER_STACK_TOP - PAGE_SIZE,
+                USER_STACK_TOP,
+                EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+                Backing::Stack {
+                    limit: USER_STACK_TOP - MAX_USER_STACK,
+                },
+            ))
+            .map_err(SpawnError::Vma)?;
+
+        let (code_selector, data_selector) = gdt::user_selectors();
+        let context = TrapFrame {
+            r15: 0,
+            r14: 0,
+            r13: 0,
+            r12: 0,
+            r11: 0,
+            r10: 0,
+            r9: 0,
+            r8: 0,
+            rbp: 0,
+            rdi: 0,
+            rsi: 0,
+            rdx: 0,
+            rcx: 0,
+            rbx: 0,
+            rax: 0,
+            stack_frame: ExceptionStackFrame::new(
+                entry as u64,
+                code_selector,
+                CpuFlags::INTERRUPT_FLAG,
+                USER_STACK_TOP as u64,
+                data_selector,
+            ),
+        };
+
+        Ok(Self {
+            pid: NEXT_PID.fetch_add(1, Ordering::Relaxed),
+            address_space,
+            context,
+        })
+    }
+
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+}
+
+/// The running process, along with the frame allocator lent to it by [run] for page faults
+struct Current {
+    process: Process,
+    frame_alloc: FrameAllocatorPtr,
+    exit: Option<ExitStatus>,
+}
+
+struct FrameAllocatorPtr(*mut KernelFrameAllocator);
+
+// only used while [run] is waiting for the process, which is the only thing touching the allocator
+unsafe impl Send for FrameAllocatorPtr {}
+
+/// Loads an ELF executable from a file, without mapping any of it yet
+pub fn spawn(
+    path: &str,
+    active_table: &ActivePageTable,
+    frame_alloc: &mut KernelFrameAllocator,
+) -> Result<Process, SpawnError> {
+    let file = File::new(path).ok_or(SpawnError::NotFound)?;
+
+    // only the headers are read here, into a scratch frame, with segments paged in on demand
+    let scratch = frame_alloc
+        .allocate_frame()
+        .ok_or(SpawnError::OutOfMemory)?;
+    let headers = unsafe {
+        core::slice::from_raw_parts_mut(
+            (scratch.start_address() + PHYS_MEM_OFFSET) as *mut u8,
+            PAGE_SIZE,
+        )
+    };
+    let read = crate::fs::read(&file, headers);
+
+    let segments = ElfFile::new_executable(&headers[..read]).map(|elf| {
+        let segments: Vec<ProgramHeader> = elf.load_segments().copied().collect();
+        (elf.entrypoint(), segments)
+    });
+    frame_alloc.deallocate_frame(scratch);
+    let (entry, segments) = segments.map_err(SpawnError::Elf)?;
+
+    let mut address_space =
+        AddressSpace::new(active_table, frame_alloc).ok_or(SpawnError::OutOfMemory)?;
+    for segment in &segments {
+        if let Err(err) = add_segment(&mut address_space, &file, segment) {
+            unsafe { address_space.free(frame_alloc) };
+            return Err(err);
+        }
+    }
+
+    // the heap starts just past the highest segment
+    let segments_end = segments
+        .iter()
+        .map(|segment| (segment.virt_addr + segment.mem_size) as usize)
+        .max();
+    if let Some(end) = segments_end {
+        address_space.set_heap_start(end);
+    }
+
+    Process::new(address_space, entry)
+}
+
+/// Adds a region backed by the part of `file` a segment is loaded from
+fn add_segment(
+    address_space: &mut AddressSpace,
+    file: &File,
+    segment: &ProgramHeader,
+) -> Result<(), SpawnError> {
+    let addr = segment.virt_addr as usize;
+    let start = align_down_to_page(addr);
+    let end = align_up_to_page(addr + segment.mem_size as usize);
+
+    // the region starts at a page boundary, so it's backed from the same distance before the
+    // segment's data in the file
+    let offset = (segment.offset as usize)
+        .checked_sub(addr - start)
+        .ok_or(SpawnError::MisalignedSegment)?;
+    let len = addr - start + segment.file_size as usize;
+
+    log::trace!("\t* segment {start:#X}-{end:#X} from file offset {offset:#X}, {len:#X} bytes");
+
+    // segments with nothing in the file, such as `.bss`, are just zeroed memory
+    let backing = if segment.file_size == 0 {
+        Backing::Anonymous
+    } else {
+        Backing::File {
+            file: file.clone(),
+            offset,
+            len,
+        }
+    };
+
+    address_space
+        .add(Vma::new(
+            start,
+            end,
+            EntryFlags::from_elf_segment_flags(segment),
+            backing,
+        ))
+        .map_err(SpawnError::Vma)
+}
+
+/// Runs a process until it exits or is killed, freeing its address space afterwards, followed by
+/// any processes forked from it. Returns how the first process ended.
+/// The frame allocator is used to handle the processes' page faults while they run.
+pub fn run(process: Process, frame_alloc: &mut KernelFrameAllocator) -> ExitStatus {
+    let status = run_one(process, frame_alloc);
+
+    loop {
+        let Some(process) = READY.lock().pop_front() else {
+            break;
+        };
+
+        run_one(process, frame_alloc);
+    }
+
+    status
+}
+
+/// Runs a single process until it exits or is killed, freeing its address space afterwards
+fn run_one(process: Process, frame_alloc: &mut KernelFrameAllocator) -> ExitStatus {
+    // `iretq` is done from a copy, as the process is owned by [CURRENT] while it runs
+    let context = process.context;
+    log::info!(
+        "running process {} at {:#X}",
+        process.pid,
+        context.stack_frame.instruction_pointer
+    );
+
+    // interrupts are turned back on by `iretq`, once the process's address space is active
+    x86_64::interrupts::disable_interrupts();
+    let (kernel_table, flags) = CR3::read();
+    unsafe { process.address_space.activate() };
+
+    *CURRENT.lock() = Some(Current {
+        process,
+        frame_alloc: FrameAllocatorPtr(frame_alloc),
+        exit: None,
+    });
+
+    unsafe {
+        enter_user(&context, addr_of_mut!(KERNEL_CONTEXT));
+
+        CR3::write(kernel_table, flags);
+    }
+    x86_64::interrupts::enable_interrupts();
+
+    let current = CURRENT
+        .lock()
+        .take()
+        .expect("process vanished while running");
+    let status = current.exit.expect("process returned without exiting");
+    log::info!("process {} {status}", current.process.pid);
+
+    unsafe { current.process.address_space.free(frame_alloc) };
+
+    status
+}
+
+/// Checks if a process is running
+pub fn is_running() -> bool {
+    CURRENT.lock().is_some()
+}
+
+/// Returns the pid of the running process
+pub fn current_pid() -> Option<usize> {
+    CURRENT.lock().as_ref().map(|current| current.process.pid)
+}
+
+/// Calls `f` with the running process's address space and the frame allocator lent to it,
+/// returning `None` if no process is running
+pub fn with_address_space<R>(
+    f: impl FnOnce(&mut AddressSpace, &mut KernelFrameAllocator) -> R,
+) -> Option<R> {
+    let mut current = CURRENT.lock();
+    let current = current.as_mut()?;
+    let frame_alloc = unsafe { &mut *current.frame_alloc.0 };
+
+    Some(f(&mut current.process.address_space, frame_alloc))
+}
+
+/// Handles a page fault in the running process's address space, returning `None` if no process
+/// is running
+pub fn handle_page_fault(
+    addr: VirtualAddress,
+    error: PageFaultErrorCode,
+) -> Option<Result<(), FaultError>> {
+    with_address_space(|address_space, frame_alloc| {
+        address_space.handle_fault(addr, error, frame_alloc)
+    })
+}
+
+/// Maps every page of a buffer the running process passed to the kernel, so the kernel can write
+/// to it without faulting. Returns false if the process can't write to all of it.
+pub fn fault_in(addr: VirtualAddress, len: usize) -> bool {
+    // kernel buffers are always mapped
+    if len == 0 || addr >= USER_SPACE_END || !is_running() {
+        return true;
+    }
+
+    let Some(end) = addr.checked_add(len).filter(|&end| end <= USER_SPACE_END) else {
+        return false;
+    };
+    if addr < USER_SPACE_START {
+        return false;
+    }
+
+    let start_page = Page::containing_address(addr);
+    let end_page = Page::containing_address(end - 1);
+    // handled as a write fault, which maps missing pages and copies shared ones, but leaves
+    // pages that are already writable alone
+    Page::range_inclusive(start_page, end_page).all(|page| {
+        handle_page_fault(page.start_address(), PageFaultErrorCode::CAUSED_BY_WRITE)
+            .is_some_and(|result| result.is_ok())
+    })
+}
+
+/// Creates a copy of the running process that resumes from `frame` with `rax` cleared, sharing
+/// its pages copy on write. The copy is queued to run once the running process exits, and its pid
+/// is returned.
+pub fn fork_current(frame: &TrapFrame) -> Option<usize> {
+    let mut current = CURRENT.lock();
+    let current = current.as_mut()?;
+    let frame_alloc = unsafe { &mut *current.frame_alloc.0 };
+
+    let address_space = current.process.address_space.fork(frame_alloc)?;
+    let mut context = *frame;
+    context.rax = 0;
+
+    let child = Process {
+        pid: NEXT_PID.fetch_add(1, Ordering::Relaxed),
+        address_space,
+        context,
+    };
+    let pid = child.pid;
+    log::trace!("process {} forked into {pid}", current.process.pid);
+
+    READY.lock().push_back(child);
+    Some(pid)
+}
+
+/// Ends the running process, returning to the kernel from [run]
+pub fn exit_current(status: ExitStatus) -> ! {
+    CURRENT.lock().as_mut().expect("no process running").exit = Some(status);
+
+    unsafe { return_to_kernel(KERNEL_CONTEXT) }
+}
+
+/// Saves the kernel's callee saved registers and stack pointer to `context`, then drops to ring 3
+/// with the registers in `frame`
+#[naked]
+unsafe extern "C" fn enter_user(_frame: *const TrapFrame, _context: *mut usize) {
+    unsafe {
+        asm!(
+            "push rbx",
+            "push rbp",
+            "push r12",
+            "push r13",
+            "push r14",
+            "push r15",
+            "mov [rsi], rsp",
+            // the frame is laid out just like the stack a trap handler returns through
+            "mov rsp, rdi",
+            "pop r15",
+            "pop r14",
+            "pop r13",
+            "pop r12",
+            "pop r11",
+            "pop r10",
+            "pop r9",
+            "pop r8",
+            "pop rbp",
+            "pop rdi",
+            "pop rsi",
+            "pop rdx",
+            "pop rcx",
+            "pop rbx",
+            "pop rax",
+            "iretq",
+            options(noreturn)
+        );
+    }
+}
+
+/// Switches back to the stack saved by [enter_user], returning from it
+#[naked]
+unsafe extern "C" fn return_to_kernel(_context: usize) -> ! {
+    unsafe {
+        asm!(
+            "mov rsp, rdi",
+            "pop r15",
+            "pop r14",
+            "pop r13",
+            "pop r12",
+            "pop rbp",
+            "pop rbx",
+            "ret",
+            options(noreturn)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kernel_shared::memory::paging::{
+        active_table::ActivePageTable, entry::EntryFlags, PHYS_MEM_OFFSET,
+    };
+    use x86_64::structures::PAGE_SIZE;
+
+    use super::{run, run_one, spawn, ExitStatus, Process, SpawnError, READY};
+    use crate::{
+        interrupts::PageFaultErrorCode,
+        memory::{
+            address_space::{AddressSpace, Backing, Vma},
+            KernelFrameAllocator,
+        },
+        testing::with_memory,
+    };
+
+    const CODE_ADDR: usize = 0x40_0000;
+
+    /// Creates a process made of a single page of code
+    fn code_process(
+        code: &[u8],
+        active_table: &ActivePageTable,
+        frame_alloc: &mut KernelFrameAllocator,
+    ) -> Process {
+        let mut space = AddressSpace::new(active_table, frame_alloc).unwrap();
+        space
+            .add(Vma::new(
+                CODE_ADDR,
+                CODE_ADDR + PAGE_SIZE,
+                EntryFlags::empty(),
+                Backing::Anonymous,
+            ))
+            .unwrap();
+        space
+            .handle_fault(CODE_ADDR, PageFaultErrorCode::empty(), frame_alloc)
+            .unwrap();
+
+        let phys = space.translate(CODE_ADDR).unwrap();
+        unsafe {
+            core::ptr::copy(
+                code.as_ptr(),
+                (phys + PHYS_MEM_OFFSET) as *mut u8,
+                code.len(),
+            );
+        }
+
+        Process::new(space, CODE_ADDR).unwrap()
+    }
+
+    /// Runs a process made of a single page of code
+    fn run_code(code: &[u8]) -> ExitStatus {
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+
+            let process = code_process(code, active_table, frame_alloc);
+            let status = run(process, frame_alloc);
+
+            assert_eq!(frame_alloc.used_frames(), used);
+            status
+        })
+    }
+
+    #[test_case]
+    fn process_exits() {
+        let code = [
+            0xB8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+            0xBF, 0x2A, 0x00, 0x00, 0x00, // mov edi, 42
+            0xCD, 0x80, // int 0x80
+        ];
+
+        assert_eq!(run_code(&code), ExitStatus::Exited(42));
+    }
+
+    #[test_case]
+    fn process_stack_grows() {
+        let code = [
+            0x48, 0x81, 0xEC, 0x00, 0x00, 0x01, 0x00, // sub rsp, 0x10000
+            0x48, 0x89, 0x24, 0x24, // mov [rsp], rsp
+            0xB8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+            0x31, 0xFF, // xor edi, edi
+            0xCD, 0x80, // int 0x80
+        ];
+
+        assert_eq!(run_code(&code), ExitStatus::Exited(0));
+    }
+
+    #[test_case]
+    fn faulting_process_is_killed() {
+        let code = [
+            0xC6, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00, 0x01, // mov byte [0], 1
+        ];
+
+        assert_eq!(run_code(&code), ExitStatus::Killed);
+    }
+
+    #[test_case]
+    fn writing_to_code_kills_process() {
+        let code = [
+            0xC6, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, // mov byte [rip], 1
+        ];
+
+        assert_eq!(run_code(&code), ExitStatus::Killed);
+    }
+
+    #[test_case]
+    fn forked_process_runs_after_parent() {
+        let code = [
+            0x48, 0xC7, 0x44, 0x24, 0xF8, 0x07, 0x00, 0x00, 0x00, // mov qword [rsp - 8], 7
+            0xB8, 0x06, 0x00, 0x00, 0x00, // mov eax, 6
+            0xCD, 0x80, // int 0x80
+            0x48, 0x85, 0xC0, // test rax, rax
+            0x74, 0x15, // jz child
+            0x48, 0xC7, 0x44, 0x24, 0xF8, 0x09, 0x00, 0x00, 0x00, // mov qword [rsp - 8], 9
+            0xB8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+            0x48, 0x8B, 0x7C, 0x24, 0xF8, // mov rdi, [rsp - 8]
+            0xCD, 0x80, // int 0x80
+            // child:
+            0xB8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+            0x48, 0x8B, 0x7C, 0x24, 0xF8, // mov rdi, [rsp - 8]
+            0xCD, 0x80, // int 0x80
+        ];
+
+        with_memory(|active_table, frame_alloc| {
+            let used = frame_alloc.used_frames();
+
+            let process = code_process(&code, active_table, frame_alloc);
+            let parent = process.pid();
+            assert_eq!(run_one(process, frame_alloc), ExitStatus::Exited(9));
+
+            // the child only sees the stack as it was when it was forked
+            let child = READY.lock().pop_front().unwrap();
+            assert!(child.pid() > parent);
+            assert_eq!(run_one(child, frame_alloc), ExitStatus::Exited(7));
+
+            assert!(READY.lock().is_empty());
+            assert_eq!(frame_alloc.used_frames(), used);
+        });
+    }
+
+    #[test_case]
+    fn process_maps_memory() {
+        let code = [
+            0xB8, 0x07, 0x00, 0x00, 0x00, // mov eax, 7
+            0x31, 0xFF, // xor edi, edi
+            0xBE, 0x00, 0x10, 0x00, 0x00, // mov esi, 0x1000
+            0xBA, 0x03, 0x00, 0x00, 0x00, // mov edx, 3
+            0x31, 0xC9, // xor ecx, ecx
+            0xCD, 0x80, // int 0x80
+            0xC6, 0x00, 0x2A, // mov byte [rax], 42
+            0x0F, 0xB6, 0x38, // movzx edi, byte [rax]
+            0xB8, 0x05, 0x00, 0x00, 0x00, // mov eax, 5
+            0xCD, 0x80, // int 0x80
+        ];
+
+        assert_eq!(run_code(&code), ExitStatus::Exited(42));
+    }
+
+    #[test_case]
+    fn spawn_rejects_non_executables() {
+        with_memory(|active_table, frame_alloc| {
+            assert_eq!(
+                spawn("ramfs//missing", active_table, frame_alloc).err(),
+                Some(SpawnError::NotFound)
+            );
+            assert!(matches!(
+                spawn("ramfs//test", active_table, frame_alloc),
+                Err(SpawnError::Elf(_))
+            ));
+        });
+    }
+}
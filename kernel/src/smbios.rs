@@ -0,0 +1,310 @@
+use alloc::{string::String, vec::Vec};
+
+use crabstd::mutex::Mutex;
+use kernel_shared::{handoff::Smbios, memory::paging::PHYS_MEM_OFFSET};
+use multiboot::SmbiosEntryPoint;
+
+/// Information decoded during [init], or `None` if no SMBIOS tables were found
+pub static SMBIOS: Mutex<Option<SystemInfo>> = Mutex::new(None);
+
+/// Stores the SMBIOS structures describing the machine
+///
+/// https://www.dmtf.org/sites/default/files/standards/documents/DSP0134_3.7.0.pdf
+#[derive(Debug)]
+pub struct SystemInfo {
+    pub bios: Option<BiosInformation>,
+    pub system: Option<SystemInformation>,
+    pub memory_devices: Vec<MemoryDevice>,
+}
+
+/// BIOS information (type 0) structure
+#[derive(Debug)]
+pub struct BiosInformation {
+    pub vendor: String,
+    pub version: String,
+    pub release_date: String,
+}
+
+/// System information (type 1) structure
+#[derive(Debug)]
+pub struct SystemInformation {
+    pub manufacturer: String,
+    pub product_name: String,
+    pub version: String,
+    pub serial_number: String,
+}
+
+/// Memory device (type 17) structure, describing a single memory slot
+#[derive(Debug)]
+pub struct MemoryDevice {
+    pub locator: String,
+    pub bank_locator: String,
+    /// Size in bytes, or `None` if the slot is empty or the size is unknown
+    pub size: Option<u64>,
+    /// Speed in megatransfers per second, or 0 if unknown
+    pub speed: u16,
+    pub manufacturer: String,
+    pub part_number: String,
+}
+
+/// A single structure in the structure table, with its formatted area and string set
+struct Structure<'a> {
+    structure_type: u8,
+    formatted: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl Structure<'_> {
+    /// Reads a value at the given offset from the start of the structure, returning `None` if it
+    /// lies past the formatted area
+    fn read<T: Copy>(&self, offset: usize) -> Option<T> {
+        let bytes = self
+            .formatted
+            .get(offset..offset + core::mem::size_of::<T>())?;
+
+        Some(unsafe { (bytes.as_ptr() as *const T).read_unaligned() })
+    }
+
+    /// Reads the string whose index is stored at the given offset, returning an empty string if
+    /// there is none
+    fn string(&self, offset: usize) -> String {
+        let index = self.read::<u8>(offset).unwrap_or(0) as usize;
+
+        // strings are numbered from 1, with 0 meaning no string
+        let string = index
+            .checked_sub(1)
+            .and_then(|index| self.strings.split(|&byte| byte == 0).nth(index))
+            .unwrap_or(&[]);
+
+        String::from_utf8_lossy(string).trim().into()
+    }
+}
+
+impl BiosInformation {
+    fn parse(structure: &Structure) -> Self {
+        /*      +-------------------+
+        u8      | type = 0          |
+        u8      | length            |
+        u16     | handle            |
+        u8      | vendor            |
+        u8      | version           |
+        u16     | starting segment  |
+        u8      | release date      |
+                +-------------------+ */
+        Self {
+            vendor: structure.string(0x04),
+            version: structure.string(0x05),
+            release_date: structure.string(0x08),
+        }
+    }
+}
+
+impl SystemInformation {
+    fn parse(structure: &Structure) -> Self {
+        /*      +-------------------+
+        u8      | type = 1          |
+        u8      | length            |
+        u16     | handle            |
+        u8      | manufacturer      |
+        u8      | product name      |
+        u8      | version           |
+        u8      | serial number     |
+                +-------------------+ */
+        Self {
+            manufacturer: structure.string(0x04),
+            product_name: structure.string(0x05),
+            version: structure.string(0x06),
+            serial_number: structure.string(0x07),
+        }
+    }
+}
+
+impl MemoryDevice {
+    fn parse(structure: &Structure) -> Self {
+        /*      +-------------------+
+        u8      | type = 17         |
+        u8      | length            |
+        u16     | handle            |
+        ...     |                   |
+        u16     | size         0x0C |
+        ...     |                   |
+        u8      | locator      0x10 |
+        u8      | bank locator 0x11 |
+        ...     |                   |
+        u16     | speed        0x15 |
+        u8      | manufacturer 0x17 |
+        ...     |                   |
+        u8      | part number  0x1A |
+        ...     |                   |
+        u32     | ext. size    0x1C |
+                +-------------------+ */
+        let size = match structure.read::<u16>(0x0C) {
+            None | Some(0) | Some(0xFFFF) => None,
+            // sizes too large for 15 bits are given in MiB in the extended size field
+            Some(0x7FFF) => structure
+                .read::<u32>(0x1C)
+                .map(|size| (size & 0x7FFFFFFF) as u64 * 1024 * 1024),
+            // top bit set means the size is in KiB instead of MiB
+            Some(size) if size & 0x8000 != 0 => Some((size & 0x7FFF) as u64 * 1024),
+            Some(size) => Some(size as u64 * 1024 * 1024),
+        };
+
+        Self {
+            locator: structure.string(0x10),
+            bank_locator: structure.string(0x11),
+            size,
+            speed: structure.read(0x15).unwrap_or(0),
+            manufacturer: structure.string(0x17),
+            part_number: structure.string(0x1A),
+        }
+    }
+}
+
+/// Returns an iterator over each structure in the table, stopping at the end-of-table structure
+fn structures(mut table: &[u8]) -> impl Iterator<Item = Structure<'_>> {
+    core::iter::from_fn(move || {
+        /*      +-------------------+
+        u8      | type              |
+        u8      | length            |
+        u16     | handle            |
+        varies  | formatted area    |
+        varies  | string set        |
+                +-------------------+ */
+        let structure_type = *table.first()?;
+        let length = *table.get(1)? as usize;
+
+        if structure_type == 127 || length < 4 {
+            return None;
+        }
+
+        let formatted = table.get(..length)?;
+
+        // string set is a list of null-terminated strings, ending with an extra null byte
+        let strings_len = table[length..].windows(2).position(|pair| pair == [0, 0])?;
+        let strings = &table[length..length + strings_len];
+
+        table = &table[length + strings_len + 2..];
+
+        Some(Structure {
+            structure_type,
+            formatted,
+            strings,
+        })
+    })
+}
+
+/// Searches the BIOS area for an entry point, preferring the 64-bit one
+fn find_entry_point() -> Option<SmbiosEntryPoint> {
+    let bios_area =
+        unsafe { core::slice::from_raw_parts((0xF0000 + PHYS_MEM_OFFSET) as *const u8, 0x10000) };
+
+    // entry points are always on a 16-byte boundary
+    (0..bios_area.len())
+        .step_by(16)
+        .filter_map(|offset| SmbiosEntryPoint::parse(&bios_area[offset..]))
+        .max_by_key(|entry_point| entry_point.major)
+}
+
+/// Finds the SMBIOS entry point passed by multiboot, or in the BIOS area if there is none, then
+/// decodes the BIOS, system and memory device structures
+pub fn init(smbios: Smbios) {
+    log::trace!("initialising smbios");
+
+    let entry_point = match smbios.table_address {
+        0 => find_entry_point(),
+        table_address => Some(SmbiosEntryPoint {
+            major: smbios.major,
+            minor: smbios.minor,
+            table_address,
+            table_length: smbios.table_length,
+        }),
+    };
+
+    let Some(entry_point) = entry_point else {
+        log::warn!("\t* no SMBIOS entry point found");
+        return;
+    };
+    log::trace!(
+        "\t* found SMBIOS {}.{} table at {:#X}",
+        entry_point.major,
+        entry_point.minor,
+        entry_point.table_address
+    );
+
+    let table = unsafe {
+        core::slice::from_raw_parts(
+            (entry_point.table_address as usize + PHYS_MEM_OFFSET) as *const u8,
+            entry_point.table_length as usize,
+        )
+    };
+
+    let mut info = SystemInfo {
+        bios: None,
+        system: None,
+        memory_devices: Vec::new(),
+    };
+
+    for structure in structures(table) {
+        match structure.structure_type {
+            0 => info.bios = Some(BiosInformation::parse(&structure)),
+            1 => info.system = Some(SystemInformation::parse(&structure)),
+            17 => info.memory_devices.push(MemoryDevice::parse(&structure)),
+            _ => {}
+        }
+    }
+
+    if let Some(bios) = &info.bios {
+        log::info!(
+            "bios: {} {} ({})",
+            bios.vendor,
+            bios.version,
+            bios.release_date
+        );
+    }
+
+    if let Some(system) = &info.system {
+        log::info!(
+            "system: {} {} {} (serial {})",
+            system.manufacturer,
+            system.product_name,
+            system.version,
+            system.serial_number
+        );
+    }
+
+    for device in &info.memory_devices {
+        match device.size {
+            Some(size) => log::info!(
+                "memory: {} {} {} MiB at {} MT/s, {} {}",
+                device.bank_locator,
+                device.locator,
+                size / (1024 * 1024),
+                device.speed,
+                device.manufacturer,
+                device.part_number
+            ),
+            None => log::info!("memory: {} {} empty", device.bank_locator, device.locator),
+        }
+    }
+
+    *SMBIOS.lock() = Some(info);
+
+    log::trace!("smbios initialised");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SMBIOS;
+
+    #[test_case]
+    fn memory_devices_found() {
+        let smbios = SMBIOS.lock();
+        let smbios = smbios.as_ref().unwrap();
+
+        assert!(smbios.system.is_some());
+        assert!(smbios
+            .memory_devices
+            .iter()
+            .any(|device| device.size.is_some()));
+    }
+}
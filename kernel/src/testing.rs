@@ -0,0 +1,94 @@
+use core::panic::PanicInfo;
+
+use crabstd::mutex::Mutex;
+use kernel_shared::{memory::paging::active_table::ActivePageTable, serial_print, serial_println};
+use x86_64::port::Port;
+
+use crate::{backtrace, memory::KernelFrameAllocator, InitInfo};
+
+/// Port of QEMU's `isa-debug-exit` device, as configured by `test_runner.sh`
+const ISA_DEBUG_EXIT_PORT: u16 = 0xF4;
+
+/// Value written to `isa-debug-exit`, which QEMU turns into the exit code `(value << 1) | 1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+/// Exits QEMU with the given code
+pub fn exit_qemu(code: QemuExitCode) -> ! {
+    unsafe {
+        Port::<u32>::new(ISA_DEBUG_EXIT_PORT).write(code as u32);
+    }
+
+    // only reached if the test kernel wasn't run with `isa-debug-exit`
+    x86_64::hlt_loop()
+}
+
+/// A single test, which prints its name and result over serial
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        serial_print!("test {} ... ", core::any::type_name::<T>());
+        self();
+        serial_println!("ok");
+    }
+}
+
+/// Runs every `#[test_case]`, exiting QEMU once they all pass
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("running {} tests", tests.len());
+
+    for test in tests {
+        test.run();
+    }
+
+    serial_println!("test result: ok. {} passed", tests.len());
+    exit_qemu(QemuExitCode::Success);
+}
+
+/// Reports the failing test and exits QEMU, since a panic means the current test failed
+pub fn panic_handler(info: &PanicInfo) -> ! {
+    serial_println!("FAILED\n");
+    serial_println!("{}", info);
+    backtrace::print();
+
+    exit_qemu(QemuExitCode::Failed)
+}
+
+/// Memory management state, shared with tests that need to map pages or allocate frames
+struct TestContext {
+    active_table: ActivePageTable,
+    frame_alloc: KernelFrameAllocator,
+}
+
+// the test kernel only ever runs on a single core
+unsafe impl Send for TestContext {}
+
+static CONTEXT: Mutex<Option<TestContext>> = Mutex::new(None);
+
+/// Runs the tests with the state returned by [crate::init]
+pub fn run(init_info: InitInfo, test_main: fn()) -> ! {
+    *CONTEXT.lock() = Some(TestContext {
+        active_table: init_info.active_table,
+        frame_alloc: init_info.frame_alloc,
+    });
+
+    test_main();
+
+    // the test runner always exits, but the harness doesn't know that
+    exit_qemu(QemuExitCode::Success)
+}
+
+/// Calls `f` with the active page table and frame allocator
+pub fn with_memory<R>(f: impl FnOnce(&mut ActivePageTable, &mut KernelFrameAllocator) -> R) -> R {
+    let mut context = CONTEXT.lock();
+    let context = context.as_mut().expect("tests are not running");
+
+    f(&mut context.active_table, &mut context.frame_alloc)
+}
@@ -1,4 +1,5 @@
 global start:function
+global start_efi64:function
 global p4_table:data
 global p3_table:data
 global p3_table_phys:data
@@ -53,4 +54,49 @@ start:
 	pop ebx
 	lgdt [gdt64.pointer]
 	jmp gdt64.code:long_mode_start
-.end:
\ No newline at end of file
+.end:
+
+section .text.efi_entry
+bits 64
+; entrypoint used by EFI bootloaders, which jump here in long mode with boot services still running
+; and the firmware's identity mapped page tables. layout.ld keeps this at the start of .text, since
+; the multiboot header needs to know its address.
+start_efi64:
+	cmp eax, 0x36d76289
+	jne .hang
+
+	; only ebx is defined to hold the boot information address, so clear the top half
+	mov ebx, ebx
+	mov rsp, stack_top
+
+	; boot services need the firmware's page tables, so exit them before switching to our own
+	extern exit_boot_services
+	mov rdi, rbx
+	call exit_boot_services
+	cli
+
+	extern set_up_page_tables_64
+	extern enable_paging_64
+
+	call set_up_page_tables_64
+	call enable_paging_64
+
+	; the firmware GDT lives in boot services memory too, and there is no far jump to an immediate
+	; in long mode, so reload the code segment with a far return
+	lgdt [gdt64.pointer]
+	push gdt64.code
+	lea rax, [rel .reload_segments]
+	push rax
+	retfq
+.reload_segments:
+	xor eax, eax
+	mov ss, ax
+	mov ds, ax
+	mov es, ax
+	mov fs, ax
+	mov gs, ax
+
+	jmp long_mode_start
+.hang:
+	hlt
+	jmp .hang
@@ -1,5 +1,7 @@
 global set_up_page_tables:function
 global enable_paging:function
+global set_up_page_tables_64:function
+global enable_paging_64:function
 
 extern p4_table
 extern p3_table
@@ -52,3 +54,47 @@ enable_paging:
 	mov cr0, eax
 
 	ret
+
+bits 64
+; same as set_up_page_tables, but for when we're already in long mode.
+; EFI firmware can load modules anywhere, so identity map the first 4GiB instead of just 1GiB
+set_up_page_tables_64:
+	mov rax, p3_table
+	or rax, 0b11
+	mov [p4_table], rax
+
+	mov rax, p3_table_phys
+	or rax, 0b11
+	mov [p4_table + 256 * 8], rax
+
+	mov rax, 0x00000000 | 0b10000011
+	mov [p3_table + 0 * 8], rax
+	mov [p3_table_phys + 0 * 8], rax
+	mov rax, 0x40000000 | 0b10000011
+	mov [p3_table + 1 * 8], rax
+	mov [p3_table_phys + 1 * 8], rax
+	mov rax, 0x80000000 | 0b10000011
+	mov [p3_table + 2 * 8], rax
+	mov [p3_table_phys + 2 * 8], rax
+	mov rax, 0xC0000000 | 0b10000011
+	mov [p3_table + 3 * 8], rax
+	mov [p3_table_phys + 3 * 8], rax
+
+	ret
+
+; long mode and PAE are already enabled by the firmware, so just set NXE and write protect before
+; loading our own P4
+enable_paging_64:
+	mov ecx, 0xC0000080
+	rdmsr
+	or eax, 1 << 11
+	wrmsr
+
+	mov rax, cr0
+	or rax, 1 << 16
+	mov cr0, rax
+
+	mov rax, p4_table
+	mov cr3, rax
+
+	ret
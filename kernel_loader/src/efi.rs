@@ -0,0 +1,159 @@
+use core::ffi::c_void;
+
+use multiboot::{prelude::*, EfiMemoryDescriptor};
+
+/// Largest number of memory regions that can be passed on to the kernel
+const MAX_MEMORY_REGIONS: usize = 256;
+
+/// `EFI_BUFFER_TOO_SMALL`, with the high bit set to mark it as an error
+const BUFFER_TOO_SMALL: usize = (1 << 63) | 5;
+
+/// Header at the start of every EFI table
+#[repr(C)]
+struct TableHeader {
+    signature: u64,
+    revision: u32,
+    header_size: u32,
+    crc32: u32,
+    _reserved: u32,
+}
+
+/// The EFI system table, which gives access to every other service
+///
+/// https://uefi.org/specs/UEFI/2.10/04_EFI_System_Table.html#efi-system-table-1
+#[repr(C)]
+struct SystemTable {
+    header: TableHeader,
+    firmware_vendor: *const u16,
+    firmware_revision: u32,
+    console_in_handle: *const c_void,
+    console_in: *const c_void,
+    console_out_handle: *const c_void,
+    console_out: *const c_void,
+    standard_error_handle: *const c_void,
+    standard_error: *const c_void,
+    runtime_services: *const c_void,
+    boot_services: *const BootServices,
+    table_entries: usize,
+    configuration_table: *const c_void,
+}
+
+/// The EFI boot services table, only filled in up to the services the loader uses
+///
+/// https://uefi.org/specs/UEFI/2.10/04_EFI_System_Table.html#efi-boot-services-table
+#[repr(C)]
+struct BootServices {
+    header: TableHeader,
+    /// `RaiseTPL()` to `FreePages()`
+    _unused_before_memory_map: [usize; 4],
+    get_memory_map: extern "efiapi" fn(
+        memory_map_size: *mut usize,
+        memory_map: *mut u8,
+        map_key: *mut usize,
+        descriptor_size: *mut usize,
+        descriptor_version: *mut u32,
+    ) -> usize,
+    /// `AllocatePool()` to `UnloadImage()`
+    _unused_before_exit: [usize; 21],
+    exit_boot_services: extern "efiapi" fn(image_handle: *const c_void, map_key: usize) -> usize,
+}
+
+/// Buffer that `GetMemoryMap()` writes into
+static mut DESCRIPTORS: [u64; 2048] = [0; 2048];
+
+/// Memory regions converted from the EFI memory map, and how many are in use
+static mut MEMORY_REGIONS: ([MemoryMapEntry; MAX_MEMORY_REGIONS], usize) = (
+    [MemoryMapEntry::new(0, 0, MemoryType::RESERVED); MAX_MEMORY_REGIONS],
+    0,
+);
+
+/// Exits EFI boot services if the bootloader left them running, saving the final memory map.
+/// Called from the 64-bit EFI entrypoint while still on the firmware's page tables.
+#[no_mangle]
+extern "C" fn exit_boot_services(addr: *const u32) {
+    let bootinfo = unsafe { BootInfo::new(addr) };
+
+    if !bootinfo.efi_boot_services_not_terminated {
+        return;
+    }
+
+    let system_table = bootinfo
+        .efi_system_table_64
+        .expect("no 64-bit EFI system table") as *const SystemTable;
+    let image_handle = bootinfo
+        .efi_image_handle_64
+        .expect("no 64-bit EFI image handle") as *const c_void;
+
+    let boot_services = unsafe { &*(*system_table).boot_services };
+
+    // the memory map can change between getting it and exiting, which invalidates the map key,
+    // so keep trying until the key is accepted
+    loop {
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(DESCRIPTORS) };
+
+        let mut size = core::mem::size_of_val(buffer);
+        let mut map_key = 0;
+        let mut descriptor_size = 0;
+        let mut descriptor_version = 0;
+
+        let status = (boot_services.get_memory_map)(
+            &mut size,
+            buffer.as_mut_ptr().cast(),
+            &mut map_key,
+            &mut descriptor_size,
+            &mut descriptor_version,
+        );
+        assert!(
+            status != BUFFER_TOO_SMALL,
+            "EFI memory map is larger than {:#X} bytes",
+            core::mem::size_of_val(buffer)
+        );
+        assert!(status == 0, "failed to get EFI memory map: {status:#X}");
+
+        if (boot_services.exit_boot_services)(image_handle, map_key) == 0 {
+            let bytes: &[u8] = unsafe { core::slice::from_raw_parts(buffer.as_ptr().cast(), size) };
+            save_memory_map(
+                bytes
+                    .chunks_exact(descriptor_size)
+                    .map(|descriptor| unsafe {
+                        descriptor
+                            .as_ptr()
+                            .cast::<EfiMemoryDescriptor>()
+                            .read_unaligned()
+                    }),
+            );
+
+            return;
+        }
+    }
+}
+
+/// Converts the descriptors into memory regions, merging neighbours of the same type
+fn save_memory_map(descriptors: impl Iterator<Item = EfiMemoryDescriptor>) {
+    let (regions, count) = unsafe { &mut *core::ptr::addr_of_mut!(MEMORY_REGIONS) };
+    *count = 0;
+
+    for entry in descriptors.map(MemoryMapEntry::from) {
+        if let Some(last) = count.checked_sub(1).map(|last| &mut regions[last]) {
+            if last.mem_type == entry.mem_type && last.base_addr + last.length == entry.base_addr {
+                last.length += entry.length;
+                continue;
+            }
+        }
+
+        assert!(*count < MAX_MEMORY_REGIONS, "too many EFI memory regions");
+        regions[*count] = entry;
+        *count += 1;
+    }
+}
+
+/// Returns the memory regions saved when exiting boot services, or converted from the EFI memory
+/// map tag if the bootloader exited them itself
+pub fn memory_map(bootinfo: &BootInfo) -> Option<&'static [MemoryMapEntry]> {
+    if let Some(efi_memory_map) = bootinfo.efi_memory_map {
+        save_memory_map(efi_memory_map.descriptors());
+    }
+
+    let (regions, count) = unsafe { &*core::ptr::addr_of!(MEMORY_REGIONS) };
+    (*count > 0).then(|| &regions[..*count])
+}
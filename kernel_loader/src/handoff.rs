@@ -0,0 +1,168 @@
+//! Builds the [Handoff] passed to the kernel, copying everything it needs out of the multiboot
+//! information into loader memory
+
+use kernel_shared::handoff::{
+    FrameAllocatorState, Framebuffer, Handoff, KernelImage, Module, PhysRange, PhysSlice, Rsdp,
+    Smbios,
+};
+use multiboot::prelude::*;
+
+/// Largest number of memory regions that can be passed on to the kernel
+const MAX_MEMORY_REGIONS: usize = 256;
+
+/// Largest number of modules that can be passed on to the kernel
+const MAX_MODULES: usize = 16;
+
+/// Longest command line that can be passed on to the kernel
+const MAX_COMMAND_LINE: usize = 4096;
+
+static mut HANDOFF: Handoff = Handoff::new();
+
+static mut MEMORY_MAP: [MemoryMapEntry; MAX_MEMORY_REGIONS] =
+    [MemoryMapEntry::new(0, 0, MemoryType::RESERVED); MAX_MEMORY_REGIONS];
+static mut MODULES: [Module; MAX_MODULES] = [Module::EMPTY; MAX_MODULES];
+static mut COMMAND_LINE: [u8; MAX_COMMAND_LINE] = [0; MAX_COMMAND_LINE];
+
+fn handoff() -> &'static mut Handoff {
+    unsafe { &mut *core::ptr::addr_of_mut!(HANDOFF) }
+}
+
+/// Copies the memory map into loader memory, returning the copy so the frame allocator can use it
+pub fn save_memory_map(memory_map: &[MemoryMapEntry]) -> &'static [MemoryMapEntry] {
+    assert!(
+        memory_map.len() <= MAX_MEMORY_REGIONS,
+        "too many memory regions to pass to kernel"
+    );
+
+    let saved = unsafe { &mut *core::ptr::addr_of_mut!(MEMORY_MAP) };
+    let saved = &mut saved[..memory_map.len()];
+    saved.copy_from_slice(memory_map);
+
+    handoff().memory_map = PhysSlice::from_identity_mapped(saved);
+    saved
+}
+
+/// Copies the modules, command line, framebuffer, RSDP and SMBIOS entry point out of the multiboot
+/// information, which the kernel never sees
+pub fn save_bootinfo(bootinfo: &BootInfo, cmdline: &str) {
+    let handoff = handoff();
+
+    let modules = unsafe { &mut *core::ptr::addr_of_mut!(MODULES) };
+    let mut count = 0;
+    for module in bootinfo.modules() {
+        if count == MAX_MODULES {
+            log::warn!("too many modules, ignoring {:?}", module.string);
+            continue;
+        }
+
+        modules[count] = Module::new(
+            PhysRange::new(module.start as usize, module.end as usize),
+            module.string.to_str().unwrap_or(""),
+        );
+        count += 1;
+    }
+    handoff.modules = PhysSlice::from_identity_mapped(&modules[..count]);
+
+    let mut len = cmdline.len().min(MAX_COMMAND_LINE);
+    while !cmdline.is_char_boundary(len) {
+        len -= 1;
+    }
+    if len < cmdline.len() {
+        log::warn!("command line truncated to {len} bytes");
+    }
+    let command_line = unsafe { &mut *core::ptr::addr_of_mut!(COMMAND_LINE) };
+    let command_line = &mut command_line[..len];
+    command_line.copy_from_slice(&cmdline.as_bytes()[..len]);
+    handoff.command_line = PhysSlice::from_identity_mapped(command_line);
+
+    let framebuffer_info = bootinfo.framebuffer_info.expect("no framebuffer info");
+    let mut framebuffer = Framebuffer {
+        phys_addr: framebuffer_info.buffer_addr,
+        // mapped by `map_framebuffer`
+        virt_addr: 0xFFFFFFFF40000000,
+        pitch: framebuffer_info.pitch,
+        width: framebuffer_info.width,
+        height: framebuffer_info.height,
+        bpp: framebuffer_info.bpp,
+        buffer_type: framebuffer_info.buffer_type,
+        ..Framebuffer::NONE
+    };
+    if let FramebufferColour::Direct {
+        red_field_position,
+        red_mask_size,
+        green_field_position,
+        green_mask_size,
+        blue_field_position,
+        blue_mask_size,
+    } = framebuffer_info.colour
+    {
+        framebuffer.red_field_position = red_field_position;
+        framebuffer.red_mask_size = red_mask_size;
+        framebuffer.green_field_position = green_field_position;
+        framebuffer.green_mask_size = green_mask_size;
+        framebuffer.blue_field_position = blue_field_position;
+        framebuffer.blue_mask_size = blue_mask_size;
+    }
+    handoff.framebuffer = framebuffer;
+
+    handoff.rsdp = rsdp(bootinfo);
+
+    if let Some(entry_point) = bootinfo.smbios.and_then(|smbios| smbios.entry_point()) {
+        handoff.smbios = Smbios {
+            table_address: entry_point.table_address,
+            table_length: entry_point.table_length,
+            major: entry_point.major,
+            minor: entry_point.minor,
+        };
+    }
+
+    handoff.multiboot = PhysRange::new(bootinfo.addr, bootinfo.addr + bootinfo.total_size);
+}
+
+/// Finds the root ACPI table from the RSDP passed by multiboot, preferring the ACPI 2.0+ pointer
+/// since it gives the 64-bit XSDT
+fn rsdp(bootinfo: &BootInfo) -> Rsdp {
+    let mut rsdp = Rsdp {
+        root_address: 0,
+        revision: 0,
+        entry_size: 0,
+    };
+
+    if let Some(xsdp) = bootinfo.acpi_new {
+        if xsdp.is_valid() {
+            rsdp.root_address = xsdp.xsdt_address;
+            rsdp.revision = xsdp.rsdp.revision;
+            rsdp.entry_size = 8;
+        } else {
+            log::warn!("ACPI 2.0+ RSDP has invalid checksum");
+        }
+    } else if let Some(old) = bootinfo.acpi_old {
+        if old.is_valid() {
+            rsdp.root_address = old.rsdt_address as u64;
+            rsdp.revision = old.revision;
+            rsdp.entry_size = 4;
+        } else {
+            log::warn!("ACPI 1.0 RSDP has invalid checksum");
+        }
+    } else {
+        log::warn!("no RSDP passed by bootloader");
+    }
+
+    rsdp
+}
+
+/// Fills in the rest of the handoff, returning its physical address
+pub fn finish(
+    frame_allocator: FrameAllocatorState,
+    kernel: KernelImage,
+    loader: PhysRange,
+) -> usize {
+    let handoff = handoff();
+
+    handoff.frame_allocator = frame_allocator;
+    handoff.kernel = kernel;
+    handoff.loader = loader;
+
+    // loader is identity mapped, so this is also the physical address
+    handoff as *const Handoff as usize
+}
@@ -1,28 +1,29 @@
 #![no_std]
 #![feature(const_mut_refs, const_trait_impl, effects)]
 
-mod elf;
-
-use core::{arch::asm, ffi::CStr, ops::DerefMut, panic::PanicInfo};
+use core::{arch::asm, ops::DerefMut, panic::PanicInfo};
 
 use kernel_shared::{
+    cmdline::{command_line, CommandLine},
+    elf::{ElfFile, R_X86_64_NONE, R_X86_64_RELATIVE},
+    handoff::{FrameAllocatorState, KernelImage, PhysRange},
     logger::Logger,
     memory::{
         frame_alloc::{bitmap::BitmapFrameAllocator, FrameAllocator},
         paging::{
             active_table::ActivePageTable, entry::EntryFlags, inactive_table::InactivePageTable,
-            mapper::Mapper,
+            mapper::Mapper, PHYS_MEM_OFFSET,
         },
+        stack::{map_stack, KERNEL_STACK_SLOT},
     },
 };
-use multiboot::{elf_symbols::SectionFlags, prelude::*};
+use multiboot::prelude::*;
 use x86_64::{
     align_down_to_page, align_up_to_page,
-    structures::{Frame, Page, PAGE_SIZE},
+    random::random,
+    structures::{Frame, Page, HUGE_L2_PAGE_SIZE, PAGE_SIZE},
 };
 
-use crate::elf::ElfFile;
-
 /// Simple dummy allocator to stop clippy complaining
 /// was easier to do this than figure out why clippy wanted a global alloc for each crate
 struct DummyAlloc;
@@ -37,6 +38,15 @@ unsafe impl core::alloc::GlobalAlloc for DummyAlloc {
     }
 }
 
+mod efi;
+mod handoff;
+
+/// Physical address of `start_efi64`, which `layout.ld` places at the start of `.text`
+const EFI_ENTRY_ADDRESS: u32 = 0x201000;
+
+/// Number of 2MiB aligned addresses the kernel can be loaded at, giving up to 1GiB of slide
+const KASLR_SLOTS: u64 = 512;
+
 #[global_allocator]
 static DUMMY_ALLOC: DummyAlloc = DummyAlloc;
 static LOGGER: Logger = Logger::new(log::LevelFilter::Trace);
@@ -53,7 +63,14 @@ extern "C" fn loader_main(addr: *const u32) {
     log::trace!("jumped to loader_main!");
 
     // find info about kernel loader, and kernel/initrd modules
-    let bootinfo = unsafe { BootInfo::<4>::new(addr) };
+    let bootinfo = unsafe { BootInfo::new(addr) };
+
+    let cmdline = command_line(&bootinfo);
+    let options = CommandLine::parse(cmdline);
+    LOGGER.set_level(options.log_level);
+    log::trace!("boot command line: `{cmdline}`");
+
+    handoff::save_bootinfo(&bootinfo, cmdline);
 
     let kernel = bootinfo.get_module(c"kernel").expect("no kernel module!");
     let initrd = bootinfo.get_module(c"initrd").expect("no initrd module!");
@@ -63,45 +80,50 @@ extern "C" fn loader_main(addr: *const u32) {
     let (kernel_start, kernel_end) = (kernel.start as usize, kernel.end as usize);
     let (initrd_start, initrd_end) = (initrd.start as usize, initrd.end as usize);
 
+    // prefer the EFI memory map when booted through EFI, since the multiboot one may be missing
+    let memory_map = match efi::memory_map(&bootinfo) {
+        Some(memory_map) => {
+            log::trace!("using EFI memory map with {} regions", memory_map.len());
+            memory_map
+        }
+        None => bootinfo.memory_map.expect("no memory map").entries,
+    };
+    // the kernel can't read multiboot memory, so it gets its own copy
+    let memory_map = handoff::save_memory_map(memory_map);
+
+    // any other modules need to be kept around for the kernel too
+    let modules_end = bootinfo
+        .modules()
+        .map(|module| module.end as usize)
+        .max()
+        .unwrap_or(0);
+
     // find first free phys address and initialise frame allocator
-    let first_free_addr =
-        align_up_to_page(bootinfo_end.max(loader_end).max(kernel_end).max(initrd_end));
+    let first_free_addr = align_up_to_page(bootinfo_end.max(loader_end).max(modules_end));
 
     let (mut frame_alloc, (alloc_start, alloc_end)) =
-        BitmapFrameAllocator::new(first_free_addr, bootinfo.memory_map.unwrap().entries);
+        BitmapFrameAllocator::new(first_free_addr, memory_map);
+    let bitmap = PhysRange::new(
+        alloc_start.start_address(),
+        alloc_end.start_address() + PAGE_SIZE,
+    );
 
     log::trace!("initialised frame allocator");
 
     // dont overwrite any existing data
     frame_alloc.set_ignored_area(bootinfo_start, bootinfo_end);
     frame_alloc.set_ignored_area(loader_start, loader_end);
-    frame_alloc.set_ignored_area(kernel_start, kernel_end);
-    frame_alloc.set_ignored_area(initrd_start, initrd_end);
+    for module in bootinfo.modules() {
+        frame_alloc.set_ignored_area(module.start as usize, module.end as usize);
+    }
 
     let table_frame = frame_alloc
         .allocate_frame()
         .expect("failed to allocate a frame for level 4 table");
     let mut table = unsafe { InactivePageTable::new(table_frame) };
 
-    // make sure to map loader, initrd and bootinfo
-    let start_page = Page::containing_address(0);
-    let end_page = Page::containing_address(bootinfo_end - bootinfo_start);
-
-    let start_frame = Frame::containing_address(bootinfo_start);
-    let end_frame = Frame::containing_address(bootinfo_end);
-
-    log::trace!(
-        "mapping bootinfo at {:#X}-{:#X}",
-        start_page.start_address(),
-        end_page.start_address()
-    );
-
-    for (page, frame) in Page::range_inclusive(start_page, end_page)
-        .zip(Frame::range_inclusive(start_frame, end_frame))
-    {
-        table.map_to(page, frame, EntryFlags::WRITABLE, &mut frame_alloc);
-    }
-
+    // make sure to map loader and initrd, bootinfo isn't needed since everything the kernel uses
+    // from it is copied into the handoff
     identity_map(
         "initrd",
         &mut frame_alloc,
@@ -131,75 +153,45 @@ extern "C" fn loader_main(addr: *const u32) {
     let elf_data = unsafe {
         core::slice::from_raw_parts(kernel_start as *const u8, kernel_end - kernel_start)
     };
-    let kernel_elf = ElfFile::new(elf_data).expect("kernel was not a valid ELF file.");
-    let string_header = kernel_elf.string_header();
-
-    for kernel_section in kernel_elf.section_headers() {
-        // only map sections that need allocating
-        if !kernel_section.flags.contains(SectionFlags::ALLOC) {
-            continue;
-        }
-
-        // ensure section is aligned to page
-        assert!(
-            kernel_section.addr as usize % PAGE_SIZE == 0,
-            "sections need to be page aligned, addr {:#X}",
-            kernel_section.addr
-        );
-
-        let flags = EntryFlags::from_elf_section_flags(kernel_section);
-
-        let start_phys = kernel_section.offset as usize + kernel_start;
-        let end_phys = (kernel_section.offset + kernel_section.size - 1) as usize + kernel_start;
-
-        let start_virt = kernel_section.addr as usize;
-        let end_virt = (kernel_section.addr + kernel_section.size - 1) as usize;
-
-        let location = (string_header.offset + kernel_start as u64) as *const i8;
-        let name = unsafe { CStr::from_ptr(location.add(kernel_section.name as usize)) };
-
-        log::trace!(
-            "mapping kernel section {:?} at {:#X}-{:#X} with flags `{}`",
-            name,
-            align_down_to_page(start_virt),
-            align_down_to_page(end_virt),
-            flags
-        );
-
-        // if SHT_NOBITS, make sure to zero
-        if kernel_section.section_type == 8 {
-            unsafe {
-                core::slice::from_raw_parts_mut(
-                    align_down_to_page(start_phys) as *mut u8,
-                    kernel_section.size as usize,
-                )
-                .fill(0);
-            }
-        }
+    let kernel_elf = match ElfFile::new_executable(elf_data) {
+        Ok(kernel_elf) => kernel_elf,
+        Err(err) => panic!("kernel is not a loadable ELF file: {err}"),
+    };
 
-        table.map_range(
-            (start_phys, end_phys),
-            (start_virt, end_virt),
-            flags,
-            &mut frame_alloc,
-            true,
-        );
-    }
+    let slide = kernel_slide(&kernel_elf, options.kaslr);
+    map_kernel(&mut frame_alloc, &mut table, &kernel_elf, slide);
+    relocate_kernel(&table, &kernel_elf, slide);
 
     map_heap(&mut frame_alloc, &mut table, kernel_shared::HEAP_SIZE);
-    map_phys_memory(&mut frame_alloc, &mut table, &bootinfo.memory_map.unwrap());
+    map_phys_memory(&mut frame_alloc, &mut table, memory_map);
 
-    // set up stack at final 16MiB of kernel space
-    log::trace!("setting up stack at {:#X}", usize::MAX);
-    let start_page = Page::containing_address(usize::MAX - kernel_shared::STACK_SIZE + 1);
-    let end_page = Page::containing_address(usize::MAX);
+    // set up stack in its own slot, so overflowing it hits an unmapped guard page
+    let stack_top = map_stack(
+        &mut table,
+        &mut frame_alloc,
+        KERNEL_STACK_SLOT,
+        kernel_shared::STACK_SIZE,
+    );
+    log::trace!("set up stack at {stack_top:#X}");
 
-    for page in Page::range_inclusive(start_page, end_page) {
-        table.map(page, EntryFlags::WRITABLE, &mut frame_alloc);
-    }
+    let (kernel_virt_start, kernel_virt_end) = kernel_range(&kernel_elf);
+    let handoff_addr = handoff::finish(
+        FrameAllocatorState {
+            bitmap,
+            virt_addr: 0xFFFFFFFF00000000,
+        },
+        KernelImage {
+            virt_start: (kernel_virt_start + slide) as u64,
+            virt_end: (kernel_virt_end + slide) as u64,
+            slide: slide as u64,
+            elf: PhysRange::new(kernel_start, kernel_end),
+        },
+        PhysRange::new(loader_start, loader_end),
+    );
+    log::trace!("passing handoff at {handoff_addr:#X} to kernel");
 
     // finally switch to new table
-    let entrypoint = kernel_elf.entrypoint();
+    let entrypoint = kernel_elf.entrypoint() + slide;
 
     // just want to drop to make sure bootinfo can't be used from here onwards,
     // since it points to invalid data after changing page table
@@ -214,12 +206,14 @@ extern "C" fn loader_main(addr: *const u32) {
     log::trace!("jumping to kernel at {entrypoint:#X}");
     unsafe {
         asm!(
-            "mov rsp, 0xFFFFFFFFFFFFFFFF",
+            "mov rsp, {}",
+            // clear frame pointer so kernel backtraces stop at the entrypoint
+            "xor ebp, ebp",
             "jmp {}",
+            // leave the stack aligned as if the entrypoint had been called
+            in(reg) stack_top - 8,
             in(reg) entrypoint,
-            in("rdi") addr as usize - align_down_to_page(addr as usize),
-            in("rsi") loader_start,
-            in("rdx") loader_end
+            in("rdi") handoff_addr
         )
     }
 }
@@ -246,6 +240,160 @@ fn identity_map<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
     }
 }
 
+/// Copies each loadable segment of the kernel into new frames and maps them with the segment's
+/// permissions, leaving any memory past the end of the segment's file data zeroed
+fn map_kernel<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
+    alloc: &mut A,
+    table: &mut T,
+    kernel_elf: &ElfFile,
+    slide: usize,
+) {
+    for segment in kernel_elf.load_segments() {
+        if segment.mem_size == 0 {
+            continue;
+        }
+
+        assert!(
+            segment.file_size <= segment.mem_size,
+            "kernel segment at {:#X} has more file data than memory",
+            segment.virt_addr
+        );
+
+        let data = kernel_elf
+            .segment_data(segment)
+            .expect("kernel segment lies outside of file");
+        let flags = EntryFlags::from_elf_segment_flags(segment);
+
+        let start_virt = segment.virt_addr as usize + slide;
+        let end_virt = start_virt + segment.mem_size as usize - 1;
+
+        log::trace!(
+            "mapping kernel segment at {:#X}-{:#X} with flags `{}`",
+            start_virt,
+            end_virt,
+            flags
+        );
+
+        let start_page = Page::containing_address(start_virt);
+        let end_page = Page::containing_address(end_virt);
+
+        for page in Page::range_inclusive(start_page, end_page) {
+            let bytes = match table.translate_page(page) {
+                // segments that aren't page aligned can share a page, so it needs the permissions
+                // of both
+                Some(frame) => {
+                    table.update_flags(page, |old| {
+                        let no_execute = old & flags & EntryFlags::NO_EXECUTE;
+                        ((old | flags) - EntryFlags::NO_EXECUTE) | no_execute
+                    });
+                    frame_bytes(&frame)
+                }
+                None => {
+                    let frame = alloc.allocate_frame().expect("out of memory");
+                    let bytes = frame_bytes(&frame);
+                    bytes.fill(0);
+
+                    table.map_to(page, frame, flags, alloc);
+                    bytes
+                }
+            };
+
+            // copy whatever part of the file data lies in this page
+            let copy_start = page.start_address().max(start_virt);
+            let copy_end = (page.start_address() + PAGE_SIZE).min(start_virt + data.len());
+
+            if copy_start < copy_end {
+                let offset = copy_start - page.start_address();
+                bytes[offset..offset + copy_end - copy_start]
+                    .copy_from_slice(&data[copy_start - start_virt..copy_end - start_virt]);
+            }
+        }
+    }
+}
+
+/// Picks a random 2MiB aligned offset to load the kernel at, or 0 if KASLR is disabled or the
+/// kernel isn't position independent
+fn kernel_slide(kernel_elf: &ElfFile, kaslr: bool) -> usize {
+    if !kernel_elf.is_position_independent() {
+        log::warn!("kernel is not position independent, loading at link address");
+        return 0;
+    }
+
+    if !kaslr {
+        log::trace!("kaslr disabled, loading kernel at link address");
+        return 0;
+    }
+
+    let slide = (random() % KASLR_SLOTS) as usize * HUGE_L2_PAGE_SIZE;
+
+    let (_, kernel_end) = kernel_range(kernel_elf);
+    assert!(
+        kernel_end.checked_add(slide).is_some(),
+        "kernel slid by {slide:#X} would wrap past the end of memory"
+    );
+
+    log::trace!("loading kernel with slide {slide:#X}");
+
+    slide
+}
+
+/// Returns the virtual range covered by the kernel's loadable segments, before any slide
+fn kernel_range(kernel_elf: &ElfFile) -> (usize, usize) {
+    let start = kernel_elf
+        .load_segments()
+        .map(|segment| segment.virt_addr as usize)
+        .min()
+        .unwrap_or(0);
+    let end = kernel_elf
+        .load_segments()
+        .map(|segment| (segment.virt_addr + segment.mem_size) as usize)
+        .max()
+        .unwrap_or(0);
+
+    (start, end)
+}
+
+/// Applies the kernel's relocations for the given slide, writing through the frames the kernel was
+/// mapped to since the new table isn't active yet
+fn relocate_kernel<T: DerefMut<Target = Mapper>>(table: &T, kernel_elf: &ElfFile, slide: usize) {
+    let relocations = match kernel_elf.relocations() {
+        Ok(relocations) => relocations,
+        Err(err) => panic!("failed to read kernel relocations: {err}"),
+    };
+
+    log::trace!("applying {} kernel relocations", relocations.len());
+
+    for relocation in relocations {
+        match relocation.relocation_type() {
+            R_X86_64_NONE => continue,
+            R_X86_64_RELATIVE => {
+                let value = (slide as u64).wrapping_add_signed(relocation.addend);
+                let addr = relocation.offset as usize + slide;
+
+                // relocations aren't guaranteed to be aligned, so could cross a page boundary
+                for (index, byte) in value.to_ne_bytes().into_iter().enumerate() {
+                    let phys = table
+                        .translate(addr + index)
+                        .expect("kernel relocation lies outside of kernel");
+
+                    unsafe { *((phys + PHYS_MEM_OFFSET) as *mut u8) = byte };
+                }
+            }
+            other => panic!("unsupported kernel relocation type {other}"),
+        }
+    }
+}
+
+/// Returns the contents of a frame, through the physical memory mapping set up during boot
+fn frame_bytes(frame: &Frame) -> &'static mut [u8] {
+    unsafe {
+        core::slice::from_raw_parts_mut(
+            (frame.start_address() + PHYS_MEM_OFFSET) as *mut u8,
+            PAGE_SIZE,
+        )
+    }
+}
+
 /// Maps frame allocator to 0xFFFFFFFF00000000
 fn map_frame_allocator<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
     alloc: &mut A,
@@ -300,14 +448,14 @@ fn map_heap<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
     }
 }
 
-/// Maps physical memory to 0xFFFF800000000000
+/// Maps physical memory to 0xFFFF800000000000, covering all RAM and every region of the memory map
+/// above it
 fn map_phys_memory<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
     alloc: &mut A,
     table: &mut T,
-    memory_map: &MemoryMap,
+    memory_map: &[MemoryMapEntry],
 ) {
     let highest_address = memory_map
-        .entries
         .iter()
         .filter(|entry| entry.mem_type == MemoryType::RAM)
         .map(|entry| entry.base_addr + entry.length)
@@ -321,21 +469,45 @@ fn map_phys_memory<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
         alloc,
         true,
     );
+
+    // firmware often puts ACPI tables in reserved regions past the end of RAM, so map those too,
+    // in address order so no page is mapped twice
+    let mut mapped_end = align_down_to_page(highest_address) + PAGE_SIZE;
+    while let Some(entry) = memory_map
+        .iter()
+        .filter(|entry| entry.length > 0 && (entry.base_addr + entry.length) as usize > mapped_end)
+        .min_by_key(|entry| entry.base_addr)
+    {
+        let start = align_down_to_page(entry.base_addr as usize).max(mapped_end);
+        let end = align_up_to_page((entry.base_addr + entry.length) as usize);
+
+        // anything past the end of the window can't be reached anyway
+        if start >= 0xFFFFC00000000000 - PHYS_MEM_OFFSET {
+            break;
+        }
+
+        table.map_range(
+            (start, end - 1),
+            (start + PHYS_MEM_OFFSET, 0xFFFFBFFFFFFFFFFF),
+            EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE,
+            alloc,
+            true,
+        );
+        mapped_end = end;
+    }
 }
 
 /// Finds where loader lies in physical memory
 fn loader_range(elf_symbols: &ElfSymbols) -> (usize, usize) {
     let loader_start = elf_symbols
-        .headers
-        .iter()
+        .headers()
         .filter(|header| header.is_loaded())
         .map(|header| header.addr)
         .min()
         .unwrap();
 
     let loader_end = elf_symbols
-        .headers
-        .iter()
+        .headers()
         .filter(|header| header.is_loaded())
         .map(|header| header.addr + header.size)
         .max()
@@ -357,5 +529,9 @@ multiboot_header! {
             depth: NoPreference
         },
         ModuleAlignment,
+        EfiBootServices,
+        EfiAmd64EntryAddress {
+            entry_addr: EFI_ENTRY_ADDRESS
+        },
     ]
 }
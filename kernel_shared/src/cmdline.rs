@@ -0,0 +1,240 @@
+use core::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+use log::LevelFilter;
+use multiboot::BootInfo;
+
+bitflags! {
+    /// Consoles that output can be written to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Consoles: u8 {
+        const SERIAL = 1 << 0;
+        const FRAMEBUFFER = 1 << 1;
+    }
+}
+
+impl FromStr for Consoles {
+    type Err = ();
+
+    /// Parses a comma separated list of consoles, such as `serial,fb`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').try_fold(Consoles::empty(), |consoles, name| {
+            let console = match name {
+                "serial" => Consoles::SERIAL,
+                "fb" => Consoles::FRAMEBUFFER,
+                _ => return Err(()),
+            };
+
+            Ok(consoles | console)
+        })
+    }
+}
+
+/// A single option given on the kernel command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootOption<'a> {
+    /// `log=<off|error|warn|info|debug|trace>`
+    LogLevel(LevelFilter),
+    /// `console=<serial|fb>[,...]`
+    Console(Consoles),
+    /// `init=<path>`
+    Init(&'a str),
+    /// `root=<device>`
+    Root(&'a str),
+    /// `kaslr=<on|off>`
+    Kaslr(bool),
+    /// `keymap=<name>`
+    Keymap(&'a str),
+}
+
+/// Error returned for an option that could not be understood
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandLineError<'a> {
+    UnknownOption(&'a str),
+    MissingValue(&'a str),
+    InvalidValue { option: &'a str, value: &'a str },
+}
+
+impl fmt::Display for CommandLineError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandLineError::UnknownOption(option) => write!(f, "unknown option `{option}`"),
+            CommandLineError::MissingValue(option) => write!(f, "option `{option}` needs a value"),
+            CommandLineError::InvalidValue { option, value } => {
+                write!(f, "invalid value `{value}` for option `{option}`")
+            }
+        }
+    }
+}
+
+/// Typed options parsed from the kernel command line, such as
+/// `log=debug console=serial,fb init=ramfs//init root=ramfs keymap=uk`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandLine<'a> {
+    pub log_level: LevelFilter,
+    pub consoles: Consoles,
+    pub init: Option<&'a str>,
+    pub root: Option<&'a str>,
+    pub kaslr: bool,
+    /// Name of the keyboard layout, which is only checked once the keyboard driver looks it up
+    pub keymap: Option<&'a str>,
+}
+
+impl Default for CommandLine<'_> {
+    fn default() -> Self {
+        Self {
+            log_level: LevelFilter::Trace,
+            consoles: Consoles::all(),
+            init: None,
+            root: None,
+            kaslr: true,
+            keymap: None,
+        }
+    }
+}
+
+impl<'a> CommandLine<'a> {
+    /// Parses a command line, skipping any invalid options.
+    /// Later options override earlier ones.
+    pub fn parse(cmdline: &'a str) -> Self {
+        options(cmdline)
+            .filter_map(Result::ok)
+            .fold(Self::default(), |mut cmdline, option| {
+                match option {
+                    BootOption::LogLevel(level) => cmdline.log_level = level,
+                    BootOption::Console(consoles) => cmdline.consoles = consoles,
+                    BootOption::Init(init) => cmdline.init = Some(init),
+                    BootOption::Root(root) => cmdline.root = Some(root),
+                    BootOption::Kaslr(kaslr) => cmdline.kaslr = kaslr,
+                    BootOption::Keymap(keymap) => cmdline.keymap = Some(keymap),
+                }
+
+                cmdline
+            })
+    }
+
+    /// Returns every option on the command line that could not be parsed
+    pub fn errors(cmdline: &'a str) -> impl Iterator<Item = CommandLineError<'a>> {
+        options(cmdline).filter_map(Result::err)
+    }
+}
+
+/// Returns the command line passed by the bootloader, or an empty string if there is none
+pub fn command_line(bootinfo: &BootInfo) -> &'static str {
+    bootinfo
+        .boot_command_line
+        .and_then(|cmdline| cmdline.to_str().ok())
+        .unwrap_or("")
+}
+
+/// Returns an iterator over each whitespace separated option on the command line
+pub fn options<'a>(
+    cmdline: &'a str,
+) -> impl Iterator<Item = Result<BootOption<'a>, CommandLineError<'a>>> {
+    cmdline.split_whitespace().map(parse_option)
+}
+
+/// Parses a single `key=value` option
+fn parse_option(option: &str) -> Result<BootOption<'_>, CommandLineError<'_>> {
+    let (key, value) = option.split_once('=').unwrap_or((option, ""));
+
+    if !matches!(
+        key,
+        "log" | "console" | "init" | "root" | "kaslr" | "keymap"
+    ) {
+        return Err(CommandLineError::UnknownOption(key));
+    }
+    if value.is_empty() {
+        return Err(CommandLineError::MissingValue(key));
+    }
+
+    let invalid = || CommandLineError::InvalidValue { option: key, value };
+
+    Ok(match key {
+        "log" => BootOption::LogLevel(value.parse().map_err(|_| invalid())?),
+        "console" => BootOption::Console(value.parse().map_err(|_| invalid())?),
+        "init" => BootOption::Init(value),
+        "keymap" => BootOption::Keymap(value),
+        "kaslr" => BootOption::Kaslr(match value {
+            "on" => true,
+            "off" => false,
+            _ => return Err(invalid()),
+        }),
+        _ => BootOption::Root(value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn empty_uses_defaults() {
+        assert_eq!(CommandLine::parse(""), CommandLine::default());
+        assert_eq!(CommandLine::parse("   "), CommandLine::default());
+    }
+
+    #[test]
+    fn all_options() {
+        let cmdline = CommandLine::parse(
+            "log=debug console=serial,fb init=ramfs//init root=ramfs kaslr=off keymap=uk",
+        );
+
+        assert_eq!(cmdline, CommandLine {
+            log_level: LevelFilter::Debug,
+            consoles: Consoles::SERIAL | Consoles::FRAMEBUFFER,
+            init: Some("ramfs//init"),
+            root: Some("ramfs"),
+            kaslr: false,
+            keymap: Some("uk"),
+        });
+    }
+
+    #[test]
+    fn single_console() {
+        assert_eq!(
+            CommandLine::parse("console=serial").consoles,
+            Consoles::SERIAL
+        );
+        assert_eq!(
+            CommandLine::parse("console=fb").consoles,
+            Consoles::FRAMEBUFFER
+        );
+    }
+
+    #[test]
+    fn later_options_override() {
+        assert_eq!(
+            CommandLine::parse("log=off log=warn").log_level,
+            LevelFilter::Warn
+        );
+    }
+
+    #[test]
+    fn invalid_options_are_skipped() {
+        let line = "quiet log=loud console=serial,vga init= root=ramfs kaslr=maybe";
+
+        assert_eq!(CommandLine::parse(line), CommandLine {
+            root: Some("ramfs"),
+            ..Default::default()
+        });
+        assert_eq!(CommandLine::errors(line).collect::<Vec<_>>(), [
+            CommandLineError::UnknownOption("quiet"),
+            CommandLineError::InvalidValue {
+                option: "log",
+                value: "loud"
+            },
+            CommandLineError::InvalidValue {
+                option: "console",
+                value: "serial,vga"
+            },
+            CommandLineError::MissingValue("init"),
+            CommandLineError::InvalidValue {
+                option: "kaslr",
+                value: "maybe"
+            },
+        ]);
+    }
+}
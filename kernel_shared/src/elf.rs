@@ -0,0 +1,586 @@
+use core::{ffi::CStr, fmt::Display};
+
+use bitflags::bitflags;
+use multiboot::elf_symbols::ElfSectionHeader;
+
+/// Section type of a symbol table
+const SHT_SYMTAB: u32 = 2;
+/// Symbol type of a function
+const STT_FUNC: u8 = 2;
+/// Segment type of a loadable segment
+const PT_LOAD: u32 = 1;
+/// Segment type of the dynamic linking table
+const PT_DYNAMIC: u32 = 2;
+
+/// Dynamic table tag marking the end of the table
+const DT_NULL: i64 = 0;
+/// Dynamic table tag giving the address of the relocation table
+const DT_RELA: i64 = 7;
+/// Dynamic table tag giving the size of the relocation table in bytes
+const DT_RELASZ: i64 = 8;
+/// Dynamic table tag giving the size of a relocation table entry
+const DT_RELAENT: i64 = 9;
+
+/// `ident` value for 64-bit files
+const ELFCLASS64: u8 = 2;
+/// `ident` value for little endian files
+const ELFDATA2LSB: u8 = 1;
+/// File type of an executable
+const ET_EXEC: u16 = 2;
+/// File type of a shared object, which position independent executables also use
+const ET_DYN: u16 = 3;
+/// Machine type of x86_64
+const EM_X86_64: u16 = 62;
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ElfHeader {
+    pub ident: [u8; 16],
+    pub file_type: u16,
+    pub machine_version: u16,
+    pub file_version: u32,
+    pub entrypoint: usize,
+    pub program_header_offset: usize,
+    pub section_header_offset: usize,
+    pub flags: u32,
+    pub header_size: u16,
+    pub program_header_size: u16,
+    pub program_header_entries: u16,
+    pub section_header_size: u16,
+    pub section_header_entries: u16,
+    pub string_table_index: u16,
+}
+
+/// A single entry in the program header table, describing a segment
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramHeader {
+    pub segment_type: u32,
+    pub flags: SegmentFlags,
+    pub offset: u64,
+    pub virt_addr: u64,
+    pub phys_addr: u64,
+    pub file_size: u64,
+    pub mem_size: u64,
+    pub align: u64,
+}
+
+bitflags! {
+    /// Permissions of a segment
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SegmentFlags: u32 {
+        const EXECUTABLE = 1;
+        const WRITE = 1 << 1;
+        const READ = 1 << 2;
+    }
+}
+
+impl ProgramHeader {
+    /// Checks if the segment should be loaded into memory
+    pub fn is_load(&self) -> bool {
+        self.segment_type == PT_LOAD
+    }
+}
+
+/// Reasons an ELF file can't be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    /// File is too small to contain a header
+    TooSmall,
+    /// File doesn't start with the ELF magic
+    InvalidMagic,
+    /// File isn't a little endian 64-bit file
+    UnsupportedClass { class: u8, data: u8 },
+    /// File isn't for x86_64
+    UnsupportedMachine(u16),
+    /// File isn't an executable
+    UnsupportedType(u16),
+    /// Program header table lies outside the file
+    InvalidProgramHeaders,
+    /// Dynamic table is malformed, or points to a relocation table outside the file
+    InvalidDynamicTable,
+}
+
+impl Display for ElfError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ElfError::TooSmall => write!(f, "file is too small to be ELF"),
+            ElfError::InvalidMagic => write!(f, "file does not start with ELF magic"),
+            ElfError::UnsupportedClass { class, data } => {
+                write!(f, "unsupported class {class} with data encoding {data}")
+            }
+            ElfError::UnsupportedMachine(machine) => write!(f, "unsupported machine {machine}"),
+            ElfError::UnsupportedType(file_type) => write!(f, "unsupported file type {file_type}"),
+            ElfError::InvalidProgramHeaders => write!(f, "program headers lie outside file"),
+            ElfError::InvalidDynamicTable => write!(f, "dynamic table is invalid"),
+        }
+    }
+}
+
+pub struct ElfFile {
+    data: &'static [u8],
+}
+
+impl ElfFile {
+    pub fn new(data: &'static [u8]) -> Option<Self> {
+        if data.get(0..4)? == b"\x7FELF" {
+            Some(Self { data })
+        } else {
+            None
+        }
+    }
+
+    /// Constructs an ELF file, checking it's an x86_64 executable (position independent or not)
+    /// that can be loaded
+    pub fn new_executable(data: &'static [u8]) -> Result<Self, ElfError> {
+        if data.len() < core::mem::size_of::<ElfHeader>() {
+            return Err(ElfError::TooSmall);
+        }
+
+        let elf = Self::new(data).ok_or(ElfError::InvalidMagic)?;
+        let header = elf.header();
+
+        let (class, encoding) = (header.ident[4], header.ident[5]);
+        if class != ELFCLASS64 || encoding != ELFDATA2LSB {
+            return Err(ElfError::UnsupportedClass {
+                class,
+                data: encoding,
+            });
+        }
+
+        if header.machine_version != EM_X86_64 {
+            return Err(ElfError::UnsupportedMachine(header.machine_version));
+        }
+
+        if header.file_type != ET_EXEC && header.file_type != ET_DYN {
+            return Err(ElfError::UnsupportedType(header.file_type));
+        }
+
+        // program headers are read straight out of the file, so make sure they fit
+        let table_size =
+            header.program_header_entries as usize * core::mem::size_of::<ProgramHeader>();
+        let table_end = header.program_header_offset.checked_add(table_size);
+        if (header.program_header_entries > 0
+            && header.program_header_size as usize != core::mem::size_of::<ProgramHeader>())
+            || !matches!(table_end, Some(end) if end <= data.len())
+        {
+            return Err(ElfError::InvalidProgramHeaders);
+        }
+
+        Ok(elf)
+    }
+
+    pub fn header(&self) -> &ElfHeader {
+        unsafe { &*(self.data.as_ptr() as *const ElfHeader) }
+    }
+
+    pub fn entrypoint(&self) -> usize {
+        self.header().entrypoint
+    }
+
+    pub fn section_headers(&self) -> &[ElfSectionHeader] {
+        let header = self.header();
+
+        unsafe {
+            core::slice::from_raw_parts(
+                self.data.as_ptr().add(header.section_header_offset) as *const _,
+                header.section_header_entries as usize,
+            )
+        }
+    }
+
+    pub fn program_headers(&self) -> &[ProgramHeader] {
+        let header = self.header();
+
+        unsafe {
+            core::slice::from_raw_parts(
+                self.data.as_ptr().add(header.program_header_offset) as *const _,
+                header.program_header_entries as usize,
+            )
+        }
+    }
+
+    /// Returns an iterator over every segment that should be loaded into memory
+    pub fn load_segments(&self) -> impl Iterator<Item = &ProgramHeader> {
+        self.program_headers()
+            .iter()
+            .filter(|segment| segment.is_load())
+    }
+
+    /// Returns the part of a segment stored in the file, or `None` if it lies outside the file
+    pub fn segment_data(&self, segment: &ProgramHeader) -> Option<&'static [u8]> {
+        let start = segment.offset as usize;
+        let end = start.checked_add(segment.file_size as usize)?;
+
+        self.data.get(start..end)
+    }
+
+    /// Checks if the file can be loaded at any address, after applying [Self::relocations]
+    pub fn is_position_independent(&self) -> bool {
+        self.header().file_type == ET_DYN
+    }
+
+    /// Converts a virtual address to an offset into the file, using the loadable segments
+    fn virt_to_offset(&self, addr: u64) -> Option<usize> {
+        let segment = self.load_segments().find(|segment| {
+            (segment.virt_addr..segment.virt_addr + segment.file_size).contains(&addr)
+        })?;
+
+        Some((addr - segment.virt_addr + segment.offset) as usize)
+    }
+
+    /// Returns the relocations found through the dynamic table, or an empty slice if there is none
+    pub fn relocations(&self) -> Result<&'static [Rela], ElfError> {
+        let Some(dynamic) = self
+            .program_headers()
+            .iter()
+            .find(|segment| segment.segment_type == PT_DYNAMIC)
+        else {
+            return Ok(&[]);
+        };
+
+        let data = self
+            .segment_data(dynamic)
+            .ok_or(ElfError::InvalidDynamicTable)?;
+        let entries = unsafe {
+            core::slice::from_raw_parts(
+                data.as_ptr() as *const Dynamic,
+                data.len() / core::mem::size_of::<Dynamic>(),
+            )
+        };
+
+        let (mut address, mut size, mut entry_size) = (None, 0, core::mem::size_of::<Rela>());
+        for entry in entries.iter().take_while(|entry| entry.tag != DT_NULL) {
+            match entry.tag {
+                DT_RELA => address = Some(entry.value),
+                DT_RELASZ => size = entry.value as usize,
+                DT_RELAENT => entry_size = entry.value as usize,
+                _ => continue,
+            }
+        }
+
+        let Some(address) = address else {
+            return Ok(&[]);
+        };
+
+        if entry_size != core::mem::size_of::<Rela>() {
+            return Err(ElfError::InvalidDynamicTable);
+        }
+
+        let start = self
+            .virt_to_offset(address)
+            .ok_or(ElfError::InvalidDynamicTable)?;
+        let table = start
+            .checked_add(size)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or(ElfError::InvalidDynamicTable)?;
+
+        Ok(unsafe {
+            core::slice::from_raw_parts(
+                table.as_ptr() as *const Rela,
+                table.len() / core::mem::size_of::<Rela>(),
+            )
+        })
+    }
+
+    pub fn string_header(&self) -> &ElfSectionHeader {
+        let header = self.header();
+        &self.section_headers()[header.string_table_index as usize]
+    }
+
+    /// Returns the contents of a section in the file, or `None` if it lies outside the file
+    pub fn section_data(&self, section: &ElfSectionHeader) -> Option<&'static [u8]> {
+        let start = section.offset as usize;
+        let end = start.checked_add(section.size as usize)?;
+
+        self.data.get(start..end)
+    }
+
+    /// Returns the symbol table and its linked string table, if the file wasn't stripped
+    pub fn symbol_table(&self) -> Option<SymbolTable> {
+        let headers = self.section_headers();
+        let symtab = headers
+            .iter()
+            .find(|header| header.section_type == SHT_SYMTAB)?;
+        let strtab = headers.get(symtab.link as usize)?;
+
+        let data = self.section_data(symtab)?;
+        let symbols = unsafe {
+            core::slice::from_raw_parts(
+                data.as_ptr() as *const ElfSymbol,
+                data.len() / core::mem::size_of::<ElfSymbol>(),
+            )
+        };
+
+        Some(SymbolTable {
+            symbols,
+            strings: self.section_data(strtab)?,
+        })
+    }
+}
+
+/// A single entry in the dynamic table
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Dynamic {
+    tag: i64,
+    value: u64,
+}
+
+/// Relocation type that does nothing
+pub const R_X86_64_NONE: u32 = 0;
+/// Relocation type that stores the load address plus the addend
+pub const R_X86_64_RELATIVE: u32 = 8;
+
+/// A single relocation with an addend
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Rela {
+    pub offset: u64,
+    pub info: u64,
+    pub addend: i64,
+}
+
+impl Rela {
+    /// Returns the type of relocation, such as [R_X86_64_RELATIVE]
+    pub fn relocation_type(&self) -> u32 {
+        self.info as u32
+    }
+}
+
+/// A single entry in the symbol table
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ElfSymbol {
+    pub name: u32,
+    pub info: u8,
+    pub other: u8,
+    pub section_index: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl ElfSymbol {
+    /// Checks if the symbol refers to a function
+    pub fn is_function(&self) -> bool {
+        self.info & 0xF == STT_FUNC
+    }
+}
+
+/// Symbols from an ELF file, used to find which function an address lies in
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolTable {
+    symbols: &'static [ElfSymbol],
+    strings: &'static [u8],
+}
+
+impl SymbolTable {
+    /// Finds the function containing an address, returning its (mangled) name and the offset of
+    /// the address into it
+    pub fn lookup(&self, addr: usize) -> Option<(&'static str, usize)> {
+        let addr = addr as u64;
+
+        let symbol = self.symbols.iter().find(|symbol| {
+            symbol.is_function() && (symbol.value..symbol.value + symbol.size).contains(&addr)
+        })?;
+
+        Some((self.name(symbol)?, (addr - symbol.value) as usize))
+    }
+
+    /// Returns the name of a symbol from the string table
+    fn name(&self, symbol: &ElfSymbol) -> Option<&'static str> {
+        let strings = self.strings.get(symbol.name as usize..)?;
+
+        CStr::from_bytes_until_nul(strings)
+            .ok()
+            .and_then(|name| name.to_str().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Builds an executable with a header followed by the given program headers
+    fn executable(machine: u16, segments: &[ProgramHeader]) -> &'static [u8] {
+        elf_file(ET_EXEC, machine, segments, &[])
+    }
+
+    /// Builds a file with a header, followed by the given program headers and then `trailer`
+    fn elf_file(
+        file_type: u16,
+        machine: u16,
+        segments: &[ProgramHeader],
+        trailer: &[u64],
+    ) -> &'static [u8] {
+        let header_size = core::mem::size_of::<ElfHeader>();
+        let header = ElfHeader {
+            ident: *b"\x7FELF\x02\x01\x01\0\0\0\0\0\0\0\0\0",
+            file_type,
+            machine_version: machine,
+            file_version: 1,
+            entrypoint: 0xFFFF_FFFF_8000_0000,
+            program_header_offset: header_size,
+            section_header_offset: 0,
+            flags: 0,
+            header_size: header_size as u16,
+            program_header_size: core::mem::size_of::<ProgramHeader>() as u16,
+            program_header_entries: segments.len() as u16,
+            section_header_size: 0,
+            section_header_entries: 0,
+            string_table_index: 0,
+        };
+
+        let mut data = Vec::new();
+        data.extend_from_slice(unsafe {
+            core::slice::from_raw_parts(&header as *const _ as *const u8, header_size)
+        });
+        for segment in segments {
+            data.extend_from_slice(unsafe {
+                core::slice::from_raw_parts(
+                    segment as *const _ as *const u8,
+                    core::mem::size_of::<ProgramHeader>(),
+                )
+            });
+        }
+
+        for word in trailer {
+            data.extend_from_slice(&word.to_ne_bytes());
+        }
+
+        // keep the data aligned for the header and program header casts
+        let words: Vec<u64> = data
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u64::from_ne_bytes(word)
+            })
+            .collect();
+        let words = words.leak();
+
+        unsafe { core::slice::from_raw_parts(words.as_ptr().cast(), data.len()) }
+    }
+
+    fn segment(segment_type: u32, flags: SegmentFlags) -> ProgramHeader {
+        ProgramHeader {
+            segment_type,
+            flags,
+            offset: 0,
+            virt_addr: 0xFFFF_FFFF_8000_0000,
+            phys_addr: 0,
+            file_size: 0x40,
+            mem_size: 0x2000,
+            align: 0x1000,
+        }
+    }
+
+    #[test]
+    fn executable_is_accepted() {
+        let data = executable(EM_X86_64, &[]);
+        let elf = ElfFile::new_executable(data).unwrap();
+
+        assert_eq!(elf.entrypoint(), 0xFFFF_FFFF_8000_0000);
+        assert!(elf.program_headers().is_empty());
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert_eq!(
+            ElfFile::new_executable(&[0x7F, b'E']).err(),
+            Some(ElfError::TooSmall)
+        );
+        assert_eq!(
+            ElfFile::new_executable(&[0; 64]).err(),
+            Some(ElfError::InvalidMagic)
+        );
+        assert_eq!(
+            ElfFile::new_executable(executable(3, &[])).err(),
+            Some(ElfError::UnsupportedMachine(3))
+        );
+
+        // truncate the file so the program header table no longer fits
+        let data = executable(EM_X86_64, &[segment(PT_LOAD, SegmentFlags::READ)]);
+        assert_eq!(
+            ElfFile::new_executable(&data[..data.len() - 1]).err(),
+            Some(ElfError::InvalidProgramHeaders)
+        );
+    }
+
+    #[test]
+    fn only_load_segments_are_returned() {
+        let data = executable(EM_X86_64, &[
+            segment(PT_LOAD, SegmentFlags::READ | SegmentFlags::EXECUTABLE),
+            segment(4, SegmentFlags::READ),
+            segment(PT_LOAD, SegmentFlags::READ | SegmentFlags::WRITE),
+        ]);
+        let elf = ElfFile::new_executable(data).unwrap();
+
+        let flags: Vec<_> = elf.load_segments().map(|segment| segment.flags).collect();
+        assert_eq!(flags, [
+            SegmentFlags::READ | SegmentFlags::EXECUTABLE,
+            SegmentFlags::READ | SegmentFlags::WRITE
+        ]);
+        assert_eq!(
+            elf.segment_data(&elf.program_headers()[0]).unwrap().len(),
+            0x40
+        );
+    }
+
+    #[test]
+    fn relocations_are_found_through_dynamic_table() {
+        const BASE: u64 = 0xFFFF_FFFF_8000_0000;
+
+        // header and two program headers come first, then the dynamic table and relocations
+        let dynamic_offset = 64 + 2 * 56;
+        let rela_offset = dynamic_offset + 4 * 16;
+        let file_size = rela_offset + 2 * 24;
+
+        let load = ProgramHeader {
+            offset: 0,
+            virt_addr: BASE,
+            file_size,
+            mem_size: file_size,
+            ..segment(PT_LOAD, SegmentFlags::READ)
+        };
+        let dynamic = ProgramHeader {
+            offset: dynamic_offset,
+            virt_addr: BASE + dynamic_offset,
+            file_size: 4 * 16,
+            mem_size: 4 * 16,
+            ..segment(PT_DYNAMIC, SegmentFlags::READ)
+        };
+
+        #[rustfmt::skip]
+        let trailer = [
+            DT_RELA as u64, BASE + rela_offset,
+            DT_RELASZ as u64, 2 * 24,
+            DT_RELAENT as u64, 24,
+            DT_NULL as u64, 0,
+            // relative relocation, then one that does nothing
+            0x100, R_X86_64_RELATIVE as u64, BASE + 0x10,
+            0x108, R_X86_64_NONE as u64, 0,
+        ];
+
+        let data = elf_file(ET_DYN, EM_X86_64, &[load, dynamic], &trailer);
+        let elf = ElfFile::new_executable(data).unwrap();
+        assert!(elf.is_position_independent());
+
+        let relocations = elf.relocations().unwrap();
+        assert_eq!(relocations.len(), 2);
+        assert_eq!(relocations[0].offset, 0x100);
+        assert_eq!(relocations[0].relocation_type(), R_X86_64_RELATIVE);
+        assert_eq!(relocations[0].addend, (BASE + 0x10) as i64);
+        assert_eq!(relocations[1].relocation_type(), R_X86_64_NONE);
+
+        // executables without a dynamic table have nothing to relocate
+        let data = executable(EM_X86_64, &[load]);
+        assert!(ElfFile::new_executable(data)
+            .unwrap()
+            .relocations()
+            .unwrap()
+            .is_empty());
+    }
+}
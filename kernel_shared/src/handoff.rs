@@ -0,0 +1,396 @@
+//! Information the loader passes to the kernel, so the kernel never needs to read the multiboot
+//! information or any other loader memory once it has copied what it needs.
+
+use core::marker::PhantomData;
+
+use multiboot::MemoryMapEntry;
+use x86_64::{
+    align_up_to_page,
+    structures::{Frame, PAGE_SIZE},
+};
+
+use crate::memory::paging::PHYS_MEM_OFFSET;
+
+/// Value of [Handoff::magic], used to catch the kernel being started by something else
+pub const HANDOFF_MAGIC: u64 = u64::from_le_bytes(*b"CRABHAND");
+
+/// Version of the [Handoff] layout, which must be bumped whenever any structure in this module
+/// changes
+pub const HANDOFF_VERSION: u32 = 1;
+
+/// Longest module name that can be passed to the kernel, including any truncated part
+pub const MODULE_NAME_LENGTH: usize = 32;
+
+/// Everything the kernel is told about the machine and how the loader set it up.
+/// All addresses are physical unless stated otherwise.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Handoff {
+    pub magic: u64,
+    pub version: u32,
+    /// Size of this structure, as a second check that both sides agree on the layout
+    pub size: u32,
+    /// Memory map after the loader has finished with it
+    pub memory_map: PhysSlice<MemoryMapEntry>,
+    pub frame_allocator: FrameAllocatorState,
+    pub framebuffer: Framebuffer,
+    /// Every module loaded by the bootloader, including the kernel and initrd
+    pub modules: PhysSlice<Module>,
+    pub command_line: PhysSlice<u8>,
+    pub rsdp: Rsdp,
+    pub smbios: Smbios,
+    pub kernel: KernelImage,
+    /// Memory used by the loader, which is still identity mapped and can be freed by the kernel
+    pub loader: PhysRange,
+    /// Memory used by the multiboot information, which is not mapped and can be freed by the kernel
+    pub multiboot: PhysRange,
+}
+
+impl Handoff {
+    /// Creates an empty handoff with the header filled in
+    pub const fn new() -> Self {
+        Self {
+            magic: HANDOFF_MAGIC,
+            version: HANDOFF_VERSION,
+            size: core::mem::size_of::<Self>() as u32,
+            memory_map: PhysSlice::EMPTY,
+            frame_allocator: FrameAllocatorState {
+                bitmap: PhysRange::EMPTY,
+                virt_addr: 0,
+            },
+            framebuffer: Framebuffer::NONE,
+            modules: PhysSlice::EMPTY,
+            command_line: PhysSlice::EMPTY,
+            rsdp: Rsdp {
+                root_address: 0,
+                revision: 0,
+                entry_size: 0,
+            },
+            smbios: Smbios {
+                table_address: 0,
+                table_length: 0,
+                major: 0,
+                minor: 0,
+            },
+            kernel: KernelImage {
+                virt_start: 0,
+                virt_end: 0,
+                slide: 0,
+                elf: PhysRange::EMPTY,
+            },
+            loader: PhysRange::EMPTY,
+            multiboot: PhysRange::EMPTY,
+        }
+    }
+
+    /// Checks the header matches the layout this crate was built with
+    pub fn is_valid(&self) -> bool {
+        self.magic == HANDOFF_MAGIC
+            && self.version == HANDOFF_VERSION
+            && self.size as usize == core::mem::size_of::<Self>()
+    }
+
+    /// Returns the first module with the given name
+    ///
+    /// # Safety
+    /// [Handoff::modules] must still be valid
+    pub unsafe fn module(&self, name: &str) -> Option<Module> {
+        self.modules
+            .as_slice()
+            .iter()
+            .find(|module| module.name() == name)
+            .copied()
+    }
+}
+
+impl Default for Handoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A range of physical memory, not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct PhysRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl PhysRange {
+    pub const EMPTY: Self = Self { start: 0, end: 0 };
+
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self {
+            start: start as u64,
+            end: end as u64,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Checks if the two ranges share any memory
+    pub const fn overlaps(&self, other: &PhysRange) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+
+    /// Returns the frames lying entirely within the range, skipping any which share a page with
+    /// one of the `in_use` ranges
+    pub fn unshared_frames<'a>(&self, in_use: &'a [PhysRange]) -> impl Iterator<Item = Frame> + 'a {
+        let start = align_up_to_page(self.start as usize) / PAGE_SIZE;
+        let end = self.end as usize / PAGE_SIZE;
+
+        (start..end)
+            .map(|number| Frame { number })
+            .filter(move |frame| {
+                let page = PhysRange::new(frame.start_address(), frame.start_address() + PAGE_SIZE);
+                !in_use.iter().any(|range| range.overlaps(&page))
+            })
+    }
+}
+
+/// An array of `T` in physical memory, read through the physical memory mapping
+#[derive(Debug)]
+#[repr(C)]
+pub struct PhysSlice<T> {
+    pub addr: u64,
+    pub len: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T> PhysSlice<T> {
+    pub const EMPTY: Self = Self {
+        addr: 0,
+        len: 0,
+        _marker: PhantomData,
+    };
+
+    /// Records where a slice lives, for a slice the loader has identity mapped
+    pub fn from_identity_mapped(slice: &[T]) -> Self {
+        Self {
+            addr: slice.as_ptr() as u64,
+            len: slice.len() as u64,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the slice through the physical memory mapping
+    ///
+    /// # Safety
+    /// The memory must be mapped at [PHYS_MEM_OFFSET] and still hold the slice
+    pub unsafe fn as_slice(&self) -> &'static [T] {
+        if self.len == 0 {
+            return &[];
+        }
+
+        core::slice::from_raw_parts(
+            (self.addr as usize + PHYS_MEM_OFFSET) as *const T,
+            self.len as usize,
+        )
+    }
+}
+
+// derives would require `T: Clone`, but only the address is copied
+impl<T> Clone for PhysSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for PhysSlice<T> {}
+
+/// Where the frame allocator's bitmaps live, so the kernel can carry on using them
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct FrameAllocatorState {
+    pub bitmap: PhysRange,
+    /// Virtual address the bitmaps are mapped at
+    pub virt_addr: u64,
+}
+
+/// Framebuffer and where it has been mapped
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Framebuffer {
+    pub phys_addr: u64,
+    /// Address the loader mapped the framebuffer at
+    pub virt_addr: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8,
+    /// 0 for indexed, 1 for direct colour and 2 for EGA text
+    pub buffer_type: u8,
+    /// Position and size of each colour field, only used by direct colour framebuffers
+    pub red_field_position: u8,
+    pub red_mask_size: u8,
+    pub green_field_position: u8,
+    pub green_mask_size: u8,
+    pub blue_field_position: u8,
+    pub blue_mask_size: u8,
+}
+
+impl Framebuffer {
+    pub const NONE: Self = Self {
+        phys_addr: 0,
+        virt_addr: 0,
+        pitch: 0,
+        width: 0,
+        height: 0,
+        bpp: 0,
+        buffer_type: 0,
+        red_field_position: 0,
+        red_mask_size: 0,
+        green_field_position: 0,
+        green_mask_size: 0,
+        blue_field_position: 0,
+        blue_mask_size: 0,
+    };
+
+    /// Size of the framebuffer in bytes
+    pub const fn size(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+}
+
+/// A module loaded by the bootloader
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Module {
+    pub range: PhysRange,
+    name: [u8; MODULE_NAME_LENGTH],
+    name_len: u32,
+}
+
+impl Module {
+    pub const EMPTY: Self = Self {
+        range: PhysRange::EMPTY,
+        name: [0; MODULE_NAME_LENGTH],
+        name_len: 0,
+    };
+
+    /// Creates a module, truncating the name to [MODULE_NAME_LENGTH] bytes
+    pub fn new(range: PhysRange, name: &str) -> Self {
+        let mut len = name.len().min(MODULE_NAME_LENGTH);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        let mut module = Self {
+            range,
+            name_len: len as u32,
+            ..Self::EMPTY
+        };
+        module.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        module
+    }
+
+    pub fn name(&self) -> &str {
+        // only ever created from a `&str` cut at a char boundary
+        unsafe { core::str::from_utf8_unchecked(&self.name[..self.name_len as usize]) }
+    }
+}
+
+/// What the kernel needs from the RSDP, which the loader has already checked
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Rsdp {
+    /// Address of the XSDT or RSDT, or 0 if no valid RSDP was found
+    pub root_address: u64,
+    /// 0 for ACPI 1.0 and 2 for ACPI 2.0+
+    pub revision: u8,
+    /// Size of each entry in the root table, 8 for the XSDT and 4 for the RSDT
+    pub entry_size: u8,
+}
+
+/// SMBIOS structure table passed by the bootloader
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Smbios {
+    /// Address of the structure table, or 0 if the bootloader didn't pass an entry point
+    pub table_address: u64,
+    pub table_length: u32,
+    pub major: u8,
+    pub minor: u8,
+}
+
+/// Where the kernel was loaded
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct KernelImage {
+    /// Virtual range covered by the loaded segments, including the slide
+    pub virt_start: u64,
+    pub virt_end: u64,
+    /// Offset from the kernel's link address
+    pub slide: u64,
+    /// The kernel ELF file itself, which is kept around for its symbols
+    pub elf: PhysRange,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{format, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn new_handoff_is_valid() {
+        assert!(Handoff::new().is_valid());
+    }
+
+    #[test]
+    fn mismatched_header_is_invalid() {
+        let mut handoff = Handoff::new();
+        handoff.version += 1;
+        assert!(!handoff.is_valid());
+
+        let mut handoff = Handoff::new();
+        handoff.size -= 8;
+        assert!(!handoff.is_valid());
+
+        let mut handoff = Handoff::new();
+        handoff.magic = 0;
+        assert!(!handoff.is_valid());
+    }
+
+    #[test]
+    fn unshared_frames_skip_pages_shared_with_modules() {
+        // multiboot information is only 8 byte aligned, so can start in the page a module ends in
+        let module = PhysRange::new(0x20_0000, 0x20_1008);
+        let multiboot = PhysRange::new(0x20_1008, 0x20_4010);
+
+        let frames = |in_use: &[PhysRange]| {
+            multiboot
+                .unshared_frames(in_use)
+                .map(|frame| frame.number)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(frames(&[module]), [0x202, 0x203]);
+
+        // and anything else in a whole page keeps that page too
+        let loader = PhysRange::new(0x20_3800, 0x20_3900);
+        assert_eq!(frames(&[module, loader]), [0x202]);
+
+        assert_eq!(frames(&[]), [0x202, 0x203]);
+    }
+
+    #[test]
+    fn module_name_is_truncated_at_char_boundary() {
+        let module = Module::new(PhysRange::new(0x1000, 0x2000), "initrd");
+        assert_eq!(module.name(), "initrd");
+
+        let long = "é".repeat(MODULE_NAME_LENGTH);
+        let module = Module::new(PhysRange::EMPTY, &long);
+        assert_eq!(module.name().len(), MODULE_NAME_LENGTH);
+
+        let module = Module::new(PhysRange::EMPTY, &format!("a{long}"));
+        assert_eq!(module.name().len(), MODULE_NAME_LENGTH - 1);
+    }
+}
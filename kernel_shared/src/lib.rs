@@ -1,6 +1,12 @@
 #![no_std]
 #![feature(iter_intersperse)]
 
+#[cfg(test)]
+extern crate std;
+
+pub mod cmdline;
+pub mod elf;
+pub mod handoff;
 pub mod logger;
 pub mod memory;
 pub mod serial;
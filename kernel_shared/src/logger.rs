@@ -15,11 +15,16 @@ impl Logger {
         log::set_max_level(self.level);
         log::set_logger(self)
     }
+
+    /// Changes the maximum level of messages that are logged
+    pub fn set_level(&self, level: LevelFilter) {
+        log::set_max_level(level);
+    }
 }
 
 impl Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level().to_level_filter() <= self.level
+        metadata.level() <= log::max_level()
     }
 
     fn log(&self, record: &log::Record) {
@@ -2,9 +2,10 @@ use multiboot::{MemoryMapEntry, MemoryType};
 use x86_64::{
     align_down_to_page, align_up,
     structures::{Frame, PAGE_SIZE},
+    PhysicalAddress,
 };
 
-use super::FrameAllocator;
+use super::{FrameAllocator, Zone};
 
 /// Length of a bitmap array that fills one page
 const BITMAP_LENGTH: usize = PAGE_SIZE / core::mem::size_of::<u64>();
@@ -170,12 +171,194 @@ impl BitmapFrameAllocator {
         }
     }
 
+    /// Returns the memory map used by the allocator
+    pub fn memory_regions(&self) -> &'static [MemoryMapEntry] {
+        self.memory_regions
+    }
+
+    /// Returns the total number of frames of RAM managed by the allocator
+    pub fn total_frames(&self) -> usize {
+        self.ram_regions()
+            .map(|region| (region.length as usize).div_ceil(PAGE_SIZE))
+            .sum()
+    }
+
+    /// Returns the number of frames currently allocated or ignored
+    pub fn used_frames(&self) -> usize {
+        let mut used = 0;
+        let mut bitmap_index = 0;
+
+        for region in self.ram_regions() {
+            let frames = (region.length as usize).div_ceil(PAGE_SIZE);
+            let bitmaps = frames.div_ceil(64);
+
+            let set_bits: usize = self.bitmaps[bitmap_index..bitmap_index + bitmaps]
+                .iter()
+                .map(|bitmap| bitmap.count_ones() as usize)
+                .sum();
+
+            // bits past the end of the region are always set, so don't count them
+            used += set_bits.saturating_sub(bitmaps * 64 - frames);
+
+            bitmap_index += align_up(bitmaps, BITMAP_LENGTH);
+        }
+
+        used
+    }
+
+    /// Checks if a frame is RAM that's neither allocated nor ignored
+    pub fn is_frame_free(&self, frame: &Frame) -> bool {
+        let addr = frame.start_address();
+
+        self.ram_regions_with_index()
+            .find(|(_, region)| {
+                (region.base_addr as usize..(region.base_addr + region.length) as usize)
+                    .contains(&addr)
+            })
+            .is_some_and(|(bitmap_index, region)| {
+                !self.is_used(bitmap_index, (addr - region.base_addr as usize) / PAGE_SIZE)
+            })
+    }
+
+    /// Returns the number of frames of RAM in a zone, and how many of them are allocated or ignored
+    pub fn zone_frames(&self, zone: Zone) -> (usize, usize) {
+        let zone = zone.range();
+        let (mut used, mut total) = (0, 0);
+
+        for (bitmap_index, region) in self.ram_regions_with_index() {
+            let region_start = region.base_addr as usize;
+            let region_end = region_start + region.length as usize;
+
+            let start = region_start.max(zone.start);
+            let end = region_end.min(zone.end);
+            if start >= end {
+                continue;
+            }
+
+            let first = (start - region_start) / PAGE_SIZE;
+            let last = (end - region_start).div_ceil(PAGE_SIZE);
+            total += last - first;
+            used += (first..last)
+                .filter(|&frame| self.is_used(bitmap_index, frame))
+                .count();
+        }
+
+        (used, total)
+    }
+
+    /// Allocates `count` physically contiguous frames, returning the first. The first frame is
+    /// aligned to `align` bytes, and the last ends at or below `max_addr`.
+    ///
+    /// Zones are tried from the highest one below `max_addr` down, so memory only some devices can
+    /// reach is left for them where possible. A run of frames never crosses from one zone into
+    /// another.
+    pub fn allocate_contiguous(
+        &mut self,
+        count: usize,
+        align: usize,
+        max_addr: PhysicalAddress,
+    ) -> Option<Frame> {
+        if count == 0 {
+            return None;
+        }
+        let align = align.max(PAGE_SIZE);
+
+        Zone::ALL
+            .into_iter()
+            .map(Zone::range)
+            .filter(|zone| zone.start < max_addr)
+            .find_map(|zone| {
+                self.allocate_contiguous_in(count, align, zone.start, zone.end.min(max_addr))
+            })
+    }
+
+    /// Allocates `count` contiguous frames between `start` and `end`, with the first aligned to
+    /// `align` bytes
+    fn allocate_contiguous_in(
+        &mut self,
+        count: usize,
+        align: usize,
+        start: PhysicalAddress,
+        end: PhysicalAddress,
+    ) -> Option<Frame> {
+        let len = count.checked_mul(PAGE_SIZE)?;
+
+        for (bitmap_index, region) in self.ram_regions_with_index() {
+            let region_start = region.base_addr as usize;
+            let end = end.min(region_start + region.length as usize);
+
+            let mut addr = align_up(region_start.max(start), align);
+            while addr
+                .checked_add(len)
+                .is_some_and(|buffer_end| buffer_end <= end)
+            {
+                let first = (addr - region_start) / PAGE_SIZE;
+
+                // the search carries on past the last used frame in the way, since nothing
+                // starting before it can fit
+                match (first..first + count)
+                    .rev()
+                    .find(|&frame| self.is_used(bitmap_index, frame))
+                {
+                    Some(used) => addr = align_up(region_start + (used + 1) * PAGE_SIZE, align),
+                    None => {
+                        for frame in first..first + count {
+                            let (index, bit) = Self::bit(bitmap_index, frame);
+                            self.bitmaps[index] |= bit;
+                        }
+
+                        return Some(Frame::containing_address(addr));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Frees `count` contiguous frames starting at `frame`, as returned by
+    /// [BitmapFrameAllocator::allocate_contiguous]
+    pub fn deallocate_contiguous(&mut self, frame: Frame, count: usize) {
+        for number in frame.number..frame.number + count {
+            self.deallocate_frame(Frame { number });
+        }
+    }
+
+    /// Returns the index into the bitmaps and the bit for a frame, given the index of its region's
+    /// first bitmap and the frame's number within the region
+    fn bit(bitmap_index: usize, frame: usize) -> (usize, u64) {
+        (bitmap_index + frame / 64, 1 << (frame % 64))
+    }
+
+    /// Checks if a frame is allocated or ignored, given the index of its region's first bitmap and
+    /// the frame's number within the region
+    fn is_used(&self, bitmap_index: usize, frame: usize) -> bool {
+        let (index, bit) = Self::bit(bitmap_index, frame);
+        self.bitmaps[index] & bit != 0
+    }
+
     /// Returns an iterator of all memory regions which are actually RAM
     fn ram_regions(&self) -> impl Iterator<Item = &'static multiboot::MemoryMapEntry> {
         self.memory_regions
             .iter()
             .filter(|region| region.mem_type == MemoryType::RAM)
     }
+
+    /// Returns an iterator of all memory regions which are actually RAM, along with the index of
+    /// the first bitmap for each
+    fn ram_regions_with_index(
+        &self,
+    ) -> impl Iterator<Item = (usize, &'static multiboot::MemoryMapEntry)> {
+        self.ram_regions().scan(0, |bitmap_index, region| {
+            let index = *bitmap_index;
+            *bitmap_index += align_up(
+                (region.length as usize).div_ceil(PAGE_SIZE * 64),
+                BITMAP_LENGTH,
+            );
+
+            Some((index, region))
+        })
+    }
 }
 
 impl FrameAllocator for BitmapFrameAllocator {
@@ -235,3 +418,86 @@ impl FrameAllocator for BitmapFrameAllocator {
 fn generate_mask(start: usize, end: usize) -> u64 {
     !(!0u64 << (end - start)) << start
 }
+
+#[cfg(test)]
+mod tests {
+    use x86_64::structures::{Frame, PAGE_SIZE};
+
+    use crate::memory::frame_alloc::{tests::allocator, FrameAllocator, Zone};
+
+    #[test]
+    fn contiguous_frames_are_aligned_and_used() {
+        let mut allocator = allocator();
+        let used = allocator.used_frames();
+
+        let frame = allocator
+            .allocate_contiguous(5, 0x10000, usize::MAX)
+            .unwrap();
+        assert_eq!(frame.start_address() % 0x10000, 0);
+        assert_eq!(allocator.used_frames(), used + 5);
+
+        allocator.deallocate_contiguous(frame, 5);
+        assert_eq!(allocator.used_frames(), used);
+    }
+
+    #[test]
+    fn contiguous_frames_come_from_highest_zone_allowed() {
+        let mut allocator = allocator();
+
+        let normal = allocator.allocate_contiguous(2, 0, usize::MAX).unwrap();
+        assert_eq!(normal.start_address(), 0x1_0000_0000);
+
+        let dma32 = allocator
+            .allocate_contiguous(2, 0, Zone::Dma32.range().end)
+            .unwrap();
+        assert_eq!(dma32.start_address(), Zone::Dma32.range().start);
+
+        let dma = allocator
+            .allocate_contiguous(2, 0, Zone::Dma.range().end)
+            .unwrap();
+        assert_eq!(dma.start_address(), 0);
+
+        // low memory is too small, and only the first 1 MiB of the next region is below the limit
+        assert!(allocator.allocate_contiguous(0x101, 0, 0x20_0000).is_none());
+        let low = allocator.allocate_contiguous(0x100, 0, 0x20_0000).unwrap();
+        assert_eq!(low.start_address(), 0x10_0000);
+    }
+
+    #[test]
+    fn contiguous_frames_skip_used_frames() {
+        let mut allocator = allocator();
+
+        let first = allocator.allocate_frame().unwrap();
+        assert_eq!(first.start_address(), 0);
+
+        // the first frame is in the way, so the run starts at the next aligned frame
+        let frame = allocator
+            .allocate_contiguous(2, 2 * PAGE_SIZE, Zone::Dma.range().end)
+            .unwrap();
+        assert_eq!(frame.start_address(), 2 * PAGE_SIZE);
+
+        // runs don't cross into reserved memory
+        assert!(allocator
+            .allocate_contiguous(0x9F, 0, Zone::Dma.range().end)
+            .is_some_and(|frame| frame.start_address() >= 0x10_0000));
+
+        allocator.deallocate_frame(first);
+        assert_eq!(allocator.allocate_frame(), Some(Frame { number: 0 }));
+    }
+
+    #[test]
+    fn zones_count_their_frames() {
+        let mut allocator = allocator();
+
+        let dma_frames = 0x9F + (0x100_0000 - 0x10_0000) / PAGE_SIZE;
+        assert_eq!(allocator.zone_frames(Zone::Dma), (0, dma_frames));
+        assert_eq!(
+            allocator.zone_frames(Zone::Dma32),
+            (0, 0x1EF_F000 / PAGE_SIZE - (dma_frames - 0x9F))
+        );
+        assert_eq!(allocator.zone_frames(Zone::Normal), (0, 0xFF));
+
+        allocator.allocate_contiguous(3, 0, usize::MAX).unwrap();
+        assert_eq!(allocator.zone_frames(Zone::Normal), (3, 0xFF));
+    }
+}
@@ -0,0 +1,552 @@
+//! Frame allocator which hands out blocks of a power of two frames.
+//!
+//! Free blocks of each size (order) are kept in a doubly linked list, with the links stored in the
+//! first frame of each block. A block is allocated by splitting the smallest free block that's
+//! large enough in half until it's the right size, and a freed block is merged with its buddy (the
+//! other half of the block it was split from) for as long as the buddy is free too, so both take
+//! at most [MAX_ORDER] steps.
+//!
+//! Alongside the lists, one byte per frame records the order of the free block starting there,
+//! which is how a freed block finds out if its buddy is free.
+
+use multiboot::{MemoryMapEntry, MemoryType};
+use x86_64::{
+    align_up,
+    structures::{Frame, PAGE_SIZE},
+    PhysicalAddress,
+};
+
+use super::{bitmap::BitmapFrameAllocator, FrameAllocator, Zone};
+use crate::memory::paging::physical::{OffsetPhysicalMemory, PhysicalMemory};
+
+/// Order of the largest blocks, which are 2^MAX_ORDER frames (4 GiB)
+pub const MAX_ORDER: usize = 20;
+
+/// Order recorded for frames which don't start a free block
+const NOT_FREE: u8 = u8::MAX;
+
+/// Marks the end of a free list
+const NONE: usize = usize::MAX;
+
+/// Neighbours of a free block in its free list, stored at the start of the block
+#[repr(C)]
+struct Link {
+    prev: usize,
+    next: usize,
+}
+
+/// Frame allocator that splits memory into blocks of a power of two frames, merging them back
+/// together as they're freed
+pub struct BuddyFrameAllocator<M: PhysicalMemory = OffsetPhysicalMemory> {
+    memory: M,
+    memory_regions: &'static [MemoryMapEntry],
+    /// Order of the free block starting at each frame, or [NOT_FREE]
+    orders: &'static mut [u8],
+    /// First free block of each order, or [NONE]
+    free_lists: [usize; MAX_ORDER + 1],
+    free_frames: usize,
+}
+
+impl<M: PhysicalMemory> BuddyFrameAllocator<M> {
+    /// Returns the number of bytes of metadata needed for a memory map, which is one for each frame
+    /// up to the end of the highest RAM region
+    pub fn metadata_len(memory_regions: &[MemoryMapEntry]) -> usize {
+        memory_regions
+            .iter()
+            .filter(|region| region.mem_type == MemoryType::RAM)
+            .map(|region| (region.base_addr + region.length) as usize)
+            .max()
+            .unwrap_or(0)
+            .div_ceil(PAGE_SIZE)
+    }
+
+    /// Creates an allocator with every frame allocated
+    ///
+    /// # Safety
+    /// Every frame of RAM in `memory_regions` must be accessible through `memory`, and nothing else
+    /// may use `metadata`
+    pub unsafe fn new(
+        memory: M,
+        memory_regions: &'static [MemoryMapEntry],
+        metadata: &'static mut [u8],
+    ) -> Self {
+        assert!(
+            metadata.len() >= Self::metadata_len(memory_regions),
+            "buddy allocator metadata is too small for memory map"
+        );
+        metadata.fill(NOT_FREE);
+
+        Self {
+            memory,
+            memory_regions,
+            orders: metadata,
+            free_lists: [NONE; MAX_ORDER + 1],
+            free_frames: 0,
+        }
+    }
+
+    /// Creates an allocator with the same frames free as a bitmap allocator, which mustn't be used
+    /// to allocate or free frames afterwards
+    ///
+    /// # Safety
+    /// Same as [BuddyFrameAllocator::new]
+    pub unsafe fn from_bitmap(
+        bitmap: &BitmapFrameAllocator,
+        memory: M,
+        metadata: &'static mut [u8],
+    ) -> Self {
+        let mut buddy = Self::new(memory, bitmap.memory_regions(), metadata);
+
+        for region in buddy.ram_regions() {
+            let start = region.base_addr as usize / PAGE_SIZE;
+            let end = start + (region.length as usize).div_ceil(PAGE_SIZE);
+
+            // runs of free frames are added as whole blocks, rather than merging frame by frame
+            let mut run_start = None;
+            for number in start..end {
+                match (bitmap.is_frame_free(&Frame { number }), run_start) {
+                    (true, None) => run_start = Some(number),
+                    (false, Some(run)) => {
+                        buddy.free_range(run, number);
+                        run_start = None;
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(run) = run_start {
+                buddy.free_range(run, end);
+            }
+        }
+
+        buddy
+    }
+
+    /// Allocates a block of 2^`order` frames, returning the first
+    pub fn allocate_block(&mut self, order: usize) -> Option<Frame> {
+        let found = (order..=MAX_ORDER).find(|&found| self.free_lists[found] != NONE)?;
+        let number = self.free_lists[found];
+        self.remove(number, found);
+
+        // the upper halves split off on the way down are left free
+        for split in (order..found).rev() {
+            self.push(number + (1 << split), split);
+        }
+
+        self.free_frames -= 1 << order;
+        Some(Frame { number })
+    }
+
+    /// Frees a block of 2^`order` frames starting at `frame`, merging it with its buddy for as long
+    /// as that's free
+    pub fn deallocate_block(&mut self, frame: Frame, mut order: usize) {
+        let mut number = frame.number;
+        debug_assert_eq!(self.orders[number], NOT_FREE, "frame freed twice");
+        self.free_frames += 1 << order;
+
+        while order < MAX_ORDER {
+            let buddy = number ^ (1 << order);
+            if self.orders.get(buddy) != Some(&(order as u8)) {
+                break;
+            }
+
+            self.remove(buddy, order);
+            number = number.min(buddy);
+            order += 1;
+        }
+
+        self.push(number, order);
+    }
+
+    /// Allocates `count` physically contiguous frames, returning the first. The first frame is
+    /// aligned to `align` bytes, and the last ends at or below `max_addr`.
+    ///
+    /// Works like [BitmapFrameAllocator::allocate_contiguous], but the run is taken from a block of
+    /// a power of two frames, with the rest of the block freed again. Finding a block in the right
+    /// zone walks the free lists, so this is slower than [BuddyFrameAllocator::allocate_block].
+    pub fn allocate_contiguous(
+        &mut self,
+        count: usize,
+        align: usize,
+        max_addr: PhysicalAddress,
+    ) -> Option<Frame> {
+        if count == 0 {
+            return None;
+        }
+
+        let align_frames = align.max(PAGE_SIZE) / PAGE_SIZE;
+        let order = count.max(align_frames).next_power_of_two().trailing_zeros() as usize;
+        if order > MAX_ORDER {
+            return None;
+        }
+
+        let number = Zone::ALL
+            .into_iter()
+            .map(Zone::range)
+            .filter(|zone| zone.start < max_addr)
+            .find_map(|zone| {
+                self.allocate_block_in(
+                    order,
+                    zone.start / PAGE_SIZE,
+                    zone.end.min(max_addr) / PAGE_SIZE,
+                )
+            })?;
+
+        self.free_range(number + count, number + (1 << order));
+        Some(Frame { number })
+    }
+
+    /// Frees `count` contiguous frames starting at `frame`, as returned by
+    /// [BuddyFrameAllocator::allocate_contiguous]
+    pub fn deallocate_contiguous(&mut self, frame: Frame, count: usize) {
+        self.free_range(frame.number, frame.number + count);
+    }
+
+    /// Returns the memory map used by the allocator
+    pub fn memory_regions(&self) -> &'static [MemoryMapEntry] {
+        self.memory_regions
+    }
+
+    /// Returns the total number of frames of RAM managed by the allocator
+    pub fn total_frames(&self) -> usize {
+        self.ram_regions()
+            .map(|region| (region.length as usize).div_ceil(PAGE_SIZE))
+            .sum()
+    }
+
+    /// Returns the number of frames currently allocated or never freed
+    pub fn used_frames(&self) -> usize {
+        self.total_frames() - self.free_frames
+    }
+
+    /// Returns the number of frames of RAM in a zone, and how many of them are allocated or never
+    /// freed
+    pub fn zone_frames(&self, zone: Zone) -> (usize, usize) {
+        let zone = zone.range();
+        let (zone_start, zone_end) = (zone.start / PAGE_SIZE, zone.end / PAGE_SIZE);
+        let overlap =
+            |start: usize, end: usize| end.min(zone_end).saturating_sub(start.max(zone_start));
+
+        let total: usize = self
+            .ram_regions()
+            .map(|region| {
+                let start = region.base_addr as usize / PAGE_SIZE;
+                overlap(start, start + (region.length as usize).div_ceil(PAGE_SIZE))
+            })
+            .sum();
+
+        let mut free = 0;
+        for (order, &head) in self.free_lists.iter().enumerate() {
+            let mut block = head;
+            while block != NONE {
+                free += overlap(block, block + (1 << order));
+                block = unsafe { (*self.link(block)).next };
+            }
+        }
+
+        (total - free, total)
+    }
+
+    /// Allocates a block of 2^`order` frames between frame numbers `start` and `end`, carving it
+    /// out of a larger free block if needed
+    fn allocate_block_in(&mut self, order: usize, start: usize, end: usize) -> Option<usize> {
+        let size = 1 << order;
+
+        for found in order..=MAX_ORDER {
+            let mut block = self.free_lists[found];
+
+            while block != NONE {
+                let block_end = block + (1 << found);
+
+                // lowest part of the block that's aligned and in range
+                let number = align_up(block.max(start), size);
+                if number + size <= block_end.min(end) {
+                    self.remove(block, found);
+                    self.free_frames -= 1 << found;
+
+                    self.free_range(block, number);
+                    self.free_range(number + size, block_end);
+                    return Some(number);
+                }
+
+                block = unsafe { (*self.link(block)).next };
+            }
+        }
+
+        None
+    }
+
+    /// Frees the frames from `start` up to `end`, as the largest aligned blocks they can be split
+    /// into
+    fn free_range(&mut self, start: usize, end: usize) {
+        let mut number = start;
+
+        while number < end {
+            let order = (number.trailing_zeros() as usize)
+                .min((end - number).ilog2() as usize)
+                .min(MAX_ORDER);
+
+            self.deallocate_block(Frame { number }, order);
+            number += 1 << order;
+        }
+    }
+
+    /// Returns a pointer to the links of a free block
+    fn link(&self, number: usize) -> *mut Link {
+        self.memory.ptr(number * PAGE_SIZE).cast()
+    }
+
+    /// Adds a block to the front of a free list
+    fn push(&mut self, number: usize, order: usize) {
+        let next = self.free_lists[order];
+
+        unsafe {
+            self.link(number).write(Link { prev: NONE, next });
+            if next != NONE {
+                (*self.link(next)).prev = number;
+            }
+        }
+
+        self.free_lists[order] = number;
+        self.orders[number] = order as u8;
+    }
+
+    /// Takes a block out of a free list
+    fn remove(&mut self, number: usize, order: usize) {
+        let Link { prev, next } = unsafe { self.link(number).read() };
+
+        match prev {
+            NONE => self.free_lists[order] = next,
+            prev => unsafe { (*self.link(prev)).next = next },
+        }
+        if next != NONE {
+            unsafe { (*self.link(next)).prev = prev };
+        }
+
+        self.orders[number] = NOT_FREE;
+    }
+
+    /// Returns an iterator of all memory regions which are actually RAM
+    fn ram_regions(&self) -> impl Iterator<Item = &'static MemoryMapEntry> {
+        self.memory_regions
+            .iter()
+            .filter(|region| region.mem_type == MemoryType::RAM)
+    }
+}
+
+impl<M: PhysicalMemory> FrameAllocator for BuddyFrameAllocator<M> {
+    fn allocate_frame(&mut self) -> Option<Frame> {
+        self.allocate_block(0)
+    }
+
+    fn deallocate_frame(&mut self, frame: Frame) {
+        self.deallocate_block(frame, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use std::{
+        boxed::Box,
+        collections::{BTreeMap, BTreeSet},
+        vec,
+        vec::Vec,
+    };
+
+    use proptest::prelude::*;
+    use x86_64::{
+        structures::{Frame, PAGE_SIZE},
+        PhysicalAddress, VirtualAddress,
+    };
+
+    use super::{BuddyFrameAllocator, NONE};
+    use crate::memory::{
+        frame_alloc::{
+            bitmap::BitmapFrameAllocator,
+            tests::{allocator, REGIONS},
+            FrameAllocator, Zone,
+        },
+        paging::physical::PhysicalMemory,
+    };
+
+    /// Stand-in for physical memory which only allocates the pages that get touched, since only
+    /// the start of each free block is
+    #[derive(Default)]
+    struct FakeMemory {
+        pages: RefCell<BTreeMap<usize, Box<[u64; PAGE_SIZE / 8]>>>,
+    }
+
+    impl PhysicalMemory for FakeMemory {
+        fn ptr(&self, addr: PhysicalAddress) -> *mut u8 {
+            let mut pages = self.pages.borrow_mut();
+            let page = pages
+                .entry(addr / PAGE_SIZE)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE / 8]));
+
+            page.as_mut_ptr()
+                .cast::<u8>()
+                .wrapping_add(addr % PAGE_SIZE)
+        }
+
+        fn invalidate(&self, _addr: VirtualAddress) {}
+    }
+
+    fn metadata() -> &'static mut [u8] {
+        vec![0u8; BuddyFrameAllocator::<FakeMemory>::metadata_len(&REGIONS)].leak()
+    }
+
+    /// Creates a buddy allocator with the same free frames as a bitmap allocator
+    fn buddy_allocator(bitmap: &BitmapFrameAllocator) -> BuddyFrameAllocator<FakeMemory> {
+        unsafe { BuddyFrameAllocator::from_bitmap(bitmap, FakeMemory::default(), metadata()) }
+    }
+
+    /// Returns the order and first frame of every free block
+    fn free_blocks(buddy: &BuddyFrameAllocator<FakeMemory>) -> BTreeSet<(usize, usize)> {
+        let mut blocks = BTreeSet::new();
+
+        for (order, &head) in buddy.free_lists.iter().enumerate() {
+            let mut block = head;
+            while block != NONE {
+                blocks.insert((order, block));
+                block = unsafe { (*buddy.link(block)).next };
+            }
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn blocks_split_and_coalesce() {
+        let mut buddy =
+            unsafe { BuddyFrameAllocator::new(FakeMemory::default(), &REGIONS, metadata()) };
+        buddy.free_range(0, 8);
+        assert_eq!(buddy.free_lists[..4], [NONE, NONE, NONE, 0]);
+
+        // splitting leaves one free block of each smaller order behind
+        assert_eq!(buddy.allocate_frame(), Some(Frame { number: 0 }));
+        assert_eq!(buddy.free_lists[..4], [1, 2, 4, NONE]);
+        assert_eq!(buddy.used_frames(), buddy.total_frames() - 7);
+
+        buddy.deallocate_frame(Frame { number: 0 });
+        assert_eq!(buddy.free_lists[..4], [NONE, NONE, NONE, 0]);
+        assert_eq!(buddy.used_frames(), buddy.total_frames() - 8);
+    }
+
+    #[test]
+    fn starts_with_same_frames_as_bitmap() {
+        let mut bitmap = allocator();
+        for _ in 0..10 {
+            bitmap.allocate_frame().unwrap();
+        }
+
+        let buddy = buddy_allocator(&bitmap);
+        assert_eq!(buddy.total_frames(), bitmap.total_frames());
+        assert_eq!(buddy.used_frames(), bitmap.used_frames());
+
+        for zone in Zone::ALL {
+            assert_eq!(buddy.zone_frames(zone), bitmap.zone_frames(zone));
+        }
+    }
+
+    #[test]
+    fn runs_out_with_bitmap() {
+        let mut bitmap = allocator();
+        let mut buddy = buddy_allocator(&bitmap);
+
+        let bitmap_frames = core::iter::from_fn(|| bitmap.allocate_frame()).count();
+        let buddy_frames: BTreeSet<_> = core::iter::from_fn(|| buddy.allocate_frame())
+            .map(|frame| frame.number)
+            .collect();
+
+        assert_eq!(buddy_frames.len(), bitmap_frames);
+        assert_eq!(buddy.used_frames(), buddy.total_frames());
+    }
+
+    #[test]
+    fn contiguous_frames_match_bitmap() {
+        let cases = [
+            (5, 0x10000, usize::MAX),
+            (0x80, 0, usize::MAX),
+            (2, 0, Zone::Dma32.range().end),
+            (2, 2 * PAGE_SIZE, Zone::Dma.range().end),
+            (0x100, 0, 0x20_0000),
+            (0x101, 0, 0x20_0000),
+        ];
+
+        for (count, align, max_addr) in cases {
+            let mut bitmap = allocator();
+            let mut buddy = buddy_allocator(&bitmap);
+            let used = buddy.used_frames();
+
+            let expected = bitmap.allocate_contiguous(count, align, max_addr);
+            let frame = buddy.allocate_contiguous(count, align, max_addr);
+            assert_eq!(frame.is_some(), expected.is_some());
+
+            let (Some(frame), Some(expected)) = (frame, expected) else {
+                continue;
+            };
+
+            let zone = |frame: &Frame| {
+                Zone::ALL
+                    .into_iter()
+                    .find(|zone| zone.range().contains(&frame.start_address()))
+            };
+            assert_eq!(zone(&frame), zone(&expected));
+            assert_eq!(frame.start_address() % align.max(PAGE_SIZE), 0);
+            assert!(frame.start_address() + count * PAGE_SIZE <= max_addr);
+            assert_eq!(buddy.used_frames(), bitmap.used_frames());
+
+            buddy.deallocate_contiguous(frame, count);
+            assert_eq!(buddy.used_frames(), used);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn allocates_and_frees_like_bitmap(
+            ops in prop::collection::vec(any::<Option<prop::sample::Index>>(), 1..512),
+        ) {
+            let original = allocator();
+            let mut bitmap = allocator();
+            let mut buddy = buddy_allocator(&bitmap);
+
+            // `None` allocates a frame from both, `Some` frees one allocated earlier
+            let mut bitmap_frames = Vec::new();
+            let mut buddy_frames = Vec::new();
+            for op in ops {
+                match op {
+                    None => {
+                        let expected = bitmap.allocate_frame();
+                        let frame = buddy.allocate_frame();
+                        prop_assert_eq!(frame.is_some(), expected.is_some());
+
+                        if let (Some(frame), Some(expected)) = (frame, expected) {
+                            prop_assert!(original.is_frame_free(&frame));
+                            prop_assert!(!buddy_frames.contains(&frame.number));
+
+                            bitmap_frames.push(expected);
+                            buddy_frames.push(frame.number);
+                        }
+                    }
+                    Some(index) if !buddy_frames.is_empty() => {
+                        let index = index.index(buddy_frames.len());
+
+                        bitmap.deallocate_frame(bitmap_frames.swap_remove(index));
+                        buddy.deallocate_frame(Frame { number: buddy_frames.swap_remove(index) });
+                    }
+                    Some(_) => {}
+                }
+
+                prop_assert_eq!(buddy.used_frames(), bitmap.used_frames());
+            }
+
+            // freeing everything merges the blocks back to how they started
+            for number in buddy_frames {
+                buddy.deallocate_frame(Frame { number });
+            }
+
+            prop_assert_eq!(free_blocks(&buddy), free_blocks(&buddy_allocator(&original)));
+        }
+    }
+}
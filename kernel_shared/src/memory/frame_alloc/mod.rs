@@ -1,9 +1,37 @@
-use x86_64::structures::Frame;
+use core::ops::Range;
+
+use x86_64::{structures::Frame, PhysicalAddress};
 
 pub mod bitmap;
+pub mod buddy;
 pub mod bump;
 pub mod tiny;
 
+/// Ranges of physical memory, split by which devices can reach them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// Below 16 MiB, the limit of ISA DMA
+    Dma,
+    /// Below 4 GiB, for devices with 32-bit addresses
+    Dma32,
+    /// Everything else
+    Normal,
+}
+
+impl Zone {
+    /// Every zone, from the highest addresses down
+    pub const ALL: [Zone; 3] = [Zone::Normal, Zone::Dma32, Zone::Dma];
+
+    /// Returns the physical addresses in the zone
+    pub const fn range(self) -> Range<PhysicalAddress> {
+        match self {
+            Zone::Dma => 0..0x100_0000,
+            Zone::Dma32 => 0x100_0000..0x1_0000_0000,
+            Zone::Normal => 0x1_0000_0000..usize::MAX,
+        }
+    }
+}
+
 pub trait FrameAllocator {
     /// Finds a free frame to allocate and return
     fn allocate_frame(&mut self) -> Option<Frame>;
@@ -11,3 +39,29 @@ pub trait FrameAllocator {
     /// Frees a given frame
     fn deallocate_frame(&mut self, frame: Frame);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{mem::size_of, vec};
+
+    use multiboot::{MemoryMapEntry, MemoryType};
+    use x86_64::structures::PAGE_SIZE;
+
+    use super::bitmap::BitmapFrameAllocator;
+
+    /// Low memory, then RAM crossing from the DMA zone into DMA32, then a little above 4 GiB
+    pub(super) static REGIONS: [MemoryMapEntry; 4] = [
+        MemoryMapEntry::new(0, 0x9F000, MemoryType::RAM),
+        MemoryMapEntry::new(0x9F000, 0x61000, MemoryType::RESERVED),
+        MemoryMapEntry::new(0x10_0000, 0x1EF_F000, MemoryType::RAM),
+        MemoryMapEntry::new(0x1_0000_0000, 0xFF000, MemoryType::RAM),
+    ];
+
+    /// Creates a bitmap allocator over [REGIONS], with its bitmaps on the heap
+    pub(super) fn allocator() -> BitmapFrameAllocator {
+        let len = BitmapFrameAllocator::frames_needed(&REGIONS) * PAGE_SIZE / size_of::<u64>();
+        let bitmaps = vec![0u64; len].leak();
+
+        BitmapFrameAllocator::new(bitmaps.as_mut_ptr() as usize, &REGIONS).0
+    }
+}
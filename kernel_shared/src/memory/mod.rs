@@ -1,2 +1,3 @@
 pub mod frame_alloc;
 pub mod paging;
+pub mod stack;
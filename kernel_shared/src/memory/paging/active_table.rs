@@ -5,6 +5,7 @@ use x86_64::registers::CR3;
 use super::{
     inactive_table::InactivePageTable,
     mapper::Mapper,
+    physical::{OffsetPhysicalMemory, PhysicalMemory},
     table::{Level4, Table},
 };
 
@@ -18,11 +19,11 @@ impl ActivePageTable {
     /// # Safety
     /// This should only ever be called once
     pub unsafe fn new() -> Self {
-        let table = CR3::read().0.start_address() + super::PHYS_MEM_OFFSET;
-        let table = table as *mut Table<Level4>;
+        let memory = OffsetPhysicalMemory;
+        let table = memory.ptr(CR3::read().0.start_address()) as *mut Table<Level4>;
 
         Self {
-            mapper: Mapper::new(table),
+            mapper: Mapper::new(table, memory),
         }
     }
 
@@ -1,9 +1,10 @@
 use core::fmt::Display;
 
 use bitflags::bitflags;
-use multiboot::elf_symbols::{ElfSectionHeader, SectionFlags};
 use x86_64::structures::Frame;
 
+use crate::elf::{ProgramHeader, SegmentFlags};
+
 bitflags! {
     /// Stores possible flags for a page entry
     #[derive(Clone, Copy)]
@@ -17,20 +18,21 @@ bitflags! {
         const DIRTY = 1 << 6;
         const HUGE_PAGE = 1 << 7;
         const GLOBAL = 1 << 8;
+        /// Available bit, marking a page shared read-only that's copied on the first write
+        const COPY_ON_WRITE = 1 << 9;
         const NO_EXECUTE = 1 << 63;
     }
 }
 
 impl EntryFlags {
-    /// Set flags based on the flags used in ELF header
-    pub fn from_elf_section_flags(section: &ElfSectionHeader) -> Self {
-        let mut flags = EntryFlags::NO_EXECUTE;
+    /// Set flags based on the flags used in an ELF program header
+    pub fn from_elf_segment_flags(segment: &ProgramHeader) -> Self {
+        let mut flags = EntryFlags::PRESENT | EntryFlags::NO_EXECUTE;
 
-        for flag in section.flags {
+        for flag in segment.flags {
             match flag {
-                SectionFlags::ALLOC => flags.insert(EntryFlags::PRESENT),
-                SectionFlags::WRITE => flags.insert(EntryFlags::WRITABLE),
-                SectionFlags::EXECUTABLE => flags.remove(EntryFlags::NO_EXECUTE),
+                SegmentFlags::WRITE => flags.insert(EntryFlags::WRITABLE),
+                SegmentFlags::EXECUTABLE => flags.remove(EntryFlags::NO_EXECUTE),
                 _ => continue,
             }
         }
@@ -4,6 +4,7 @@ use x86_64::structures::Frame;
 
 use super::{
     mapper::Mapper,
+    physical::OffsetPhysicalMemory,
     table::{Level4, Table},
 };
 
@@ -22,7 +23,7 @@ impl InactivePageTable {
         let table = frame.start_address() as *mut Table<Level4>;
 
         Self {
-            mapper: Mapper::new(table),
+            mapper: Mapper::new(table, OffsetPhysicalMemory),
             frame,
         }
     }
@@ -1,32 +1,40 @@
 use core::ptr::NonNull;
 
 use x86_64::{
-    align_down_to_page, align_up, invalidate_address,
+    align_down, align_down_to_page,
     structures::{Frame, Page, HUGE_L2_PAGE_SIZE, HUGE_L3_PAGE_SIZE, PAGE_SIZE},
     PhysicalAddress, VirtualAddress,
 };
 
 use super::{
     entry::EntryFlags,
+    physical::{OffsetPhysicalMemory, PhysicalMemory},
     table::{Level4, Table},
 };
 use crate::memory::{frame_alloc::FrameAllocator, paging::ENTRY_COUNT};
 
-pub struct Mapper {
+pub struct Mapper<M: PhysicalMemory = OffsetPhysicalMemory> {
     table: NonNull<Table<Level4>>,
+    memory: M,
 }
 
-impl Mapper {
-    /// Creates a new mapper with the given page 4 table
+impl<M: PhysicalMemory> Mapper<M> {
+    /// Creates a new mapper with the given page 4 table, accessing lower level tables through `memory`
     ///
     /// # Safety
     /// This should only ever be called with a valid table
-    pub unsafe fn new(table: *mut Table<Level4>) -> Self {
+    pub unsafe fn new(table: *mut Table<Level4>, memory: M) -> Self {
         Self {
             table: NonNull::new_unchecked(table),
+            memory,
         }
     }
 
+    /// Returns the physical memory accessor used to reach lower level tables
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
     /// Returns a reference to the in-use level 4 table
     pub fn p4(&self) -> &Table<Level4> {
         unsafe { self.table.as_ref() }
@@ -37,6 +45,12 @@ impl Mapper {
         unsafe { self.table.as_mut() }
     }
 
+    /// Returns a mutable reference to the level 4 table alongside the memory accessor, so lower
+    /// level tables can be reached while it's borrowed
+    fn tables_mut(&mut self) -> (&mut Table<Level4>, &M) {
+        (unsafe { self.table.as_mut() }, &self.memory)
+    }
+
     /// Translates a given virtual address to its physical address
     pub fn translate(&self, virt_addr: VirtualAddress) -> Option<PhysicalAddress> {
         let offset = virt_addr % PAGE_SIZE;
@@ -47,7 +61,7 @@ impl Mapper {
 
     /// Finds the frame that a given page points to
     pub fn translate_page(&self, page: Page) -> Option<Frame> {
-        let p3 = self.p4().next_table(page.p4_index());
+        let p3 = self.p4().next_table(page.p4_index(), &self.memory);
 
         let huge_page = || {
             p3.and_then(|p3| {
@@ -65,7 +79,7 @@ impl Mapper {
                         return frame;
                     }
                 }
-                if let Some(p2) = p3.next_table(page.p3_index()) {
+                if let Some(p2) = p3.next_table(page.p3_index(), &self.memory) {
                     let p2_entry = &p2[page.p2_index()];
                     // 2MiB page?
                     if let Some(start_frame) = p2_entry.pointed_frame() {
@@ -82,8 +96,8 @@ impl Mapper {
             })
         };
 
-        p3.and_then(|p3| p3.next_table(page.p3_index()))
-            .and_then(|p2| p2.next_table(page.p2_index()))
+        p3.and_then(|p3| p3.next_table(page.p3_index(), &self.memory))
+            .and_then(|p2| p2.next_table(page.p2_index(), &self.memory))
             .and_then(|p1| p1[page.p1_index()].pointed_frame())
             .or_else(huge_page)
     }
@@ -96,10 +110,10 @@ impl Mapper {
         flags: EntryFlags,
         allocator: &mut A,
     ) {
-        let p4 = self.p4_mut();
-        let p3 = p4.next_table_create(page.p4_index(), allocator);
-        let p2 = p3.next_table_create(page.p3_index(), allocator);
-        let p1 = p2.next_table_create(page.p2_index(), allocator);
+        let (p4, memory) = self.tables_mut();
+        let p3 = p4.next_table_create(page.p4_index(), allocator, memory);
+        let p2 = p3.next_table_create(page.p3_index(), allocator, memory);
+        let p1 = p2.next_table_create(page.p2_index(), allocator, memory);
 
         assert!(p1[page.p1_index()].is_unused());
 
@@ -114,9 +128,9 @@ impl Mapper {
         flags: EntryFlags,
         allocator: &mut A,
     ) {
-        let p4 = self.p4_mut();
-        let p3 = p4.next_table_create(page.p4_index(), allocator);
-        let p2 = p3.next_table_create(page.p3_index(), allocator);
+        let (p4, memory) = self.tables_mut();
+        let p3 = p4.next_table_create(page.p4_index(), allocator, memory);
+        let p2 = p3.next_table_create(page.p3_index(), allocator, memory);
 
         assert_eq!(page.p1_index(), 0);
         assert!(p2[page.p2_index()].is_unused());
@@ -132,8 +146,8 @@ impl Mapper {
         flags: EntryFlags,
         allocator: &mut A,
     ) {
-        let p4 = self.p4_mut();
-        let p3 = p4.next_table_create(page.p4_index(), allocator);
+        let (p4, memory) = self.tables_mut();
+        let p3 = p4.next_table_create(page.p4_index(), allocator, memory);
 
         assert_eq!(page.p1_index(), 0);
         assert_eq!(page.p2_index(), 0);
@@ -177,11 +191,11 @@ impl Mapper {
 
         // check how addresses are aligned relative to each other to check if huge tables are even possible
         let huge_l3_possible =
-            use_huge_tables && is_aligned(start_virt - start_phys, HUGE_L3_PAGE_SIZE);
+            use_huge_tables && is_aligned(start_virt.wrapping_sub(start_phys), HUGE_L3_PAGE_SIZE);
         let huge_l2_possible =
-            use_huge_tables && is_aligned(start_virt - start_phys, HUGE_L2_PAGE_SIZE);
+            use_huge_tables && is_aligned(start_virt.wrapping_sub(start_phys), HUGE_L2_PAGE_SIZE);
 
-        let to_map = (end_phys - start_phys).min(start_virt - end_virt);
+        let to_map = (end_phys - start_phys).min(end_virt - start_virt);
         let mut mapped = 0;
 
         while mapped <= to_map {
@@ -227,6 +241,78 @@ impl Mapper {
         }
     }
 
+    /// Changes the flags of a mapped page with `update`, keeping the frame it points to and
+    /// returning the previous flags
+    pub fn update_flags(
+        &mut self,
+        page: Page,
+        update: impl FnOnce(EntryFlags) -> EntryFlags,
+    ) -> EntryFlags {
+        let (p4, memory) = self.tables_mut();
+        let p1 = p4
+            .next_table_mut(page.p4_index(), memory)
+            .and_then(|p3| p3.next_table_mut(page.p3_index(), memory))
+            .and_then(|p2| p2.next_table_mut(page.p2_index(), memory))
+            .expect("mapping code does not support huge pages");
+
+        let entry = &mut p1[page.p1_index()];
+        let frame = entry.pointed_frame().expect("page is not mapped");
+        let flags = entry.flags();
+
+        entry.set(frame, update(flags) | EntryFlags::PRESENT);
+        memory.invalidate(page.start_address());
+
+        flags
+    }
+
+    /// Returns the flags a page is mapped with, or `None` if it isn't mapped
+    pub fn page_flags(&self, page: Page) -> Option<EntryFlags> {
+        self.p4()
+            .next_table(page.p4_index(), &self.memory)
+            .and_then(|p3| p3.next_table(page.p3_index(), &self.memory))
+            .and_then(|p2| p2.next_table(page.p2_index(), &self.memory))
+            .map(|p1| &p1[page.p1_index()])
+            .filter(|entry| !entry.is_unused())
+            .map(|entry| entry.flags())
+    }
+
+    /// Points a mapped page at a different frame with the given flags, returning the frame it
+    /// pointed to before without freeing it
+    pub fn remap(&mut self, page: Page, frame: Frame, flags: EntryFlags) -> Frame {
+        let (p4, memory) = self.tables_mut();
+        let p1 = p4
+            .next_table_mut(page.p4_index(), memory)
+            .and_then(|p3| p3.next_table_mut(page.p3_index(), memory))
+            .and_then(|p2| p2.next_table_mut(page.p2_index(), memory))
+            .expect("mapping code does not support huge pages");
+
+        let entry = &mut p1[page.p1_index()];
+        let old = entry.pointed_frame().expect("page is not mapped");
+
+        entry.set(frame, flags | EntryFlags::PRESENT);
+        memory.invalidate(page.start_address());
+
+        old
+    }
+
+    /// Unmaps a given page, returning the frame it pointed to without freeing it or any tables
+    pub fn unmap_frame(&mut self, page: Page) -> Frame {
+        let (p4, memory) = self.tables_mut();
+        let p1 = p4
+            .next_table_mut(page.p4_index(), memory)
+            .and_then(|p3| p3.next_table_mut(page.p3_index(), memory))
+            .and_then(|p2| p2.next_table_mut(page.p2_index(), memory))
+            .expect("mapping code does not support huge pages");
+
+        let entry = &mut p1[page.p1_index()];
+        let frame = entry.pointed_frame().expect("page is not mapped");
+
+        entry.set_unused();
+        memory.invalidate(page.start_address());
+
+        frame
+    }
+
     /// Unmaps a given page
     pub fn unmap<A>(&mut self, page: Page, allocator: &mut A, free_unused_tables: bool)
     where
@@ -234,21 +320,21 @@ impl Mapper {
     {
         assert!(self.translate(page.start_address()).is_some());
 
-        let p3 = self
-            .p4_mut()
-            .next_table_mut(page.p4_index())
+        let (p4, memory) = self.tables_mut();
+        let p3 = p4
+            .next_table_mut(page.p4_index(), memory)
             .expect("mapping code does not support huge pages");
         let p2 = p3
-            .next_table_mut(page.p3_index())
+            .next_table_mut(page.p3_index(), memory)
             .expect("mapping code does not support huge pages");
         let p1 = p2
-            .next_table_mut(page.p2_index())
+            .next_table_mut(page.p2_index(), memory)
             .expect("mapping code does not support huge pages");
 
         let frame = p1[page.p1_index()].pointed_frame().unwrap();
         p1[page.p1_index()].set_unused();
 
-        invalidate_address(frame.start_address());
+        memory.invalidate(page.start_address());
         allocator.deallocate_frame(frame);
 
         // TODO: remove repeated code
@@ -258,8 +344,6 @@ impl Mapper {
                 p2[page.p2_index()].set_unused();
 
                 log::trace!("freeing unused p1 table at frame {p1_frame:?}");
-
-                invalidate_address(p1_frame.start_address());
                 allocator.deallocate_frame(p1_frame);
             }
 
@@ -268,16 +352,14 @@ impl Mapper {
                 p3[page.p3_index()].set_unused();
 
                 log::trace!("freeing unused p2 table at frame {p2_frame:?}");
-                invalidate_address(p2_frame.start_address());
                 allocator.deallocate_frame(p2_frame);
             }
 
             if p3.is_empty() {
-                let p3_frame = self.p4()[page.p4_index()].pointed_frame().unwrap();
-                self.p4_mut()[page.p4_index()].set_unused();
+                let p3_frame = p4[page.p4_index()].pointed_frame().unwrap();
+                p4[page.p4_index()].set_unused();
 
                 log::trace!("freeing unused p3 table at frame {p3_frame:?}");
-                invalidate_address(p3_frame.start_address());
                 allocator.deallocate_frame(p3_frame);
             }
         }
@@ -285,5 +367,445 @@ impl Mapper {
 }
 
 fn is_aligned(addr: usize, alignment: usize) -> bool {
-    align_up(addr, alignment) == addr
+    align_down(addr, alignment) == addr
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        vec,
+        vec::Vec,
+    };
+
+    use proptest::prelude::*;
+    use x86_64::{
+        align_down_to_page,
+        structures::{Frame, Page, HUGE_L2_PAGE_SIZE, HUGE_L3_PAGE_SIZE, PAGE_SIZE},
+        PhysicalAddress, VirtualAddress,
+    };
+
+    use super::Mapper;
+    use crate::memory::{
+        frame_alloc::FrameAllocator,
+        paging::{
+            entry::EntryFlags,
+            physical::PhysicalMemory,
+            table::{Level4, Table},
+            PHYS_MEM_OFFSET,
+        },
+    };
+
+    /// Number of frames of fake RAM, which is plenty for the tables of a few hundred pages
+    const FRAME_COUNT: usize = 512;
+
+    /// Heap allocated stand-in for physical memory, with the level 4 table in frame 0
+    struct FakeMemory {
+        ram: Vec<u64>,
+        base: *mut u8,
+    }
+
+    impl FakeMemory {
+        fn new() -> Self {
+            let mut ram = vec![0u64; FRAME_COUNT * PAGE_SIZE / 8];
+            let base = ram.as_mut_ptr().cast();
+
+            Self { ram, base }
+        }
+    }
+
+    impl PhysicalMemory for FakeMemory {
+        fn ptr(&self, addr: PhysicalAddress) -> *mut u8 {
+            assert!(
+                addr < self.ram.len() * 8,
+                "physical address {addr:#X} is outside fake RAM"
+            );
+
+            self.base.wrapping_add(addr)
+        }
+
+        fn invalidate(&self, _addr: VirtualAddress) {}
+    }
+
+    /// Hands out frames of fake RAM, checking every frame is freed exactly once
+    struct MockAllocator {
+        free: Vec<usize>,
+        allocated: BTreeSet<usize>,
+    }
+
+    impl MockAllocator {
+        fn new() -> Self {
+            Self {
+                free: (1..FRAME_COUNT).rev().collect(),
+                allocated: BTreeSet::new(),
+            }
+        }
+
+        fn outstanding(&self) -> usize {
+            self.allocated.len()
+        }
+    }
+
+    impl FrameAllocator for MockAllocator {
+        fn allocate_frame(&mut self) -> Option<Frame> {
+            let number = self.free.pop()?;
+            self.allocated.insert(number);
+
+            Some(Frame { number })
+        }
+
+        fn deallocate_frame(&mut self, frame: Frame) {
+            assert!(
+                self.allocated.remove(&frame.number),
+                "frame {} was freed without being allocated",
+                frame.number
+            );
+            self.free.push(frame.number);
+        }
+    }
+
+    fn mapper() -> Mapper<FakeMemory> {
+        let memory = FakeMemory::new();
+        let table = memory.ptr(0) as *mut Table<Level4>;
+
+        unsafe { Mapper::new(table, memory) }
+    }
+
+    #[test]
+    fn unmapped_address_does_not_translate() {
+        let mapper = mapper();
+
+        assert_eq!(mapper.translate(0), None);
+        assert_eq!(mapper.translate(0xFFFF_FFFF_8000_1234), None);
+    }
+
+    #[test]
+    fn map_to_translates_with_offset() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        let frame = allocator.allocate_frame().unwrap();
+        let frame_addr = frame.start_address();
+        let page = Page::containing_address(0xFFFF_FFFF_8000_0000);
+
+        mapper.map_to(page, frame, EntryFlags::WRITABLE, &mut allocator);
+
+        assert_eq!(
+            mapper.translate(0xFFFF_FFFF_8000_0123),
+            Some(frame_addr + 0x123)
+        );
+        assert_eq!(mapper.translate(0xFFFF_FFFF_8000_1000), None);
+    }
+
+    #[test]
+    fn map_shares_tables() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        for addr in [0x1000, 0x2000] {
+            mapper.map(
+                Page::containing_address(addr),
+                EntryFlags::WRITABLE,
+                &mut allocator,
+            );
+        }
+
+        // both pages share the same p3, p2 and p1 tables
+        assert_eq!(allocator.outstanding(), 3 + 2);
+    }
+
+    #[test]
+    fn update_flags_keeps_frame() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+        let page = Page::containing_address(0x4000_0000);
+
+        mapper.map(page, EntryFlags::WRITABLE, &mut allocator);
+        let frame = mapper.translate_page(page);
+
+        let old = mapper.update_flags(page, |flags| {
+            (flags - EntryFlags::WRITABLE) | EntryFlags::NO_EXECUTE
+        });
+        assert_eq!(
+            old.bits(),
+            (EntryFlags::PRESENT | EntryFlags::WRITABLE).bits()
+        );
+
+        let old = mapper.update_flags(page, |flags| flags);
+        assert_eq!(
+            old.bits(),
+            (EntryFlags::PRESENT | EntryFlags::NO_EXECUTE).bits()
+        );
+        assert_eq!(mapper.translate_page(page), frame);
+    }
+
+    #[test]
+    fn remap_returns_old_frame() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+        let page = Page::containing_address(0x4000_0000);
+
+        assert_eq!(mapper.page_flags(page).map(|flags| flags.bits()), None);
+
+        mapper.map(page, EntryFlags::WRITABLE, &mut allocator);
+        let old = mapper.translate_page(page).unwrap();
+        let new = allocator.allocate_frame().unwrap();
+        let new_number = new.number;
+
+        assert_eq!(mapper.remap(page, new, EntryFlags::COPY_ON_WRITE), old);
+        assert_eq!(
+            mapper.translate_page(page),
+            Some(Frame { number: new_number })
+        );
+        assert_eq!(
+            mapper.page_flags(page).map(|flags| flags.bits()),
+            Some((EntryFlags::PRESENT | EntryFlags::COPY_ON_WRITE).bits())
+        );
+    }
+
+    #[test]
+    fn unmap_frame_keeps_frame_and_tables() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+        let page = Page::containing_address(0x4000_0000);
+
+        mapper.map(page, EntryFlags::WRITABLE, &mut allocator);
+        let frame = mapper.translate_page(page).unwrap();
+
+        assert_eq!(mapper.unmap_frame(page), frame);
+        assert_eq!(mapper.translate(0x4000_0000), None);
+        assert_eq!(allocator.outstanding(), 4);
+    }
+
+    #[test]
+    fn unmap_frees_unused_tables() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+        let page = Page::containing_address(0x4000_0000);
+
+        mapper.map(page, EntryFlags::WRITABLE, &mut allocator);
+        mapper.unmap(page, &mut allocator, true);
+
+        assert_eq!(mapper.translate(0x4000_0000), None);
+        assert_eq!(allocator.outstanding(), 0);
+        assert!(mapper.p4().is_empty());
+    }
+
+    #[test]
+    fn unmap_keeps_tables_when_asked() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+        let page = Page::containing_address(0x4000_0000);
+
+        mapper.map(page, EntryFlags::WRITABLE, &mut allocator);
+        mapper.unmap(page, &mut allocator, false);
+
+        assert_eq!(mapper.translate(0x4000_0000), None);
+        assert_eq!(allocator.outstanding(), 3);
+    }
+
+    #[test]
+    fn unmap_keeps_tables_in_use() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        for addr in [0x1000, 0x2000] {
+            mapper.map(
+                Page::containing_address(addr),
+                EntryFlags::WRITABLE,
+                &mut allocator,
+            );
+        }
+        let other = mapper.translate(0x2000);
+
+        mapper.unmap(Page::containing_address(0x1000), &mut allocator, true);
+
+        assert_eq!(mapper.translate(0x1000), None);
+        assert_eq!(mapper.translate(0x2000), other);
+        assert_eq!(allocator.outstanding(), 3 + 1);
+    }
+
+    #[test]
+    fn huge_l2_page_translates() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        // huge pages point straight at their frames, so they don't need to be in fake RAM
+        let frame = Frame::containing_address(0x4000_0000);
+        mapper.map_to_huge_l2(
+            Page::containing_address(0xFFFF_FFFF_4000_0000),
+            frame,
+            EntryFlags::WRITABLE,
+            &mut allocator,
+        );
+
+        assert_eq!(mapper.translate(0xFFFF_FFFF_4012_3456), Some(0x4012_3456));
+        assert_eq!(
+            mapper.translate(0xFFFF_FFFF_4000_0000 + HUGE_L2_PAGE_SIZE),
+            None
+        );
+        assert_eq!(allocator.outstanding(), 2);
+    }
+
+    #[test]
+    fn huge_l3_page_translates() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        let frame = Frame::containing_address(0x1_0000_0000);
+        mapper.map_to_huge_l3(
+            Page::containing_address(PHYS_MEM_OFFSET),
+            frame,
+            EntryFlags::WRITABLE,
+            &mut allocator,
+        );
+
+        assert_eq!(
+            mapper.translate(PHYS_MEM_OFFSET + 0x3FFF_FFFF),
+            Some(0x1_3FFF_FFFF)
+        );
+        assert_eq!(mapper.translate(PHYS_MEM_OFFSET + HUGE_L3_PAGE_SIZE), None);
+        assert_eq!(allocator.outstanding(), 1);
+    }
+
+    #[test]
+    fn map_range_uses_huge_pages() {
+        let mut mapper = mapper();
+        let mut allocator = MockAllocator::new();
+
+        // 1GiB, then 2MiB, then 3 normal pages
+        let end = HUGE_L3_PAGE_SIZE + HUGE_L2_PAGE_SIZE + 3 * PAGE_SIZE - 1;
+        mapper.map_range(
+            (0, end),
+            (PHYS_MEM_OFFSET, PHYS_MEM_OFFSET + end),
+            EntryFlags::WRITABLE,
+            &mut allocator,
+            true,
+        );
+
+        for addr in [0, HUGE_L3_PAGE_SIZE - 1, HUGE_L3_PAGE_SIZE, end] {
+            assert_eq!(mapper.translate(PHYS_MEM_OFFSET + addr), Some(addr));
+        }
+        assert_eq!(mapper.translate(PHYS_MEM_OFFSET + end + 1), None);
+
+        // one p3, one p2 for the second GiB, and one p1 for the normal pages
+        assert_eq!(allocator.outstanding(), 3);
+    }
+
+    /// Page numbers of canonical addresses in either half of the address space
+    fn page_number() -> impl Strategy<Value = usize> {
+        prop_oneof![
+            0..(0x0000_8000_0000_0000 / PAGE_SIZE),
+            (0xFFFF_8000_0000_0000 / PAGE_SIZE)..=(usize::MAX / PAGE_SIZE),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn map_translate_unmap_round_trip(
+            numbers in prop::collection::btree_set(page_number(), 1..32),
+            offset in 0..PAGE_SIZE,
+            unmap_count in 0..32usize,
+        ) {
+            let mut mapper = mapper();
+            let mut allocator = MockAllocator::new();
+
+            let mut frames = BTreeMap::new();
+            for &number in &numbers {
+                mapper.map(Page { number }, EntryFlags::WRITABLE, &mut allocator);
+
+                let frame = mapper.translate_page(Page { number }).unwrap();
+                frames.insert(number, frame.number);
+            }
+
+            // every page got its own frame, and addresses within it translate with the same offset
+            let distinct: BTreeSet<_> = frames.values().collect();
+            prop_assert_eq!(distinct.len(), numbers.len());
+
+            for (&number, &frame) in &frames {
+                let addr = Page { number }.start_address() + offset;
+                prop_assert_eq!(mapper.translate(addr), Some(frame * PAGE_SIZE + offset));
+            }
+
+            // unmapping some pages leaves the rest untouched
+            let (unmapped, kept): (Vec<_>, Vec<_>) = numbers
+                .iter()
+                .enumerate()
+                .partition(|(index, _)| *index < unmap_count);
+
+            for (_, &number) in &unmapped {
+                mapper.unmap(Page { number }, &mut allocator, true);
+                prop_assert_eq!(mapper.translate_page(Page { number }), None);
+            }
+
+            for (_, number) in &kept {
+                let frame = mapper.translate_page(Page { number: **number }).map(|frame| frame.number);
+                prop_assert_eq!(frame, Some(frames[number]));
+            }
+
+            // and once everything is unmapped, every table has been freed
+            for (_, &number) in &kept {
+                mapper.unmap(Page { number }, &mut allocator, true);
+            }
+
+            prop_assert_eq!(allocator.outstanding(), 0);
+            prop_assert!(mapper.p4().is_empty());
+        }
+
+        #[test]
+        fn map_range_translates_every_address(
+            start_page in 0..(1usize << 20),
+            len in 0..(3 * HUGE_L3_PAGE_SIZE),
+            probes in prop::collection::vec(any::<prop::sample::Index>(), 16),
+        ) {
+            let mut mapper = mapper();
+            let mut allocator = MockAllocator::new();
+
+            // keeping virtual and physical addresses aligned with each other allows huge pages
+            let start = start_page * PAGE_SIZE;
+            mapper.map_range(
+                (start, start + len),
+                (PHYS_MEM_OFFSET + start, PHYS_MEM_OFFSET + start + len),
+                EntryFlags::WRITABLE,
+                &mut allocator,
+                true,
+            );
+
+            for probe in probes {
+                let addr = start + probe.index(len + 1);
+                prop_assert_eq!(mapper.translate(PHYS_MEM_OFFSET + addr), Some(addr));
+            }
+
+            let after = align_down_to_page(start + len) + PAGE_SIZE;
+            prop_assert_eq!(mapper.translate(PHYS_MEM_OFFSET + after), None);
+        }
+
+        #[test]
+        fn map_range_without_huge_pages(
+            start_page in 0..(1usize << 20),
+            virt_page in 0..(0x0000_8000_0000_0000 / PAGE_SIZE - 64),
+            page_count in 1..64usize,
+        ) {
+            let mut mapper = mapper();
+            let mut allocator = MockAllocator::new();
+
+            let start = start_page * PAGE_SIZE;
+            let virt_start = virt_page * PAGE_SIZE;
+            let len = page_count * PAGE_SIZE - 1;
+
+            mapper.map_range(
+                (start, start + len),
+                (virt_start, virt_start + len),
+                EntryFlags::WRITABLE,
+                &mut allocator,
+                false,
+            );
+
+            for page in 0..page_count {
+                let offset = page * PAGE_SIZE;
+                prop_assert_eq!(mapper.translate(virt_start + offset), Some(start + offset));
+            }
+            prop_assert_eq!(mapper.translate(virt_start + len + 1), None);
+        }
+    }
 }
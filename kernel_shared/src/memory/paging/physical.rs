@@ -0,0 +1,26 @@
+use x86_64::{invalidate_address, PhysicalAddress, VirtualAddress};
+
+use super::PHYS_MEM_OFFSET;
+
+/// Provides access to the physical memory that page tables live in
+pub trait PhysicalMemory {
+    /// Returns a pointer through which the given physical address can be accessed
+    fn ptr(&self, addr: PhysicalAddress) -> *mut u8;
+
+    /// Called after the mapping of a virtual address changes, so any cached translation can be flushed
+    fn invalidate(&self, addr: VirtualAddress);
+}
+
+/// Physical memory accessed through the mapping at [PHYS_MEM_OFFSET], as set up by the loader
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OffsetPhysicalMemory;
+
+impl PhysicalMemory for OffsetPhysicalMemory {
+    fn ptr(&self, addr: PhysicalAddress) -> *mut u8 {
+        (addr + PHYS_MEM_OFFSET) as *mut u8
+    }
+
+    fn invalidate(&self, addr: VirtualAddress) {
+        invalidate_address(addr);
+    }
+}
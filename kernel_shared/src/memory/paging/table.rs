@@ -3,9 +3,12 @@ use core::{
     ops::{Index, IndexMut},
 };
 
+use x86_64::PhysicalAddress;
+
 use super::{
     entry::{Entry, EntryFlags},
-    ENTRY_COUNT, PHYS_MEM_OFFSET,
+    physical::PhysicalMemory,
+    ENTRY_COUNT,
 };
 use crate::memory::frame_alloc::FrameAllocator;
 
@@ -67,51 +70,63 @@ impl<L: TableLevel> Table<L> {
 }
 
 impl<L: HierarchicalLevel> Table<L> {
-    /// Finds address of the next level table at the specified index
-    fn next_table_address(&self, index: usize) -> Option<usize> {
+    /// Finds physical address of the next level table at the specified index
+    fn next_table_address(&self, index: usize) -> Option<PhysicalAddress> {
         let entry_flags = self[index].flags();
 
         if entry_flags.contains(EntryFlags::PRESENT) && !entry_flags.contains(EntryFlags::HUGE_PAGE)
         {
-            let address = self[index].pointed_frame().unwrap().start_address() + PHYS_MEM_OFFSET;
-            Some(address)
+            Some(self[index].pointed_frame().unwrap().start_address())
         } else {
             None
         }
     }
 
     /// Finds the next level table with the specified index
-    pub fn next_table(&self, index: usize) -> Option<&Table<L::NextLevel>> {
+    pub fn next_table<M: PhysicalMemory>(
+        &self,
+        index: usize,
+        memory: &M,
+    ) -> Option<&Table<L::NextLevel>> {
         self.next_table_address(index)
-            .map(|address| unsafe { &*(address as *const _) })
+            .map(|address| unsafe { &*(memory.ptr(address) as *const _) })
     }
 
     /// Finds the next level table with the specified index
-    pub fn next_table_mut(&mut self, index: usize) -> Option<&mut Table<L::NextLevel>> {
+    pub fn next_table_mut<M: PhysicalMemory>(
+        &mut self,
+        index: usize,
+        memory: &M,
+    ) -> Option<&mut Table<L::NextLevel>> {
         self.next_table_address(index)
-            .map(|address| unsafe { &mut *(address as *mut _) })
+            .map(|address| unsafe { &mut *(memory.ptr(address) as *mut _) })
     }
 
     /// Finds the next level table with the specified index, creating a blank table if it doesnt exist
-    pub fn next_table_create<A: FrameAllocator>(
+    pub fn next_table_create<A: FrameAllocator, M: PhysicalMemory>(
         &mut self,
         index: usize,
         allocator: &mut A,
+        memory: &M,
     ) -> &mut Table<L::NextLevel> {
-        if self.next_table(index).is_none() {
+        if self.next_table(index, memory).is_none() {
             assert!(
                 !self.entries[index].flags().contains(EntryFlags::HUGE_PAGE),
                 "mapping code does not support huge pages"
             );
 
-            // create, set, and zero a new frame
+            // create, set, and zero a new frame. tables are always user accessible, since whether
+            // user code can reach a page is decided by the page's own entry
             let frame = allocator.allocate_frame().expect("no available frames");
 
-            self.entries[index].set(frame, EntryFlags::PRESENT | EntryFlags::WRITABLE);
-            self.next_table_mut(index).unwrap().zero();
+            self.entries[index].set(
+                frame,
+                EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE,
+            );
+            self.next_table_mut(index, memory).unwrap().zero();
         }
 
-        self.next_table_mut(index).unwrap()
+        self.next_table_mut(index, memory).unwrap()
     }
 }
 
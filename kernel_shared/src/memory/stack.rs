@@ -0,0 +1,116 @@
+//! Layout of the region stacks are mapped in.
+//!
+//! The region is split into fixed size slots, with each stack mapped at the top of its slot and
+//! the rest of the slot left unmapped, so running off the bottom of a stack faults instead of
+//! silently writing over whatever lies below it.
+
+/*  +------------------+ <- slot top, initial stack pointer
+    | stack            |
+    +------------------+
+    | guard            |  unmapped, at least one page
+    +------------------+ <- slot base, top of the previous slot
+*/
+
+use core::ops::DerefMut;
+
+use x86_64::structures::{Page, PAGE_SIZE};
+
+use super::{
+    frame_alloc::FrameAllocator,
+    paging::{entry::EntryFlags, mapper::Mapper},
+};
+
+/// Start of the stack region
+pub const STACK_REGION_START: usize = 0xFFFF_FE00_0000_0000;
+
+/// Virtual space reserved for each stack, including its guard
+pub const STACK_SLOT_SIZE: usize = 1024 * 1024; // 1 MiB
+
+/// Number of slots in the stack region
+pub const STACK_SLOTS: usize = 4096;
+
+/// Largest stack that still leaves a guard page in its slot
+pub const MAX_STACK_SIZE: usize = STACK_SLOT_SIZE - PAGE_SIZE;
+
+/// Slot of the stack the kernel starts on, mapped by the loader
+pub const KERNEL_STACK_SLOT: usize = 0;
+
+/// Returns the address just past the top of the given slot, which is the initial stack pointer
+pub const fn slot_top(slot: usize) -> usize {
+    assert!(slot < STACK_SLOTS, "stack slot out of range");
+
+    STACK_REGION_START + (slot + 1) * STACK_SLOT_SIZE
+}
+
+/// Returns the slot an address lies in, if it's in the stack region
+pub const fn slot_containing(addr: usize) -> Option<usize> {
+    if addr < STACK_REGION_START {
+        return None;
+    }
+
+    let slot = (addr - STACK_REGION_START) / STACK_SLOT_SIZE;
+    if slot < STACK_SLOTS {
+        Some(slot)
+    } else {
+        None
+    }
+}
+
+/// Maps a stack of `size` bytes at the top of a slot, returning the initial stack pointer
+pub fn map_stack<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
+    table: &mut T,
+    alloc: &mut A,
+    slot: usize,
+    size: usize,
+) -> usize {
+    assert!(
+        size > 0 && size <= MAX_STACK_SIZE,
+        "stack of {size:#X} bytes doesn't fit in a slot with a guard page"
+    );
+
+    let top = slot_top(slot);
+    let start_page = Page::containing_address(top - size);
+    let end_page = Page::containing_address(top - 1);
+
+    for page in Page::range_inclusive(start_page, end_page) {
+        table.map(page, EntryFlags::WRITABLE | EntryFlags::NO_EXECUTE, alloc);
+    }
+
+    top
+}
+
+/// Unmaps a stack mapped with [map_stack], freeing its frames
+pub fn unmap_stack<A: FrameAllocator, T: DerefMut<Target = Mapper>>(
+    table: &mut T,
+    alloc: &mut A,
+    slot: usize,
+    size: usize,
+) {
+    let top = slot_top(slot);
+    let start_page = Page::containing_address(top - size);
+    let end_page = Page::containing_address(top - 1);
+
+    for page in Page::range_inclusive(start_page, end_page) {
+        table.unmap(page, alloc, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_are_contiguous() {
+        assert_eq!(slot_top(0), STACK_REGION_START + STACK_SLOT_SIZE);
+        assert_eq!(slot_top(1) - slot_top(0), STACK_SLOT_SIZE);
+    }
+
+    #[test]
+    fn addresses_map_to_their_slot() {
+        assert_eq!(slot_containing(STACK_REGION_START - 1), None);
+        assert_eq!(slot_containing(STACK_REGION_START), Some(0));
+        assert_eq!(slot_containing(slot_top(0) - 1), Some(0));
+        assert_eq!(slot_containing(slot_top(0)), Some(1));
+        assert_eq!(slot_containing(slot_top(STACK_SLOTS - 1)), None);
+    }
+}
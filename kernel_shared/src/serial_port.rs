@@ -30,6 +30,9 @@ macro_rules! wait_for {
 pub struct SerialPort(u16);
 
 impl SerialPort {
+    /// Size of the transmit FIFO enabled by [Self::init]
+    pub const FIFO_SIZE: usize = 16;
+
     /// Creates a new serial port at the given port
     ///
     /// # Safety
@@ -65,6 +68,41 @@ impl SerialPort {
         }
     }
 
+    /// Checks if a UART responds at the port, since reads from a missing port return all ones
+    pub fn is_present(&self) -> bool {
+        unsafe { self.port_line_status().read() != 0xFF }
+    }
+
+    /// Sets which events raise an interrupt
+    pub fn set_interrupts(&mut self, interrupts: InterruptEnableFlags) {
+        unsafe {
+            self.port_int_en().write(interrupts.bits());
+        }
+    }
+
+    /// Receives a byte, returning `None` if no data is waiting
+    pub fn receive(&mut self) -> Option<u8> {
+        if self.line_status().contains(LineStatusFlags::INPUT_FULL) {
+            Some(unsafe { self.port_data().read() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the transmit FIFO is empty, so up to [Self::FIFO_SIZE] bytes can be sent
+    /// without waiting
+    pub fn is_output_empty(&self) -> bool {
+        self.line_status().contains(LineStatusFlags::OUTPUT_EMPTY)
+    }
+
+    /// Sends a byte as-is without waiting, which must only be called when there is room in the
+    /// transmit FIFO
+    pub fn send_raw(&mut self, data: u8) {
+        unsafe {
+            self.port_data().write(data);
+        }
+    }
+
     /// Sends a byte
     pub fn send(&mut self, data: u8) {
         unsafe {
@@ -130,6 +168,17 @@ impl Write for SerialPort {
     }
 }
 
+bitflags! {
+    /// Events which can raise an interrupt
+    #[derive(Debug, Clone, Copy)]
+    pub struct InterruptEnableFlags: u8 {
+        const RECEIVED_DATA = 1;
+        const TRANSMIT_EMPTY = 1 << 1;
+        const LINE_STATUS = 1 << 2;
+        const MODEM_STATUS = 1 << 3;
+    }
+}
+
 bitflags! {
     /// Line status flags
     struct LineStatusFlags: u8 {
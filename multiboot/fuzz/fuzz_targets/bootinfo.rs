@@ -0,0 +1,86 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use multiboot::{elf_symbols::ElfSectionHeader, BootInfo, FramebufferColour};
+
+fuzz_target!(|data: &[u8]| {
+    // boot information has to be 8-byte aligned, which the fuzzer's input isn't
+    let mut words = vec![0u64; data.len().div_ceil(8)];
+    let aligned =
+        unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr().cast::<u8>(), data.len()) };
+    aligned.copy_from_slice(data);
+
+    // the parser wants `'static` data, which is fine as long as nothing parsed outlives `words`
+    let bytes: &'static [u8] = unsafe { core::slice::from_raw_parts(aligned.as_ptr(), data.len()) };
+
+    if let Ok(info) = BootInfo::parse(bytes) {
+        exercise(&info);
+    }
+
+    drop(words);
+});
+
+/// Touches everything the parser handed out, so bad slices show up under the sanitizers
+fn exercise(info: &BootInfo) {
+    for tag in info.tags() {
+        std::hint::black_box(tag);
+    }
+    std::hint::black_box(
+        info.modules()
+            .map(|module| module.string.to_bytes().len())
+            .sum::<usize>(),
+    );
+
+    if let Some(memory_map) = info.memory_map {
+        let total: u64 = memory_map
+            .entries
+            .iter()
+            .map(|entry| entry.length.wrapping_add(entry.mem_type as u64))
+            .fold(0, u64::wrapping_add);
+        std::hint::black_box(total);
+    }
+
+    if let Some(elf_symbols) = info.elf_symbols {
+        let loaded = elf_symbols
+            .headers()
+            .filter(ElfSectionHeader::is_loaded)
+            .count();
+        std::hint::black_box((
+            loaded,
+            elf_symbols.string_header().map(|header| header.size),
+        ));
+    }
+
+    if let Some(framebuffer) = info.framebuffer_info {
+        if let FramebufferColour::Indexed(palette) = framebuffer.colour {
+            std::hint::black_box(
+                palette
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .fold(0u8, u8::wrapping_add),
+            );
+        }
+    }
+
+    if let Some(rsdp) = info.acpi_old {
+        std::hint::black_box(rsdp.is_valid());
+    }
+
+    if let Some(xsdp) = info.acpi_new {
+        std::hint::black_box(xsdp.is_valid());
+    }
+
+    if let Some(smbios) = info.smbios {
+        std::hint::black_box(smbios.entry_point());
+    }
+
+    if let Some(efi_memory_map) = info.efi_memory_map {
+        let usable: u64 = efi_memory_map
+            .descriptors()
+            .filter(|descriptor| descriptor.is_usable())
+            .map(|descriptor| descriptor.page_count)
+            .fold(0, u64::wrapping_add);
+        std::hint::black_box(usable);
+    }
+}
@@ -0,0 +1,232 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::ffi::CStr;
+
+use super::tags::{elf_symbols::ElfSectionHeader, *};
+
+/// Builds multiboot2 boot information in the format GRUB passes to the kernel, for testing the
+/// parser without booting
+#[derive(Debug, Default, Clone)]
+pub struct BootInfoBuilder {
+    tags: Vec<u8>,
+}
+
+impl BootInfoBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a tag with the given type and contents, padding it to the next 8-byte boundary
+    pub fn tag(mut self, tag_type: u32, contents: &[u8]) -> Self {
+        let size = (contents.len() + 8) as u32;
+
+        self.tags.extend_from_slice(&tag_type.to_ne_bytes());
+        self.tags.extend_from_slice(&size.to_ne_bytes());
+        self.tags.extend_from_slice(contents);
+        self.tags.resize(self.tags.len().next_multiple_of(8), 0);
+
+        self
+    }
+
+    pub fn boot_command_line(self, command_line: &CStr) -> Self {
+        self.tag(BOOT_COMMAND_LINE, command_line.to_bytes_with_nul())
+    }
+
+    pub fn bootloader_name(self, name: &CStr) -> Self {
+        self.tag(BOOTLOADER_NAME, name.to_bytes_with_nul())
+    }
+
+    pub fn module(self, start: u32, end: u32, string: &CStr) -> Self {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&start.to_ne_bytes());
+        contents.extend_from_slice(&end.to_ne_bytes());
+        contents.extend_from_slice(string.to_bytes_with_nul());
+
+        self.tag(MODULE, &contents)
+    }
+
+    pub fn memory(self, memory: Memory) -> Self {
+        self.tag(MEMORY, bytes_of(&memory))
+    }
+
+    pub fn bios_device(self, bios_device: BiosDevice) -> Self {
+        self.tag(BIOS_DEVICE, bytes_of(&bios_device))
+    }
+
+    pub fn memory_map(self, entries: &[MemoryMapEntry]) -> Self {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&(core::mem::size_of::<MemoryMapEntry>() as u32).to_ne_bytes());
+        contents.extend_from_slice(&0u32.to_ne_bytes());
+
+        for entry in entries {
+            contents.extend_from_slice(&entry.base_addr.to_ne_bytes());
+            contents.extend_from_slice(&entry.length.to_ne_bytes());
+            contents.extend_from_slice(&(entry.mem_type as u32).to_ne_bytes());
+            contents.extend_from_slice(&0u32.to_ne_bytes());
+        }
+
+        self.tag(MEMORY_MAP, &contents)
+    }
+
+    pub fn vbe_info(self, vbe_info: VBEInfo) -> Self {
+        self.tag(VBE_INFO, bytes_of(&vbe_info))
+    }
+
+    pub fn framebuffer_info(
+        self,
+        buffer_addr: u64,
+        pitch: u32,
+        width: u32,
+        height: u32,
+        bpp: u8,
+        colour: FramebufferColour,
+    ) -> Self {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&buffer_addr.to_ne_bytes());
+        contents.extend_from_slice(&pitch.to_ne_bytes());
+        contents.extend_from_slice(&width.to_ne_bytes());
+        contents.extend_from_slice(&height.to_ne_bytes());
+        contents.push(bpp);
+
+        match colour {
+            FramebufferColour::Indexed(palette) => {
+                contents.extend_from_slice(&[0, 0, 0]);
+                contents.extend_from_slice(&(palette.len() as u16).to_ne_bytes());
+                contents.extend(palette.iter().flatten().copied());
+            }
+            FramebufferColour::Direct {
+                red_field_position,
+                red_mask_size,
+                green_field_position,
+                green_mask_size,
+                blue_field_position,
+                blue_mask_size,
+            } => {
+                contents.extend_from_slice(&[1, 0, 0]);
+                contents.extend_from_slice(&[
+                    red_field_position,
+                    red_mask_size,
+                    green_field_position,
+                    green_mask_size,
+                    blue_field_position,
+                    blue_mask_size,
+                ]);
+            }
+            // EGA text mode
+            FramebufferColour::None => contents.extend_from_slice(&[2, 0, 0]),
+        }
+
+        self.tag(FRAMEBUFFER_INFO, &contents)
+    }
+
+    pub fn elf_symbols(self, string_table_index: u32, headers: &[ElfSectionHeader]) -> Self {
+        let entry_size = core::mem::size_of::<ElfSectionHeader>() as u32;
+
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&(headers.len() as u32).to_ne_bytes());
+        contents.extend_from_slice(&entry_size.to_ne_bytes());
+        contents.extend_from_slice(&string_table_index.to_ne_bytes());
+        for header in headers {
+            contents.extend_from_slice(bytes_of(header));
+        }
+
+        self.tag(ELF_SYMBOLS, &contents)
+    }
+
+    pub fn apm(self, apm: Apm) -> Self {
+        self.tag(APM_TABLE, bytes_of(&apm))
+    }
+
+    pub fn efi_system_table_32(self, pointer: u32) -> Self {
+        self.tag(EFI_TABLE_32BIT, &pointer.to_ne_bytes())
+    }
+
+    pub fn efi_system_table_64(self, pointer: u64) -> Self {
+        self.tag(EFI_TABLE_64BIT, &pointer.to_ne_bytes())
+    }
+
+    pub fn smbios(self, major: u8, minor: u8, tables: &[u8]) -> Self {
+        let mut contents = vec![major, minor, 0, 0, 0, 0, 0, 0];
+        contents.extend_from_slice(tables);
+
+        self.tag(SMBIOS_TABLE, &contents)
+    }
+
+    pub fn acpi_old(self, rsdp: Rsdp) -> Self {
+        self.tag(ACPI_RSDP_OLD, bytes_of(&rsdp))
+    }
+
+    pub fn acpi_new(self, xsdp: Xsdp) -> Self {
+        self.tag(ACPI_RSDP_NEW, bytes_of(&xsdp))
+    }
+
+    pub fn networking(self, dhcp_ack: &[u8]) -> Self {
+        self.tag(NETWORKING, dhcp_ack)
+    }
+
+    /// Adds an EFI memory map, padding each descriptor to `descriptor_size` bytes
+    pub fn efi_memory_map(self, descriptor_size: u32, descriptors: &[EfiMemoryDescriptor]) -> Self {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&descriptor_size.to_ne_bytes());
+        contents.extend_from_slice(&1u32.to_ne_bytes());
+
+        for descriptor in descriptors {
+            let start = contents.len();
+            contents.extend_from_slice(bytes_of(descriptor));
+            contents.resize(start + descriptor_size as usize, 0);
+        }
+
+        self.tag(EFI_MEMORY_MAP, &contents)
+    }
+
+    pub fn efi_boot_services_not_terminated(self) -> Self {
+        self.tag(EFI_BOOT_SERVICES_NOT_TERMINATED, &[])
+    }
+
+    pub fn efi_image_handle_32(self, pointer: u32) -> Self {
+        self.tag(EFI_HANDLE_32BIT, &pointer.to_ne_bytes())
+    }
+
+    pub fn efi_image_handle_64(self, pointer: u64) -> Self {
+        self.tag(EFI_HANDLE_64BIT, &pointer.to_ne_bytes())
+    }
+
+    pub fn image_load_address(self, address: u32) -> Self {
+        self.tag(IMAGE_LOAD_ADDRESS, &address.to_ne_bytes())
+    }
+
+    /// Returns the finished boot information, including the fixed header and end tag
+    pub fn build(self) -> Vec<u8> {
+        let builder = self.tag(END, &[]);
+        let total_size = (builder.tags.len() + 8) as u32;
+
+        let mut bytes = Vec::with_capacity(total_size as usize);
+        bytes.extend_from_slice(&total_size.to_ne_bytes());
+        bytes.extend_from_slice(&0u32.to_ne_bytes());
+        bytes.extend_from_slice(&builder.tags);
+
+        bytes
+    }
+
+    /// Builds the boot information into an 8-byte aligned allocation that is never freed, as
+    /// required by [crate::BootInfo::parse]
+    pub fn leak(self) -> &'static [u8] {
+        leak_aligned(&self.build())
+    }
+}
+
+/// Copies bytes into an 8-byte aligned allocation that is never freed
+pub fn leak_aligned(bytes: &[u8]) -> &'static [u8] {
+    let words = vec![0u64; bytes.len().div_ceil(8)];
+    let words: &'static mut [u64] = Box::leak(words.into_boxed_slice());
+
+    let aligned =
+        unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr().cast::<u8>(), bytes.len()) };
+    aligned.copy_from_slice(bytes);
+
+    aligned
+}
+
+/// Returns the in-memory representation of a value
+fn bytes_of<T: FromBytes>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts((value as *const T).cast(), core::mem::size_of::<T>()) }
+}
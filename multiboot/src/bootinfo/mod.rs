@@ -1,3 +1,5 @@
+#[cfg(test)]
+pub mod builder;
 pub mod tags;
 
 use core::ffi::CStr;
@@ -6,92 +8,806 @@ pub use tags::*;
 
 /// Contains information returned by multiboot during the booting process
 #[derive(Default, Debug)]
-pub struct BootInfo<const MAX_MODULES: usize>
-where
-    [Option<Module>; MAX_MODULES]: Default,
-{
+pub struct BootInfo {
     pub addr: usize,
     pub total_size: usize,
     pub boot_command_line: Option<&'static CStr>,
     pub bootloader_name: Option<&'static CStr>,
-    pub modules: [Option<Module>; MAX_MODULES],
     pub memory: Option<Memory>,
     pub bios_device: Option<BiosDevice>,
     pub memory_map: Option<MemoryMap>,
     pub vbe_info: Option<VBEInfo>,
     pub framebuffer_info: Option<FramebufferInfo>,
     pub elf_symbols: Option<ElfSymbols>,
+    pub apm: Option<Apm>,
+    pub efi_system_table_32: Option<u32>,
+    pub efi_system_table_64: Option<u64>,
+    pub smbios: Option<Smbios>,
+    pub acpi_old: Option<Rsdp>,
+    pub acpi_new: Option<Xsdp>,
+    pub networking: Option<Networking>,
+    pub efi_memory_map: Option<EfiMemoryMap>,
+    /// Set if the kernel was started without calling `ExitBootServices()`
+    pub efi_boot_services_not_terminated: bool,
+    pub efi_image_handle_32: Option<u32>,
+    pub efi_image_handle_64: Option<u64>,
+    /// Physical address the loader image was loaded at, if it was relocated
+    pub image_load_address: Option<u32>,
+    /// Tags following the fixed header, which have already been validated
+    tags: &'static [u8],
 }
 
-impl<const MAX_MODULES: usize> BootInfo<MAX_MODULES>
-where
-    [Option<Module>; MAX_MODULES]: Default,
-{
+impl BootInfo {
     /// Creates a new bootinfo struct from the given address
     ///
     /// # Safety
     /// This is **very** unsafe and must only ever be called with the address returned by multiboot2
-    pub unsafe fn new(mut bootinfo: *const u32) -> Self {
-        let mut info = Self::default();
-
+    pub unsafe fn new(bootinfo: *const u32) -> Self {
         let total_size = *bootinfo as usize;
-        info.addr = bootinfo as usize;
-        info.total_size = total_size;
-
-        let mut advanced = 0; // keep track of how far into bootinfo we've advanced
-        assert_eq!(*bootinfo.add(1), 0); // second element in bootinfo is reserved - must equal 0
-
-        // now advance to first tag
-        bootinfo = bootinfo.add(2);
-        advanced += 8;
-
-        // keep track of module index
-        let mut module_index = 0;
-
-        // then iterate through each tag, setting each one as it's found
-        while advanced < total_size {
-            let (tag, size) = Tag::parse_tag(bootinfo);
-            // round size up to next multiple of 8 and advance pointers
-            let size = (size + 7) & !7;
-            bootinfo = bootinfo.byte_add(size);
-            advanced += size;
-
-            if let Some(tag) = tag {
-                match tag {
-                    Tag::End => break,
-                    Tag::BootCommandLine(boot_command_line) => {
-                        info.boot_command_line = Some(boot_command_line)
-                    }
-                    Tag::BootloaderName(bootloader_name) => {
-                        info.bootloader_name = Some(bootloader_name)
-                    }
-                    Tag::Module(module) => {
-                        if module_index == MAX_MODULES {
-                            panic!("too many modules");
-                        }
-
-                        info.modules[module_index] = Some(module);
-                        module_index += 1;
-                    }
-                    Tag::Memory(memory) => info.memory = Some(memory),
-                    Tag::BiosDevice(bios_device) => info.bios_device = Some(bios_device),
-                    Tag::MemoryMap(memory_map) => info.memory_map = Some(memory_map),
-                    Tag::VBEInfo(vbe_info) => info.vbe_info = Some(vbe_info),
-                    Tag::FramebufferInfo(framebuffer_info) => {
-                        info.framebuffer_info = Some(framebuffer_info)
-                    }
-                    Tag::ElfSymbols(elf_symbols) => info.elf_symbols = Some(elf_symbols),
+        let bytes = core::slice::from_raw_parts(bootinfo as *const u8, total_size);
+
+        Self::parse(bytes).expect("invalid multiboot information")
+    }
+
+    /// Parses boot information from a slice, which must start at the fixed header and be 8-byte
+    /// aligned
+    pub fn parse(bytes: &'static [u8]) -> Result<Self, ParseError> {
+        /*      +-------------------+
+        u32     | total_size        |
+        u32     | reserved          |
+                +-------------------+ */
+        if bytes.as_ptr() as usize % 8 != 0 {
+            return Err(ParseError::Misaligned);
+        }
+
+        let header = bytes.get(..8).ok_or(ParseError::UnexpectedEnd)?;
+        let total_size = u32::from_ne_bytes(header[..4].try_into().unwrap()) as usize;
+        let reserved = u32::from_ne_bytes(header[4..].try_into().unwrap());
+
+        if reserved != 0 {
+            return Err(ParseError::InvalidReserved);
+        }
+
+        let bytes = bytes.get(..total_size).ok_or(ParseError::UnexpectedEnd)?;
+        let mut info = Self {
+            addr: bytes.as_ptr() as usize,
+            total_size,
+            tags: &bytes[8..],
+            ..Default::default()
+        };
+
+        // then iterate through each tag, setting each one as it's found. repeated tags such as
+        // modules are read lazily instead, but still need checking here
+        for tag in TagIter::new(info.tags) {
+            match tag? {
+                Tag::End | Tag::Module(_) => {}
+                Tag::BootCommandLine(boot_command_line) => {
+                    info.boot_command_line = Some(boot_command_line)
+                }
+                Tag::BootloaderName(bootloader_name) => {
+                    info.bootloader_name = Some(bootloader_name)
                 }
+                Tag::Memory(memory) => info.memory = Some(memory),
+                Tag::BiosDevice(bios_device) => info.bios_device = Some(bios_device),
+                Tag::MemoryMap(memory_map) => info.memory_map = Some(memory_map),
+                Tag::VBEInfo(vbe_info) => info.vbe_info = Some(vbe_info),
+                Tag::FramebufferInfo(framebuffer_info) => {
+                    info.framebuffer_info = Some(framebuffer_info)
+                }
+                Tag::ElfSymbols(elf_symbols) => info.elf_symbols = Some(elf_symbols),
+                Tag::Apm(apm) => info.apm = Some(apm),
+                Tag::EfiSystemTable32(pointer) => info.efi_system_table_32 = Some(pointer),
+                Tag::EfiSystemTable64(pointer) => info.efi_system_table_64 = Some(pointer),
+                // a tag can be passed for each SMBIOS version, so keep the newest
+                Tag::Smbios(smbios) => match info.smbios {
+                    Some(old) if old.major > smbios.major => {}
+                    _ => info.smbios = Some(smbios),
+                },
+                Tag::AcpiOld(rsdp) => info.acpi_old = Some(rsdp),
+                Tag::AcpiNew(xsdp) => info.acpi_new = Some(xsdp),
+                Tag::Networking(networking) => info.networking = Some(networking),
+                Tag::EfiMemoryMap(efi_memory_map) => info.efi_memory_map = Some(efi_memory_map),
+                Tag::EfiBootServicesNotTerminated => info.efi_boot_services_not_terminated = true,
+                Tag::EfiImageHandle32(pointer) => info.efi_image_handle_32 = Some(pointer),
+                Tag::EfiImageHandle64(pointer) => info.efi_image_handle_64 = Some(pointer),
+                Tag::ImageLoadAddress(address) => info.image_load_address = Some(address),
             }
         }
 
-        info
+        Ok(info)
+    }
+
+    /// Returns an iterator over every tag, in the order they were passed by the bootloader
+    pub fn tags(&self) -> impl Iterator<Item = Tag> {
+        // tags were validated when parsing, so this never actually stops early
+        TagIter::new(self.tags).map_while(Result::ok)
+    }
+
+    /// Returns an iterator over every module loaded alongside the kernel
+    pub fn modules(&self) -> impl Iterator<Item = Module> {
+        self.tags().filter_map(|tag| match tag {
+            Tag::Module(module) => Some(module),
+            _ => None,
+        })
+    }
+
+    pub fn get_module(&self, module_str: &CStr) -> Option<Module> {
+        self.modules().find(|module| module.string == module_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::{
+        builder::BootInfoBuilder,
+        elf_symbols::{ElfSectionHeader, SectionFlags},
+        *,
+    };
+
+    fn parse(builder: BootInfoBuilder) -> Result<BootInfo, ParseError> {
+        BootInfo::parse(builder.leak())
+    }
+
+    /// Builds boot information, then lets `f` corrupt it before parsing
+    fn parse_corrupted(
+        builder: BootInfoBuilder,
+        f: impl FnOnce(&mut Vec<u8>),
+    ) -> Result<BootInfo, ParseError> {
+        let mut bytes = builder.build();
+        f(&mut bytes);
+
+        BootInfo::parse(builder::leak_aligned(&bytes))
+    }
+
+    fn rsdp() -> Rsdp {
+        Rsdp {
+            signature: *b"RSD PTR ",
+            checksum: 0,
+            oem_id: *b"CRABOS",
+            revision: 2,
+            rsdt_address: 0x1234,
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let bytes = BootInfoBuilder::new().leak();
+        let info = BootInfo::parse(bytes).unwrap();
+
+        assert_eq!(info.addr, bytes.as_ptr() as usize);
+        assert_eq!(info.total_size, 16);
+        assert!(info.boot_command_line.is_none());
+        assert_eq!(info.modules().count(), 0);
+        assert!(info.memory_map.is_none());
     }
 
-    pub fn get_module(&self, module_str: &CStr) -> Option<&Module> {
-        self.modules
+    #[test]
+    fn boot_command_line() {
+        let info = parse(BootInfoBuilder::new().boot_command_line(c"log=debug")).unwrap();
+        assert_eq!(info.boot_command_line, Some(c"log=debug"));
+    }
+
+    #[test]
+    fn bootloader_name() {
+        let info = parse(BootInfoBuilder::new().bootloader_name(c"GRUB 2.12")).unwrap();
+        assert_eq!(info.bootloader_name, Some(c"GRUB 2.12"));
+    }
+
+    #[test]
+    fn modules() {
+        let info = parse(
+            BootInfoBuilder::new()
+                .module(0x1000, 0x2000, c"kernel")
+                .module(0x3000, 0x4000, c"initrd"),
+        )
+        .unwrap();
+
+        let initrd = info.get_module(c"initrd").unwrap();
+        assert_eq!((initrd.start, initrd.end), (0x3000, 0x4000));
+        assert_eq!(info.get_module(c"kernel").unwrap().start, 0x1000);
+        assert!(info.get_module(c"missing").is_none());
+    }
+
+    #[test]
+    fn many_modules() {
+        let names = [
+            c"kernel", c"initrd", c"drivers", c"config", c"fonts", c"extra",
+        ];
+        let builder = names
             .iter()
-            .flatten()
-            .find(|module| module.string == module_str)
+            .zip(0..)
+            .fold(BootInfoBuilder::new(), |builder, (name, index)| {
+                builder.module(index * 0x1000, (index + 1) * 0x1000, name)
+            });
+
+        let info = parse(builder).unwrap();
+
+        assert!(info.modules().map(|module| module.string).eq(names));
+        assert_eq!(info.get_module(c"extra").unwrap().start, 0x5000);
+    }
+
+    #[test]
+    fn tags_in_order() {
+        let info = parse(
+            BootInfoBuilder::new()
+                .boot_command_line(c"a")
+                .module(0, 1, c"b")
+                .tag(0xDEAD, &[])
+                .memory(Memory { lower: 0, upper: 0 }),
+        )
+        .unwrap();
+
+        let mut tags = info.tags();
+        assert!(matches!(tags.next(), Some(Tag::BootCommandLine(_))));
+        assert!(matches!(tags.next(), Some(Tag::Module(_))));
+        assert!(matches!(tags.next(), Some(Tag::Memory(_))));
+        assert!(tags.next().is_none());
+    }
+
+    #[test]
+    fn invalid_module() {
+        let result = parse(BootInfoBuilder::new().tag(MODULE, &[0; 6]));
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(MODULE));
+    }
+
+    #[test]
+    fn memory() {
+        let info = parse(BootInfoBuilder::new().memory(Memory {
+            lower: 639,
+            upper: 130048,
+        }))
+        .unwrap();
+
+        let memory = info.memory.unwrap();
+        assert_eq!((memory.lower, memory.upper), (639, 130048));
+    }
+
+    #[test]
+    fn bios_device() {
+        let info = parse(BootInfoBuilder::new().bios_device(BiosDevice {
+            biosdev: 0x80,
+            partition: 1,
+            sub_partition: 0xFFFFFFFF,
+        }))
+        .unwrap();
+
+        let bios_device = info.bios_device.unwrap();
+        assert_eq!(bios_device.biosdev, 0x80);
+        assert_eq!(bios_device.partition, 1);
+        assert_eq!(bios_device.sub_partition, 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn memory_map() {
+        let info = parse(BootInfoBuilder::new().memory_map(&[
+            MemoryMapEntry::new(0, 0x9FC00, MemoryType::RAM),
+            MemoryMapEntry::new(0x9FC00, 0x400, MemoryType::RESERVED),
+            MemoryMapEntry::new(0x100000, 0x7EE0000, MemoryType::RAM),
+        ]))
+        .unwrap();
+
+        let entries = info.memory_map.unwrap().entries;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].base_addr, 0x9FC00);
+        assert_eq!(entries[2].length, 0x7EE0000);
+        assert_eq!(entries[2].mem_type, MemoryType::RAM);
+    }
+
+    #[test]
+    fn memory_map_with_invalid_type() {
+        let builder =
+            BootInfoBuilder::new().memory_map(&[MemoryMapEntry::new(0, 0x1000, MemoryType::RAM)]);
+
+        // the type of the first entry is 16 bytes into it, after the fixed header, tag header and
+        // entry size and version
+        let result = parse_corrupted(builder, |bytes| bytes[8 + 8 + 8 + 16] = 42);
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(MEMORY_MAP));
+    }
+
+    #[test]
+    fn memory_map_with_unexpected_entry_size() {
+        let builder =
+            BootInfoBuilder::new().memory_map(&[MemoryMapEntry::new(0, 0x1000, MemoryType::RAM)]);
+
+        let result = parse_corrupted(builder, |bytes| bytes[8 + 8] = 20);
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(MEMORY_MAP));
+    }
+
+    #[test]
+    fn vbe_info() {
+        let mut control_info = [0; 512];
+        control_info[..4].copy_from_slice(b"VESA");
+
+        let info = parse(BootInfoBuilder::new().vbe_info(VBEInfo {
+            mode: 0x118,
+            interface_seg: 0xC000,
+            interface_off: 0x10,
+            interface_len: 0x20,
+            control_info,
+            mode_info: [0xAB; 256],
+        }))
+        .unwrap();
+
+        let vbe_info = info.vbe_info.unwrap();
+        assert_eq!(vbe_info.mode, 0x118);
+        assert_eq!(&vbe_info.control_info[..4], b"VESA");
+        assert_eq!(vbe_info.mode_info[255], 0xAB);
+    }
+
+    #[test]
+    fn framebuffer_direct() {
+        let colour = FramebufferColour::Direct {
+            red_field_position: 16,
+            red_mask_size: 8,
+            green_field_position: 8,
+            green_mask_size: 8,
+            blue_field_position: 0,
+            blue_mask_size: 8,
+        };
+        let info = parse(
+            BootInfoBuilder::new().framebuffer_info(0xFD000000, 7680, 1920, 1080, 32, colour),
+        )
+        .unwrap();
+
+        let framebuffer = info.framebuffer_info.unwrap();
+        assert_eq!(framebuffer.buffer_addr, 0xFD000000);
+        assert_eq!(framebuffer.pitch, 7680);
+        assert_eq!((framebuffer.width, framebuffer.height), (1920, 1080));
+        assert_eq!(framebuffer.bpp, 32);
+        assert_eq!(framebuffer.buffer_type, 1);
+        assert!(matches!(framebuffer.colour, FramebufferColour::Direct {
+            red_field_position: 16,
+            green_field_position: 8,
+            blue_field_position: 0,
+            ..
+        }));
+    }
+
+    #[test]
+    fn framebuffer_indexed() {
+        let palette: &[[u8; 3]] = &[[0, 0, 0], [0xFF, 0x80, 0x00]];
+        let colour = FramebufferColour::Indexed(palette);
+        let info =
+            parse(BootInfoBuilder::new().framebuffer_info(0xA0000, 320, 320, 200, 8, colour))
+                .unwrap();
+
+        let FramebufferColour::Indexed(parsed) = info.framebuffer_info.unwrap().colour else {
+            panic!("expected an indexed framebuffer");
+        };
+        assert_eq!(parsed, palette);
+    }
+
+    #[test]
+    fn framebuffer_text() {
+        let colour = FramebufferColour::None;
+        let info = parse(BootInfoBuilder::new().framebuffer_info(0xB8000, 160, 80, 25, 16, colour))
+            .unwrap();
+
+        let framebuffer = info.framebuffer_info.unwrap();
+        assert_eq!(framebuffer.buffer_type, 2);
+        assert!(matches!(framebuffer.colour, FramebufferColour::None));
+    }
+
+    #[test]
+    fn framebuffer_palette_past_end() {
+        let palette: &[[u8; 3]] = &[[0, 0, 0]; 4];
+        let builder = BootInfoBuilder::new().framebuffer_info(
+            0xA0000,
+            320,
+            320,
+            200,
+            8,
+            FramebufferColour::Indexed(palette),
+        );
+
+        // claim far more colours than the tag holds
+        let result = parse_corrupted(builder, |bytes| bytes[8 + 8 + 24] = 0xFF);
+        assert_eq!(
+            result.unwrap_err(),
+            ParseError::InvalidTag(FRAMEBUFFER_INFO)
+        );
+    }
+
+    #[test]
+    fn elf_symbols() {
+        let header = |name, addr, size, flags| ElfSectionHeader {
+            name,
+            section_type: 1,
+            flags,
+            addr,
+            offset: 0,
+            size,
+            link: 0,
+            info: 0,
+            addralign: 0x1000,
+            entry_size: 0,
+        };
+        let headers = [
+            header(0, 0, 0, SectionFlags::empty()),
+            header(
+                1,
+                0x100000,
+                0x2000,
+                SectionFlags::ALLOC | SectionFlags::EXECUTABLE,
+            ),
+            header(
+                7,
+                0x102000,
+                0x1000,
+                SectionFlags::ALLOC | SectionFlags::WRITE,
+            ),
+            header(13, 0, 0x20, SectionFlags::empty()),
+        ];
+
+        let info = parse(BootInfoBuilder::new().elf_symbols(3, &headers)).unwrap();
+        let elf_symbols = info.elf_symbols.unwrap();
+
+        assert_eq!(elf_symbols.num, 4);
+        assert_eq!(elf_symbols.string_header().unwrap().name, 13);
+        assert_eq!(
+            elf_symbols
+                .headers()
+                .filter(ElfSectionHeader::is_loaded)
+                .count(),
+            2
+        );
+        assert_eq!(elf_symbols.header(2).unwrap().addr, 0x102000);
+        assert!(elf_symbols.header(4).is_none());
+    }
+
+    #[test]
+    fn elf_symbols_past_end() {
+        let builder = BootInfoBuilder::new().elf_symbols(0, &[]);
+
+        let result = parse_corrupted(builder, |bytes| bytes[8 + 8] = 3);
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(ELF_SYMBOLS));
+    }
+
+    #[test]
+    fn acpi_old() {
+        let info = parse(BootInfoBuilder::new().acpi_old(rsdp())).unwrap();
+
+        let rsdp = info.acpi_old.unwrap();
+        assert_eq!(rsdp.signature, *b"RSD PTR ");
+        assert_eq!({ rsdp.rsdt_address }, 0x1234);
+    }
+
+    #[test]
+    fn acpi_new() {
+        let xsdp = Xsdp::new(rsdp(), 36, 0x5678, 0);
+        let info = parse(BootInfoBuilder::new().acpi_new(xsdp)).unwrap();
+
+        let xsdp = info.acpi_new.unwrap();
+        assert_eq!({ xsdp.rsdp.revision }, 2);
+        assert_eq!({ xsdp.length }, 36);
+        assert_eq!({ xsdp.xsdt_address }, 0x5678);
+    }
+
+    #[test]
+    fn apm() {
+        let info = parse(BootInfoBuilder::new().apm(Apm {
+            version: 0x102,
+            cseg: 0xF000,
+            offset: 0x1234,
+            cseg_16: 0xF000,
+            dseg: 0x40,
+            flags: 3,
+            cseg_len: 0xFFFF,
+            cseg_16_len: 0xFFFF,
+            dseg_len: 0xFF,
+        }))
+        .unwrap();
+
+        let apm = info.apm.unwrap();
+        assert_eq!(apm.version, 0x102);
+        assert_eq!(apm.offset, 0x1234);
+        assert_eq!(apm.dseg_len, 0xFF);
+    }
+
+    #[test]
+    fn efi_pointers() {
+        let info = parse(
+            BootInfoBuilder::new()
+                .efi_system_table_32(0x1000)
+                .efi_system_table_64(0x1_0000_2000)
+                .efi_image_handle_32(0x3000)
+                .efi_image_handle_64(0x1_0000_4000)
+                .efi_boot_services_not_terminated()
+                .image_load_address(0x200000),
+        )
+        .unwrap();
+
+        assert_eq!(info.efi_system_table_32, Some(0x1000));
+        assert_eq!(info.efi_system_table_64, Some(0x1_0000_2000));
+        assert_eq!(info.efi_image_handle_32, Some(0x3000));
+        assert_eq!(info.efi_image_handle_64, Some(0x1_0000_4000));
+        assert!(info.efi_boot_services_not_terminated);
+        assert_eq!(info.image_load_address, Some(0x200000));
+    }
+
+    #[test]
+    fn efi_not_passed() {
+        let info = parse(BootInfoBuilder::new()).unwrap();
+
+        assert!(info.efi_system_table_64.is_none());
+        assert!(!info.efi_boot_services_not_terminated);
+        assert!(info.efi_memory_map.is_none());
+    }
+
+    #[test]
+    fn efi_memory_map() {
+        let descriptors = [
+            EfiMemoryDescriptor::new(EfiMemoryType::CONVENTIONAL as u32, 0, 0, 0x9F, 0xF),
+            EfiMemoryDescriptor::new(EfiMemoryType::ACPI_NVS as u32, 0x9F000, 0, 1, 0xF),
+            EfiMemoryDescriptor::new(0x8000_0000, 0x100000, 0, 0x10, 0),
+            EfiMemoryDescriptor::new(EfiMemoryType::BOOT_SERVICES_DATA as u32, 0x200000, 0, 4, 0),
+        ];
+
+        // firmware commonly pads descriptors to 48 bytes
+        let info = parse(BootInfoBuilder::new().efi_memory_map(48, &descriptors)).unwrap();
+        let efi_memory_map = info.efi_memory_map.unwrap();
+
+        assert_eq!(efi_memory_map.descriptor_size, 48);
+        assert_eq!(efi_memory_map.descriptor_version, 1);
+
+        let parsed: Vec<_> = efi_memory_map.descriptors().collect();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[1].phys_start, 0x9F000);
+        assert_eq!(parsed[1].mem_type(), Ok(EfiMemoryType::ACPI_NVS));
+        assert_eq!(parsed[2].mem_type(), Err(0x8000_0000));
+        assert_eq!(parsed[3].page_count, 4);
+
+        let usable: Vec<_> = parsed.iter().map(EfiMemoryDescriptor::is_usable).collect();
+        assert_eq!(usable, [true, false, false, true]);
+    }
+
+    #[test]
+    fn efi_memory_descriptor_to_entry() {
+        let entry = |mem_type: EfiMemoryType| {
+            MemoryMapEntry::from(EfiMemoryDescriptor::new(
+                mem_type as u32,
+                0x100000,
+                0,
+                16,
+                0,
+            ))
+        };
+
+        assert_eq!(entry(EfiMemoryType::CONVENTIONAL).mem_type, MemoryType::RAM);
+        assert_eq!(
+            entry(EfiMemoryType::BOOT_SERVICES_CODE).mem_type,
+            MemoryType::RAM
+        );
+        assert_eq!(
+            entry(EfiMemoryType::ACPI_RECLAIM).mem_type,
+            MemoryType::ACPI_INFO
+        );
+        assert_eq!(
+            entry(EfiMemoryType::RUNTIME_SERVICES_DATA).mem_type,
+            MemoryType::RESERVED
+        );
+        assert_eq!(entry(EfiMemoryType::MMIO).mem_type, MemoryType::RESERVED);
+
+        let conventional = entry(EfiMemoryType::CONVENTIONAL);
+        assert_eq!(conventional.base_addr, 0x100000);
+        assert_eq!(conventional.length, 0x10000);
+    }
+
+    #[test]
+    fn efi_memory_map_with_small_descriptors() {
+        let builder = BootInfoBuilder::new().efi_memory_map(48, &[]);
+
+        let result = parse_corrupted(builder, |bytes| bytes[8 + 8] = 24);
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(EFI_MEMORY_MAP));
+    }
+
+    /// Builds an entry point, fixing up the checksums over `length` bytes and the `_DMI_` section
+    fn smbios_entry_point(mut bytes: Vec<u8>, checksum_offset: usize, length: usize) -> Vec<u8> {
+        let sum = |bytes: &[u8]| {
+            0u8.wrapping_sub(bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)))
+        };
+
+        if bytes.starts_with(b"_SM_") {
+            bytes[21] = 0;
+            bytes[21] = sum(&bytes[16..31]);
+        }
+
+        bytes[checksum_offset] = 0;
+        bytes[checksum_offset] = sum(&bytes[..length]);
+
+        bytes
+    }
+
+    fn smbios_64() -> Vec<u8> {
+        let mut bytes = vec![0; 24];
+        bytes[..5].copy_from_slice(b"_SM3_");
+        bytes[6] = 24;
+        bytes[7..9].copy_from_slice(&[3, 5]);
+        bytes[12..16].copy_from_slice(&0x1000u32.to_ne_bytes());
+        bytes[16..24].copy_from_slice(&0x7FFF_0000u64.to_ne_bytes());
+
+        smbios_entry_point(bytes, 5, 24)
+    }
+
+    fn smbios_32() -> Vec<u8> {
+        let mut bytes = vec![0; 31];
+        bytes[..4].copy_from_slice(b"_SM_");
+        bytes[5] = 31;
+        bytes[6..8].copy_from_slice(&[2, 8]);
+        bytes[16..21].copy_from_slice(b"_DMI_");
+        bytes[22..24].copy_from_slice(&0x1A0u16.to_ne_bytes());
+        bytes[24..28].copy_from_slice(&0xF0000u32.to_ne_bytes());
+
+        smbios_entry_point(bytes, 4, 31)
+    }
+
+    #[test]
+    fn smbios_64_bit_entry_point() {
+        let info = parse(BootInfoBuilder::new().smbios(3, 5, &smbios_64())).unwrap();
+        let smbios = info.smbios.unwrap();
+
+        assert_eq!((smbios.major, smbios.minor), (3, 5));
+        assert_eq!(
+            smbios.entry_point(),
+            Some(SmbiosEntryPoint {
+                major: 3,
+                minor: 5,
+                table_address: 0x7FFF_0000,
+                table_length: 0x1000,
+            })
+        );
+    }
+
+    #[test]
+    fn smbios_32_bit_entry_point() {
+        let info = parse(BootInfoBuilder::new().smbios(2, 8, &smbios_32())).unwrap();
+
+        assert_eq!(
+            info.smbios.unwrap().entry_point(),
+            Some(SmbiosEntryPoint {
+                major: 2,
+                minor: 8,
+                table_address: 0xF0000,
+                table_length: 0x1A0,
+            })
+        );
+    }
+
+    #[test]
+    fn smbios_invalid_entry_point() {
+        let mut bad_checksum = smbios_64();
+        bad_checksum[10] ^= 1;
+        assert!(SmbiosEntryPoint::parse(&bad_checksum).is_none());
+
+        let mut bad_anchor = smbios_32();
+        bad_anchor[16] = b'X';
+        assert!(SmbiosEntryPoint::parse(&bad_anchor).is_none());
+
+        assert!(SmbiosEntryPoint::parse(&smbios_64()[..20]).is_none());
+        assert!(SmbiosEntryPoint::parse(b"nothing here").is_none());
+    }
+
+    #[test]
+    fn newest_smbios_is_kept() {
+        let info = parse(BootInfoBuilder::new().smbios(3, 5, &smbios_64()).smbios(
+            2,
+            8,
+            &smbios_32(),
+        ))
+        .unwrap();
+
+        assert_eq!(info.smbios.unwrap().major, 3);
+    }
+
+    #[test]
+    fn networking() {
+        let info = parse(BootInfoBuilder::new().networking(&[2, 1, 6, 0])).unwrap();
+        assert_eq!(info.networking.unwrap().dhcp_ack, [2, 1, 6, 0]);
+    }
+
+    #[test]
+    fn unknown_tags_are_skipped() {
+        let info = parse(
+            BootInfoBuilder::new()
+                .tag(0xBEEF, &[0; 20])
+                .tag(0xDEAD, &[1, 2, 3])
+                .boot_command_line(c"after"),
+        )
+        .unwrap();
+
+        assert_eq!(info.boot_command_line, Some(c"after"));
+    }
+
+    #[test]
+    fn tags_after_end_are_ignored() {
+        let mut bytes = BootInfoBuilder::new().build();
+        let extra = BootInfoBuilder::new().boot_command_line(c"ignored").build();
+        bytes.extend_from_slice(&extra[8..]);
+
+        let total_size = bytes.len() as u32;
+        bytes[..4].copy_from_slice(&total_size.to_ne_bytes());
+
+        let info = BootInfo::parse(builder::leak_aligned(&bytes)).unwrap();
+        assert!(info.boot_command_line.is_none());
+    }
+
+    #[test]
+    fn misaligned() {
+        let bytes = BootInfoBuilder::new().leak();
+        assert_eq!(
+            BootInfo::parse(&bytes[4..]).unwrap_err(),
+            ParseError::Misaligned
+        );
+    }
+
+    #[test]
+    fn too_short_for_header() {
+        let bytes = BootInfoBuilder::new().leak();
+        assert_eq!(
+            BootInfo::parse(&bytes[..4]).unwrap_err(),
+            ParseError::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn total_size_past_end() {
+        let bytes = BootInfoBuilder::new().leak();
+        assert_eq!(
+            BootInfo::parse(&bytes[..12]).unwrap_err(),
+            ParseError::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn reserved_must_be_zero() {
+        let result = parse_corrupted(BootInfoBuilder::new(), |bytes| bytes[4] = 1);
+        assert_eq!(result.unwrap_err(), ParseError::InvalidReserved);
+    }
+
+    #[test]
+    fn missing_end() {
+        let result = parse_corrupted(BootInfoBuilder::new().boot_command_line(c"a"), |bytes| {
+            // drop the end tag
+            bytes.truncate(bytes.len() - 8);
+            let total_size = bytes.len() as u32;
+            bytes[..4].copy_from_slice(&total_size.to_ne_bytes());
+        });
+
+        assert_eq!(result.unwrap_err(), ParseError::MissingEnd);
+    }
+
+    #[test]
+    fn tag_smaller_than_header() {
+        let result = parse_corrupted(
+            BootInfoBuilder::new().memory(Memory { lower: 0, upper: 0 }),
+            |bytes| bytes[12] = 4,
+        );
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(MEMORY));
+    }
+
+    #[test]
+    fn tag_too_small_for_contents() {
+        let result = parse(BootInfoBuilder::new().tag(MEMORY, &[0; 4]));
+        assert_eq!(result.unwrap_err(), ParseError::InvalidTag(MEMORY));
+    }
+
+    #[test]
+    fn tag_past_end() {
+        let result = parse_corrupted(
+            BootInfoBuilder::new().memory(Memory { lower: 0, upper: 0 }),
+            |bytes| bytes[12] = 0xFF,
+        );
+        assert_eq!(result.unwrap_err(), ParseError::UnexpectedEnd);
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let result = parse(BootInfoBuilder::new().tag(BOOT_COMMAND_LINE, b"no terminator"));
+        assert_eq!(
+            result.unwrap_err(),
+            ParseError::InvalidTag(BOOT_COMMAND_LINE)
+        );
     }
 }
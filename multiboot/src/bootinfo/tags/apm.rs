@@ -0,0 +1,37 @@
+use super::{read, FromBytes, ParseTag, Tag};
+
+/// Stores the APM (advanced power management) BIOS interface
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#APM-table
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Apm {
+    pub version: u16,
+    pub cseg: u16,
+    pub offset: u32,
+    pub cseg_16: u16,
+    pub dseg: u16,
+    pub flags: u16,
+    pub cseg_len: u16,
+    pub cseg_16_len: u16,
+    pub dseg_len: u16,
+}
+
+unsafe impl FromBytes for Apm {}
+
+impl ParseTag for Apm {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        u16     | version           |
+        u16     | cseg              |
+        u32     | offset            |
+        u16     | cseg_16           |
+        u16     | dseg              |
+        u16     | flags             |
+        u16     | cseg_len          |
+        u16     | cseg_16_len       |
+        u16     | dseg_len          |
+                +-------------------+ */
+        read(bytes, 0).map(Tag::Apm)
+    }
+}
@@ -1,4 +1,4 @@
-use super::{ParseTag, Tag};
+use super::{read, FromBytes, ParseTag, Tag};
 
 /// Stores information about the BIOS boot device
 ///
@@ -11,13 +11,15 @@ pub struct BiosDevice {
     pub sub_partition: u32,
 }
 
+unsafe impl FromBytes for BiosDevice {}
+
 impl ParseTag for BiosDevice {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*      +-------------------+
         u32     | biosdev           |
         u32     | partition         |
         u32     | sub_partition     |
                 +-------------------+ */
-        Some(Tag::BiosDevice(*(addr as *const _)))
+        read(bytes, 0).map(Tag::BiosDevice)
     }
 }
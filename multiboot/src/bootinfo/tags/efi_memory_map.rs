@@ -0,0 +1,165 @@
+use super::{read, FromBytes, MemoryMapEntry, MemoryType, ParseTag, Tag};
+
+/// Stores the memory map returned by EFI boot services
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#EFI-memory-map
+#[derive(Debug, Clone, Copy)]
+pub struct EfiMemoryMap {
+    pub descriptor_size: u32,
+    pub descriptor_version: u32,
+    descriptors: &'static [u8],
+}
+
+/// Stores information about a single region of memory, as returned by `GetMemoryMap()`
+///
+/// https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-getmemorymap
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct EfiMemoryDescriptor {
+    mem_type: u32,
+    _padding: u32,
+    pub phys_start: u64,
+    pub virt_start: u64,
+    pub page_count: u64,
+    pub attributes: u64,
+}
+
+unsafe impl FromBytes for EfiMemoryDescriptor {}
+
+/// The type of an EFI memory region
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum EfiMemoryType {
+    RESERVED,
+    LOADER_CODE,
+    LOADER_DATA,
+    BOOT_SERVICES_CODE,
+    BOOT_SERVICES_DATA,
+    RUNTIME_SERVICES_CODE,
+    RUNTIME_SERVICES_DATA,
+    CONVENTIONAL,
+    UNUSABLE,
+    ACPI_RECLAIM,
+    ACPI_NVS,
+    MMIO,
+    MMIO_PORT_SPACE,
+    PAL_CODE,
+    PERSISTENT,
+    UNACCEPTED,
+}
+
+impl TryFrom<u32> for EfiMemoryType {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::RESERVED),
+            1 => Ok(Self::LOADER_CODE),
+            2 => Ok(Self::LOADER_DATA),
+            3 => Ok(Self::BOOT_SERVICES_CODE),
+            4 => Ok(Self::BOOT_SERVICES_DATA),
+            5 => Ok(Self::RUNTIME_SERVICES_CODE),
+            6 => Ok(Self::RUNTIME_SERVICES_DATA),
+            7 => Ok(Self::CONVENTIONAL),
+            8 => Ok(Self::UNUSABLE),
+            9 => Ok(Self::ACPI_RECLAIM),
+            10 => Ok(Self::ACPI_NVS),
+            11 => Ok(Self::MMIO),
+            12 => Ok(Self::MMIO_PORT_SPACE),
+            13 => Ok(Self::PAL_CODE),
+            14 => Ok(Self::PERSISTENT),
+            15 => Ok(Self::UNACCEPTED),
+            _ => Err(value),
+        }
+    }
+}
+
+impl EfiMemoryMap {
+    /// Returns an iterator over each descriptor in the memory map
+    pub fn descriptors(&self) -> impl Iterator<Item = EfiMemoryDescriptor> {
+        self.descriptors
+            .chunks_exact(self.descriptor_size as usize)
+            .filter_map(|descriptor| read(descriptor, 0))
+    }
+}
+
+impl EfiMemoryDescriptor {
+    pub const fn new(
+        mem_type: u32,
+        phys_start: u64,
+        virt_start: u64,
+        page_count: u64,
+        attributes: u64,
+    ) -> Self {
+        Self {
+            mem_type,
+            _padding: 0,
+            phys_start,
+            virt_start,
+            page_count,
+            attributes,
+        }
+    }
+
+    /// Returns the type of memory, or the raw value if it's OEM or OS specific
+    pub fn mem_type(&self) -> Result<EfiMemoryType, u32> {
+        EfiMemoryType::try_from(self.mem_type)
+    }
+
+    /// Returns true if the region is free to use once boot services have exited
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self.mem_type(),
+            Ok(EfiMemoryType::LOADER_CODE
+                | EfiMemoryType::LOADER_DATA
+                | EfiMemoryType::BOOT_SERVICES_CODE
+                | EfiMemoryType::BOOT_SERVICES_DATA
+                | EfiMemoryType::CONVENTIONAL)
+        )
+    }
+}
+
+impl From<EfiMemoryDescriptor> for MemoryMapEntry {
+    /// Converts a descriptor to the equivalent multiboot memory map entry, treating memory used by
+    /// the bootloader and boot services as free
+    fn from(descriptor: EfiMemoryDescriptor) -> Self {
+        let mem_type = match descriptor.mem_type() {
+            _ if descriptor.is_usable() => MemoryType::RAM,
+            Ok(EfiMemoryType::ACPI_RECLAIM) => MemoryType::ACPI_INFO,
+            Ok(EfiMemoryType::ACPI_NVS) => MemoryType::PRESERVED_ON_HIBERNATION,
+            Ok(EfiMemoryType::UNUSABLE) => MemoryType::DEFECTIVE,
+            _ => MemoryType::RESERVED,
+        };
+
+        // EFI pages are always 4KiB
+        MemoryMapEntry::new(
+            descriptor.phys_start,
+            descriptor.page_count * 4096,
+            mem_type,
+        )
+    }
+}
+
+impl ParseTag for EfiMemoryMap {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        u32     | descriptor_size   |
+        u32     | descr_vers        |
+        varies  | EFI memory map    |
+                +-------------------+ */
+        let descriptor_size: u32 = read(bytes, 0)?;
+        let descriptor_version = read(bytes, 4)?;
+
+        // descriptors can grow in later versions, but never shrink
+        if (descriptor_size as usize) < core::mem::size_of::<EfiMemoryDescriptor>() {
+            return None;
+        }
+
+        Some(Tag::EfiMemoryMap(Self {
+            descriptor_size,
+            descriptor_version,
+            descriptors: &bytes[8..],
+        }))
+    }
+}
@@ -2,7 +2,7 @@ use core::{ffi::CStr, fmt::Display};
 
 use bitflags::bitflags;
 
-use super::{ParseTag, Tag};
+use super::{read, FromBytes, ParseTag, Tag};
 
 bitflags! {
     /// Flags for a specific section
@@ -40,14 +40,24 @@ pub struct ElfSymbols {
     pub entry_size: u32,
     /// Index into [Self::headers] for the string table
     pub string_table_index: u32,
-    /// List of headers for each section
-    pub headers: &'static [ElfSectionHeader],
+    /// Raw headers for each section, which are only 4-byte aligned
+    headers: &'static [u8],
 }
 
 impl ElfSymbols {
+    /// Returns the header of the section at the given index
+    pub fn header(&self, index: usize) -> Option<ElfSectionHeader> {
+        read(self.headers, index.checked_mul(self.entry_size as usize)?)
+    }
+
+    /// Returns an iterator over the header for each section
+    pub fn headers(&self) -> impl Iterator<Item = ElfSectionHeader> + '_ {
+        (0..self.num as usize).filter_map(|index| self.header(index))
+    }
+
     /// Returns the header for the string table
-    pub const fn string_header(&self) -> &ElfSectionHeader {
-        &self.headers[self.string_table_index as usize]
+    pub fn string_header(&self) -> Option<ElfSectionHeader> {
+        self.header(self.string_table_index as usize)
     }
 }
 
@@ -67,6 +77,8 @@ pub struct ElfSectionHeader {
     pub entry_size: u64,
 }
 
+unsafe impl FromBytes for ElfSectionHeader {}
+
 impl ElfSectionHeader {
     /// Returns the name of the header using the provided string table
     pub fn name(&self, string_header: &ElfSectionHeader) -> &'static CStr {
@@ -82,7 +94,7 @@ impl ElfSectionHeader {
 }
 
 impl ParseTag for ElfSymbols {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*      +-------------------+
         u32     | num               |
         u32     | entsize           |
@@ -90,12 +102,17 @@ impl ParseTag for ElfSymbols {
         varies  | section headers   |
                 +-------------------+ */
 
-        let num = *addr;
-        let entry_size = *addr.add(1);
-        let string_table_index = *addr.add(2);
+        let num: u32 = read(bytes, 0)?;
+        let entry_size: u32 = read(bytes, 4)?;
+        let string_table_index = read(bytes, 8)?;
+
+        // headers are only ever read as `ElfSectionHeader`, so any other size can't be handled
+        if entry_size as usize != core::mem::size_of::<ElfSectionHeader>() {
+            return None;
+        }
 
-        let headers =
-            core::slice::from_raw_parts(addr.add(3) as *const ElfSectionHeader, num as usize);
+        let len = (num as usize).checked_mul(entry_size as usize)?;
+        let headers = bytes.get(12..12usize.checked_add(len)?)?;
 
         Some(Tag::ElfSymbols(ElfSymbols {
             num,
@@ -1,4 +1,4 @@
-use super::{ParseTag, Tag};
+use super::{read, read_slice, ParseTag, Tag};
 
 /// Stores information about the framebuffer
 ///
@@ -12,7 +12,7 @@ pub struct FramebufferInfo {
     pub height: u32,
     pub bpp: u8,
     pub buffer_type: u8,
-    _reserved: u8,
+    _reserved: u16,
     pub colour: FramebufferColour,
 }
 
@@ -34,7 +34,7 @@ pub enum FramebufferColour {
 }
 
 impl ParseTag for FramebufferInfo {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*      +--------------------+
         u32     | type = 8           |
         u32     | size               |
@@ -44,24 +44,22 @@ impl ParseTag for FramebufferInfo {
         u32     | framebuffer_height |
         u8      | framebuffer_bpp    |
         u8      | framebuffer_type   |
-        u8      | reserved           |
+        u16     | reserved           |
         varies  | color_info         |
                 +--------------------+ */
 
-        let buffer_addr = *(addr as *const u64);
-        let pitch = *addr.add(2);
-        let width = *addr.add(3);
-        let height = *addr.add(4);
-
-        let addr = addr.add(5) as *const u8;
-        let bpp = *addr;
-        let buffer_type = *addr.add(1);
-        let reserved = *addr.add(2);
+        let buffer_addr = read(bytes, 0)?;
+        let pitch = read(bytes, 8)?;
+        let width = read(bytes, 12)?;
+        let height = read(bytes, 16)?;
+        let bpp = read(bytes, 20)?;
+        let buffer_type = read(bytes, 21)?;
+        let reserved = read(bytes, 22)?;
 
         let colour = match buffer_type {
             0 => {
                 /*       +----------------------------------+
-                u32     | framebuffer_palette_num_colors   |
+                u16     | framebuffer_palette_num_colors   |
                 varies  | framebuffer_palette              |
                         +----------------------------------+  */
 
@@ -71,11 +69,8 @@ impl ParseTag for FramebufferInfo {
                 u8      | blue_value  |
                         +-------------+ */
 
-                let num = *(addr.add(3) as *const u32) as usize;
-                FramebufferColour::Indexed(core::slice::from_raw_parts(
-                    addr.add(7) as *const [u8; 3],
-                    num,
-                ))
+                let num = read::<u16>(bytes, 24)? as usize;
+                FramebufferColour::Indexed(read_slice(bytes, 26, num)?)
             }
             1 => {
                 /*     +----------------------------------+
@@ -87,15 +82,16 @@ impl ParseTag for FramebufferInfo {
                 u8     | framebuffer_blue_mask_size       |
                       +----------------------------------+ */
 
-                let addr = addr.add(3);
+                let [red_field_position, red_mask_size, green_field_position, green_mask_size, blue_field_position, blue_mask_size] =
+                    read(bytes, 24)?;
 
                 FramebufferColour::Direct {
-                    red_field_position: *addr,
-                    red_mask_size: *addr.add(1),
-                    green_field_position: *addr.add(2),
-                    green_mask_size: *addr.add(3),
-                    blue_field_position: *addr.add(4),
-                    blue_mask_size: *addr.add(5),
+                    red_field_position,
+                    red_mask_size,
+                    green_field_position,
+                    green_mask_size,
+                    blue_field_position,
+                    blue_mask_size,
                 }
             }
             _ => FramebufferColour::None,
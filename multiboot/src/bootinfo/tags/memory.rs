@@ -1,4 +1,4 @@
-use super::{ParseTag, Tag};
+use super::{read, FromBytes, ParseTag, Tag};
 
 /// Stores basic memory information
 ///
@@ -10,12 +10,14 @@ pub struct Memory {
     pub upper: u32,
 }
 
+unsafe impl FromBytes for Memory {}
+
 impl ParseTag for Memory {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*      +-------------------+
         u32     | mem_lower         |
         u32     | mem_upper         |
                 +-------------------+ */
-        Some(Tag::Memory(*(addr as *const _)))
+        read(bytes, 0).map(Tag::Memory)
     }
 }
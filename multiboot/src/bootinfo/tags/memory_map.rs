@@ -1,4 +1,4 @@
-use super::{ParseTag, Tag};
+use super::{cast_slice, read, ParseTag, Tag};
 
 /// Stores a list of memory regions available to the kernel
 ///
@@ -20,6 +20,17 @@ pub struct MemoryMapEntry {
     _reserved: u32,
 }
 
+impl MemoryMapEntry {
+    pub const fn new(base_addr: u64, length: u64, mem_type: MemoryType) -> Self {
+        Self {
+            base_addr,
+            length,
+            mem_type,
+            _reserved: 0,
+        }
+    }
+}
+
 /// The type of memory region
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,8 +43,23 @@ pub enum MemoryType {
     DEFECTIVE,
 }
 
+impl TryFrom<u32> for MemoryType {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::RAM),
+            2 => Ok(Self::RESERVED),
+            3 => Ok(Self::ACPI_INFO),
+            4 => Ok(Self::PRESERVED_ON_HIBERNATION),
+            5 => Ok(Self::DEFECTIVE),
+            _ => Err(value),
+        }
+    }
+}
+
 impl ParseTag for MemoryMap {
-    unsafe fn parse(addr: *const u32, size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*       +-------------------+
         u32     | entry_size        |
         u32     | entry_version     |
@@ -48,14 +74,24 @@ impl ParseTag for MemoryMap {
         u32     | reserved          |
                 +-------------------+ */
 
-        let entry_size = *addr as usize;
-        let version = *addr.add(1);
+        let entry_size = read::<u32>(bytes, 0)? as usize;
+        let version = read(bytes, 4)?;
+
+        // entries are only ever read as `MemoryMapEntry`, so any other size can't be handled
+        if entry_size != core::mem::size_of::<MemoryMapEntry>() {
+            return None;
+        }
 
         // calculate number of entries from given info, and then construct a slice with that many entries
-        let entries_size = size - 16;
-        let num_entries = entries_size / entry_size;
+        let num_entries = (bytes.len() - 8) / entry_size;
+
+        // make sure every type is valid before reinterpreting the entries
+        for index in 0..num_entries {
+            let mem_type: u32 = read(bytes, 8 + index * entry_size + 16)?;
+            MemoryType::try_from(mem_type).ok()?;
+        }
 
-        let entries = core::slice::from_raw_parts(addr.add(2) as *const _, num_entries);
+        let entries = unsafe { cast_slice(bytes, 8, num_entries)? };
 
         Some(Tag::MemoryMap(MemoryMap { version, entries }))
     }
@@ -2,20 +2,30 @@
 
 use core::ffi::CStr;
 
+pub mod apm;
 pub mod bios_device;
+pub mod efi_memory_map;
 pub mod elf_symbols;
 pub mod framebuffer_info;
 pub mod memory;
 pub mod memory_map;
 pub mod module;
+pub mod networking;
+pub mod rsdp;
+pub mod smbios;
 pub mod vbe_info;
 
+pub use apm::Apm;
 pub use bios_device::BiosDevice;
+pub use efi_memory_map::*;
 pub use elf_symbols::ElfSymbols;
 pub use framebuffer_info::*;
 pub use memory::Memory;
 pub use memory_map::*;
 pub use module::Module;
+pub use networking::Networking;
+pub use rsdp::{Rsdp, Xsdp};
+pub use smbios::{Smbios, SmbiosEntryPoint};
 pub use vbe_info::VBEInfo;
 
 // each of these represents the tags found at https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Boot-information-format
@@ -44,7 +54,78 @@ pub const IMAGE_LOAD_ADDRESS: u32 = 21;
 
 /// Represents a type that can be parsed into a tag
 trait ParseTag: Sized {
-    unsafe fn parse(addr: *const u32, size: usize) -> Option<Tag>;
+    /// Parses the contents of a tag, after its type and size, returning `None` if they're malformed
+    fn parse(bytes: &'static [u8]) -> Option<Tag>;
+}
+
+/// Types which can be read directly out of boot information
+///
+/// # Safety
+/// Implementors must be valid for every possible bit pattern, and contain no padding
+pub(crate) unsafe trait FromBytes: Copy {}
+
+unsafe impl FromBytes for u8 {}
+unsafe impl FromBytes for u16 {}
+unsafe impl FromBytes for u32 {}
+unsafe impl FromBytes for u64 {}
+unsafe impl<T: FromBytes, const N: usize> FromBytes for [T; N] {}
+
+/// Reads a value at `offset`, returning `None` if it doesn't fit in `bytes`
+fn read<T: FromBytes>(bytes: &[u8], offset: usize) -> Option<T> {
+    let bytes = bytes.get(offset..offset.checked_add(core::mem::size_of::<T>())?)?;
+
+    Some(unsafe { bytes.as_ptr().cast::<T>().read_unaligned() })
+}
+
+/// Reinterprets `count` values at `offset` as a slice, returning `None` if they don't fit in
+/// `bytes` or aren't aligned
+fn read_slice<T: FromBytes>(
+    bytes: &'static [u8],
+    offset: usize,
+    count: usize,
+) -> Option<&'static [T]> {
+    unsafe { cast_slice(bytes, offset, count) }
+}
+
+/// Reinterprets `count` values at `offset` as a slice, returning `None` if they don't fit in
+/// `bytes` or aren't aligned
+///
+/// # Safety
+/// The bytes must be a valid representation of `count` values of `T`
+unsafe fn cast_slice<T>(bytes: &'static [u8], offset: usize, count: usize) -> Option<&'static [T]> {
+    let len = count.checked_mul(core::mem::size_of::<T>())?;
+    let bytes = bytes.get(offset..offset.checked_add(len)?)?;
+
+    if bytes.as_ptr() as usize % core::mem::align_of::<T>() != 0 {
+        return None;
+    }
+
+    Some(core::slice::from_raw_parts(bytes.as_ptr().cast(), count))
+}
+
+/// Returns true if the bytes sum to zero, ignoring overflow
+fn checksum(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// Reads a null-terminated string at `offset`
+fn read_str(bytes: &'static [u8], offset: usize) -> Option<&'static CStr> {
+    CStr::from_bytes_until_nul(bytes.get(offset..)?).ok()
+}
+
+/// Error returned when boot information is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Boot information must be 8-byte aligned
+    Misaligned,
+    /// The data ended before a header or tag did
+    UnexpectedEnd,
+    /// The reserved field of the fixed header was not zero
+    InvalidReserved,
+    /// A tag of the given type was too small, or its contents were invalid
+    InvalidTag(u32),
+    /// The tags were not terminated by an end tag
+    MissingEnd,
 }
 
 /// Stores data about an individual multiboot tag
@@ -63,25 +144,39 @@ pub enum Tag {
     VBEInfo(VBEInfo),
     FramebufferInfo(FramebufferInfo),
     ElfSymbols(ElfSymbols),
+    Apm(Apm),
+    EfiSystemTable32(u32),
+    EfiSystemTable64(u64),
+    Smbios(Smbios),
+    AcpiOld(Rsdp),
+    AcpiNew(Xsdp),
+    Networking(Networking),
+    EfiMemoryMap(EfiMemoryMap),
+    EfiBootServicesNotTerminated,
+    EfiImageHandle32(u32),
+    EfiImageHandle64(u64),
+    ImageLoadAddress(u32),
 }
 
 impl Tag {
-    /// Parses a tag at the given address, returning the parsed tag and the size of it
-    ///
-    /// # Safety
-    /// `addr` must point to the start of a valid multiboot2 tag.
-    pub unsafe fn parse_tag(mut addr: *const u32) -> (Option<Self>, usize) {
+    /// Parses the tag at the start of `bytes`, returning the parsed tag (or `None` if the type is
+    /// unknown) and the size of it
+    pub fn parse(bytes: &'static [u8]) -> Result<(Option<Self>, usize), ParseError> {
         /* all tags start with following format
                 +-------------------+
         u32     | type              |
         u32     | size              |
                 +-------------------+
         */
-        let tag_type = *addr;
-        let size = *addr.add(1) as usize;
+        let tag_type: u32 = read(bytes, 0).ok_or(ParseError::UnexpectedEnd)?;
+        let size = read::<u32>(bytes, 4).ok_or(ParseError::UnexpectedEnd)? as usize;
+
+        if size < 8 {
+            return Err(ParseError::InvalidTag(tag_type));
+        }
 
         // then skip past first two entries to make parsing easier
-        addr = addr.add(2);
+        let bytes = bytes.get(8..size).ok_or(ParseError::UnexpectedEnd)?;
 
         let tag = match tag_type {
             END => Some(Tag::End),
@@ -90,25 +185,114 @@ impl Tag {
                 u8[n]   | string            |
                         +-------------------+ */
 
-                Some(Tag::BootCommandLine(CStr::from_ptr(addr as *const i8)))
+                read_str(bytes, 0).map(Tag::BootCommandLine)
             }
             BOOTLOADER_NAME => {
                 /*      +-------------------+
                 u8[n]   | string            |
                         +-------------------+ */
 
-                Some(Tag::BootloaderName(CStr::from_ptr(addr as *const i8)))
+                read_str(bytes, 0).map(Tag::BootloaderName)
+            }
+            MODULE => Module::parse(bytes),
+            MEMORY => Memory::parse(bytes),
+            BIOS_DEVICE => BiosDevice::parse(bytes),
+            MEMORY_MAP => MemoryMap::parse(bytes),
+            VBE_INFO => VBEInfo::parse(bytes),
+            FRAMEBUFFER_INFO => FramebufferInfo::parse(bytes),
+            ELF_SYMBOLS => ElfSymbols::parse(bytes),
+            APM_TABLE => Apm::parse(bytes),
+            EFI_TABLE_32BIT => {
+                /*      +-------------------+
+                u32     | pointer           |
+                        +-------------------+ */
+                read(bytes, 0).map(Tag::EfiSystemTable32)
             }
-            MODULE => Module::parse(addr, size),
-            MEMORY => Memory::parse(addr, size),
-            BIOS_DEVICE => BiosDevice::parse(addr, size),
-            MEMORY_MAP => MemoryMap::parse(addr, size),
-            VBE_INFO => VBEInfo::parse(addr, size),
-            FRAMEBUFFER_INFO => FramebufferInfo::parse(addr, size),
-            ELF_SYMBOLS => ElfSymbols::parse(addr, size),
-            _ => None,
+            EFI_TABLE_64BIT => {
+                /*      +-------------------+
+                u64     | pointer           |
+                        +-------------------+ */
+                read(bytes, 0).map(Tag::EfiSystemTable64)
+            }
+            SMBIOS_TABLE => Smbios::parse(bytes),
+            ACPI_RSDP_OLD => Rsdp::parse(bytes),
+            ACPI_RSDP_NEW => Xsdp::parse(bytes),
+            NETWORKING => Networking::parse(bytes),
+            EFI_MEMORY_MAP => EfiMemoryMap::parse(bytes),
+            EFI_BOOT_SERVICES_NOT_TERMINATED => Some(Tag::EfiBootServicesNotTerminated),
+            EFI_HANDLE_32BIT => {
+                /*      +-------------------+
+                u32     | pointer           |
+                        +-------------------+ */
+                read(bytes, 0).map(Tag::EfiImageHandle32)
+            }
+            EFI_HANDLE_64BIT => {
+                /*      +-------------------+
+                u64     | pointer           |
+                        +-------------------+ */
+                read(bytes, 0).map(Tag::EfiImageHandle64)
+            }
+            IMAGE_LOAD_ADDRESS => {
+                /*      +-------------------+
+                u32     | load_base_addr    |
+                        +-------------------+ */
+                read(bytes, 0).map(Tag::ImageLoadAddress)
+            }
+            _ => return Ok((None, size)),
         };
 
-        (tag, size)
+        match tag {
+            Some(tag) => Ok((Some(tag), size)),
+            None => Err(ParseError::InvalidTag(tag_type)),
+        }
+    }
+}
+
+/// Iterator over the tags following the fixed header of boot information, which stops at the end
+/// tag or after the first malformed tag. Tags of unknown types are skipped.
+#[derive(Debug, Clone)]
+pub struct TagIter {
+    bytes: &'static [u8],
+    finished: bool,
+}
+
+impl TagIter {
+    pub fn new(bytes: &'static [u8]) -> Self {
+        Self {
+            bytes,
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for TagIter {
+    type Item = Result<Tag, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            if self.bytes.is_empty() {
+                self.finished = true;
+                return Some(Err(ParseError::MissingEnd));
+            }
+
+            let (tag, size) = match Tag::parse(self.bytes) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            };
+
+            // tags are padded to the next multiple of 8, except maybe the last one
+            self.bytes = self.bytes.get(size.next_multiple_of(8)..).unwrap_or(&[]);
+
+            match tag {
+                Some(Tag::End) => self.finished = true,
+                Some(tag) => return Some(Ok(tag)),
+                None => continue,
+            }
+        }
+
+        None
     }
 }
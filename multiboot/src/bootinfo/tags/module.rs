@@ -1,6 +1,6 @@
 use core::ffi::CStr;
 
-use super::{ParseTag, Tag};
+use super::{read, read_str, ParseTag, Tag};
 
 /// Stores information about a module loaded alongside the kernel
 ///
@@ -14,16 +14,16 @@ pub struct Module {
 }
 
 impl ParseTag for Module {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*      +-------------------+
         u32     | mod_start         |
         u32     | mod_end           |
         u8[n]   | string            |
                 +-------------------+ */
 
-        let start = *addr;
-        let end = *addr.add(1);
-        let string = CStr::from_ptr(addr.add(2) as *const i8);
+        let start = read(bytes, 0)?;
+        let end = read(bytes, 4)?;
+        let string = read_str(bytes, 8)?;
 
         Some(Tag::Module(Self { start, end, string }))
     }
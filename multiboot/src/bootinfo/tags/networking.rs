@@ -0,0 +1,19 @@
+use super::{ParseTag, Tag};
+
+/// Stores the network information the bootloader was configured with
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Networking-information
+#[derive(Debug, Clone, Copy)]
+pub struct Networking {
+    /// Whole DHCP ACK packet, as received from the DHCP server
+    pub dhcp_ack: &'static [u8],
+}
+
+impl ParseTag for Networking {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        varies  | DHCP ACK          |
+                +-------------------+ */
+        Some(Tag::Networking(Self { dhcp_ack: bytes }))
+    }
+}
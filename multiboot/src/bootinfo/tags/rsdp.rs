@@ -0,0 +1,80 @@
+use super::{checksum, read, FromBytes, ParseTag, Tag};
+
+/// Stores a copy of the ACPI 1.0 root system description pointer
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#ACPI-old-RSDP
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+}
+
+/// Stores a copy of the ACPI 2.0+ root system description pointer
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#ACPI-new-RSDP
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Xsdp {
+    pub rsdp: Rsdp,
+    pub length: u32,
+    pub xsdt_address: u64,
+    pub extended_checksum: u8,
+    _reserved: [u8; 3],
+}
+
+impl Rsdp {
+    /// Checks the signature and that all bytes of the structure sum to zero
+    pub fn is_valid(&self) -> bool {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(self as *const _ as *const u8, core::mem::size_of::<Self>())
+        };
+
+        self.signature == *b"RSD PTR " && checksum(bytes)
+    }
+}
+
+impl Xsdp {
+    pub const fn new(rsdp: Rsdp, length: u32, xsdt_address: u64, extended_checksum: u8) -> Self {
+        Self {
+            rsdp,
+            length,
+            xsdt_address,
+            extended_checksum,
+            _reserved: [0; 3],
+        }
+    }
+
+    /// Checks both the ACPI 1.0 checksum and the extended checksum covering the entire structure
+    pub fn is_valid(&self) -> bool {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(self as *const _ as *const u8, core::mem::size_of::<Self>())
+        };
+
+        self.rsdp.is_valid() && checksum(bytes)
+    }
+}
+
+unsafe impl FromBytes for Rsdp {}
+unsafe impl FromBytes for Xsdp {}
+
+impl ParseTag for Rsdp {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        varies  | copy of RSDPv1    |
+                +-------------------+ */
+        read(bytes, 0).map(Tag::AcpiOld)
+    }
+}
+
+impl ParseTag for Xsdp {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        varies  | copy of RSDPv2    |
+                +-------------------+ */
+        read(bytes, 0).map(Tag::AcpiNew)
+    }
+}
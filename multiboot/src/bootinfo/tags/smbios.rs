@@ -0,0 +1,115 @@
+use super::{checksum, read, ParseTag, Tag};
+
+/// Stores a copy of the SMBIOS entry point
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#SMBIOS-tables
+#[derive(Debug, Clone, Copy)]
+pub struct Smbios {
+    pub major: u8,
+    pub minor: u8,
+    pub tables: &'static [u8],
+}
+
+impl Smbios {
+    /// Decodes the entry point copied by the bootloader
+    pub fn entry_point(&self) -> Option<SmbiosEntryPoint> {
+        SmbiosEntryPoint::parse(self.tables)
+    }
+}
+
+impl ParseTag for Smbios {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
+        /*      +-------------------+
+        u8      | major             |
+        u8      | minor             |
+        u8[6]   | reserved          |
+        varies  | smbios tables     |
+                +-------------------+ */
+        let major = read(bytes, 0)?;
+        let minor = read(bytes, 1)?;
+        let tables = bytes.get(8..)?;
+
+        Some(Tag::Smbios(Self {
+            major,
+            minor,
+            tables,
+        }))
+    }
+}
+
+/// Location of the SMBIOS structure table, decoded from either a 32-bit (`_SM_`) or 64-bit
+/// (`_SM3_`) entry point
+///
+/// https://www.dmtf.org/sites/default/files/standards/documents/DSP0134_3.7.0.pdf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmbiosEntryPoint {
+    pub major: u8,
+    pub minor: u8,
+    pub table_address: u64,
+    /// Length of the structure table for 32-bit entry points, or its maximum length for 64-bit
+    pub table_length: u32,
+}
+
+impl SmbiosEntryPoint {
+    /// Decodes an entry point, returning `None` if the anchor or checksum is invalid
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"_SM3_") {
+            /*      +-----------------------+
+            u8[5]   | anchor `_SM3_`        |
+            u8      | checksum              |
+            u8      | length                |
+            u8      | major                 |
+            u8      | minor                 |
+            u8      | docrev                |
+            u8      | revision              |
+            u8      | reserved              |
+            u32     | table maximum size    |
+            u64     | table address         |
+                    +-----------------------+ */
+            let length = read::<u8>(bytes, 6)? as usize;
+            if !checksum(bytes.get(..length)?) {
+                return None;
+            }
+
+            Some(Self {
+                major: read(bytes, 7)?,
+                minor: read(bytes, 8)?,
+                table_address: read(bytes, 16)?,
+                table_length: read(bytes, 12)?,
+            })
+        } else if bytes.starts_with(b"_SM_") {
+            /*      +-----------------------+
+            u8[4]   | anchor `_SM_`         |
+            u8      | checksum              |
+            u8      | length                |
+            u8      | major                 |
+            u8      | minor                 |
+            u16     | max structure size    |
+            u8      | revision              |
+            u8[5]   | formatted area        |
+            u8[5]   | anchor `_DMI_`        |
+            u8      | intermediate checksum |
+            u16     | table length          |
+            u32     | table address         |
+            u16     | number of structures  |
+            u8      | BCD revision          |
+                    +-----------------------+ */
+            let length = read::<u8>(bytes, 5)? as usize;
+            if !checksum(bytes.get(..length)?)
+                || bytes.get(16..21)? != b"_DMI_"
+                || !checksum(bytes.get(16..31)?)
+            {
+                return None;
+            }
+
+            Some(Self {
+                major: read(bytes, 6)?,
+                minor: read(bytes, 7)?,
+                table_address: read::<u32>(bytes, 24)? as u64,
+                table_length: read::<u16>(bytes, 22)? as u32,
+            })
+        } else {
+            None
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use core::fmt;
 
-use super::{ParseTag, Tag};
+use super::{read, FromBytes, ParseTag, Tag};
 
 /// Stores information about VBE
 ///
@@ -16,8 +16,10 @@ pub struct VBEInfo {
     pub mode_info: [u8; 256],
 }
 
+unsafe impl FromBytes for VBEInfo {}
+
 impl ParseTag for VBEInfo {
-    unsafe fn parse(addr: *const u32, _size: usize) -> Option<Tag> {
+    fn parse(bytes: &'static [u8]) -> Option<Tag> {
         /*       +-------------------+
         u16     | vbe_mode          |
         u16     | vbe_interface_seg |
@@ -27,7 +29,7 @@ impl ParseTag for VBEInfo {
         u8[256] | vbe_mode_info     |
                 +-------------------+ */
 
-        Some(Tag::VBEInfo(*(addr as *const _)))
+        read(bytes, 0).map(Tag::VBEInfo)
     }
 }
 
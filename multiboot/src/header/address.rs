@@ -0,0 +1,34 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Physical addresses to load the image at, for images that aren't ELF
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#The-address-tag-of-Multiboot2-header
+pub struct Address {
+    /// Address the multiboot header should be loaded at
+    pub header_addr: u32,
+    /// Address of the start of the text segment, which must be no higher than `header_addr`
+    pub load_addr: u32,
+    /// Address of the end of the data segment, or 0 to load the whole file
+    pub load_end_addr: u32,
+    /// Address of the end of the bss segment, or 0 if there is no bss
+    pub bss_end_addr: u32,
+}
+
+impl const HeaderTag for Address {
+    fn size(&self) -> u32 {
+        24
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(2);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+
+        buffer.write_u32(self.header_addr);
+        buffer.write_u32(self.load_addr);
+        buffer.write_u32(self.load_end_addr);
+        buffer.write_u32(self.bss_end_addr);
+    }
+}
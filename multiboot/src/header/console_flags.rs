@@ -16,10 +16,14 @@ bitflags! {
 }
 
 impl const HeaderTag for ConsoleFlags {
+    fn size(&self) -> u32 {
+        12
+    }
+
     fn write_to_buffer(&self, buffer: &mut Cursor) {
         buffer.write_u16(4);
         buffer.write_u16(0);
-        buffer.write_u32(12);
+        buffer.write_u32(self.size());
 
         buffer.write_u32(self.bits());
     }
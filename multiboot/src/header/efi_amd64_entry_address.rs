@@ -0,0 +1,26 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Physical address to jump to in 64-bit mode when booted on EFI amd64 with [EfiBootServices]
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#EFI-amd64-entry-address-tag-of-Multiboot2-header
+///
+/// [EfiBootServices]: crate::EfiBootServices
+pub struct EfiAmd64EntryAddress {
+    pub entry_addr: u32,
+}
+
+impl const HeaderTag for EfiAmd64EntryAddress {
+    fn size(&self) -> u32 {
+        12
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(9);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+
+        buffer.write_u32(self.entry_addr);
+    }
+}
@@ -0,0 +1,21 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Asks the bootloader to start the image without calling `ExitBootServices()`, leaving the
+/// image to call it
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#EFI-boot-services-tag
+pub struct EfiBootServices;
+
+impl const HeaderTag for EfiBootServices {
+    fn size(&self) -> u32 {
+        8
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(7);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+    }
+}
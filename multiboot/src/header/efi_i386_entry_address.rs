@@ -0,0 +1,26 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Physical address to jump to in 32-bit mode when booted on EFI i386 with [EfiBootServices]
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#EFI-i386-entry-address-tag-of-Multiboot2-header
+///
+/// [EfiBootServices]: crate::EfiBootServices
+pub struct EfiI386EntryAddress {
+    pub entry_addr: u32,
+}
+
+impl const HeaderTag for EfiI386EntryAddress {
+    fn size(&self) -> u32 {
+        12
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(8);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+
+        buffer.write_u32(self.entry_addr);
+    }
+}
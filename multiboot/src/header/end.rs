@@ -6,9 +6,13 @@ use crate::HeaderTag;
 pub struct End;
 
 impl const HeaderTag for End {
+    fn size(&self) -> u32 {
+        8
+    }
+
     fn write_to_buffer(&self, buffer: &mut Cursor) {
         buffer.write_u16(0);
         buffer.write_u16(0);
-        buffer.write_u32(8);
+        buffer.write_u32(self.size());
     }
 }
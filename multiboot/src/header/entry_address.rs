@@ -0,0 +1,24 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Physical address to jump to in 32-bit mode, instead of the ELF entrypoint
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#The-entry-address-tag-of-Multiboot2-header
+pub struct EntryAddress {
+    pub entry_addr: u32,
+}
+
+impl const HeaderTag for EntryAddress {
+    fn size(&self) -> u32 {
+        12
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(3);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+
+        buffer.write_u32(self.entry_addr);
+    }
+}
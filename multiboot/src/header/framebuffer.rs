@@ -12,10 +12,14 @@ pub struct Framebuffer {
 }
 
 impl const HeaderTag for Framebuffer {
+    fn size(&self) -> u32 {
+        20
+    }
+
     fn write_to_buffer(&self, buffer: &mut Cursor) {
         buffer.write_u16(5);
         buffer.write_u16(0);
-        buffer.write_u32(20);
+        buffer.write_u32(self.size());
         buffer.write_u32(self.width.unwrap_or_default(0));
         buffer.write_u32(self.height.unwrap_or_default(0));
         buffer.write_u32(self.depth.unwrap_or_default(0));
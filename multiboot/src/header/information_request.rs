@@ -10,10 +10,14 @@ pub struct InformationRequest {
 }
 
 impl const HeaderTag for InformationRequest {
+    fn size(&self) -> u32 {
+        8 + 4 * self.requests.len() as u32
+    }
+
     fn write_to_buffer(&self, buffer: &mut Cursor) {
         buffer.write_u16(1);
         buffer.write_u16(0);
-        buffer.write_u32(8 + 4 * self.requests.len() as u32);
+        buffer.write_u32(self.size());
 
         let mut index = 0;
         while index < self.requests.len() {
@@ -1,16 +1,28 @@
 use crabstd::cursor::Cursor;
 
+pub mod address;
 pub mod console_flags;
+pub mod efi_amd64_entry_address;
+pub mod efi_boot_services;
+pub mod efi_i386_entry_address;
 pub mod end;
+pub mod entry_address;
 pub mod framebuffer;
 pub mod information_request;
 pub mod module_alignment;
+pub mod relocatable;
 
+pub use address::Address;
 pub use console_flags::ConsoleFlags;
+pub use efi_amd64_entry_address::EfiAmd64EntryAddress;
+pub use efi_boot_services::EfiBootServices;
+pub use efi_i386_entry_address::EfiI386EntryAddress;
 pub use end::End;
+pub use entry_address::EntryAddress;
 pub use framebuffer::Framebuffer;
 pub use information_request::InformationRequest;
 pub use module_alignment::ModuleAlignment;
+pub use relocatable::{LoadPreference, Relocatable};
 
 /// Essentially an [Option] implementation with const unwrap method
 #[derive(Copy, Clone)]
@@ -29,23 +41,36 @@ impl<T: Default + Copy> HeaderTagValue<T> {
     }
 }
 
-/// Helper struct for constructing a multiboot header with provided architecture and flags
+/// Helper struct for constructing a multiboot header with provided architecture and flags,
+/// where `SIZE` is the total size of the header as computed by [header_size]
 #[repr(C)]
-pub struct MultibootHeader {
+pub struct MultibootHeader<const SIZE: usize> {
     arch: u32,
-    out: [u8; Self::SIZE as usize],
+    out: [u8; SIZE],
     out_cursor: Cursor<'static>,
-    buffer: [u8; Self::SIZE as usize],
-    buffer_cursor: Cursor<'static>,
 }
 
-impl MultibootHeader {
+/// Size of the magic, architecture, length and checksum fields at the start of the header
+const FIXED_FIELDS_SIZE: usize = 16;
+
+/// Computes the size of a header containing tags of the given sizes, followed by the end tag
+pub const fn header_size(tag_sizes: &[u32]) -> usize {
+    let mut size = FIXED_FIELDS_SIZE;
+
+    let mut index = 0;
+    while index < tag_sizes.len() {
+        // every tag is padded to an 8 byte boundary
+        size += (tag_sizes[index] as usize + 7) & !7;
+        index += 1;
+    }
+
+    size + End.size() as usize
+}
+
+impl<const SIZE: usize> MultibootHeader<SIZE> {
     /// Multiboot2 header magic value
     const MAGIC: u32 = 0xE85250D6;
 
-    /// Slightly hacky workaround to dynamically sized flags - just write a 4096 long block of bytes, padded with 0s
-    pub const SIZE: u32 = 4096;
-
     /// Constructs a new header **without** initialising pointers
     ///
     /// # Safety
@@ -53,17 +78,14 @@ impl MultibootHeader {
     pub const unsafe fn new() -> Self {
         Self {
             arch: 0,
-            out: [0; Self::SIZE as usize],
+            out: [0; SIZE],
             out_cursor: Cursor::default(),
-            buffer: [0; Self::SIZE as usize],
-            buffer_cursor: Cursor::default(),
         }
     }
 
-    /// Sets the pointers within the cursors to point at the buffers
+    /// Sets the pointer within the cursor to point at the buffer
     pub const fn set_cursors(&mut self) -> &mut Self {
         self.out_cursor = Cursor::new(&mut self.out);
-        self.buffer_cursor = Cursor::new(&mut self.buffer);
 
         self
     }
@@ -84,16 +106,20 @@ impl MultibootHeader {
 
     /// Writes a given tag to the multiboot header
     pub const fn write_tag(&mut self, tag: &impl ~const HeaderTag) -> &mut Self {
-        // write the byte using saved buffer, resetting cursor position afterwards so buffer can be re-used
-        tag.write_bytes(&mut self.buffer_cursor, &mut self.out_cursor);
-        self.buffer_cursor.reset_position();
+        tag.write_bytes(&mut self.out_cursor);
 
         self
     }
 
     /// Return the bytes representing multiboot header, setting the size and checksum fields
-    pub const fn as_bytes(&mut self) -> [u8; Self::SIZE as usize] {
-        let written = self.out_cursor.position() as u32;
+    pub const fn as_bytes(&mut self) -> [u8; SIZE] {
+        let written = self.out_cursor.position();
+        assert!(
+            written == SIZE,
+            "multiboot header size doesn't match the tags written"
+        );
+
+        let written = written as u32;
 
         // write new size and checksum
         unsafe {
@@ -118,20 +144,18 @@ impl MultibootHeader {
 /// Trait which represents a tag which can be written into the multiboot header
 #[const_trait]
 pub trait HeaderTag {
-    /// Write the tag to an intermediary buffer
-    fn write_to_buffer(&self, buffer: &mut Cursor);
+    /// Size of the tag in bytes, not including padding
+    fn size(&self) -> u32;
 
-    /// Write the tag to the output buffer, by initially writing to intermediary buffer,
-    /// copying to output buffer, and performing alignment
-    fn write_bytes(&self, buffer: &mut Cursor, out: &mut Cursor) {
-        self.write_to_buffer(buffer);
-        let end_tag = buffer.position();
+    /// Write the tag to the buffer
+    fn write_to_buffer(&self, buffer: &mut Cursor);
 
-        let slice = unsafe { core::slice::from_raw_parts(buffer.as_ptr(), end_tag) };
-        out.write_slice(slice);
+    /// Write the tag to the output buffer, performing alignment
+    fn write_bytes(&self, out: &mut Cursor) {
+        self.write_to_buffer(out);
 
         // align to 8 byte boundary, write 0s if needed
-        let mut alignment = ((end_tag + 7) & !7) - end_tag;
+        let mut alignment = ((self.size() + 7) & !7) - self.size();
         while alignment > 0 {
             out.write_u8(0);
             alignment -= 1;
@@ -141,20 +165,14 @@ pub trait HeaderTag {
 
 /// Constructs a multiboot header with the given architecture and (optionally) tags.
 ///
-/// Creates a static `HEADER` variable in the `.multiboot` section
+/// Creates a static `HEADER` variable in the `.multiboot` section, sized to fit the tags
 #[macro_export]
 macro_rules! multiboot_header {
     (arch: $arch:expr) => {
-        #[no_mangle]
-        #[used]
-        #[link_section = ".multiboot"]
-        static HEADER: [u8; multiboot::MultibootHeader::SIZE as usize] = unsafe {
-            multiboot::MultibootHeader::new()
-                .set_cursors()
-                .write_header($arch)
-                .write_tag(&multiboot::End)
-                .as_bytes()
-        };
+        $crate::multiboot_header! {
+            arch: $arch,
+            tags: []
+        }
     };
     (
         arch: $arch:expr,
@@ -165,15 +183,81 @@ macro_rules! multiboot_header {
         #[no_mangle]
         #[used]
         #[link_section = ".multiboot"]
-        static HEADER: [u8; multiboot::MultibootHeader::SIZE as usize] = unsafe {
-            multiboot::MultibootHeader::new()
+        static HEADER: [u8; $crate::header::header_size(&[
+            $( $crate::HeaderTag::size(&$tag), )*
+        ])] = unsafe {
+            $crate::MultibootHeader::new()
                 .set_cursors()
                 .write_header($arch)
                 $(
                     .write_tag(&$tag)
                 )*
-                .write_tag(&multiboot::End)
+                .write_tag(&$crate::End)
                 .as_bytes()
         };
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: InformationRequest = InformationRequest { requests: &[1, 6, 14] };
+    const RELOCATABLE: Relocatable = Relocatable {
+        min_addr: 0x100000,
+        max_addr: 0xFFFFFFFF,
+        align: 0x1000,
+        preference: LoadPreference::LOWEST,
+    };
+
+    const HEADER: [u8; header_size(&[REQUEST.size(), EfiBootServices.size(), RELOCATABLE.size()])] = unsafe {
+        MultibootHeader::new()
+            .set_cursors()
+            .write_header(0)
+            .write_tag(&REQUEST)
+            .write_tag(&EfiBootServices)
+            .write_tag(&RELOCATABLE)
+            .write_tag(&End)
+            .as_bytes()
+    };
+
+    fn read_u32(offset: usize) -> u32 {
+        u32::from_ne_bytes(HEADER[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn size_is_computed_from_tags() {
+        // fixed fields, information request padded from 20 to 24, boot services, relocatable, end
+        assert_eq!(HEADER.len(), 16 + 24 + 8 + 24 + 8);
+        assert_eq!(read_u32(8) as usize, HEADER.len());
+    }
+
+    #[test]
+    fn checksum_is_valid() {
+        let sum = (0..4).fold(0u32, |sum, field| sum.wrapping_add(read_u32(field * 4)));
+        assert_eq!(sum, 0);
+    }
+
+    #[test]
+    fn tags_are_aligned() {
+        // information request, with the padding after it zeroed
+        assert_eq!(read_u32(16), 1);
+        assert_eq!(read_u32(20), 20);
+        assert_eq!(read_u32(32), 14);
+        assert_eq!(read_u32(36), 0);
+
+        // boot services
+        assert_eq!(read_u32(40), 7);
+        assert_eq!(read_u32(44), 8);
+
+        // relocatable
+        assert_eq!(read_u32(48), 10);
+        assert_eq!(read_u32(52), 24);
+        assert_eq!(read_u32(56), 0x100000);
+        assert_eq!(read_u32(68), LoadPreference::LOWEST as u32);
+
+        // end
+        assert_eq!(read_u32(72), 0);
+        assert_eq!(read_u32(76), 8);
+    }
+}
@@ -8,9 +8,13 @@ use crate::HeaderTag;
 pub struct ModuleAlignment;
 
 impl const HeaderTag for ModuleAlignment {
+    fn size(&self) -> u32 {
+        8
+    }
+
     fn write_to_buffer(&self, buffer: &mut Cursor) {
         buffer.write_u16(6);
         buffer.write_u16(0);
-        buffer.write_u32(8);
+        buffer.write_u32(self.size());
     }
 }
@@ -0,0 +1,43 @@
+use crabstd::cursor::Cursor;
+
+use crate::HeaderTag;
+
+/// Marks the image as relocatable, letting the bootloader load it anywhere within a range
+///
+/// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Relocatable-header-tag
+pub struct Relocatable {
+    /// Lowest physical address the image can be loaded at
+    pub min_addr: u32,
+    /// Highest physical address the end of the image can be loaded at
+    pub max_addr: u32,
+    /// Alignment of the load address
+    pub align: u32,
+    pub preference: LoadPreference,
+}
+
+/// Where in the allowed range the bootloader should prefer to load the image
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum LoadPreference {
+    NONE = 0,
+    LOWEST = 1,
+    HIGHEST = 2,
+}
+
+impl const HeaderTag for Relocatable {
+    fn size(&self) -> u32 {
+        24
+    }
+
+    fn write_to_buffer(&self, buffer: &mut Cursor) {
+        buffer.write_u16(10);
+        buffer.write_u16(0);
+        buffer.write_u32(self.size());
+
+        buffer.write_u32(self.min_addr);
+        buffer.write_u32(self.max_addr);
+        buffer.write_u32(self.align);
+        buffer.write_u32(self.preference as u32);
+    }
+}
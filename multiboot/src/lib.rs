@@ -7,6 +7,9 @@
     iter_intersperse
 )]
 
+#[cfg(test)]
+extern crate alloc;
+
 pub mod bootinfo;
 pub mod header;
 
@@ -0,0 +1,31 @@
+#!/bin/sh
+# Cargo runner for test kernels: boots the kernel passed as $1 under QEMU with the normal loader
+# and initrd, and turns the code written to isa-debug-exit into a process exit code.
+set -e
+
+KERNEL="$1"
+ISO_DIR=target/test-isofiles
+ISO_FILE=target/crabos-test.iso
+
+mkdir -p $ISO_DIR/boot
+cp -r kernel_loader/src/arch/x86_64/boot/ $ISO_DIR
+cp target/isofiles/boot/crabos-loader target/isofiles/boot/crabos.initrd $ISO_DIR/boot/
+cp "$KERNEL" $ISO_DIR/boot/crabos
+
+grub-mkrescue -o $ISO_FILE $ISO_DIR 2> /dev/null
+
+set +e
+timeout 300 qemu-system-x86_64 \
+    -drive file=$ISO_FILE,format=raw \
+    -device isa-debug-exit,iobase=0xf4,iosize=0x04 \
+    -serial stdio -display none -m 256M
+CODE=$?
+set -e
+
+# QEMU exits with (code << 1) | 1, so a success code of 0x10 becomes 33
+if [ $CODE -eq 33 ]; then
+    exit 0
+fi
+
+echo "test kernel failed with exit code $CODE"
+exit 1
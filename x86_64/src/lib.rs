@@ -7,6 +7,7 @@ use structures::{GlobalDescriptorTable, InterruptDescriptorTable, PAGE_SIZE};
 
 pub mod interrupts;
 pub mod port;
+pub mod random;
 pub mod registers;
 pub mod segment_selector;
 pub mod structures;
@@ -70,7 +71,34 @@ pub struct DescriptorTablePointer<T> {
     phantom: PhantomData<T>,
 }
 
+impl<T> DescriptorTablePointer<T> {
+    /// Returns the address of the descriptor table
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Returns the size of the descriptor table in bytes, minus one
+    pub fn limit(&self) -> u16 {
+        self.limit
+    }
+}
+
 impl DescriptorTablePointer<InterruptDescriptorTable> {
+    /// Reads the pointer to the currently loaded interrupt descriptor table
+    pub fn read_idt() -> Self {
+        let mut dtr = Self {
+            limit: 0,
+            base: 0,
+            phantom: PhantomData {},
+        };
+
+        unsafe {
+            asm!("sidt [{}]", in(reg) &mut dtr, options(nostack, preserves_flags));
+        }
+
+        dtr
+    }
+
     /// Loads the given descriptor table as an interrupt descriptor table
     ///
     /// # Safety
@@ -83,6 +111,21 @@ impl DescriptorTablePointer<InterruptDescriptorTable> {
 }
 
 impl DescriptorTablePointer<GlobalDescriptorTable> {
+    /// Reads the pointer to the currently loaded global descriptor table
+    pub fn read_gdt() -> Self {
+        let mut dtr = Self {
+            limit: 0,
+            base: 0,
+            phantom: PhantomData {},
+        };
+
+        unsafe {
+            asm!("sgdt [{}]", in(reg) &mut dtr, options(nostack, preserves_flags));
+        }
+
+        dtr
+    }
+
     /// Loads the given descriptor table as an global descriptor table
     ///
     /// # Safety
@@ -0,0 +1,83 @@
+use core::arch::asm;
+
+/// Number of times to retry `rdrand` before giving up, as recommended by Intel
+const RDRAND_RETRIES: usize = 10;
+
+/// Checks if the CPU supports the `rdrand` instruction
+pub fn has_rdrand() -> bool {
+    let ecx: u32;
+
+    // rbx is reserved by llvm, so needs to be saved around cpuid
+    unsafe {
+        asm!(
+            "mov {tmp}, rbx",
+            "cpuid",
+            "mov rbx, {tmp}",
+            tmp = out(reg) _,
+            inout("eax") 1 => _,
+            out("ecx") ecx,
+            out("edx") _,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    ecx & (1 << 30) != 0
+}
+
+/// Reads a random number from the CPU's hardware generator, returning `None` if it isn't supported
+/// or doesn't have enough entropy available
+pub fn rdrand() -> Option<u64> {
+    if !has_rdrand() {
+        return None;
+    }
+
+    for _ in 0..RDRAND_RETRIES {
+        let value: u64;
+        let success: u8;
+
+        unsafe {
+            asm!(
+                "rdrand {value}",
+                "setc {success}",
+                value = out(reg) value,
+                success = out(reg_byte) success,
+                options(nomem, nostack)
+            );
+        }
+
+        if success != 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Reads the timestamp counter
+pub fn rdtsc() -> u64 {
+    let (low, high): (u32, u32);
+
+    unsafe {
+        asm!(
+            "rdtsc",
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+
+    ((high as u64) << 32) | low as u64
+}
+
+/// Returns a random number, from `rdrand` if possible and the timestamp counter otherwise.
+/// Not suitable for cryptography.
+pub fn random() -> u64 {
+    rdrand().unwrap_or_else(|| {
+        // the low bits of the timestamp counter change the fastest, so mix them into the top bits
+        let mut value = rdtsc();
+        value ^= value >> 33;
+        value = value.wrapping_mul(0xFF51AFD7ED558CCD);
+        value ^= value >> 33;
+        value
+    })
+}
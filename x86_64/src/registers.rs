@@ -60,6 +60,22 @@ impl CR2 {
         value as VirtualAddress
     }
 }
+
+pub struct RBP;
+
+impl RBP {
+    /// Reads the frame pointer of the calling function
+    #[inline(always)]
+    pub fn read() -> VirtualAddress {
+        let value: u64;
+
+        unsafe {
+            asm!("mov {}, rbp", out(reg) value, options(nomem, nostack, preserves_flags));
+        }
+
+        value as VirtualAddress
+    }
+}
 bitflags! {
     #[repr(transparent)]
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
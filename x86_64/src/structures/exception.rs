@@ -2,7 +2,7 @@ use core::fmt::Display;
 
 use crate::{registers::CpuFlags, segment_selector::SegmentSelector};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct ExceptionStackFrame {
     pub instruction_pointer: u64,
@@ -14,6 +14,27 @@ pub struct ExceptionStackFrame {
     _reserved2: [u8; 6],
 }
 
+impl ExceptionStackFrame {
+    /// Creates a frame which `iretq` will return through to the given state
+    pub const fn new(
+        instruction_pointer: u64,
+        code_segment: SegmentSelector,
+        cpu_flags: CpuFlags,
+        stack_pointer: u64,
+        stack_segment: SegmentSelector,
+    ) -> Self {
+        Self {
+            instruction_pointer,
+            code_segment,
+            _reserved1: [0; 6],
+            cpu_flags,
+            stack_pointer,
+            stack_segment,
+            _reserved2: [0; 6],
+        }
+    }
+}
+
 impl Display for ExceptionStackFrame {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "Exception stack frame:")?;
@@ -147,6 +147,14 @@ impl Descriptor {
         Descriptor::UserSegment(DescriptorFlags::KERNEL_DATA.bits())
     }
 
+    pub fn user_code_segment() -> Self {
+        Descriptor::UserSegment(DescriptorFlags::USER_CODE64.bits())
+    }
+
+    pub const fn user_data_segment() -> Descriptor {
+        Descriptor::UserSegment(DescriptorFlags::USER_DATA.bits())
+    }
+
     pub fn tss_segment(tss: &'static TaskStateSegment) -> Self {
         let tss = tss as *const TaskStateSegment;
         let ptr = tss as u64;
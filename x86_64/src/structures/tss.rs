@@ -14,8 +14,8 @@ pub struct TaskStateSegment {
     pub base_addr: u16,
 }
 
-impl Default for TaskStateSegment {
-    fn default() -> Self {
+impl TaskStateSegment {
+    pub const fn new() -> Self {
         Self {
             privilege_stack_table: [0; 3],
             interrupt_stack_table: [0; 7],
@@ -28,6 +28,12 @@ impl Default for TaskStateSegment {
     }
 }
 
+impl Default for TaskStateSegment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display for TaskStateSegment {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let privilege_table = self.privilege_stack_table;